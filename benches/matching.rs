@@ -0,0 +1,54 @@
+//! Selector matching against large stylesheets.
+//!
+//! Styles a generated document with generated stylesheets of increasing size. Since rules are
+//! indexed by id, class, and tag, the time to style the document should grow much more slowly
+//! than the number of rules.
+//!
+//! Run with `cargo bench`.
+
+extern crate chameleon;
+
+use chameleon::{css, html, style};
+use std::time::Instant;
+
+const ELEMENTS: usize = 1000;
+const ITERATIONS: u32 = 10;
+
+fn main() {
+    let document = document(ELEMENTS);
+    let root = html::parse(&document);
+    for &rules in &[10, 100, 1000, 10000] {
+        let source = stylesheet(rules);
+        let stylesheet = css::parse(&source);
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            style::style_tree(&root, &stylesheet);
+        }
+        let elapsed = start.elapsed() / ITERATIONS;
+        println!("{:>5} elements, {:>5} rules: {:>10.3?} per style tree", ELEMENTS, rules, elapsed);
+    }
+}
+
+/// A flat document of `elements` elements, each with an id and two classes.
+fn document(elements: usize) -> String {
+    let mut source = String::from("<html>");
+    for i in 0..elements {
+        let tag = if i % 2 == 0 { "div" } else { "p" };
+        source += &format!("<{0} id=\"e{1}\" class=\"c{2} d{3}\"></{0}>", tag, i, i % 50, i % 7);
+    }
+    source + "</html>"
+}
+
+/// A stylesheet of `rules` rules, with a mix of id, class, and type selectors.
+fn stylesheet(rules: usize) -> String {
+    let mut source = String::new();
+    for i in 0..rules {
+        source += &match i % 4 {
+            0 => format!("#e{} {{ width: {}px; }}\n", i, i),
+            1 => format!(".c{} {{ height: 10px; }}\n", i),
+            2 => format!("div.d{} {{ padding: 1px; }}\n", i),
+            _ => format!("t{} {{ display: block; }}\n", i),
+        };
+    }
+    source
+}
@@ -0,0 +1,453 @@
+extern crate chameleon;
+extern crate getopts;
+extern crate image;
+
+use chameleon::{css, dom, engine, geometry, geometry_tree, html, layout, limits, paint, resource,
+                style, terminal};
+
+use std::fmt::Display;
+use std::io::{self, BufWriter, Write};
+use std::fs::{self, File};
+use std::{process, thread};
+
+fn main() {
+    // Parse command-line options:
+    let mut opts = getopts::Options::new();
+    opts.optopt("d", "html", "HTML document (or, with the http feature, URL)", "FILENAME");
+    opts.optmulti("s", "css", "CSS stylesheet (may be repeated)", "FILENAME");
+    opts.optmulti("u", "user-css", "User CSS stylesheet (may be repeated)", "FILENAME");
+    opts.optopt("o", "out", "Output image of the viewport", "FILENAME");
+    opts.optopt("f", "format", "Output format: png, ppm, svg, or ansi for colored text \
+                               (default: from the filename)", "FORMAT");
+    opts.optopt("", "columns", "Width of ansi output, in characters (default: 80)", "N");
+    opts.optopt("w", "width", "Viewport width", "N");
+    opts.optopt("h", "height", "Viewport height", "N");
+    opts.optopt("", "scale", "Device pixels per px, such as 2 for HiDPI output (default: 1)", "N");
+    opts.optflag("", "profile-style", "Report the most expensive selectors and elements");
+    opts.optflag("", "linear-blending", "Blend translucent colors in linear light");
+    opts.optopt("", "snap", "Fit box edges to device pixels by rounding them (round, floor, \
+                             the default, or ceil), or by anti-aliasing them (antialias)",
+                "POLICY");
+    opts.optflag("", "layout-only", "Print the geometry of the laid out boxes instead of painting");
+    opts.optflag("", "dump-style", "Print the style tree");
+    opts.optflag("", "dump-layout", "Print the layout tree");
+    #[cfg(feature = "serde")]
+    opts.optflag("", "dump-layout-json", "Print the layout tree as JSON");
+    opts.optflag("", "dump-display-list", "Print the display list");
+    #[cfg(feature = "serde")]
+    opts.optflag("", "dump-display-list-json", "Print the display list as JSON");
+    opts.optflag("", "help", "Print this help");
+
+    let usage = opts.usage("Usage: chameleon [options]");
+    let matches = match opts.parse(std::env::args().skip(1)) {
+        Ok(matches) => matches,
+        Err(error) => fail(format!("{}\n\n{}", error, usage)),
+    };
+    if matches.opt_present("help") {
+        print!("{}", usage);
+        return;
+    }
+    let str_arg = |flag: &str, default: &str| -> String {
+        matches.opt_str(flag).unwrap_or(String::from(default))
+    };
+    let num_arg = |flag: &str, default: usize| -> usize {
+        match matches.opt_get_default::<usize>(flag, default) {
+            Ok(n) => n,
+            Err(_) => fail(format!("The {} must be a whole number\n\n{}", flag, usage)),
+        }
+    };
+
+    // Read and parse input files:
+    let html_file = str_arg("d", "examples/test.html");
+    let mut author_css_files = matches.opt_strs("s");
+    if author_css_files.is_empty() {
+        author_css_files.push(String::from("examples/test.css"));
+    }
+    let css_files: Vec<(css::Origin, String)> = matches.opt_strs("u").into_iter()
+        .map(|file| (css::Origin::User, file))
+        .chain(author_css_files.into_iter().map(|file| (css::Origin::Author, file)))
+        .collect();
+    let document_url = if resource::is_http(&html_file) {
+        html_file.clone()
+    } else {
+        fs::canonicalize(&html_file)
+            .map(|path| format!("file://{}", path.display()))
+            .unwrap_or_else(|_| html_file.clone())
+    };
+    let loader = resource::loader_for(&document_url);
+    let document = match loader.fetch(&document_url) {
+        Ok(document) => document,
+        Err(error) => fail(format!("Error loading {}: {}", html_file, error)),
+    };
+    if document.mime_type != "text/html" && document.mime_type != "text/plain" {
+        eprintln!("Warning: {}: rendering {} as HTML", html_file, document.mime_type);
+    }
+    let html_source = String::from_utf8_lossy(&document.bytes).into_owned();
+    let css_sources: Vec<String> = css_files.iter()
+        .map(|(_, file)| match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => fail(format!("Error reading {}: {}", file, error)),
+        })
+        .collect();
+    let limits = limits::Limits::default();
+    let (root_node, stylesheet) = match parse(&html_file, &html_source, &css_files, &css_sources,
+                                              &limits) {
+        Ok(parsed) => parsed,
+        Err(error) => fail(format!("Error parsing {}: {}", html_file, error)),
+    };
+
+    // Add the styles embedded in and linked from the document, which come after the others:
+    let linked = resource::fetch_stylesheets(&root_node, Some(&document.url), &*loader);
+    for stylesheet in &linked {
+        if let Err(ref error) = stylesheet.source {
+            eprintln!("Warning: {}: cannot load {}: {}", html_file, stylesheet.url, error);
+        }
+    }
+    let (document_stylesheet, errors) = style::document_stylesheet(&root_node, &linked);
+    for (url, error) in errors {
+        match url {
+            Some(url) => eprintln!("Warning: {}:{}", url, error),
+            None => eprintln!("Warning: {}: embedded style: {}", html_file, error),
+        }
+    }
+    let stylesheet = css::Stylesheet::combine(vec![stylesheet, document_stylesheet]);
+    for error in style::check_stylesheet(&stylesheet) {
+        eprintln!("Warning: ignoring declaration in {}", error);
+    }
+
+    // Print the style tree:
+    if matches.opt_present("dump-style") {
+        dump_style(&root_node, &style::style_tree(&root_node, &stylesheet), 0);
+    }
+
+    // Profile selector matching:
+    if matches.opt_present("profile-style") {
+        let mut profile = style::StyleProfile::default();
+        style::style_tree_profiled(&root_node, &stylesheet, &mut profile);
+        print!("{}", profile.report(&stylesheet, 10));
+    }
+
+    // Configure viewport size:
+    let width  = num_arg("width", 800);
+    let height = num_arg("height", 600);
+    let scale = match matches.opt_get_default::<f32>("scale", 1.0) {
+        Ok(scale) if scale > 0.0 && scale.is_finite() => scale,
+        _ => fail("The scale must be a positive number"),
+    };
+
+    // Rendering:
+    let context = layout::LayoutContext {
+        viewport_width: width as f32,
+        viewport_height: height as f32,
+    };
+
+    // Print the layout instead of painting it:
+    if matches.opt_present("layout-only") {
+        if let Some(geometry) = geometry_tree::layout_only(&root_node, &stylesheet, context) {
+            print!("{}", geometry);
+        }
+        return;
+    }
+
+    let mut options = paint::PaintOptions::default();
+    if matches.opt_present("linear-blending") {
+        options.blending = paint::ColorSpace::LinearSrgb;
+    }
+    options.snap = match matches.opt_str("snap").as_deref() {
+        None | Some("floor") => paint::SnapPolicy::Floor,
+        Some("round") => paint::SnapPolicy::Round,
+        Some("ceil") => paint::SnapPolicy::Ceil,
+        Some("antialias") => paint::SnapPolicy::Antialias,
+        Some(policy) => fail(format!("Unknown snapping policy {}", policy)),
+    };
+    let engine = match engine::Engine::with_limits(&root_node, &stylesheet, context, limits) {
+        Ok(mut engine) => {
+            engine.set_paint_options(options);
+            engine.set_scale_factor(scale);
+            engine
+        }
+        Err(error) => fail(format!("Error rendering {}: {}", html_file, error)),
+    };
+
+    // Print the layout tree and the display list:
+    if matches.opt_present("dump-layout") {
+        if let Some(root) = engine.layout_tree().root() {
+            print!("{}", root.dump());
+        }
+    }
+    #[cfg(feature = "serde")]
+    {
+        if matches.opt_present("dump-layout-json") {
+            println!("{}", engine.layout_tree().to_json());
+        }
+    }
+    if matches.opt_present("dump-display-list") {
+        for item in engine.display_list().items() {
+            println!("{:?}", item);
+        }
+    }
+    #[cfg(feature = "serde")]
+    {
+        if matches.opt_present("dump-display-list-json") {
+            println!("{}", engine.display_list().to_json());
+        }
+    }
+
+    // Choose the output format:
+    let format = match matches.opt_str("f") {
+        Some(format) => match Format::from_extension(&format) {
+            Some(format) => format,
+            None => fail(format!("Unknown output format {}", format)),
+        },
+        None => matches.opt_str("o")
+            .and_then(|filename| {
+                let extension = filename.rsplit_once('.')?.1.to_string();
+                Format::from_extension(&extension)
+            })
+            .unwrap_or(Format::Png),
+    };
+
+    // Print colored text to the terminal, unless it is to be saved:
+    let columns = num_arg("columns", 80);
+    if format == Format::Ansi && !matches.opt_present("o") {
+        match engine.render() {
+            Ok(canvas) => print!("{}", terminal::to_ansi(&canvas, columns, ansi_colors())),
+            Err(error) => fail(format!("Error rendering {}: {}", html_file, error)),
+        }
+        return;
+    }
+
+    // Create the output file:
+    let filename = matches.opt_str("o")
+        .unwrap_or_else(|| format!("output.{}", format.extension()));
+    let mut file = match File::create(&filename) {
+        Ok(file) => BufWriter::new(file),
+        Err(error) => fail(format!("Error creating {}: {}", filename, error)),
+    };
+
+    // Write to the file:
+    let written = match format {
+        Format::Svg => write_svg(&mut file, &engine),
+        Format::Png | Format::Ppm | Format::Ansi => {
+            let canvas = match engine.render() {
+                Ok(canvas) => canvas,
+                Err(error) => fail(format!("Error rendering {}: {}", html_file, error)),
+            };
+            match format {
+                Format::Ppm => write_ppm(&mut file, &canvas),
+                Format::Ansi => {
+                    file.write_all(terminal::to_ansi(&canvas, columns, ansi_colors()).as_bytes())
+                }
+                _ => write_png(&mut file, canvas),
+            }
+        }
+    };
+    match written {
+        Ok(()) => println!("Saved output as {}", filename),
+        Err(error) => fail(format!("Error saving output as {}: {}", filename, error)),
+    }
+}
+
+/// Report an error on standard error, and exit unsuccessfully.
+fn fail<M: Display>(message: M) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// The formats the rendered viewport can be saved in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Png,
+    /// Binary PPM, which drops the alpha channel.
+    Ppm,
+    /// SVG, drawn from the display list rather than from pixels.
+    Svg,
+    /// Text colored with ANSI escape sequences, for terminals.
+    Ansi,
+}
+
+impl Format {
+    fn from_extension(extension: &str) -> Option<Format> {
+        match &*extension.to_ascii_lowercase() {
+            "png" => Some(Format::Png),
+            "ppm" => Some(Format::Ppm),
+            "svg" => Some(Format::Svg),
+            "ansi" | "ans" => Some(Format::Ansi),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Ppm => "ppm",
+            Format::Svg => "svg",
+            Format::Ansi => "ans",
+        }
+    }
+}
+
+fn write_png<W: Write>(file: &mut W, canvas: paint::Canvas) -> io::Result<()> {
+    let (w, h) = (canvas.width as u32, canvas.height as u32);
+    let img = image::ImageBuffer::from_fn(w, h, move |x, y| {
+        let color = canvas.pixels[(y * w + x) as usize];
+        image::Pixel::from_channels(color.r, color.g, color.b, color.a)
+    });
+    image::ImageRgba8(img).save(file, image::PNG).map_err(io::Error::other)
+}
+
+/// The colors of the terminal: 24-bit if `COLORTERM` says so, as terminals that support them
+/// set it to, and the 256-color palette otherwise.
+fn ansi_colors() -> terminal::AnsiColors {
+    match std::env::var("COLORTERM").as_deref() {
+        Ok("truecolor") | Ok("24bit") => terminal::AnsiColors::TrueColor,
+        _ => terminal::AnsiColors::Palette256,
+    }
+}
+
+fn write_ppm<W: Write>(file: &mut W, canvas: &paint::Canvas) -> io::Result<()> {
+    write!(file, "P6\n{} {}\n255\n", canvas.width, canvas.height)?;
+    for color in &canvas.pixels {
+        file.write_all(&[color.r, color.g, color.b])?;
+    }
+    file.flush()
+}
+
+/// Write the viewport as an SVG image of the rectangles in the display list, on white.
+fn write_svg<W: Write>(file: &mut W, engine: &engine::Engine) -> io::Result<()> {
+    let viewport = engine.viewport();
+    let scale = engine.scale_factor();
+    writeln!(file, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                    viewBox=\"{} {} {} {}\">", viewport.width * scale, viewport.height * scale,
+             viewport.x, viewport.y, viewport.width, viewport.height)?;
+    writeln!(file, "<rect x=\"{}\" y=\"{}\" width=\"100%\" height=\"100%\" fill=\"white\"/>",
+             viewport.x, viewport.y)?;
+    let mut depth = 0;
+    for (i, item) in engine.display_list().items().iter().enumerate() {
+        match *item {
+            paint::DisplayCommand::SolidColor { color, x, y, width, height } => {
+                if color.a == 0 {
+                    continue;
+                }
+                writeln!(file, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                                fill=\"rgb({}, {}, {})\" fill-opacity=\"{}\"/>",
+                         x, y, width, height, color.r, color.g, color.b, color.alpha())?;
+            }
+            paint::DisplayCommand::BoxShadow { color, x, y, width, height, blur, border_box } => {
+                if color.a == 0 {
+                    continue;
+                }
+                // Blur the rectangle with a filter as large as the shadow, and cut the box out of
+                // it with a clip path around the shadow and the box.
+                let bounds = item.bounds();
+                writeln!(file, "<filter id=\"shadow{}\" filterUnits=\"userSpaceOnUse\" x=\"{}\" \
+                                y=\"{}\" width=\"{}\" height=\"{}\"><feGaussianBlur \
+                                stdDeviation=\"{}\"/></filter>",
+                         i, bounds.x, bounds.y, bounds.width, bounds.height, blur / 2.0)?;
+                writeln!(file, "<clipPath id=\"outside{}\"><path clip-rule=\"evenodd\" \
+                                d=\"M{} {}h{}v{}h{}z M{} {}h{}v{}h{}z\"/></clipPath>",
+                         i, bounds.x, bounds.y, bounds.width, bounds.height, -bounds.width,
+                         border_box.x, border_box.y, border_box.width, border_box.height,
+                         -border_box.width)?;
+                writeln!(file, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                                fill=\"rgb({}, {}, {})\" fill-opacity=\"{}\" \
+                                filter=\"url(#shadow{})\" clip-path=\"url(#outside{})\"/>",
+                         x, y, width, height, color.r, color.g, color.b, color.alpha(), i, i)?;
+            }
+            paint::DisplayCommand::Gradient { x, y, width, height, shape, ref stops } => {
+                // SVG clamps the offsets of the stops to between 0 and 1.
+                let stops: String = stops.iter()
+                    .map(|stop| format!("<stop offset=\"{}\" stop-color=\"rgb({}, {}, {})\" \
+                                         stop-opacity=\"{}\"/>", stop.offset, stop.color.r,
+                                        stop.color.g, stop.color.b, stop.color.alpha()))
+                    .collect();
+                match shape {
+                    paint::GradientShape::Linear { start, end } => {
+                        writeln!(file, "<linearGradient id=\"gradient{}\" \
+                                        gradientUnits=\"userSpaceOnUse\" x1=\"{}\" y1=\"{}\" \
+                                        x2=\"{}\" y2=\"{}\">{}</linearGradient>",
+                                 i, start.x, start.y, end.x, end.y, stops)?;
+                    }
+                    paint::GradientShape::Radial { center, radius_x, radius_y } => {
+                        // Stretch a circle vertically into the ellipse.
+                        let ratio = if radius_x > 0.0 { radius_y / radius_x } else { 1.0 };
+                        writeln!(file, "<radialGradient id=\"gradient{}\" \
+                                        gradientUnits=\"userSpaceOnUse\" cx=\"{}\" cy=\"{}\" \
+                                        r=\"{}\" gradientTransform=\"translate({} {}) \
+                                        scale(1 {}) translate({} {})\">{}</radialGradient>",
+                                 i, center.x, center.y, radius_x, center.x, center.y, ratio,
+                                 -center.x, -center.y, stops)?;
+                    }
+                }
+                writeln!(file, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                                fill=\"url(#gradient{})\"/>", x, y, width, height, i)?;
+            }
+            paint::DisplayCommand::PushTransform { transform } => {
+                let geometry::Transform { a, b, c, d, e, f } = transform;
+                writeln!(file, "<g transform=\"matrix({} {} {} {} {} {})\">", a, b, c, d, e, f)?;
+                depth += 1;
+            }
+            paint::DisplayCommand::PopTransform if depth > 0 => {
+                writeln!(file, "</g>")?;
+                depth -= 1;
+            }
+            paint::DisplayCommand::PopTransform => {}
+        }
+    }
+    for _ in 0..depth {
+        writeln!(file, "</g>")?;
+    }
+    writeln!(file, "</svg>")?;
+    file.flush()
+}
+
+/// Print the style tree, with the properties of each element that differ from their initial
+/// values.
+fn dump_style(node: &dom::Node, styled: &style::StyledNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match node.node_type {
+        dom::NodeType::Element(ref elem) => {
+            let properties: Vec<String> = styled.specified.non_initial_values().into_iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect();
+            println!("{}<{}> {:?}: {}", indent, elem.tag, node.id, properties.join("; "));
+        }
+        dom::NodeType::Text(ref text) => println!("{}{:?}", indent, text),
+    }
+    let styled_children = styled.children.iter().filter(|child| child.pseudo.is_none());
+    for (child, styled_child) in node.children.iter().zip(styled_children) {
+        dump_style(child, styled_child, depth + 1);
+    }
+}
+
+/// Parse an HTML document and its stylesheets, which borrow from the source text.
+///
+/// Every source is parsed on its own thread. The stylesheets are combined in order after the user
+/// agent stylesheet, so later rules win among equally specific selectors of the same origin.
+/// Errors in the HTML and syntax errors in the stylesheets, which the parsers recover from, are
+/// reported as warnings. Documents that exceed the DOM limits of `limits` are not parsed further.
+fn parse<'a>(html_file: &str, html_source: &'a str, css_files: &[(css::Origin, String)],
+             css_sources: &'a [String], limits: &limits::Limits)
+    -> Result<(dom::Node<'a>, css::Stylesheet<'a>), limits::LimitError>
+{
+    thread::scope(|scope| {
+        let html = scope.spawn(|| html::parse_with_limits(html_source, limits));
+        let sheets: Vec<_> = css_sources.iter()
+            .map(|source| scope.spawn(move || css::parse_with_errors(source)))
+            .collect();
+
+        let mut stylesheets = vec![css::user_agent_stylesheet()];
+        for (&(origin, ref file), sheet) in css_files.iter().zip(sheets) {
+            let (sheet, errors) = sheet.join().unwrap();
+            for error in errors {
+                eprintln!("Warning: {}:{}", file, error);
+            }
+            stylesheets.push(sheet.with_origin(origin));
+        }
+        let (root, errors) = html.join().unwrap()?;
+        for error in errors {
+            eprintln!("Warning: {}:{}", html_file, error);
+        }
+        Ok((root, css::Stylesheet::combine(stylesheets)))
+    })
+}
@@ -0,0 +1,193 @@
+//! Copying a selected range of a document, as HTML and as plain text.
+//!
+//! The HTML flavor is a well-formed fragment of the elements the selection touches, with the
+//! styles that matter once pasted elsewhere (display, colors, borders, and padding) written into
+//! `style` attributes, since the stylesheet does not come along.
+
+use dom::{Node, NodeId, NodeType};
+use html::escape;
+use style::{Display, Style, StyledNode};
+use std::fmt::Write;
+
+/// A boundary point of a selection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Position {
+    pub node: NodeId,
+    /// For a text node, a byte offset into its text. For an element, the number of its children
+    /// before the point.
+    pub offset: usize,
+}
+
+/// A range of the document between two boundary points, in either order.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Selection {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// The two flavors of a copied selection.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ClipboardContents {
+    pub html: String,
+    pub text: String,
+}
+
+/// Copy the selected part of a document.
+///
+/// `style_root` must be the style tree of `root`. If either end of the selection is not in the
+/// document, nothing is copied.
+pub fn copy(root: &Node, style_root: &StyledNode, selection: Selection) -> ClipboardContents {
+    let mut contents = ClipboardContents::default();
+    let (start, end) = match (point(root, selection.start), point(root, selection.end)) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        (Some(start), Some(end)) => (end, start),
+        _ => return contents,
+    };
+    let mut path = Vec::new();
+    copy_node(root, style_root, &mut path, (&start, &end), &mut contents);
+    contents
+}
+
+/// A boundary point as a path of child indices from the root, followed by the offset.
+///
+/// Points compare in document order: a point in a text node comes after the point before the
+/// text node and before the point after it.
+type Point = Vec<usize>;
+
+/// Find the point of a position in the document, if its node is in the document.
+fn point(root: &Node, position: Position) -> Option<Point> {
+    fn find(node: &Node, id: NodeId, path: &mut Vec<usize>) -> bool {
+        if node.id == id {
+            return true;
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            if find(child, id, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    let mut path = Vec::new();
+    if !find(root, position.node, &mut path) {
+        return None;
+    }
+    let node = path.iter().fold(root, |node, &i| &node.children[i]);
+    path.push(match node.node_type {
+        NodeType::Text(ref text) => floor_char_boundary(text, position.offset),
+        NodeType::Element(_) => position.offset.min(node.children.len()),
+    });
+    Some(path)
+}
+
+/// The largest character boundary of `text` no greater than `offset`.
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    (0..=offset.min(text.len())).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0)
+}
+
+/// Copy the selected part of `node`, which is at `path`, if it overlaps the selection.
+fn copy_node(node: &Node, style: &StyledNode, path: &mut Vec<usize>, (start, end): (&Point, &Point),
+             contents: &mut ClipboardContents) {
+    // The node lies between the point before it (its own path) and the point after it.
+    let mut after = path.clone();
+    if let Some(last) = after.last_mut() {
+        *last += 1;
+    }
+    if !path.is_empty() && (*path >= *end || after <= *start) {
+        return;
+    }
+
+    // The offset within this node of a point, clamped to the node's contents.
+    let len = match node.node_type {
+        NodeType::Text(ref text) => text.len(),
+        NodeType::Element(_) => node.children.len(),
+    };
+    let offset = |point: &Point| if point.len() > path.len() && point.starts_with(path) {
+        point[path.len()].min(len)
+    } else if point < path {
+        0
+    } else {
+        len
+    };
+
+    match node.node_type {
+        NodeType::Text(ref text) => {
+            let text = &text[offset(start)..offset(end)];
+            escape(&mut contents.html, text, false);
+            contents.text += text;
+        }
+        NodeType::Element(ref elem) => {
+            write!(contents.html, "<{}", elem.tag).unwrap();
+            let mut attributes: Vec<_> = elem.attributes.iter()
+                .filter(|&(name, _)| !matches!(&**name, "id" | "class" | "style"))
+                .collect();
+            attributes.sort();
+            for (name, value) in attributes {
+                write!(contents.html, " {}=\"", name).unwrap();
+                escape(&mut contents.html, value, true);
+                contents.html.push('"');
+            }
+            let declarations = inline_style(&style.specified);
+            if !declarations.is_empty() {
+                write!(contents.html, " style=\"{}\"", declarations).unwrap();
+            }
+            contents.html.push('>');
+
+            let child_styles = style.children.iter().filter(|child| child.pseudo.is_none());
+            for (i, (child, child_style)) in node.children.iter().zip(child_styles).enumerate() {
+                path.push(i);
+                copy_node(child, child_style, path, (start, end), contents);
+                path.pop();
+            }
+
+            write!(contents.html, "</{}>", elem.tag).unwrap();
+            if style.specified.display() != Display::Inline && !contents.text.ends_with('\n') {
+                contents.text.push('\n');
+            }
+        }
+    }
+}
+
+/// The declarations of the styles to keep with a copied element.
+fn inline_style(style: &Style) -> String {
+    let initial = Style::default();
+    let mut declarations = Vec::new();
+    if style.display() != initial.display() {
+        let display = match style.display() {
+            Display::Inline => "inline",
+            Display::Block => "block",
+            Display::ListItem => "list-item",
+            Display::Table => "table",
+            Display::TableRow => "table-row",
+            Display::TableCell => "table-cell",
+            Display::Flex => "flex",
+            Display::Grid => "grid",
+            Display::None => "none",
+        };
+        declarations.push(format!("display: {}", display));
+    }
+    for &(name, color) in &[("background-color", style.background_color()),
+                            ("border-color", style.border_color())] {
+        if color.a > 0 {
+            declarations.push(format!("{}: {}", name, color));
+        }
+    }
+    let lengths = [
+        ("border-top-width", style.border_top_width()),
+        ("border-right-width", style.border_right_width()),
+        ("border-bottom-width", style.border_bottom_width()),
+        ("border-left-width", style.border_left_width()),
+        ("padding-top", style.padding_top()),
+        ("padding-right", style.padding_right()),
+        ("padding-bottom", style.padding_bottom()),
+        ("padding-left", style.padding_left()),
+    ];
+    for &(name, length) in &lengths {
+        if length != 0.0 {
+            declarations.push(format!("{}: {}px", name, length));
+        }
+    }
+    declarations.join("; ")
+}
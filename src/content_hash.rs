@@ -0,0 +1,130 @@
+//! Content hashes of the products of each rendering stage, for cache keys and change detection.
+//!
+//! A hash depends only on what a stage produced: not on node ids, addresses, or the order of hash
+//! maps. The same document rendered with the same version of chameleon hashes the same in every
+//! run, process, and platform, so services can compare hashes instead of pixels. The hashes are
+//! 64-bit FNV-1a, which is fast but not meant to resist deliberate collisions.
+
+use geometry::{Rect, Transform};
+use layout::LayoutTree;
+use paint::{DisplayCommand, DisplayList, GradientShape};
+use style::StyledNode;
+
+/// A stage of the rendering pipeline whose result can be hashed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    /// The style tree: its shape and the specified values of every node.
+    Style,
+    /// The layout tree: the type, position, size, and edges of every box.
+    Layout,
+    /// The display list of the whole document.
+    DisplayList,
+}
+
+/// The hash of a style tree.
+pub fn style_hash(root: &StyledNode) -> u64 {
+    fn hash_node(hasher: &mut Fnv, node: &StyledNode) {
+        let values = node.specified.non_initial_values();
+        hasher.write_u64(values.len() as u64);
+        for (name, value) in values {
+            hasher.write_str(name);
+            hasher.write_str(&value);
+        }
+        hasher.write_u64(node.children.len() as u64);
+        for child in &node.children {
+            hash_node(hasher, child);
+        }
+    }
+
+    let mut hasher = Fnv::new();
+    hash_node(&mut hasher, root);
+    hasher.finish()
+}
+
+/// The hash of a layout tree. Trees with the same `dump` hash the same.
+pub fn layout_hash(tree: &LayoutTree) -> u64 {
+    let mut hasher = Fnv::new();
+    if let Some(root) = tree.root() {
+        hasher.write_str(&root.dump());
+    }
+    hasher.finish()
+}
+
+/// The hash of a display list.
+pub fn display_list_hash(list: &DisplayList) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher.write_u64(list.len() as u64);
+    for item in list.items() {
+        match *item {
+            DisplayCommand::SolidColor { color, x, y, width, height } => {
+                hasher.write(&[0, color.r, color.g, color.b, color.a]);
+                for value in [x, y, width, height] {
+                    hasher.write(&value.to_bits().to_le_bytes());
+                }
+            }
+            DisplayCommand::BoxShadow { color, x, y, width, height, blur, border_box } => {
+                hasher.write(&[1, color.r, color.g, color.b, color.a]);
+                let Rect { x: box_x, y: box_y, width: box_width, height: box_height } = border_box;
+                for value in [x, y, width, height, blur, box_x, box_y, box_width, box_height] {
+                    hasher.write(&value.to_bits().to_le_bytes());
+                }
+            }
+            DisplayCommand::Gradient { x, y, width, height, shape, ref stops } => {
+                let shape = match shape {
+                    GradientShape::Linear { start, end } => [0.0, start.x, start.y, end.x, end.y],
+                    GradientShape::Radial { center, radius_x, radius_y } => {
+                        [1.0, center.x, center.y, radius_x, radius_y]
+                    }
+                };
+                hasher.write(&[2]);
+                for &value in [x, y, width, height].iter().chain(&shape) {
+                    hasher.write(&value.to_bits().to_le_bytes());
+                }
+                hasher.write_u64(stops.len() as u64);
+                for stop in stops {
+                    hasher.write(&stop.offset.to_bits().to_le_bytes());
+                    hasher.write(&[stop.color.r, stop.color.g, stop.color.b, stop.color.a]);
+                }
+            }
+            DisplayCommand::PushTransform { transform } => {
+                hasher.write(&[3]);
+                let Transform { a, b, c, d, e, f } = transform;
+                for value in [a, b, c, d, e, f] {
+                    hasher.write(&value.to_bits().to_le_bytes());
+                }
+            }
+            DisplayCommand::PopTransform => hasher.write(&[4]),
+        }
+    }
+    hasher.finish()
+}
+
+/// A 64-bit FNV-1a hasher.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Hash a string with its length, so that consecutive strings cannot run together.
+    fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -3,37 +3,148 @@
 //! To support more CSS syntax, it would probably be easiest to replace this
 //! hand-rolled parser with one based on a library or parser generator.
 
+use dom;
+use geometry::Size;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 // Data structures:
+//
+// Names and keywords borrow from the source text where possible.
 
 #[derive(Debug)]
-pub struct Stylesheet {
-    pub rules: Vec<Rule>,
+pub struct Stylesheet<'a> {
+    rules: Vec<Rule<'a>>,
+    index: RuleIndex<'a>,
+    /// Identifies this version of the stylesheet; see `revision`.
+    revision: u64,
+}
+
+/// The rules of a stylesheet, indexed by the most selective part of each of their selectors.
+///
+/// A rule can only match an element if one of its selectors does, and a selector can only match
+/// elements with its id, its first class, or its type (checked in that order), so only the rules
+/// filed under the element's id, classes, and type (and rules with universal selectors) need to be
+/// matched against it.
+#[derive(Debug, Default)]
+struct RuleIndex<'a> {
+    by_id: HashMap<Cow<'a, str>, Vec<usize>>,
+    by_class: HashMap<Cow<'a, str>, Vec<usize>>,
+    by_tag: HashMap<Cow<'a, str>, Vec<usize>>,
+    universal: Vec<usize>,
+    /// Whether any selector styles a pseudo-element.
+    pseudo_elements: bool,
 }
 
 #[derive(Debug)]
-pub struct Rule {
-    pub selectors: Vec<Selector>,
-    pub declarations: Vec<Declaration>,
+pub struct Rule<'a> {
+    pub selectors: Vec<Selector<'a>>,
+    pub declarations: Vec<Declaration<'a>>,
+    /// The origin of the stylesheet the rule comes from.
+    pub origin: Origin,
+    /// The query lists of the `@media` rules the rule is nested in, outermost first. The rule
+    /// only applies where every one of them matches.
+    pub media: Vec<MediaQueryList>,
+}
+
+/// The kind of device a document is rendered for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MediaType {
+    #[default]
+    Screen,
+    Print,
+}
+
+/// What media queries are evaluated against: the device a document is rendered for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MediaContext {
+    /// The size of the viewport, in px.
+    pub viewport: Size,
+    pub media_type: MediaType,
+}
+
+/// The comma-separated media queries of an `@media` rule, which match if any of them does (or
+/// if there are none).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MediaQueryList(pub Vec<MediaQuery>);
+
+/// A media query, such as `screen and (min-width: 600px)`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaQuery {
+    /// Whether the query starts with `not`, which negates all of it.
+    pub negated: bool,
+    /// The media type the query tests, or `None` for `all`.
+    pub media_type: Option<MediaType>,
+    /// The features that must all match.
+    pub features: Vec<MediaFeature>,
+}
+
+/// A test of a feature of the device in a media query, such as `(min-width: 600px)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MediaFeature {
+    MinWidth(f32),
+    MaxWidth(f32),
+    MinHeight(f32),
+    MaxHeight(f32),
+}
+
+/// Where a stylesheet comes from, which decides the precedence of its declarations in the
+/// cascade.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Origin {
+    /// The browser's default styles, such as `user_agent_stylesheet`.
+    UserAgent,
+    /// Styles that the reader chose.
+    User,
+    /// The document's own styles.
+    #[default]
+    Author,
 }
 
 #[derive(Debug)]
-pub enum Selector {
-    Simple(SimpleSelector),
+pub enum Selector<'a> {
+    Simple(SimpleSelector<'a>),
 }
 
 #[derive(Debug)]
-pub struct SimpleSelector {
-    pub tag: Option<String>,
-    pub id: Option<String>,
-    pub class: Vec<String>,
+pub struct SimpleSelector<'a> {
+    pub tag: Option<Cow<'a, str>>,
+    pub id: Option<Cow<'a, str>>,
+    pub class: Vec<Cow<'a, str>>,
+    /// The states the elements must be in, such as `:focus`.
+    pub pseudo_classes: Vec<PseudoClass>,
+    /// The pseudo-element the selector styles, instead of the elements it matches.
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+/// A pseudo-class: a state of an element that selectors can test.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PseudoClass {
+    /// `:focus`, the element that has the keyboard focus.
+    Focus,
+}
+
+/// A pseudo-element: content generated for an element that is styled as if it were a child.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PseudoElement {
+    /// `::before`, the first child of the element.
+    Before,
+    /// `::after`, the last child of the element.
+    After,
 }
 
 #[derive(Debug)]
-pub struct Declaration {
-    pub name: String,
-    pub value: Value,
+pub struct Declaration<'a> {
+    pub name: Cow<'a, str>,
+    pub value: Value<'a>,
+    /// Whether the declaration is marked `!important`.
+    pub important: bool,
 }
 
 // pub struct Position<T> {
@@ -136,15 +247,28 @@ pub struct Declaration {
 // }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Value {
-    Keyword(String),
+pub enum Value<'a> {
+    Keyword(Cow<'a, str>),
+    Number(f32),
+    Percentage(f32),
     Length(f32, Unit),
     ColorValue(Color),
+    /// A quoted string, without the quotes.
+    String(Cow<'a, str>),
+    /// A delimiter between component values, such as `/` or `,`, or an operator in `calc()`.
+    Delimiter(char),
+    /// Several whitespace-separated component values.
+    List(Vec<Value<'a>>),
+    /// A function, such as `linear-gradient(...)`, and its arguments. A block in parentheses is a
+    /// function with an empty name.
+    Function(Cow<'a, str>, Vec<Value<'a>>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Unit {
     Px,
+    Fr,
+    Deg,
     // Em,
     // Pt,
     // Cm,
@@ -152,6 +276,7 @@ pub enum Unit {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -161,13 +286,169 @@ pub struct Color {
 
 pub type Specificity = (usize, usize, usize);
 
-impl Selector {
+impl<'a> Stylesheet<'a> {
+    /// Create a stylesheet from its rules, in order of appearance.
+    pub fn new(rules: Vec<Rule<'a>>) -> Self {
+        let mut index = RuleIndex::default();
+        for (i, rule) in rules.iter().enumerate() {
+            for selector in &rule.selectors {
+                let Selector::Simple(ref simple) = *selector;
+                index.pseudo_elements |= simple.pseudo_element.is_some();
+                let bucket = if let Some(ref id) = simple.id {
+                    index.by_id.entry(id.clone()).or_default()
+                } else if let Some(class) = simple.class.first() {
+                    index.by_class.entry(class.clone()).or_default()
+                } else if let Some(ref tag) = simple.tag {
+                    index.by_tag.entry(dom::lowercase_name(tag.clone())).or_default()
+                } else {
+                    &mut index.universal
+                };
+                // A rule with several selectors under the same key is only filed there once.
+                if bucket.last() != Some(&i) {
+                    bucket.push(i);
+                }
+            }
+        }
+        Stylesheet { rules, index, revision: next_revision() }
+    }
+
+    /// A number that no other stylesheet, nor this one before or after any change, has. Caches of
+    /// the results of matching against the stylesheet are valid as long as it stays the same.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether any selector of the stylesheet styles a pseudo-element, such as `::before`.
+    pub fn has_pseudo_elements(&self) -> bool {
+        self.index.pseudo_elements
+    }
+
+    /// The rules of the stylesheet, in order of appearance.
+    pub fn rules(&self) -> &[Rule<'a>] {
+        &self.rules
+    }
+
+    /// Combine several stylesheets into one, in order. Every rule keeps its origin, so the
+    /// stylesheets may come from different origins.
+    pub fn combine<I: IntoIterator<Item = Stylesheet<'a>>>(stylesheets: I) -> Self {
+        Stylesheet::new(stylesheets.into_iter().flat_map(Stylesheet::into_rules).collect())
+    }
+
+    /// Set the origin of every rule in the stylesheet.
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        for rule in &mut self.rules {
+            rule.origin = origin;
+        }
+        self.revision = next_revision();
+        self
+    }
+
+    /// Take the rules out of the stylesheet.
+    pub fn into_rules(self) -> Vec<Rule<'a>> {
+        self.rules
+    }
+
+    /// The rules that might match an element with the given type, id, and classes, in order of
+    /// appearance. Every other rule certainly does not match it.
+    pub fn candidate_rules(&self, tag: &str, id: Option<&str>, classes: &HashSet<&str>)
+        -> Vec<&Rule<'a>>
+    {
+        self.candidate_rule_indices(tag, id, classes).into_iter().map(|i| &self.rules[i]).collect()
+    }
+
+    /// Like `candidate_rules`, but returns the indices of the rules in `rules()`.
+    pub fn candidate_rule_indices(&self, tag: &str, id: Option<&str>, classes: &HashSet<&str>)
+        -> Vec<usize>
+    {
+        let index = &self.index;
+        let mut candidates: Vec<usize> = index.universal.clone();
+        let mut add = |bucket: Option<&Vec<usize>>| if let Some(bucket) = bucket {
+            candidates.extend(bucket);
+        };
+        add(id.and_then(|id| index.by_id.get(id)));
+        for class in classes {
+            add(index.by_class.get(*class));
+        }
+        add(index.by_tag.get(&*dom::lowercase_name(tag)));
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Stylesheet<'_> {
+    /// Whether any rule applies in one of the media contexts but not in the other, so that the
+    /// styles of a document would change between them.
+    pub fn media_changes(&self, from: &MediaContext, to: &MediaContext) -> bool {
+        self.rules.iter()
+            .filter(|rule| !rule.media.is_empty())
+            .any(|rule| rule.matches_media(from) != rule.matches_media(to))
+    }
+}
+
+impl Rule<'_> {
+    /// Whether the rule applies in the media context: whether every `@media` rule it is in
+    /// matches.
+    pub fn matches_media(&self, context: &MediaContext) -> bool {
+        self.media.iter().all(|list| list.matches(context))
+    }
+}
+
+impl Default for MediaContext {
+    /// A screen with the default viewport of a `Document`, 800 by 600 px.
+    fn default() -> MediaContext {
+        MediaContext {
+            viewport: Size { width: 800.0, height: 600.0 },
+            media_type: MediaType::Screen,
+        }
+    }
+}
+
+impl MediaQueryList {
+    /// Whether any of the queries matches, or there are none.
+    pub fn matches(&self, context: &MediaContext) -> bool {
+        self.0.is_empty() || self.0.iter().any(|query| query.matches(context))
+    }
+}
+
+impl MediaQuery {
+    /// A query that matches nothing, which stands in for one with an unknown media type.
+    fn nothing() -> MediaQuery {
+        MediaQuery { negated: true, media_type: None, features: Vec::new() }
+    }
+
+    pub fn matches(&self, context: &MediaContext) -> bool {
+        let media_type = self.media_type.is_none_or(|media_type| media_type == context.media_type);
+        let features = self.features.iter().all(|feature| feature.matches(context));
+        (media_type && features) != self.negated
+    }
+}
+
+impl MediaFeature {
+    pub fn matches(&self, context: &MediaContext) -> bool {
+        let Size { width, height } = context.viewport;
+        match *self {
+            MediaFeature::MinWidth(min) => width >= min,
+            MediaFeature::MaxWidth(max) => width <= max,
+            MediaFeature::MinHeight(min) => height >= min,
+            MediaFeature::MaxHeight(max) => height <= max,
+        }
+    }
+}
+
+/// Allocate a stylesheet revision.
+fn next_revision() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Selector<'_> {
     pub fn specificity(&self) -> Specificity {
         // http://www.w3.org/TR/selectors/#specificity
         let Selector::Simple(ref simple) = *self;
         let a = simple.id.iter().count();
-        let b = simple.class.len();
-        let c = simple.tag.iter().count();
+        let b = simple.class.len() + simple.pseudo_classes.len();
+        let c = simple.tag.iter().count() + simple.pseudo_element.iter().count();
         (a, b, c)
     }
 }
@@ -191,34 +472,102 @@ impl Color {
         (r, g, b)
     }
 
+    /// Composite this color over `below`, which may be translucent too. The channels are
+    /// premultiplied by their alpha to blend, and divided by the resulting alpha again, so a
+    /// transparent color below contributes nothing, rather than darkening the result.
     pub fn over(&self, below: &Self) -> Color {
-        let (red_a, green_a, blue_a) = self.channels();
-        let (red_b, green_b, blue_b) = below.channels();
-
-        let alpha_a = self.alpha();
-        let alpha_b = below.alpha();
+        match (self.a, below.a) {
+            (255, _) | (_, 0) => return *self,
+            (0, _) => return *below,
+            _ => {}
+        }
+        let (alpha_a, alpha_b) = (self.alpha(), below.alpha());
         let alpha_c = alpha_a + alpha_b * (1.0 - alpha_a);
-
-        let compose = |channel_a, channel_b| {
-            (channel_a * alpha_a + channel_b * alpha_b * (1.0 - alpha_a)) / alpha_c
+        let compose = |channel_a: u8, channel_b: u8| {
+            let premultiplied = channel_a as f32 * alpha_a
+                + channel_b as f32 * alpha_b * (1.0 - alpha_a);
+            (premultiplied / alpha_c).round().min(255.0) as u8
         };
-        let scale = |channel| (255.0 * channel) as u8;
 
         Color {
-            r: scale(compose(red_a, red_b)),
-            g: scale(compose(green_a, green_b)),
-            b: scale(compose(blue_a, blue_b)),
-            a: scale(alpha_c),
+            r: compose(self.r, below.r),
+            g: compose(self.g, below.g),
+            b: compose(self.b, below.b),
+            a: (alpha_c * 255.0).round() as u8,
         }
     }
 }
 
-impl fmt::Display for Value {
+impl fmt::Display for Selector<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Selector::Simple(ref simple) = *self;
+        match simple.tag {
+            Some(ref tag) => write!(f, "{}", tag)?,
+            None if simple.id.is_none() && simple.class.is_empty()
+            && simple.pseudo_classes.is_empty() => write!(f, "*")?,
+            None => {}
+        }
+        if let Some(ref id) = simple.id {
+            write!(f, "#{}", id)?;
+        }
+        for class in &simple.class {
+            write!(f, ".{}", class)?;
+        }
+        for pseudo_class in &simple.pseudo_classes {
+            match *pseudo_class {
+                PseudoClass::Focus => write!(f, ":focus")?,
+            }
+        }
+        match simple.pseudo_element {
+            Some(PseudoElement::Before) => write!(f, "::before"),
+            Some(PseudoElement::After) => write!(f, "::after"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Value<'_> {
+    /// This value, with its strings copied so that it does not borrow the stylesheet.
+    pub fn into_owned(self) -> Value<'static> {
+        match self {
+            Value::Keyword(keyword) => Value::Keyword(Cow::Owned(keyword.into_owned())),
+            Value::Number(n) => Value::Number(n),
+            Value::Percentage(p) => Value::Percentage(p),
+            Value::Length(l, u) => Value::Length(l, u),
+            Value::ColorValue(c) => Value::ColorValue(c),
+            Value::String(s) => Value::String(Cow::Owned(s.into_owned())),
+            Value::Delimiter(c) => Value::Delimiter(c),
+            Value::List(values) => Value::List(values.into_iter().map(Value::into_owned).collect()),
+            Value::Function(name, arguments) => Value::Function(
+                Cow::Owned(name.into_owned()),
+                arguments.into_iter().map(Value::into_owned).collect(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Value::Keyword(ref kw) => write!(f, "\"{}\"", kw),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Percentage(p) => write!(f, "{}%", p),
             Value::Length(l, u) => write!(f, "{}{}", l, u),
-            Value::ColorValue(c) => write!(f, "{}", c)
+            Value::ColorValue(c) => write!(f, "{}", c),
+            Value::String(ref s) => write!(f, "{:?}", s),
+            Value::Delimiter(c) => write!(f, "{}", c),
+            Value::List(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                Ok(())
+            }
+            Value::Function(ref name, ref arguments) => {
+                write!(f, "{}({})", name, Value::List(arguments.clone()))
+            }
         }
     }
 }
@@ -227,6 +576,8 @@ impl fmt::Display for Unit {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Unit::Px => write!(f, "px"),
+            Unit::Fr => write!(f, "fr"),
+            Unit::Deg => write!(f, "deg"),
         }
     }
 }
@@ -237,183 +588,682 @@ impl fmt::Display for Color {
     }
 }
 
-/// Parse a whole CSS stylesheet.
-pub fn parse(source: String) -> Stylesheet {
-    let mut parser = Parser { pos: 0, input: source };
-    Stylesheet { rules: parser.parse_rules() }
+/// Parse a whole CSS stylesheet, skipping anything that is not valid CSS (see
+/// `parse_with_errors`).
+///
+/// The stylesheet borrows names and keywords from `source` rather than copying them. Its rules
+/// have the `Author` origin; see `Stylesheet::with_origin` for others.
+pub fn parse(source: &str) -> Stylesheet<'_> {
+    parse_with_errors(source).0
+}
+
+/// Parse a whole CSS stylesheet, also returning the syntax errors found in it, in order.
+///
+/// The parser recovers from errors as CSS specifies: an unsupported at-rule (anything but
+/// `@media`) is skipped, a rule with an invalid selector is dropped with its block, and an invalid
+/// declaration is dropped up to the next `;` or the end of its block. An invalid media query
+/// matches nothing. The rest of the stylesheet is kept.
+pub fn parse_with_errors(source: &str) -> (Stylesheet<'_>, Vec<ParseError>) {
+    let mut parser = Parser { pos: 0, input: source, errors: Vec::new(), media: Vec::new() };
+    let rules = parser.parse_rules(false);
+    (Stylesheet::new(rules), parser.errors)
+}
+
+/// Parse a whole CSS stylesheet in the given mode. In `Strict` mode, the first syntax error is
+/// returned instead of the stylesheet; in `Lenient` mode, this is `parse_with_errors`.
+pub fn parse_with_mode(source: &str, mode: ParseMode)
+    -> Result<(Stylesheet<'_>, Vec<ParseError>), ParseError>
+{
+    let (stylesheet, errors) = parse_with_errors(source);
+    match (mode, errors.first()) {
+        (ParseMode::Strict, Some(error)) => Err(error.clone()),
+        _ => Ok((stylesheet, errors)),
+    }
+}
+
+/// Parse the declarations of a `style` attribute, also returning the syntax errors found in them.
+pub fn parse_inline_style(source: &str) -> (Vec<Declaration<'_>>, Vec<ParseError>) {
+    let mut parser = Parser { pos: 0, input: source, errors: Vec::new(), media: Vec::new() };
+    let declarations = parser.parse_declaration_list(false);
+    (declarations, parser.errors)
 }
 
-struct Parser {
+/// Parse a comma-separated list of selectors on its own, such as `p, .note`, as
+/// `Node::query_selector` takes them.
+pub fn parse_selector_list(source: &str) -> Result<Vec<Selector<'_>>, ParseError> {
+    let mut parser = Parser { pos: 0, input: source, errors: Vec::new(), media: Vec::new() };
+    parser.consume_whitespace();
+    match parser.parse_selectors(None) {
+        Ok(selectors) => Ok(selectors),
+        Err(message) => {
+            parser.error(message);
+            Err(parser.errors.remove(0))
+        }
+    }
+}
+
+/// The default styles for HTML elements, with the `UserAgent` origin.
+pub fn user_agent_stylesheet() -> Stylesheet<'static> {
+    parse(include_str!("user_agent.css")).with_origin(Origin::UserAgent)
+}
+
+/// A syntax error in a stylesheet (or an HTML document), which the parser recovered from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    /// Line of the error, counting from 1.
+    pub line: usize,
+    /// Column of the error in characters, counting from 1.
+    pub column: usize,
+    pub message: String,
+}
+
+/// How a parser treats syntax errors, for the HTML and CSS parsers alike.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ParseMode {
+    /// Fail on the first error, as a linter would.
+    Strict,
+    /// Recover from every error, as a browser does, and report them all.
+    #[default]
+    Lenient,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// A pseudo-class or pseudo-element in a selector.
+enum Pseudo {
+    Class(PseudoClass),
+    Element(PseudoElement),
+}
+
+struct Parser<'a> {
     pos: usize,
-    input: String,
+    input: &'a str,
+    errors: Vec<ParseError>,
+    /// The query lists of the `@media` rules being parsed, outermost first.
+    media: Vec<MediaQueryList>,
 }
 
-impl Parser {
-    /// Parse a list of rule sets, separated by optional whitespace.
-    fn parse_rules(&mut self) -> Vec<Rule> {
+impl<'a> Parser<'a> {
+    /// Parse a list of rule sets, separated by optional whitespace, up to the end of the
+    /// enclosing block (if `in_block` is set) or of the input.
+    fn parse_rules(&mut self, in_block: bool) -> Vec<Rule<'a>> {
         let mut rules = Vec::new();
         loop {
             self.consume_whitespace();
-            if self.eof() { break }
-            rules.push(self.parse_rule());
+            match self.peek() {
+                None => {
+                    if in_block {
+                        self.error(String::from("unexpected end of stylesheet in @media"));
+                    }
+                    break;
+                }
+                Some('}') if in_block => {
+                    self.consume_char();
+                    break;
+                }
+                Some('@') => rules.extend(self.parse_at_rule()),
+                Some(_) => rules.extend(self.parse_rule()),
+            }
         }
         rules
     }
 
-    /// Parse a rule set: `<selectors> { <declarations> }`.
-    fn parse_rule(&mut self) -> Rule {
-        Rule {
-            selectors: self.parse_selectors(),
-            declarations: self.parse_declarations(),
+    /// Parse a rule set: `<selectors> { <declarations> }`. If a selector is invalid, skip the
+    /// rule and return `None`.
+    fn parse_rule(&mut self) -> Option<Rule<'a>> {
+        match self.parse_selectors(Some('{')) {
+            Ok(selectors) => Some(Rule {
+                selectors,
+                declarations: self.parse_declarations(),
+                origin: Origin::Author,
+                media: self.media.clone(),
+            }),
+            Err(message) => {
+                self.error(message);
+                self.skip_until(&['{']);
+                self.skip_block();
+                None
+            }
+        }
+    }
+
+    /// Parse an `@media` rule, returning the rules in it. Other at-rules are skipped, up to the
+    /// `;` or the block that ends them.
+    fn parse_at_rule(&mut self) -> Vec<Rule<'a>> {
+        let start = self.pos;
+        self.consume_char();
+        let name = self.parse_identifier();
+        if name.eq_ignore_ascii_case("media") {
+            return self.parse_media_rule();
         }
+        self.error_at(start, format!("unsupported at-rule @{}", name));
+        self.skip_until(&[';', '{']);
+        if self.eof() || self.next_char() == '{' {
+            self.skip_block();
+        } else {
+            self.consume_char();
+        }
+        Vec::new()
     }
 
-    /// Parse a comma-separated list of selectors.
-    fn parse_selectors(&mut self) -> Vec<Selector> {
+    /// Parse a comma-separated list of selectors, up to `end` (or the end of the input, if
+    /// `None`), which is not consumed.
+    fn parse_selectors(&mut self, end: Option<char>) -> Result<Vec<Selector<'a>>, String> {
         let mut selectors = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector()));
+            selectors.push(Selector::Simple(self.parse_simple_selector()?));
             self.consume_whitespace();
-            match self.next_char() {
-                ',' => { self.consume_char(); self.consume_whitespace(); }
-                '{' => break,
-                c   => panic!("Unexpected character {} in selector list", c)
+            match self.peek() {
+                Some(',') => { self.consume_char(); self.consume_whitespace(); }
+                next if next == end => break,
+                _ => return Err(format!("{} in selector list", self.unexpected())),
             }
         }
         // Return selectors with highest specificity first, for use in matching.
-        selectors.sort_by(|a,b| b.specificity().cmp(&a.specificity()));
-        selectors
+        selectors.sort_by_key(|s| Reverse(s.specificity()));
+        Ok(selectors)
     }
 
-    /// Parse one simple selector, e.g.: `type#id.class1.class2.class3`
-    fn parse_simple_selector(&mut self) -> SimpleSelector {
-        let mut selector = SimpleSelector { tag: None, id: None, class: Vec::new() };
-        while !self.eof() {
+    /// Parse one simple selector, e.g.: `type#id.class1.class2:focus`, optionally followed by a
+    /// pseudo-element.
+    fn parse_simple_selector(&mut self) -> Result<SimpleSelector<'a>, String> {
+        let start = self.pos;
+        let mut selector = SimpleSelector {
+            tag: None,
+            id: None,
+            class: Vec::new(),
+            pseudo_classes: Vec::new(),
+            pseudo_element: None,
+        };
+        while !self.eof() && selector.pseudo_element.is_none() {
             match self.next_char() {
                 '#' => {
                     self.consume_char();
-                    selector.id = Some(self.parse_identifier());
+                    selector.id = Some(self.parse_name()?.into());
                 }
                 '.' => {
                     self.consume_char();
-                    selector.class.push(self.parse_identifier());
+                    selector.class.push(self.parse_name()?.into());
                 }
                 '*' => {
                     // universal selector
                     self.consume_char();
                 }
+                ':' => match self.parse_pseudo()? {
+                    Pseudo::Class(pseudo_class) => selector.pseudo_classes.push(pseudo_class),
+                    Pseudo::Element(pseudo_element) => {
+                        selector.pseudo_element = Some(pseudo_element);
+                    }
+                },
                 c if valid_identifier_char(c) => {
-                    selector.tag = Some(self.parse_identifier());
+                    selector.tag = Some(self.parse_identifier().into());
                 }
                 _ => break
             }
         }
-        selector
+        if self.pos == start {
+            return Err(format!("{} instead of a selector", self.unexpected()));
+        }
+        Ok(selector)
+    }
+
+    /// Parse a pseudo-class, such as `:focus`, or a pseudo-element, written `::before` or (as in
+    /// CSS 2) `:before`.
+    fn parse_pseudo(&mut self) -> Result<Pseudo, String> {
+        let start = self.pos;
+        self.expect(':')?;
+        let double_colon = self.peek() == Some(':');
+        if double_colon {
+            self.consume_char();
+        }
+        match (&*self.parse_name()?.to_ascii_lowercase(), double_colon) {
+            ("focus", false) => Ok(Pseudo::Class(PseudoClass::Focus)),
+            ("before", _) => Ok(Pseudo::Element(PseudoElement::Before)),
+            ("after", _) => Ok(Pseudo::Element(PseudoElement::After)),
+            _ => {
+                let name = &self.input[start..self.pos];
+                let kind = if double_colon { "pseudo-element" } else { "pseudo-class" };
+                let message = format!("unsupported {} {}", kind, name);
+                self.pos = start;
+                Err(message)
+            }
+        }
     }
 
-    /// Parse a list of declarations enclosed in `{ ... }`.
-    fn parse_declarations(&mut self) -> Vec<Declaration> {
+    /// Parse the query list and the block of rules of an `@media` rule, after the name.
+    fn parse_media_rule(&mut self) -> Vec<Rule<'a>> {
+        let queries = self.parse_media_query_list().unwrap_or_else(|message| {
+            self.error(message);
+            self.skip_until(&[';', '{']);
+            MediaQueryList(vec![MediaQuery::nothing()])
+        });
+        if self.peek() != Some('{') {
+            self.error(format!("expected '{{' but found {}", self.found()));
+            if !self.eof() {
+                self.consume_char();
+            }
+            return Vec::new();
+        }
+        self.consume_char();
+        self.media.push(queries);
+        let rules = self.parse_rules(true);
+        self.media.pop();
+        rules
+    }
+
+    /// Parse comma-separated media queries, up to the `{` of the block.
+    fn parse_media_query_list(&mut self) -> Result<MediaQueryList, String> {
+        let mut queries = Vec::new();
+        self.consume_whitespace();
+        while !matches!(self.peek(), Some('{') | None) {
+            queries.push(self.parse_media_query()?);
+            self.consume_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.consume_char();
+                    self.consume_whitespace();
+                }
+                Some('{') | None => {}
+                _ => return Err(format!("{} in media query", self.unexpected())),
+            }
+        }
+        Ok(MediaQueryList(queries))
+    }
+
+    /// Parse `[not | only] <type> [and <feature>]*` or `<feature> [and <feature>]*`. A query
+    /// for an unknown media type (such as `tv`) is valid, but matches nothing.
+    fn parse_media_query(&mut self) -> Result<MediaQuery, String> {
+        let mut query = MediaQuery { negated: false, media_type: None, features: Vec::new() };
+        let mut known = true;
+        if self.peek() == Some('(') {
+            query.features.push(self.parse_media_feature()?);
+        } else {
+            let mut name = self.parse_name()?;
+            if name.eq_ignore_ascii_case("not") || name.eq_ignore_ascii_case("only") {
+                query.negated = name.eq_ignore_ascii_case("not");
+                self.consume_whitespace();
+                name = self.parse_name()?;
+            }
+            match &*name.to_ascii_lowercase() {
+                "all" => {}
+                "screen" => query.media_type = Some(MediaType::Screen),
+                "print" => query.media_type = Some(MediaType::Print),
+                _ => known = false,
+            }
+        }
+        loop {
+            self.consume_whitespace();
+            if matches!(self.peek(), Some(',') | Some('{') | None) {
+                break;
+            }
+            if !self.parse_identifier().eq_ignore_ascii_case("and") {
+                return Err(format!("expected 'and' in media query but found {}", self.found()));
+            }
+            self.consume_whitespace();
+            query.features.push(self.parse_media_feature()?);
+        }
+        // `not` turns a query for an unknown type into one that matches everything.
+        Ok(match (known, query.negated) {
+            (true, _) => query,
+            (false, false) => MediaQuery::nothing(),
+            (false, true) => MediaQuery { negated: false, media_type: None, features: Vec::new() },
+        })
+    }
+
+    /// Parse a media feature in parentheses, such as `(max-width: 600px)`.
+    fn parse_media_feature(&mut self) -> Result<MediaFeature, String> {
+        self.expect('(')?;
+        self.consume_whitespace();
+        let name = self.parse_name()?;
+        let feature: fn(f32) -> MediaFeature = match &*name.to_ascii_lowercase() {
+            "min-width" => MediaFeature::MinWidth,
+            "max-width" => MediaFeature::MaxWidth,
+            "min-height" => MediaFeature::MinHeight,
+            "max-height" => MediaFeature::MaxHeight,
+            _ => return Err(format!("unsupported media feature {}", name)),
+        };
+        self.consume_whitespace();
+        self.expect(':')?;
+        self.consume_whitespace();
+        let length = match self.parse_value()? {
+            Value::Length(px, Unit::Px) => px,
+            Value::Number(0.0) => 0.0,
+            value => return Err(format!("expected length in media feature but found {}", value)),
+        };
+        self.consume_whitespace();
+        self.expect(')')?;
+        Ok(feature(length))
+    }
+
+    /// Parse a list of declarations enclosed in `{ ... }`, skipping invalid ones.
+    fn parse_declarations(&mut self) -> Vec<Declaration<'a>> {
         assert_eq!(self.consume_char(), '{');
+        self.parse_declaration_list(true)
+    }
+
+    /// Parse declarations up to the end of the enclosing block (if `in_block` is set) or of the
+    /// input, skipping invalid ones.
+    fn parse_declaration_list(&mut self, in_block: bool) -> Vec<Declaration<'a>> {
         let mut declarations = Vec::new();
         loop {
             self.consume_whitespace();
-            if self.next_char() == '}' {
-                self.consume_char();
-                break;
+            match self.peek() {
+                None => {
+                    if in_block {
+                        self.error(String::from("unexpected end of stylesheet in rule"));
+                    }
+                    break;
+                }
+                Some('}') if in_block => {
+                    self.consume_char();
+                    break;
+                }
+                Some('}') => {
+                    self.error(self.unexpected());
+                    self.consume_char();
+                }
+                Some(';') => {
+                    self.consume_char();
+                }
+                Some(_) => match self.parse_declaration() {
+                    Ok(declaration) => declarations.push(declaration),
+                    Err(message) => {
+                        self.error(message);
+                        self.skip_until(&[';', '}']);
+                    }
+                },
             }
-            declarations.push(self.parse_declaration());
         }
         declarations
     }
 
-    /// Parse one `<property>: <value>;` declaration.
-    fn parse_declaration(&mut self) -> Declaration {
-        let property_name = self.parse_identifier();
+    /// Parse one `<property>: <value> [!important];` declaration. The `;` may be left out of the
+    /// last declaration in a block.
+    fn parse_declaration(&mut self) -> Result<Declaration<'a>, String> {
+        let property_name = self.parse_name()?;
         self.consume_whitespace();
-        assert_eq!(self.consume_char(), ':');
+        self.expect(':')?;
+        let value = self.parse_values()?;
+        let important = self.parse_important()?;
+        if self.peek() == Some(';') {
+            self.consume_char();
+        }
+
+        Ok(Declaration {
+            name: property_name.into(),
+            value,
+            important,
+        })
+    }
+
+    /// Parse `!important` at the end of a declaration, if it is there.
+    fn parse_important(&mut self) -> Result<bool, String> {
+        if self.peek() != Some('!') {
+            return Ok(false);
+        }
+        self.consume_char();
         self.consume_whitespace();
-        let value = self.parse_value();
+        if !self.parse_identifier().eq_ignore_ascii_case("important") {
+            return Err(String::from("expected 'important' after '!'"));
+        }
         self.consume_whitespace();
-        assert_eq!(self.consume_char(), ';');
-
-        Declaration {
-            name: property_name,
-            value: value,
+        match self.peek() {
+            None | Some(';') | Some('}') => Ok(true),
+            _ => Err(format!("{} after !important", self.unexpected())),
         }
     }
 
     // Methods for parsing values:
 
-    fn parse_value(&mut self) -> Value {
+    /// Parse the component values of a declaration, up to the closing `;` or `!important`.
+    fn parse_values(&mut self) -> Result<Value<'a>, String> {
+        let mut values = Vec::new();
+        loop {
+            self.consume_whitespace();
+            match self.peek() {
+                None | Some(';') | Some('}') | Some('!') => break,
+                _ => values.push(self.parse_value()?),
+            }
+        }
+        match values.len() {
+            0 => Err(String::from("expected a value")),
+            1 => Ok(values.swap_remove(0)),
+            _ => Ok(Value::List(values)),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value<'a>, String> {
         match self.next_char() {
-            '0'...'9' => self.parse_length(),
+            '0'..='9' => self.parse_number(),
+            '-' if self.input[self.pos + 1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                self.parse_number()
+            }
             '#' => self.parse_color(),
-            _ => Value::Keyword(self.parse_identifier())
+            '"' | '\'' => self.parse_string(),
+            '/' | ',' | '+' | '*' => Ok(Value::Delimiter(self.consume_char())),
+            // A `-` on its own is the subtraction operator of `calc()`.
+            '-' if self.input[self.pos + 1..].starts_with(char::is_whitespace) => {
+                Ok(Value::Delimiter(self.consume_char()))
+            }
+            '(' => self.parse_function(""),
+            c if valid_identifier_char(c) => {
+                let name = self.parse_identifier();
+                if self.peek() == Some('(') {
+                    self.parse_function(name)
+                } else {
+                    Ok(Value::Keyword(name.into()))
+                }
+            }
+            _ => Err(format!("{} in value", self.unexpected())),
         }
     }
 
-    fn parse_length(&mut self) -> Value {
-        Value::Length(self.parse_float(), self.parse_unit())
+    /// Parse the arguments of a function, from the opening parenthesis to the closing one.
+    fn parse_function(&mut self, name: &'a str) -> Result<Value<'a>, String> {
+        assert_eq!(self.consume_char(), '(');
+        let mut arguments = Vec::new();
+        loop {
+            self.consume_whitespace();
+            match self.peek() {
+                Some(')') => {
+                    self.consume_char();
+                    return Ok(Value::Function(name.into(), arguments));
+                }
+                None | Some(';') | Some('}') => {
+                    return Err(format!("{} in the arguments of {}()", self.unexpected(), name));
+                }
+                _ => arguments.push(self.parse_value()?),
+            }
+        }
     }
 
-    fn parse_float(&mut self) -> f32 {
-        let s = self.consume_while(|c| match c {
-            '0'...'9' | '.' => true,
-            _ => false
-        });
-        s.parse().unwrap()
+    /// Parse a number, which is a length if it has a unit.
+    fn parse_number(&mut self) -> Result<Value<'a>, String> {
+        let number = self.parse_float()?;
+        Ok(match self.peek() {
+            Some('%') => {
+                self.consume_char();
+                Value::Percentage(number)
+            }
+            Some(c) if valid_identifier_char(c) => Value::Length(number, self.parse_unit()?),
+            _ => Value::Number(number),
+        })
+    }
+
+    fn parse_float(&mut self) -> Result<f32, String> {
+        let start = self.pos;
+        let sign = if self.next_char() == '-' { self.consume_char(); -1.0 } else { 1.0 };
+        let s = self.consume_while(|c| matches!(c, '0'..='9' | '.'));
+        match s.parse::<f32>() {
+            Ok(number) => Ok(sign * number),
+            Err(_) => {
+                let message = format!("invalid number {}", &self.input[start..self.pos]);
+                self.pos = start;
+                Err(message)
+            }
+        }
     }
 
-    fn parse_unit(&mut self) -> Unit {
+    fn parse_unit(&mut self) -> Result<Unit, String> {
+        let start = self.pos;
         match &*self.parse_identifier().to_ascii_lowercase() {
-            "px" => Unit::Px,
-            _ => panic!("unrecognized unit")
+            "px" => Ok(Unit::Px),
+            "fr" => Ok(Unit::Fr),
+            "deg" => Ok(Unit::Deg),
+            unit => {
+                let message = format!("unrecognized unit {}", unit);
+                self.pos = start;
+                Err(message)
+            }
         }
     }
 
-    fn parse_color(&mut self) -> Value {
+    fn parse_color(&mut self) -> Result<Value<'a>, String> {
         assert_eq!(self.consume_char(), '#');
-        Value::ColorValue(Color {
-            r: self.parse_hex_pair(),
-            g: self.parse_hex_pair(),
-            b: self.parse_hex_pair(),
+        Ok(Value::ColorValue(Color {
+            r: self.parse_hex_pair()?,
+            g: self.parse_hex_pair()?,
+            b: self.parse_hex_pair()?,
             a: 255
-        })
+        }))
+    }
+
+    /// Parse a string in double or single quotes. A backslash escapes the character after it.
+    fn parse_string(&mut self) -> Result<Value<'a>, String> {
+        let start = self.pos;
+        let quote = self.consume_char();
+        let mut value = Cow::Borrowed("");
+        let mut run = self.pos;
+        loop {
+            match self.peek() {
+                None | Some('\n') => {
+                    self.pos = start;
+                    return Err(String::from("unterminated string"));
+                }
+                Some('\\') => {
+                    value.to_mut().push_str(&self.input[run..self.pos]);
+                    self.consume_char();
+                    run = self.pos;
+                    if self.eof() {
+                        continue;
+                    }
+                    self.consume_char();
+                }
+                Some(c) if c == quote => break,
+                Some(_) => {
+                    self.consume_char();
+                }
+            }
+        }
+        match value {
+            Cow::Borrowed(_) => value = Cow::Borrowed(&self.input[run..self.pos]),
+            Cow::Owned(ref mut value) => value.push_str(&self.input[run..self.pos]),
+        }
+        self.consume_char();
+        Ok(Value::String(value))
     }
 
     /// Parse two hexadecimal digits.
-    fn parse_hex_pair(&mut self) -> u8 {
-        let s = &self.input[self.pos .. self.pos + 2];
-        self.pos += 2;
-        u8::from_str_radix(s, 16).unwrap()
+    fn parse_hex_pair(&mut self) -> Result<u8, String> {
+        let pair = self.input.get(self.pos .. self.pos + 2)
+            .filter(|s| s.chars().all(|c| c.is_ascii_hexdigit()));
+        match pair {
+            Some(s) => {
+                self.pos += 2;
+                Ok(u8::from_str_radix(s, 16).unwrap())
+            }
+            None => Err(String::from("expected six hexadecimal digits in color")),
+        }
     }
 
     /// Parse a property name or keyword.
-    fn parse_identifier(&mut self) -> String {
+    fn parse_identifier(&mut self) -> &'a str {
         self.consume_while(valid_identifier_char)
     }
 
-    /// Consume and discard zero or more whitespace characters.
+    /// Parse a property name or keyword, which must not be empty.
+    fn parse_name(&mut self) -> Result<&'a str, String> {
+        match self.parse_identifier() {
+            "" => Err(format!("{} instead of a name", self.unexpected())),
+            name => Ok(name),
+        }
+    }
+
+    /// Consume the character `c`, or fail if the next character is another one.
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.consume_char();
+            Ok(())
+        } else {
+            Err(format!("expected '{}' but found {}", c, self.found()))
+        }
+    }
+
+    /// Skip to the next of the `stops` characters that is not nested in a block, or to the end of
+    /// the input.
+    fn skip_until(&mut self, stops: &[char]) {
+        let mut closers = Vec::new();
+        while let Some(c) = self.peek() {
+            if closers.is_empty() && stops.contains(&c) {
+                break;
+            }
+            self.consume_char();
+            match c {
+                '{' => closers.push('}'),
+                '(' => closers.push(')'),
+                '[' => closers.push(']'),
+                '}' | ')' | ']' if closers.last() == Some(&c) => { closers.pop(); }
+                _ => {}
+            }
+        }
+    }
+
+    /// Skip a `{ ... }` block, if there is one.
+    fn skip_block(&mut self) {
+        if self.peek() == Some('{') {
+            self.consume_char();
+            self.skip_until(&['}']);
+            if !self.eof() {
+                self.consume_char();
+            }
+        }
+    }
+
+    /// Consume and discard zero or more whitespace characters and comments.
     fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+        loop {
+            self.consume_while(char::is_whitespace);
+            if !self.input[self.pos..].starts_with("/*") {
+                break;
+            }
+            match self.input[self.pos + 2..].find("*/") {
+                Some(end) => self.pos += end + 4,
+                None => {
+                    self.error(String::from("unterminated comment"));
+                    self.pos = self.input.len();
+                }
+            }
+        }
     }
 
-    /// Consume characters until `test` returns false.
-    fn consume_while<F>(&mut self, test: F) -> String
+    /// Consume characters until `test` returns false, returning the consumed slice of the input.
+    fn consume_while<F>(&mut self, test: F) -> &'a str
             where F: Fn(char) -> bool {
-        let mut result = String::new();
+        let start = self.pos;
         while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
+            self.consume_char();
         }
-        result
+        &self.input[start..self.pos]
     }
 
     /// Return the current character, and advance self.pos to the next character.
     fn consume_char(&mut self) -> char {
-        let mut iter = self.input[self.pos..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
-        self.pos += next_pos;
+        let cur_char = self.next_char();
+        self.pos += cur_char.len_utf8();
         cur_char
     }
 
@@ -422,15 +1272,46 @@ impl Parser {
         self.input[self.pos..].chars().next().unwrap()
     }
 
+    /// Read the current character without consuming it, or `None` at the end of the input.
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
     /// Return true if all input is consumed.
     fn eof(&self) -> bool {
         self.pos >= self.input.len()
     }
+
+    /// Describe the current character, for an error message.
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("'{}'", c),
+            None => String::from("end of stylesheet"),
+        }
+    }
+
+    /// Describe the current character as unexpected, for an error message.
+    fn unexpected(&self) -> String {
+        format!("unexpected {}", self.found())
+    }
+
+    /// Record an error at the current position.
+    fn error(&mut self, message: String) {
+        self.error_at(self.pos, message);
+    }
+
+    /// Record an error at the byte offset `pos`.
+    fn error_at(&mut self, pos: usize, message: String) {
+        let before = &self.input[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        self.errors.push(ParseError { line, column, message });
+    }
 }
 
 fn valid_identifier_char(c: char) -> bool {
     match c {
-        'a'...'z' | 'A'...'Z' | '0'...'9' | '-' | '_' => true, // TODO: Include U+00A0 and higher.
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true, // TODO: Include U+00A0 and higher.
         _ => false,
     }
 }
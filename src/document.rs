@@ -0,0 +1,286 @@
+//! A document and everything needed to render it, behind one type.
+//!
+//! `Document` wires together the stages that otherwise have to be called one by one: parsing the
+//! HTML and CSS, cascading them with the user agent stylesheet and the document's own styles,
+//! layout, and painting.
+
+pub use css::MediaType;
+
+use css::{self, MediaContext, ParseError, ParseMode, Stylesheet};
+use dom::{Node, NodeId, NodeType};
+use engine::Engine;
+use html;
+use index::ElementIndex;
+use layout::LayoutContext;
+use limits::{LimitError, Limits};
+use paint::{Canvas, PaintOptions};
+use script::{self, ScriptError, ScriptHost};
+use std::fmt;
+use style::{self, Pixels};
+use widget::WidgetRegistry;
+
+/// The size of the viewport, in px.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    pub width: Pixels,
+    pub height: Pixels,
+}
+
+impl Default for Viewport {
+    fn default() -> Viewport {
+        Viewport { width: 800.0, height: 600.0 }
+    }
+}
+
+/// The properties of the device a document is rendered for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Media {
+    /// The kind of device, which `@media` rules can test.
+    pub media_type: MediaType,
+    /// Device pixels per px: `paint` renders the viewport at this scale.
+    pub device_pixel_ratio: f32,
+}
+
+impl Default for Media {
+    fn default() -> Media {
+        Media { media_type: MediaType::Screen, device_pixel_ratio: 1.0 }
+    }
+}
+
+/// The first syntax error in a document, from parsing it in `ParseMode::Strict`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SyntaxError {
+    /// An error in the HTML markup.
+    Html(ParseError),
+    /// An error in the author stylesheet or in the document's styles.
+    Css(ParseError),
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyntaxError::Html(ref error) => write!(f, "HTML {}", error),
+            SyntaxError::Css(ref error) => write!(f, "CSS {}", error),
+        }
+    }
+}
+
+/// A parsed HTML document with its author stylesheet, ready to be laid out and painted.
+///
+/// The document borrows from its HTML and CSS sources.
+pub struct Document<'a> {
+    root: Node<'a>,
+    index: ElementIndex,
+    css: &'a str,
+    media: Media,
+    limits: Limits,
+    paint_options: PaintOptions,
+    widgets: WidgetRegistry,
+    html_errors: Vec<ParseError>,
+    css_errors: Vec<ParseError>,
+    engine: Option<Engine>,
+}
+
+impl<'a> Document<'a> {
+    /// Parse an HTML document and its author stylesheet, recovering from syntax errors. The CSS
+    /// is parsed when the document is laid out, and the limits are checked then too; elements
+    /// are nested no more than `html::MAX_NESTING_DEPTH` deep meanwhile.
+    pub fn load_html(html: &'a str, css: &'a str) -> Document<'a> {
+        let (root, html_errors) = html::parse_with_errors(html);
+        Document {
+            index: ElementIndex::new(&root),
+            root,
+            css,
+            media: Media::default(),
+            limits: Limits::default(),
+            paint_options: PaintOptions::default(),
+            widgets: WidgetRegistry::new(),
+            html_errors,
+            css_errors: Vec::new(),
+            engine: None,
+        }
+    }
+
+    /// Parse an HTML document and its author stylesheet in the given mode.
+    ///
+    /// In `Strict` mode, the HTML, the author stylesheet, and the document's styles are checked
+    /// now, in that order, and the first syntax error is returned. In `Lenient` mode, this is
+    /// `load_html`, and never fails.
+    pub fn load_html_with_mode(html: &'a str, css: &'a str, mode: ParseMode)
+        -> Result<Document<'a>, SyntaxError>
+    {
+        let document = Document::load_html(html, css);
+        if mode == ParseMode::Strict {
+            if let Some(error) = document.html_errors.first() {
+                return Err(SyntaxError::Html(error.clone()));
+            }
+            if let Some(error) = parse_css(&document.root, document.css).1.into_iter().next() {
+                return Err(SyntaxError::Css(error));
+            }
+        }
+        Ok(document)
+    }
+
+    /// The root of the DOM.
+    pub fn root(&self) -> &Node<'a> {
+        &self.root
+    }
+
+    /// The element with the given id attribute (the first in document order, if several have it).
+    pub fn element_by_id(&self, id: &str) -> Option<&Node<'a>> {
+        self.index.get(&self.root, self.index.element_by_id(id)?)
+    }
+
+    /// The first element that matches a comma-separated list of selectors, like
+    /// `Node::query_selector`, found through the document's index of its elements.
+    pub fn query_selector(&self, selectors: &str) -> Option<&Node<'a>> {
+        self.index.query_selector(&self.root, selectors)
+    }
+
+    /// Every element that matches a list of selectors, in document order, like
+    /// `Node::query_selector_all`, found through the document's index of its elements.
+    pub fn query_selector_all(&self, selectors: &str) -> Vec<&Node<'a>> {
+        self.index.query_selector_all(&self.root, selectors)
+    }
+
+    /// The index of the document's elements by id, class, and type.
+    pub fn index(&self) -> &ElementIndex {
+        &self.index
+    }
+
+    /// Change the DOM with `mutation`, returning its result, and update the layout (if the
+    /// document has been laid out) to match.
+    ///
+    /// `changed` is the node whose subtree the mutation changes, as `Engine::update` takes it: for
+    /// example, the parent of an appended child. The index of the document's elements is updated
+    /// for that subtree. A change to the text of a `<style>` element restyles the whole document.
+    ///
+    /// If laying the changed document out exceeds the limits, the layout is dropped, as if
+    /// `layout` had not been called, and the next call to `layout` or `paint` returns the error.
+    pub fn mutate<F, R>(&mut self, changed: NodeId, mutation: F) -> R
+        where F: FnOnce(&mut Node<'a>) -> R
+    {
+        let styles = style_sources(&self.root);
+        let result = mutation(&mut self.root);
+        self.index.update(&self.root, changed);
+        if let Some(mut engine) = self.engine.take() {
+            let (stylesheet, errors) = parse_css(&self.root, self.css);
+            self.css_errors = errors;
+            let changed = if style_sources(&self.root) == styles { changed } else { self.root.id };
+            if engine.update(&self.root, &stylesheet, changed).is_ok() {
+                self.engine = Some(engine);
+            }
+        }
+        result
+    }
+
+    /// Run the document's scripts with `host`, like `script::run_scripts`, and update the index
+    /// of its elements and its layout (if it has been laid out) to match. Returns the errors of
+    /// the scripts that failed.
+    pub fn run_scripts(&mut self, host: &mut dyn ScriptHost) -> Vec<ScriptError> {
+        let root = self.root.id;
+        self.mutate(root, |root| script::run_scripts(root, host))
+    }
+
+    /// Set the properties of the device to render for.
+    pub fn set_media(&mut self, media: Media) {
+        self.media = media;
+    }
+
+    /// Set the limits on the resources used to lay out and paint the document. They apply from
+    /// the next call to `layout`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Set the options used to paint the document, such as the tile size.
+    pub fn set_paint_options(&mut self, options: PaintOptions) {
+        self.paint_options = options;
+        if let Some(ref mut engine) = self.engine {
+            engine.set_paint_options(options);
+        }
+    }
+
+    /// Set the widgets that draw elements with particular tag names. They apply from the next call
+    /// to `layout`.
+    pub fn set_widgets(&mut self, widgets: WidgetRegistry) {
+        self.widgets = widgets;
+    }
+
+    /// Style and lay out the document in a viewport, returning the engine that holds the result
+    /// (for scrolling, hit testing, and the like).
+    ///
+    /// The document is styled with the user agent stylesheet, the author stylesheet, and the
+    /// styles of its `<style>` elements and `style` attributes, in that order.
+    /// `@media` rules apply if they match the viewport and the media type of `set_media`.
+    pub fn layout(&mut self, viewport: Viewport) -> Result<&mut Engine, LimitError> {
+        let (stylesheet, errors) = parse_css(&self.root, self.css);
+        self.css_errors = errors;
+
+        let context = LayoutContext {
+            viewport_width: viewport.width,
+            viewport_height: viewport.height,
+        };
+        let mut engine = Engine::with_limits(&self.root, &stylesheet, context, self.limits)?;
+        let media = MediaContext { media_type: self.media.media_type, ..engine.media() };
+        engine.set_media(&self.root, &stylesheet, media)?;
+        engine.set_paint_options(self.paint_options);
+        if !self.widgets.is_empty() {
+            engine.set_widgets(&self.root, self.widgets.clone())?;
+        }
+        Ok(self.engine.insert(engine))
+    }
+
+    /// Paint the viewport at the device pixel ratio. The document is laid out in the default
+    /// viewport first if `layout` has not been called.
+    pub fn paint(&mut self) -> Result<Canvas, LimitError> {
+        if self.engine.is_none() {
+            self.layout(Viewport::default())?;
+        }
+        let engine = self.engine.as_ref().expect("document was just laid out");
+        engine.render_region(engine.viewport(), self.media.device_pixel_ratio)
+    }
+
+    /// The engine from the last call to `layout`, if any.
+    pub fn engine(&self) -> Option<&Engine> {
+        self.engine.as_ref()
+    }
+
+    /// The errors in the HTML markup, which the parser recovered from.
+    pub fn html_errors(&self) -> &[ParseError] {
+        &self.html_errors
+    }
+
+    /// The syntax errors in the author stylesheet and in the document's styles, as of the last
+    /// call to `layout`. The rules and declarations they affect were skipped.
+    pub fn css_errors(&self) -> &[ParseError] {
+        &self.css_errors
+    }
+}
+
+/// Parse the author stylesheet and the styles of the document at `root`, and combine them after
+/// the user agent stylesheet.
+fn parse_css<'b>(root: &'b Node, css: &'b str) -> (Stylesheet<'b>, Vec<ParseError>) {
+    let (author, mut errors) = css::parse_with_errors(css);
+    let (embedded, embedded_errors) = style::document_stylesheet(root, &[]);
+    errors.extend(embedded_errors.into_iter().map(|(_, error)| error));
+    (Stylesheet::combine(vec![css::user_agent_stylesheet(), author, embedded]), errors)
+}
+
+/// The text of the `<style>` elements of the document at `root`, in document order.
+fn style_sources(root: &Node) -> Vec<String> {
+    let mut sources = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        if let NodeType::Element(ref elem) = node.node_type {
+            if elem.tag == "style" {
+                sources.extend(node.children.iter().filter_map(|child| match child.node_type {
+                    NodeType::Text(ref text) => Some(text.to_string()),
+                    NodeType::Element(_) => None,
+                }));
+            }
+        }
+    }
+    sources
+}
@@ -1,51 +1,382 @@
 //! Basic DOM data structures.
+//!
+//! The tree can be changed after it is built through the methods of its root `Node`, which find
+//! nodes by `NodeId`. After a change, pass the node whose subtree changed to `Engine::update` to
+//! restyle and lay out just that subtree: the parent of added, removed, or replaced children, the
+//! element whose attributes changed, or the node whose text changed (the parent, for a text node).
 
+use css::{self, Selector};
+use html;
+use std::borrow::Cow;
 use std::collections::{HashMap,HashSet};
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use style;
 
-pub type AttrMap = HashMap<String, String>;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Attributes by name. Names and values borrow from the source document where possible.
+pub type AttrMap<'a> = HashMap<Cow<'a, str>, Cow<'a, str>>;
+
+/// Identifies a node, so that trees derived from the DOM can refer back to it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Allocate an identifier that no other node has.
+    pub fn next() -> NodeId {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// The identifier as a plain number, for code outside of Rust (such as a script engine) to
+    /// hold on to.
+    pub fn to_raw(self) -> u64 {
+        self.0 as u64
+    }
+
+    /// The identifier that a number from `to_raw` stands for.
+    pub fn from_raw(raw: u64) -> NodeId {
+        NodeId(raw as usize)
+    }
+}
 
 #[derive(Debug)]
-pub struct Node {
+pub struct Node<'a> {
     // data common to all nodes:
-    pub children: Vec<Node>,
+    pub id: NodeId,
+    pub children: Vec<Node<'a>>,
 
     // data specific to each node type:
-    pub node_type: NodeType,
+    pub node_type: NodeType<'a>,
 }
 
 #[derive(Debug)]
-pub enum NodeType {
-    Element(ElementData),
-    Text(String),
+pub enum NodeType<'a> {
+    Element(ElementData<'a>),
+    Text(Cow<'a, str>),
 }
 
+/// The namespace of SVG elements, such as an `<svg>` embedded in an HTML document.
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+/// The namespace of MathML elements.
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
 #[derive(Debug)]
-pub struct ElementData {
-    pub tag: String,
-    pub attributes: AttrMap,
+pub struct ElementData<'a> {
+    pub tag: Cow<'a, str>,
+    pub attributes: AttrMap<'a>,
+    /// The namespace of a foreign element, such as `SVG_NAMESPACE`, or `None` for an HTML element.
+    pub namespace: Option<Cow<'a, str>>,
+}
+
+/// Why a change to the DOM could not be made.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MutationError {
+    /// The node is not in the document.
+    NotFound,
+    /// The node is a text node, which has no children or attributes.
+    NotAnElement,
+    /// The node is not a child of the given parent.
+    NotAChild,
+    /// The index is past the end of the parent's children.
+    InvalidIndex,
+    /// The node would become its own descendant.
+    Cycle,
+}
+
+impl fmt::Display for MutationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MutationError::NotFound => write!(f, "node is not in the document"),
+            MutationError::NotAnElement => write!(f, "node is not an element"),
+            MutationError::NotAChild => write!(f, "node is not a child of the parent"),
+            MutationError::InvalidIndex => write!(f, "child index is out of range"),
+            MutationError::Cycle => write!(f, "node would contain itself"),
+        }
+    }
 }
 
 // Constructor functions for convenience:
 
-pub fn text(data: String) -> Node {
-    Node { children: vec![], node_type: NodeType::Text(data) }
+pub fn text<'a, S: Into<Cow<'a, str>>>(data: S) -> Node<'a> {
+    Node { id: NodeId::next(), children: vec![], node_type: NodeType::Text(data.into()) }
 }
 
-pub fn elem(name: String, attrs: AttrMap, children: Vec<Node>) -> Node {
+pub fn elem<'a, S: Into<Cow<'a, str>>>(name: S, attrs: AttrMap<'a>, children: Vec<Node<'a>>)
+    -> Node<'a>
+{
     Node {
-        children: children,
+        id: NodeId::next(),
+        children,
         node_type: NodeType::Element(ElementData {
-            tag: name,
+            tag: name.into(),
             attributes: attrs,
+            namespace: None,
         })
     }
 }
 
+/// Like `elem`, but for a foreign element in the given namespace, such as `SVG_NAMESPACE`.
+pub fn elem_ns<'a, S, N>(namespace: N, name: S, attrs: AttrMap<'a>, children: Vec<Node<'a>>)
+    -> Node<'a>
+    where S: Into<Cow<'a, str>>, N: Into<Cow<'a, str>>
+{
+    let mut node = elem(name, attrs, children);
+    if let NodeType::Element(ref mut elem) = node.node_type {
+        elem.namespace = Some(namespace.into());
+    }
+    node
+}
+
+/// Normalize an HTML tag or attribute name, which is ASCII case-insensitive, to lowercase. The
+/// name is borrowed rather than copied if it is lowercase already.
+pub fn lowercase_name<'a, S: Into<Cow<'a, str>>>(name: S) -> Cow<'a, str> {
+    let name = name.into();
+    if name.bytes().any(|b| b.is_ascii_uppercase()) {
+        name.to_ascii_lowercase().into()
+    } else {
+        name
+    }
+}
+
+// Tree methods
+
+impl<'a> Node<'a> {
+    /// The node `id` in this subtree, if any.
+    pub fn find(&self, id: NodeId) -> Option<&Node<'a>> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+
+    /// The node `id` in this subtree, if any, for changing it.
+    pub fn find_mut(&mut self, id: NodeId) -> Option<&mut Node<'a>> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|child| child.find_mut(id))
+    }
+
+    /// The parent of the node `id` in this subtree, if the node is in it and is not this node.
+    pub fn parent(&self, id: NodeId) -> Option<&Node<'a>> {
+        if self.children.iter().any(|child| child.id == id) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.parent(id))
+    }
+
+    /// Add `child` after the other children of the element `parent`.
+    pub fn append_child(&mut self, parent: NodeId, child: Node<'a>) -> Result<(), MutationError> {
+        let parent = self.find_element_mut(parent)?;
+        parent.children.push(child);
+        Ok(())
+    }
+
+    /// Insert `child` among the children of the element `parent`, before the one at `index`.
+    pub fn insert_child(&mut self, parent: NodeId, index: usize, child: Node<'a>)
+        -> Result<(), MutationError>
+    {
+        let parent = self.find_element_mut(parent)?;
+        if index > parent.children.len() {
+            return Err(MutationError::InvalidIndex);
+        }
+        parent.children.insert(index, child);
+        Ok(())
+    }
+
+    /// Remove the node `child` from the children of `parent`, returning it.
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId)
+        -> Result<Node<'a>, MutationError>
+    {
+        let parent = self.find_mut(parent).ok_or(MutationError::NotFound)?;
+        let index = parent.child_index(child)?;
+        Ok(parent.children.remove(index))
+    }
+
+    /// Put `new` in the place of the node `old` among the children of `parent`, returning `old`.
+    pub fn replace_child(&mut self, parent: NodeId, old: NodeId, new: Node<'a>)
+        -> Result<Node<'a>, MutationError>
+    {
+        let parent = self.find_mut(parent).ok_or(MutationError::NotFound)?;
+        let index = parent.child_index(old)?;
+        Ok(mem::replace(&mut parent.children[index], new))
+    }
+
+    /// Set an attribute of the element `id`, returning its previous value, if any. The names of
+    /// the attributes of HTML elements are lowercased, as the parser does.
+    pub fn set_attribute<N, V>(&mut self, id: NodeId, name: N, value: V)
+        -> Result<Option<Cow<'a, str>>, MutationError>
+        where N: Into<Cow<'a, str>>, V: Into<Cow<'a, str>>
+    {
+        let elem = self.find_element_data_mut(id)?;
+        let name = if elem.is_html() { lowercase_name(name) } else { name.into() };
+        Ok(elem.attributes.insert(name, value.into()))
+    }
+
+    /// Remove an attribute of the element `id`, returning its value, if it had one.
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str)
+        -> Result<Option<Cow<'a, str>>, MutationError>
+    {
+        let elem = self.find_element_data_mut(id)?;
+        let name = if elem.is_html() { lowercase_name(name) } else { name.into() };
+        Ok(elem.attributes.remove(&*name))
+    }
+
+    /// Set the text of the node `id`. A text node's data is replaced; an element's children are
+    /// replaced by a single text node, or by nothing if `text` is empty.
+    pub fn set_text<S: Into<Cow<'a, str>>>(&mut self, id: NodeId, text: S)
+        -> Result<(), MutationError>
+    {
+        let node = self.find_mut(id).ok_or(MutationError::NotFound)?;
+        let text = text.into();
+        match node.node_type {
+            NodeType::Text(ref mut data) => *data = text,
+            NodeType::Element(_) => {
+                node.children.clear();
+                if !text.is_empty() {
+                    node.children.push(self::text(text));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The first element in this subtree (this node included, then its descendants in document
+    /// order) that matches a comma-separated list of selectors, such as `p, .note`. An invalid
+    /// list matches no element; see `css::parse_selector_list`.
+    pub fn query_selector(&self, selectors: &str) -> Option<&Node<'a>> {
+        let selectors = css::parse_selector_list(selectors).ok()?;
+        let mut found = None;
+        self.visit_matching(&selectors, &mut |node| {
+            found = Some(node);
+            false
+        });
+        found
+    }
+
+    /// Every element in this subtree that matches a list of selectors, like `query_selector`, in
+    /// document order.
+    pub fn query_selector_all(&self, selectors: &str) -> Vec<&Node<'a>> {
+        let selectors = match css::parse_selector_list(selectors) {
+            Ok(selectors) => selectors,
+            Err(_) => return Vec::new(),
+        };
+        let mut found = Vec::new();
+        self.visit_matching(&selectors, &mut |node| {
+            found.push(node);
+            true
+        });
+        found
+    }
+
+    /// Call `visit` on the elements of this subtree that match any of `selectors`, in document
+    /// order, while it returns true. Returns false if it stopped early.
+    fn visit_matching<'b, F>(&'b self, selectors: &[Selector], visit: &mut F) -> bool
+        where F: FnMut(&'b Node<'a>) -> bool
+    {
+        if let NodeType::Element(ref elem) = self.node_type {
+            if selectors.iter().any(|selector| style::matches_selector(elem, selector))
+                && !visit(self) {
+                return false;
+            }
+        }
+        self.children.iter().all(|child| child.visit_matching(selectors, visit))
+    }
+
+    /// Serialize this node and its descendants as HTML. Parsing the HTML of a parsed document
+    /// gives back the same tree.
+    ///
+    /// Text and attribute values are escaped, except the text of `<style>` and `<script>`
+    /// elements, which is written as it is. Attributes are double-quoted and sorted by name. Void
+    /// elements such as `<br>` have no closing tag (nor children, which HTML cannot give them),
+    /// and foreign elements without children are written as self-closing tags.
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        self.write_html(&mut out, false);
+        out
+    }
+
+    fn write_html(&self, out: &mut String, raw_text: bool) {
+        let elem = match self.node_type {
+            NodeType::Text(ref text) if raw_text => return out.push_str(text),
+            NodeType::Text(ref text) => return html::escape(out, text, false),
+            NodeType::Element(ref elem) => elem,
+        };
+        out.push('<');
+        out.push_str(&elem.tag);
+        let mut attributes: Vec<_> = elem.attributes.iter().collect();
+        attributes.sort();
+        for (name, value) in attributes {
+            out.push(' ');
+            out.push_str(name);
+            out.push_str("=\"");
+            html::escape(out, value, true);
+            out.push('"');
+        }
+        if !elem.is_html() && self.children.is_empty() {
+            return out.push_str("/>");
+        }
+        out.push('>');
+        if elem.is_html() && html::is_void_element(&elem.tag) {
+            return;
+        }
+        let raw_text = elem.is_html() && html::is_raw_text_element(&elem.tag);
+        for child in &self.children {
+            child.write_html(out, raw_text);
+        }
+        out.push_str("</");
+        out.push_str(&elem.tag);
+        out.push('>');
+    }
+
+    fn find_element_mut(&mut self, id: NodeId) -> Result<&mut Node<'a>, MutationError> {
+        let node = self.find_mut(id).ok_or(MutationError::NotFound)?;
+        match node.node_type {
+            NodeType::Element(_) => Ok(node),
+            NodeType::Text(_) => Err(MutationError::NotAnElement),
+        }
+    }
+
+    fn find_element_data_mut(&mut self, id: NodeId)
+        -> Result<&mut ElementData<'a>, MutationError>
+    {
+        match self.find_mut(id).ok_or(MutationError::NotFound)?.node_type {
+            NodeType::Element(ref mut elem) => Ok(elem),
+            NodeType::Text(_) => Err(MutationError::NotAnElement),
+        }
+    }
+
+    fn child_index(&self, child: NodeId) -> Result<usize, MutationError> {
+        self.children.iter().position(|node| node.id == child).ok_or(MutationError::NotAChild)
+    }
+}
+
 // Element methods
 
-impl ElementData {
-    pub fn id(&self) -> Option<&String> {
-        self.attributes.get("id")
+impl<'a> ElementData<'a> {
+    /// Is this an HTML element, whose tag and attribute names are ASCII case-insensitive?
+    pub fn is_html(&self) -> bool {
+        self.namespace.is_none()
+    }
+
+    /// Does this element have the given tag name? HTML tag names are compared ignoring ASCII
+    /// case, and those of foreign elements exactly.
+    pub fn has_tag(&self, name: &str) -> bool {
+        if self.is_html() {
+            self.tag.eq_ignore_ascii_case(name)
+        } else {
+            self.tag == name
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.attributes.get("id").map(|id| &**id)
     }
 
     pub fn classes(&self) -> HashSet<&str> {
@@ -0,0 +1,279 @@
+//! Editing the text of editable regions, for hosts that implement text input.
+//!
+//! An element whose `contenteditable` attribute is anything but `"false"` makes its subtree
+//! editable, up to any descendant that sets it to `"false"`. The host keeps the caret (a
+//! `clipboard::Position`), turns keystrokes and IME input into calls to `insert_text` and
+//! `delete`, and passes the changed node of each `Edit` to `Engine::update`, which restyles and
+//! lays out only what the edit touched.
+
+use clipboard::Position;
+use dom::{self, Node, NodeId, NodeType};
+use std::fmt;
+
+/// The result of an edit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Edit {
+    /// Where the caret is after the edit.
+    pub caret: Position,
+    /// The node whose subtree changed: the element holding the edited text.
+    pub changed: NodeId,
+}
+
+/// Why an edit could not be made.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EditError {
+    /// The caret's node is not in the document.
+    NotFound,
+    /// The caret is not in an editable region.
+    NotEditable,
+    /// The caret's offset is past the end of its node, or inside a character.
+    InvalidOffset,
+    /// There is no editable text before (or after) the caret to delete.
+    NothingToDelete,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditError::NotFound => write!(f, "caret is not in the document"),
+            EditError::NotEditable => write!(f, "caret is not in an editable region"),
+            EditError::InvalidOffset => write!(f, "caret offset is out of range"),
+            EditError::NothingToDelete => write!(f, "nothing to delete"),
+        }
+    }
+}
+
+/// Which character `delete` removes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeleteDirection {
+    /// The character before the caret, as with Backspace.
+    Backward,
+    /// The character after the caret, as with Delete.
+    Forward,
+}
+
+/// Make an element's subtree editable, or not, by setting its `contenteditable` attribute. Text
+/// nodes are left alone.
+pub fn set_editable(node: &mut Node, editable: bool) {
+    if let NodeType::Element(ref mut elem) = node.node_type {
+        let value = if editable { "true" } else { "false" };
+        elem.attributes.insert("contenteditable".into(), value.into());
+    }
+}
+
+/// Is the node `id` in an editable region? Returns `false` if it is not in the document.
+pub fn is_editable(root: &Node, id: NodeId) -> bool {
+    match find(root, id) {
+        Some(path) => editable_at(root, &path),
+        None => false,
+    }
+}
+
+/// Insert text at the caret.
+///
+/// A caret in a text node inserts into that node. A caret between the children of an element
+/// inserts into the text node next to it, or into a new text node if there is none. The returned
+/// caret is just after the inserted text.
+pub fn insert_text(root: &mut Node, caret: Position, text: &str) -> Result<Edit, EditError> {
+    let path = find(root, caret.node).ok_or(EditError::NotFound)?;
+    if !editable_at(root, &path) {
+        return Err(EditError::NotEditable);
+    }
+    let parent_id = parent(root, &path).map_or(caret.node, |parent| parent.id);
+    let node = node_at_mut(root, &path);
+    let offset = caret.offset;
+    let node_id = node.id;
+    match node.node_type {
+        NodeType::Text(ref mut data) => {
+            if !data.is_char_boundary(offset) {
+                return Err(EditError::InvalidOffset);
+            }
+            data.to_mut().insert_str(offset, text);
+            let caret = Position { node: node_id, offset: offset + text.len() };
+            return Ok(Edit { caret, changed: parent_id });
+        }
+        NodeType::Element(_) if offset > node.children.len() => {
+            return Err(EditError::InvalidOffset);
+        }
+        NodeType::Element(_) => {}
+    }
+
+    // Append to the text node before the caret, or prepend to the one after it.
+    if let Some(child) = offset.checked_sub(1).map(|i| &mut node.children[i]) {
+        if let NodeType::Text(ref mut data) = child.node_type {
+            data.to_mut().push_str(text);
+            let caret = Position { node: child.id, offset: data.len() };
+            return Ok(Edit { caret, changed: node_id });
+        }
+    }
+    if let Some(child) = node.children.get_mut(offset) {
+        if let NodeType::Text(ref mut data) = child.node_type {
+            data.to_mut().insert_str(0, text);
+            let caret = Position { node: child.id, offset: text.len() };
+            return Ok(Edit { caret, changed: node_id });
+        }
+    }
+    let child = dom::text(text.to_string());
+    let caret = Position { node: child.id, offset: text.len() };
+    node.children.insert(offset, child);
+    Ok(Edit { caret, changed: node_id })
+}
+
+/// Delete the character before or after the caret.
+///
+/// At the edge of a text node, the character is taken from the nearest editable text in that
+/// direction, within the same editable region. Characters are Unicode scalar values.
+pub fn delete(root: &mut Node, caret: Position, direction: DeleteDirection)
+    -> Result<Edit, EditError>
+{
+    let path = find(root, caret.node).ok_or(EditError::NotFound)?;
+    if !editable_at(root, &path) {
+        return Err(EditError::NotEditable);
+    }
+
+    // The editable text nodes of the region, in document order.
+    let host = editing_host(root, &path);
+    let mut texts = Vec::new();
+    collect_texts(root, &mut host.clone(), &mut texts);
+    texts.retain(|text| editable_at(root, text));
+
+    // Find the text the caret is in, or the text nodes on either side of it.
+    let (before, within) = match node_at(root, &path).node_type {
+        NodeType::Text(ref data) => {
+            if caret.offset > data.len() || !data.is_char_boundary(caret.offset) {
+                return Err(EditError::InvalidOffset);
+            }
+            let index = texts.iter().position(|text| *text == path).unwrap_or(texts.len());
+            (index, Some(caret.offset))
+        }
+        NodeType::Element(_) => {
+            if caret.offset > node_at(root, &path).children.len() {
+                return Err(EditError::InvalidOffset);
+            }
+            let mut point = path.clone();
+            point.push(caret.offset);
+            (texts.iter().filter(|text| **text < point).count(), None)
+        }
+    };
+
+    let (index, offset) = match (direction, within) {
+        (DeleteDirection::Backward, Some(offset)) if offset > 0 => (before, offset),
+        (DeleteDirection::Forward, Some(offset)) if offset < text_len(root, &texts[before]) => {
+            (before, offset)
+        }
+        (DeleteDirection::Backward, _) => {
+            let index = (0..before).rev().find(|&i| text_len(root, &texts[i]) > 0)
+                .ok_or(EditError::NothingToDelete)?;
+            (index, text_len(root, &texts[index]))
+        }
+        (DeleteDirection::Forward, _) => {
+            let start = if within.is_some() { before + 1 } else { before };
+            let index = (start..texts.len()).find(|&i| text_len(root, &texts[i]) > 0)
+                .ok_or(EditError::NothingToDelete)?;
+            (index, 0)
+        }
+    };
+
+    let text_path = &texts[index];
+    let changed = parent(root, text_path).map_or(caret.node, |parent| parent.id);
+    let node = node_at_mut(root, text_path);
+    let node_id = node.id;
+    let data = match node.node_type {
+        NodeType::Text(ref mut data) => data.to_mut(),
+        NodeType::Element(_) => unreachable!("not a text node"),
+    };
+    let offset = match direction {
+        DeleteDirection::Backward => {
+            let removed = data[..offset].chars().next_back().expect("no character before caret");
+            offset - removed.len_utf8()
+        }
+        DeleteDirection::Forward => offset,
+    };
+    data.remove(offset);
+    Ok(Edit { caret: Position { node: node_id, offset }, changed })
+}
+
+/// The path of child indices from the root to the node `id`.
+fn find(root: &Node, id: NodeId) -> Option<Vec<usize>> {
+    fn find_in(node: &Node, id: NodeId, path: &mut Vec<usize>) -> bool {
+        if node.id == id {
+            return true;
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            if find_in(child, id, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    let mut path = Vec::new();
+    if find_in(root, id, &mut path) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn node_at<'b, 'a>(root: &'b Node<'a>, path: &[usize]) -> &'b Node<'a> {
+    path.iter().fold(root, |node, &i| &node.children[i])
+}
+
+fn node_at_mut<'b, 'a>(root: &'b mut Node<'a>, path: &[usize]) -> &'b mut Node<'a> {
+    path.iter().fold(root, |node, &i| &mut node.children[i])
+}
+
+fn parent<'b, 'a>(root: &'b Node<'a>, path: &[usize]) -> Option<&'b Node<'a>> {
+    path.split_last().map(|(_, parent)| node_at(root, parent))
+}
+
+/// The `contenteditable` state an element sets for its subtree, if any.
+fn editable_attribute(node: &Node) -> Option<bool> {
+    match node.node_type {
+        NodeType::Element(ref elem) => {
+            elem.attributes.get("contenteditable").map(|value| !value.eq_ignore_ascii_case("false"))
+        }
+        NodeType::Text(_) => None,
+    }
+}
+
+/// Is the node at `path` editable: does its nearest ancestor (or itself) with a
+/// `contenteditable` attribute make it so?
+fn editable_at(root: &Node, path: &[usize]) -> bool {
+    (0..=path.len()).rev()
+        .find_map(|depth| editable_attribute(node_at(root, &path[..depth])))
+        .unwrap_or(false)
+}
+
+/// The path of the editing host of the editable node at `path`: the outermost element of the
+/// editable region it is in.
+fn editing_host(root: &Node, path: &[usize]) -> Vec<usize> {
+    let mut depth = path.len();
+    while depth > 0 && editable_at(root, &path[..depth - 1]) {
+        depth -= 1;
+    }
+    path[..depth].to_vec()
+}
+
+/// Collect the paths of the text nodes in the subtree at `path`, in document order.
+fn collect_texts(root: &Node, path: &mut Vec<usize>, texts: &mut Vec<Vec<usize>>) {
+    let node = node_at(root, path);
+    if let NodeType::Text(_) = node.node_type {
+        texts.push(path.clone());
+    }
+    for i in 0..node.children.len() {
+        path.push(i);
+        collect_texts(root, path, texts);
+        path.pop();
+    }
+}
+
+/// The length in bytes of the text node at `path`.
+fn text_len(root: &Node, path: &[usize]) -> usize {
+    match node_at(root, path).node_type {
+        NodeType::Text(ref data) => data.len(),
+        NodeType::Element(_) => 0,
+    }
+}
@@ -0,0 +1,506 @@
+//! The rendering pipeline, from a parsed document to pixels.
+
+use content_hash::{self, Stage};
+use css::{MediaContext, MediaType, Stylesheet};
+use dom::{Node, NodeId};
+use focus::{self, FocusEntry};
+use geometry::{Point, Rect, Size};
+use layout::{self, LayoutContext, LayoutTree};
+use limits::{LimitError, Limits};
+use links::{self, Link};
+use metrics::{self, SubtreeMetrics};
+use observer::{LayoutChange, LayoutObservers, ObserverId};
+use outline::{self, Heading};
+use paint::{self, Canvas, DisplayList, PaintOptions, SpriteSheet};
+use snapshot::SessionState;
+use style::{self, ElementStates, MatchCache, Pixels, StyledNode};
+use std::mem;
+use widget::WidgetRegistry;
+
+/// A document that has been styled and laid out, ready to be rendered.
+pub struct Engine {
+    context: LayoutContext,
+    /// The kind of device the document is rendered for, which `@media` rules can test.
+    media_type: MediaType,
+    limits: Limits,
+    paint_options: PaintOptions,
+    /// Device pixels per px, which `render` paints the viewport at.
+    scale_factor: f32,
+    style: StyledNode,
+    /// The rules that matched the elements, reused when they are restyled.
+    matches: MatchCache,
+    /// The states of the elements, such as which one has the focus.
+    states: ElementStates,
+    layout: LayoutTree,
+    /// Whether the last relayout exceeded the limits and was thrown away, so that the next one
+    /// must lay out the whole document.
+    needs_full_layout: bool,
+    display_list: DisplayList,
+    widgets: WidgetRegistry,
+    observers: LayoutObservers,
+    /// Offset of the viewport from the origin of the document.
+    scroll_position: (Pixels, Pixels),
+}
+
+/// A screen with the viewport of `context`.
+fn screen(context: LayoutContext) -> MediaContext {
+    MediaContext {
+        viewport: Size { width: context.viewport_width, height: context.viewport_height },
+        media_type: MediaType::Screen,
+    }
+}
+
+/// Where to put an element that is scrolled into view, along each axis.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScrollAlignment {
+    /// Align the start edges of the element and the viewport.
+    Start,
+    /// Center the element in the viewport.
+    Center,
+    /// Align the end edges of the element and the viewport.
+    End,
+    /// Scroll as little as possible: not at all if the element is already in view, and otherwise
+    /// to whichever of `Start` and `End` is closer.
+    Nearest,
+}
+
+impl Engine {
+    /// Style and lay out a document in the given viewport, on a screen, like `with_limits` with
+    /// no limits.
+    pub fn new(root: &Node, stylesheet: &Stylesheet, context: LayoutContext) -> Engine {
+        Engine::with_limits(root, stylesheet, context, Limits::unlimited())
+            .expect("a document cannot exceed no limits")
+    }
+
+    /// Style and lay out a document in the given viewport, unless it exceeds `limits`.
+    ///
+    /// The limits also apply to rendering, and to laying the document out again: after a change
+    /// of media, focus, widgets, or the document, a relayout that takes more than
+    /// `max_layouts` box layouts is abandoned, leaving the document without boxes until a later
+    /// relayout succeeds.
+    pub fn with_limits(root: &Node, stylesheet: &Stylesheet, context: LayoutContext,
+                       limits: Limits) -> Result<Engine, LimitError> {
+        limits.check_document(root, stylesheet)?;
+        let matches = MatchCache::new();
+        let media = screen(context);
+        let style_root = style::style_tree_with_media(root, stylesheet, &matches,
+                                                      ElementStates::default(), media);
+        let layout = layout::layout_tree_limited(&style_root, context, limits.max_layouts)?;
+        let display_list = layout::display_list(&layout);
+        Ok(Engine {
+            context,
+            media_type: media.media_type,
+            limits,
+            paint_options: PaintOptions::default(),
+            scale_factor: 1.0,
+            style: style_root,
+            matches,
+            states: ElementStates::default(),
+            layout,
+            needs_full_layout: false,
+            display_list,
+            widgets: WidgetRegistry::new(),
+            observers: LayoutObservers::new(),
+            scroll_position: (0.0, 0.0),
+        })
+    }
+
+    /// Set the options used to render the document, such as the tile size.
+    pub fn set_paint_options(&mut self, options: PaintOptions) {
+        self.paint_options = options;
+    }
+
+    /// Render the viewport at `scale` device pixels per px, such as 2 for a HiDPI screen. Layout
+    /// stays in px: only painting is scaled, with the edges of boxes snapped to device pixels.
+    pub fn set_scale_factor(&mut self, scale: f32) {
+        self.scale_factor = scale;
+    }
+
+    /// Device pixels per px that `render` paints the viewport at, 1 unless set.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Draw the elements with the registered tag names with their widgets, and lay out and paint
+    /// the document again.
+    ///
+    /// `root` must be the document this engine was created from.
+    pub fn set_widgets(&mut self, root: &Node, widgets: WidgetRegistry)
+        -> Result<(), LimitError>
+    {
+        let tree_widgets = widgets.widgets_for(root);
+        self.widgets = widgets;
+        self.relayout(root, |layout, style| {
+            layout.set_widgets(tree_widgets);
+            layout.relayout(style, root.id);
+        })
+    }
+
+    /// Update the engine after the subtree of the node `id` changed (for example, by an edit):
+    /// restyle that subtree, and lay out the document again, rebuilding only its boxes.
+    ///
+    /// Elements like ones matched before (of the same type, id, and classes) are not matched
+    /// against the stylesheet again, unless it changed.
+    ///
+    /// `root` must be the document this engine was created from, as changed, and `stylesheet`
+    /// the same stylesheet. The children of nodes outside the subtree must not have changed.
+    pub fn update(&mut self, root: &Node, stylesheet: &Stylesheet, id: NodeId)
+        -> Result<(), LimitError>
+    {
+        if self.style.mark_dirty(id) {
+            let media = self.media();
+            self.style.restyle_dirty_with_media(root, stylesheet, &self.matches, self.states,
+                                                media);
+        }
+        let widgets = match self.widgets.is_empty() {
+            true => None,
+            false => Some(self.widgets.widgets_for(root)),
+        };
+        self.relayout(root, |layout, style| {
+            if let Some(widgets) = widgets {
+                layout.set_widgets(widgets);
+            }
+            layout.relayout(style, id);
+        })
+    }
+
+    /// Lay the document out again with `relayout`, within the limits, and paint it again. If it
+    /// exceeds them, the unfinished layout is thrown away, leaving the document without boxes,
+    /// and the next relayout lays out the whole document instead.
+    fn relayout<F>(&mut self, root: &Node, relayout: F) -> Result<(), LimitError>
+        where F: FnOnce(&mut LayoutTree, &StyledNode)
+    {
+        let before = self.observers.snapshot(&self.layout);
+        let (layout, style, context) = (&mut self.layout, &self.style, self.context);
+        let result = if self.needs_full_layout {
+            let widgets = self.widgets.widgets_for(root);
+            layout::with_layout_budget(self.limits.max_layouts, || {
+                *layout = layout::layout_tree_with_widgets(style, context, widgets);
+            })
+        } else {
+            layout::with_layout_budget(self.limits.max_layouts, || relayout(layout, style))
+        };
+        self.needs_full_layout = result.is_err();
+        if self.needs_full_layout {
+            self.layout.clear();
+        }
+        self.display_list = layout::display_list(&self.layout);
+        self.observers.notify(before, &self.layout);
+        result
+    }
+
+    /// Call `callback` after every relayout (by `update`, `focus`, or `set_widgets`) with the
+    /// old and new geometry of the first box of each element that `filter` accepts and that
+    /// moved, changed size, or gained or lost its box. Returns an id to remove the observer with.
+    ///
+    /// Hosts can use this to keep native overlays, such as tooltips, anchored to elements.
+    pub fn on_layout_changed<F, C>(&mut self, filter: F, callback: C) -> ObserverId
+        where F: Fn(NodeId) -> bool + Send + Sync + 'static,
+              C: FnMut(&LayoutChange) + Send + Sync + 'static
+    {
+        self.observers.add(filter, callback)
+    }
+
+    /// Stop calling a layout observer, returning whether it was registered.
+    pub fn remove_layout_observer(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
+
+    /// The laid out document.
+    pub fn layout_tree(&self) -> &LayoutTree {
+        &self.layout
+    }
+
+    /// The display list of the whole document, before it is culled to the viewport.
+    pub fn display_list(&self) -> &DisplayList {
+        &self.display_list
+    }
+
+    /// A hash of the result of a rendering stage, which stays the same as long as the result
+    /// does: in other runs, processes, and engines, and after updates that change nothing.
+    pub fn content_hash(&self, stage: Stage) -> u64 {
+        match stage {
+            Stage::Style => content_hash::style_hash(&self.style),
+            Stage::Layout => content_hash::layout_hash(&self.layout),
+            Stage::DisplayList => content_hash::display_list_hash(&self.display_list),
+        }
+    }
+
+    /// The device the document is rendered for: the size of the viewport and the media type.
+    pub fn media(&self) -> MediaContext {
+        MediaContext { media_type: self.media_type, ..screen(self.context) }
+    }
+
+    /// Render the document for another device, such as a resized viewport or a printer:
+    /// restyle the document if any `@media` rule starts or stops applying, and lay it out again
+    /// in the new viewport. The scroll position is kept as far as the document still extends.
+    ///
+    /// `root` must be the document this engine was created from, and `stylesheet` the same
+    /// stylesheet.
+    pub fn set_media(&mut self, root: &Node, stylesheet: &Stylesheet, media: MediaContext)
+        -> Result<(), LimitError>
+    {
+        let previous = self.media();
+        if media == previous {
+            return Ok(());
+        }
+        let context = LayoutContext {
+            viewport_width: media.viewport.width,
+            viewport_height: media.viewport.height,
+        };
+        self.context = context;
+        self.media_type = media.media_type;
+        let restyled = stylesheet.media_changes(&previous, &media)
+            && self.style.mark_dirty(root.id);
+        if restyled {
+            self.style.restyle_dirty_with_media(root, stylesheet, &self.matches, self.states,
+                                                media);
+        }
+        let widgets = self.widgets.widgets_for(root);
+        let result = self.relayout(root, |layout, style| match restyled {
+            true => *layout = layout::layout_tree_with_widgets(style, context, widgets),
+            false => layout.set_context(context),
+        });
+        let (x, y) = self.scroll_position;
+        self.scroll_to(x, y);
+        result
+    }
+
+    /// The part of the document shown in the viewport.
+    pub fn viewport(&self) -> Rect {
+        let (x, y) = self.scroll_position;
+        Rect { x, y, width: self.context.viewport_width, height: self.context.viewport_height }
+    }
+
+    /// The offset of the viewport from the origin of the document.
+    pub fn scroll_position(&self) -> (Pixels, Pixels) {
+        self.scroll_position
+    }
+
+    /// Scroll the viewport to the given offset, as far as the document extends.
+    pub fn scroll_to(&mut self, x: Pixels, y: Pixels) {
+        let extent = self.extent();
+        let max_x = (extent.x - self.context.viewport_width).max(0.0);
+        let max_y = (extent.y - self.context.viewport_height).max(0.0);
+        self.scroll_position = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    }
+
+    /// The far corner of the document, which extends from the origin to the far edges of
+    /// everything it paints.
+    fn extent(&self) -> Point {
+        let items = self.display_list.items().iter().zip(self.display_list.transforms());
+        items.filter_map(|(item, transform)| transform.map(|t| t.bounding_rect(item.bounds())))
+            .chain(self.layout.root().map(|root| root.geometry().margin_box))
+            .fold(Rect::default(), Rect::union)
+            .max()
+    }
+
+    /// Scroll the viewport so that the border box of an element is in view, placed according to
+    /// `alignment` along both axes. Returns `false` if the element has no box.
+    ///
+    /// The viewport is the only scroll container, since there is no `overflow: scroll`.
+    pub fn scroll_into_view(&mut self, node: &Node, alignment: ScrollAlignment) -> bool {
+        let target = match self.layout.box_for_node(node) {
+            Some(geometry) => geometry.border_box,
+            None => return false,
+        };
+        let viewport = self.viewport();
+        let x = scroll_offset(viewport.x, viewport.width, target.x, target.width, alignment);
+        let y = scroll_offset(viewport.y, viewport.height, target.y, target.height, alignment);
+        self.scroll_to(x, y);
+        true
+    }
+
+    /// The hyperlinks of the document and the rectangles they cover, with their URLs resolved
+    /// against `base_url`, for output formats that can make them clickable.
+    ///
+    /// `root` must be the document this engine was created from.
+    pub fn links(&self, root: &Node, base_url: Option<&str>) -> Vec<Link> {
+        links::links(root, &self.layout, base_url)
+    }
+
+    /// The headings of the document, nested by rank, with their positions, for a table of
+    /// contents. `scroll_into_view` can jump to any of them.
+    ///
+    /// `root` must be the document this engine was created from.
+    pub fn outline(&self, root: &Node) -> Vec<Heading> {
+        outline::outline(root, &self.layout)
+    }
+
+    /// The number of nodes, boxes, and characters of text in the subtree of an element, the
+    /// bounds of its boxes, and how far they overflow it. Returns `None` if the element has no box.
+    ///
+    /// `node` must be in the document this engine was created from.
+    pub fn subtree_metrics(&self, node: &Node) -> Option<SubtreeMetrics> {
+        metrics::subtree_metrics(node, &self.layout)
+    }
+
+    /// The elements of the document in the order the keyboard focus moves through them, with
+    /// their positions.
+    ///
+    /// `root` must be the document this engine was created from.
+    pub fn tab_order(&self, root: &Node) -> Vec<FocusEntry> {
+        focus::tab_order(root, &self.layout)
+    }
+
+    /// The element with the keyboard focus, if any.
+    pub fn focused(&self) -> Option<NodeId> {
+        self.states.focused
+    }
+
+    /// Give the keyboard focus to the element `node`, or take it away with `None`, and restyle
+    /// the elements that gain or lose `:focus`.
+    ///
+    /// `root` must be the document this engine was created from, and `stylesheet` the same
+    /// stylesheet.
+    pub fn focus(&mut self, root: &Node, stylesheet: &Stylesheet, node: Option<NodeId>)
+        -> Result<(), LimitError>
+    {
+        let previous = mem::replace(&mut self.states.focused, node);
+        if previous == node {
+            return Ok(());
+        }
+        let changed: Vec<NodeId> = previous.into_iter().chain(node)
+            .filter(|&id| self.style.mark_dirty(id))
+            .collect();
+        let media = self.media();
+        self.style.restyle_dirty_with_media(root, stylesheet, &self.matches, self.states, media);
+        self.relayout(root, |layout, style| {
+            for id in changed {
+                layout.relayout(style, id);
+            }
+        })
+    }
+
+    /// Move the keyboard focus to the next element in the tab order (or the first, if no element
+    /// in it has the focus), as the Tab key does, returning the border box of the element's first
+    /// box to scroll into view. After the last element, the focus wraps around to the first.
+    /// Returns `None` if no element can have the focus.
+    ///
+    /// `root` must be the document this engine was created from, and `stylesheet` the same
+    /// stylesheet.
+    pub fn focus_next(&mut self, root: &Node, stylesheet: &Stylesheet)
+        -> Result<Option<Rect>, LimitError>
+    {
+        self.move_focus(root, stylesheet, true)
+    }
+
+    /// Move the keyboard focus to the previous element in the tab order (or the last), as
+    /// Shift+Tab does, like `focus_next`.
+    pub fn focus_prev(&mut self, root: &Node, stylesheet: &Stylesheet)
+        -> Result<Option<Rect>, LimitError>
+    {
+        self.move_focus(root, stylesheet, false)
+    }
+
+    /// The scroll position and the states of the elements, to save in a snapshot with the
+    /// document and its stylesheet.
+    pub fn session_state(&self) -> SessionState {
+        SessionState { scroll_position: self.scroll_position, states: self.states }
+    }
+
+    /// Give the elements the states of a restored session, and then scroll to its position.
+    ///
+    /// `root` must be the document this engine was created from, and `stylesheet` the same
+    /// stylesheet, both restored from the same snapshot as `state`.
+    pub fn restore_session_state(&mut self, root: &Node, stylesheet: &Stylesheet,
+                                 state: SessionState) -> Result<(), LimitError> {
+        self.focus(root, stylesheet, state.states.focused)?;
+        let (x, y) = state.scroll_position;
+        self.scroll_to(x, y);
+        Ok(())
+    }
+
+    fn move_focus(&mut self, root: &Node, stylesheet: &Stylesheet, forward: bool)
+        -> Result<Option<Rect>, LimitError>
+    {
+        let order = self.tab_order(root);
+        let len = order.len();
+        let current = self.states.focused
+            .and_then(|id| order.iter().position(|entry| entry.node == id));
+        let next = match (current, forward) {
+            _ if len == 0 => return Ok(None),
+            (Some(i), true) => (i + 1) % len,
+            (Some(i), false) => (i + len - 1) % len,
+            (None, true) => 0,
+            (None, false) => len - 1,
+        };
+        let node = order[next].node;
+        self.focus(root, stylesheet, Some(node))?;
+
+        // The focus styles may have moved the element.
+        let id = match self.layout.boxes_for_node(node).first() {
+            Some(&id) => id,
+            None => return Ok(None),
+        };
+        Ok(self.layout.get(id).map(|layout_box| layout_box.geometry().border_box))
+    }
+
+    /// Render the viewport, at the scale factor.
+    pub fn render(&self) -> Result<Canvas, LimitError> {
+        self.render_region(self.viewport(), self.scale_factor)
+    }
+
+    /// Render a rectangle of the document (in px), scaled by `scale` pixels per px.
+    ///
+    /// The document is not laid out again, so this is cheap enough to render thumbnails or a
+    /// minimap of any part of the page.
+    pub fn render_region(&self, region: Rect, scale: f32) -> Result<Canvas, LimitError> {
+        let (width, height) = paint::canvas_size(region, scale);
+        self.limits.check_canvas(width, height)?;
+
+        // Leave out the items that would not show up on the canvas (which may extend slightly
+        // past the region, to a whole number of pixels).
+        let mut display_list = self.display_list.clone();
+        let (width, height) = (width as f32 / scale, height as f32 / scale);
+        let options = self.paint_options;
+        display_list.optimize_with_snap(Rect { width, height, ..region }, options.snap);
+
+        Ok(paint::paint_region_with_options(&display_list, region, scale, options))
+    }
+
+    /// Render thumbnails of the whole length of the document into a sprite sheet with `columns`
+    /// thumbnails per row, scaled by `scale` pixels per px: one of every page (the height of the
+    /// viewport) with `pages_per_frame` of 1, one of every other page with 2, and so on.
+    ///
+    /// Every thumbnail is painted from the same layout and display list.
+    pub fn sprite_sheet(&self, pages_per_frame: usize, columns: usize, scale: f32)
+                        -> Result<SpriteSheet, LimitError> {
+        let (width, height) = (self.context.viewport_width, self.context.viewport_height);
+        let step = height * pages_per_frame.max(1) as Pixels;
+        let count = if step > 0.0 { (self.extent().y / step).ceil().max(1.0) as usize } else { 1 };
+        let frames: Vec<Rect> = (0..count)
+            .map(|i| Rect { x: 0.0, y: i as Pixels * step, width, height })
+            .collect();
+        let (_, _, sheet_width, sheet_height) = paint::sprite_sheet_size(&frames, scale, columns);
+        self.limits.check_canvas(sheet_width, sheet_height)?;
+        let options = self.paint_options;
+        Ok(paint::paint_sprite_sheet(&self.display_list, frames, scale, columns, options))
+    }
+}
+
+/// The scroll offset along one axis that brings a range of the document, starting at `start` and
+/// of size `size`, into a viewport of size `viewport` now scrolled to `current`.
+fn scroll_offset(current: Pixels, viewport: Pixels, start: Pixels, size: Pixels,
+                 alignment: ScrollAlignment) -> Pixels {
+    let align_start = start;
+    let align_end = start + size - viewport;
+    match alignment {
+        ScrollAlignment::Start => align_start,
+        ScrollAlignment::Center => start + (size - viewport) / 2.0,
+        ScrollAlignment::End => align_end,
+        ScrollAlignment::Nearest => {
+            // https://drafts.csswg.org/cssom-view/#element-scrolling-members
+            let (before, after) = (start < current, start + size > current + viewport);
+            let fits = size <= viewport;
+            if before == after {
+                // Entirely in view, or covering the whole viewport.
+                current
+            } else if before == fits {
+                align_start
+            } else {
+                align_end
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! DOM events, dispatched along the tree in capture, target, and bubble phases.
+//!
+//! Listeners are registered on nodes, by `NodeId`, in an `EventListeners` registry that the
+//! embedder keeps next to the document. `dispatch_event` calls the capturing listeners of the
+//! target's ancestors from the root down, then the listeners of the target, then (if the event
+//! bubbles) the bubbling listeners of the ancestors back up to the root, as browsers do.
+//!
+//! Embedders turn pointer input into events with the help of hit testing: `dispatch_at` targets
+//! the topmost element under a point, and `Hover` dispatches `mouseover` and `mouseout` as the
+//! pointer moves from one element to another.
+
+use dom::{Node, NodeId, NodeType};
+use layout::LayoutTree;
+use style::Pixels;
+
+/// Which listeners an event is being dispatched to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventPhase {
+    /// The event is not being dispatched.
+    None,
+    /// The capturing listeners of the target's ancestors, from the root down.
+    Capturing,
+    /// The listeners of the target itself.
+    AtTarget,
+    /// The bubbling listeners of the target's ancestors, up to the root.
+    Bubbling,
+}
+
+/// An event, such as a click, and the state of its dispatch.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Event {
+    /// The type of the event, such as `click`.
+    pub event_type: String,
+    /// Whether the event goes back up the tree after reaching its target.
+    pub bubbles: bool,
+    /// Whether listeners can cancel the default action of the event with `prevent_default`.
+    pub cancelable: bool,
+    target: Option<NodeId>,
+    current_target: Option<NodeId>,
+    phase: EventPhase,
+    propagation_stopped: bool,
+    immediate_propagation_stopped: bool,
+    default_prevented: bool,
+}
+
+impl Event {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Event {
+        Event {
+            event_type: event_type.to_string(),
+            bubbles,
+            cancelable,
+            target: None,
+            current_target: None,
+            phase: EventPhase::None,
+            propagation_stopped: false,
+            immediate_propagation_stopped: false,
+            default_prevented: false,
+        }
+    }
+
+    /// The node the event was dispatched to, once it has been.
+    pub fn target(&self) -> Option<NodeId> {
+        self.target
+    }
+
+    /// The node whose listener is being called, during dispatch.
+    pub fn current_target(&self) -> Option<NodeId> {
+        self.current_target
+    }
+
+    pub fn phase(&self) -> EventPhase {
+        self.phase
+    }
+
+    /// Call no listeners of other nodes after those of the current one.
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    /// Call no other listeners at all, not even the rest of the current node's.
+    pub fn stop_immediate_propagation(&mut self) {
+        self.propagation_stopped = true;
+        self.immediate_propagation_stopped = true;
+    }
+
+    /// Cancel the default action of the event, if it is cancelable.
+    pub fn prevent_default(&mut self) {
+        if self.cancelable {
+            self.default_prevented = true;
+        }
+    }
+
+    pub fn default_prevented(&self) -> bool {
+        self.default_prevented
+    }
+}
+
+/// Identifies an event listener, to remove it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ListenerId(usize);
+
+struct Listener {
+    id: ListenerId,
+    node: NodeId,
+    event_type: String,
+    capture: bool,
+    callback: Box<dyn FnMut(&mut Event) + Send + Sync>,
+}
+
+/// The event listeners of the nodes of a document.
+#[derive(Default)]
+pub struct EventListeners {
+    listeners: Vec<Listener>,
+    next_id: usize,
+}
+
+impl EventListeners {
+    pub fn new() -> EventListeners {
+        EventListeners::default()
+    }
+
+    /// Call `callback` with the events of type `event_type` dispatched to `node` or to its
+    /// descendants: in the capture phase if `capture` is set, and in the bubble phase if not.
+    /// Events dispatched to `node` itself reach it either way, its capturing listeners first.
+    /// Returns an id to remove the listener with.
+    pub fn add<C>(&mut self, node: NodeId, event_type: &str, capture: bool, callback: C)
+        -> ListenerId
+        where C: FnMut(&mut Event) + Send + Sync + 'static
+    {
+        let id = ListenerId(self.next_id);
+        self.next_id += 1;
+        let event_type = event_type.to_string();
+        let callback = Box::new(callback);
+        self.listeners.push(Listener { id, node, event_type, capture, callback });
+        id
+    }
+
+    /// Remove a listener, returning whether it was there.
+    pub fn remove(&mut self, id: ListenerId) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|listener| listener.id != id);
+        self.listeners.len() != len
+    }
+
+    /// Remove the listeners of a node, such as one removed from the document.
+    pub fn remove_node(&mut self, node: NodeId) {
+        self.listeners.retain(|listener| listener.node != node);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// Call the capturing (or bubbling) listeners of `node` for `event`, in the order they were
+    /// added, until one stops immediate propagation.
+    fn invoke(&mut self, node: NodeId, event: &mut Event, capture: bool) {
+        event.current_target = Some(node);
+        for listener in &mut self.listeners {
+            if event.immediate_propagation_stopped {
+                break;
+            }
+            if listener.node == node && listener.event_type == event.event_type
+                && listener.capture == capture {
+                (listener.callback)(event);
+            }
+        }
+    }
+}
+
+/// Dispatch `event` to the node `target` of the document at `root`, returning `false` if a
+/// listener canceled its default action, and `true` otherwise (also if `target` is not in the
+/// document, in which case no listener is called).
+pub fn dispatch_event(root: &Node, listeners: &mut EventListeners, target: NodeId,
+                      event: &mut Event) -> bool {
+    let mut path = Vec::new();
+    if !ancestors(root, target, &mut path) {
+        return true;
+    }
+    event.target = Some(target);
+    event.propagation_stopped = false;
+    event.immediate_propagation_stopped = false;
+
+    event.phase = EventPhase::Capturing;
+    for &node in &path {
+        if event.propagation_stopped {
+            break;
+        }
+        listeners.invoke(node, event, true);
+    }
+    if !event.propagation_stopped {
+        event.phase = EventPhase::AtTarget;
+        listeners.invoke(target, event, true);
+        listeners.invoke(target, event, false);
+    }
+    if event.bubbles {
+        event.phase = EventPhase::Bubbling;
+        for &node in path.iter().rev() {
+            if event.propagation_stopped {
+                break;
+            }
+            listeners.invoke(node, event, false);
+        }
+    }
+
+    event.phase = EventPhase::None;
+    event.current_target = None;
+    !event.default_prevented
+}
+
+/// Dispatch `event` to the topmost element under the point `(x, y)` of the layout of the
+/// document at `root`, returning the element and whether the default action of the event is to
+/// be taken, or `None` if there is no element there.
+pub fn dispatch_at(root: &Node, layout: &LayoutTree, listeners: &mut EventListeners,
+                   x: Pixels, y: Pixels, event: &mut Event) -> Option<(NodeId, bool)> {
+    let target = element_at(root, layout, x, y)?;
+    Some((target, dispatch_event(root, listeners, target, event)))
+}
+
+/// The element under the pointer, to dispatch `mouseover` and `mouseout` events as it moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Hover {
+    hovered: Option<NodeId>,
+}
+
+impl Hover {
+    pub fn new() -> Hover {
+        Hover::default()
+    }
+
+    /// The element under the pointer, as of the last move.
+    pub fn hovered(&self) -> Option<NodeId> {
+        self.hovered
+    }
+
+    /// Move the pointer to `(x, y)`. If this leaves the element that was under it for another
+    /// one, dispatch a `mouseout` event to the old element (if it is still in the document) and
+    /// then a `mouseover` event to the new one. Both bubble and are cancelable. Returns whether
+    /// the hovered element changed.
+    pub fn pointer_moved(&mut self, root: &Node, layout: &LayoutTree,
+                         listeners: &mut EventListeners, x: Pixels, y: Pixels) -> bool {
+        let hovered = element_at(root, layout, x, y);
+        if hovered == self.hovered {
+            return false;
+        }
+        if let Some(old) = self.hovered {
+            dispatch_event(root, listeners, old, &mut Event::new("mouseout", true, true));
+        }
+        if let Some(new) = hovered {
+            dispatch_event(root, listeners, new, &mut Event::new("mouseover", true, true));
+        }
+        self.hovered = hovered;
+        true
+    }
+}
+
+/// The topmost element with a box under the point `(x, y)`.
+fn element_at(root: &Node, layout: &LayoutTree, x: Pixels, y: Pixels) -> Option<NodeId> {
+    layout.hit_test(x, y).into_iter().find(|&id| {
+        root.find(id).is_some_and(|node| matches!(node.node_type, NodeType::Element(_)))
+    })
+}
+
+/// Collect the ancestors of the node `target` in the subtree of `node`, from the outermost in,
+/// returning whether it is there.
+fn ancestors(node: &Node, target: NodeId, path: &mut Vec<NodeId>) -> bool {
+    if node.id == target {
+        return true;
+    }
+    path.push(node.id);
+    if node.children.iter().any(|child| ancestors(child, target, path)) {
+        return true;
+    }
+    path.pop();
+    false
+}
@@ -0,0 +1,67 @@
+//! The order in which the keyboard focus moves between elements with Tab and Shift+Tab.
+//!
+//! Elements with a positive `tabindex` come first, by increasing `tabindex` and then in document
+//! order, followed by elements with a `tabindex` of 0 and elements that can be focused by default
+//! (links, form controls, and the like), in document order. Elements with a negative `tabindex`
+//! can have the focus but are skipped, as are disabled controls and elements without a box.
+
+use dom::{ElementData, Node, NodeId, NodeType};
+use geometry::Rect;
+use layout::LayoutTree;
+
+/// An element that the keyboard focus can move to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FocusEntry {
+    pub node: NodeId,
+    /// The border box of the element's first box, in px, to scroll into view when it gets the
+    /// focus.
+    pub rect: Rect,
+}
+
+/// The elements of a document in the order the keyboard focus moves through them.
+///
+/// `layout` must be laid out from `root`.
+pub fn tab_order(root: &Node, layout: &LayoutTree) -> Vec<FocusEntry> {
+    // Entries by their `tabindex`, where 0 sorts last, then in document order.
+    let mut entries = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        let tab_index = match node.node_type {
+            NodeType::Element(ref elem) => tab_index(elem),
+            NodeType::Text(_) => None,
+        };
+        let tab_index = match tab_index {
+            Some(tab_index) if tab_index >= 0 => tab_index,
+            _ => continue,
+        };
+        if let Some(geometry) = layout.box_for_node(node) {
+            let key = if tab_index == 0 { i64::MAX } else { tab_index };
+            entries.push((key, FocusEntry { node: node.id, rect: geometry.border_box }));
+        }
+    }
+    // The sort is stable, so elements with the same `tabindex` stay in document order.
+    entries.sort_by_key(|&(key, _)| key);
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The `tabindex` of an element, or `None` if it cannot have the focus. Elements that can be
+/// focused by default have a `tabindex` of 0 unless they say otherwise.
+fn tab_index(elem: &ElementData) -> Option<i64> {
+    let focusable = match &*elem.tag {
+        "a" | "area" => elem.attributes.contains_key("href"),
+        "input" => elem.attributes.get("type").is_none_or(|kind| kind != "hidden"),
+        "button" | "select" | "textarea" | "iframe" | "summary" => true,
+        _ => false,
+    };
+    let disabled = elem.attributes.contains_key("disabled")
+        && matches!(&*elem.tag, "button" | "input" | "select" | "textarea");
+    if disabled {
+        return None;
+    }
+    match elem.attributes.get("tabindex").and_then(|value| value.trim().parse().ok()) {
+        Some(tab_index) => Some(tab_index),
+        None if focusable => Some(0),
+        None => None,
+    }
+}
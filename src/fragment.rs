@@ -0,0 +1,119 @@
+//! Rendering a fragment of HTML, such as a single component, inside a container.
+//!
+//! Component screenshots and email previews render markup that is not a whole document. The
+//! fragment is placed in a root element that stands in for its container, with the size, direction,
+//! writing mode, and background the caller gives it, and inherits what it can from it. There is no
+//! `color` or `font-size` property to inherit yet.
+
+use css::{self, Color, Declaration, Origin, Rule, Selector, SimpleSelector, Stylesheet, Unit};
+use css::Value;
+use dom::{self, Node};
+use engine::Engine;
+use geometry::Rect;
+use html;
+use layout::LayoutContext;
+use limits::{LimitError, Limits};
+use paint::Canvas;
+use std::collections::HashMap;
+use style::{self, Direction, Pixels, WritingMode};
+
+/// The container a fragment is rendered in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FragmentContext {
+    /// The width of the container's content box, in px.
+    pub width: Pixels,
+    /// The height of the container's content box, in px. Without one, the container is as tall as
+    /// the fragment.
+    pub height: Option<Pixels>,
+    pub direction: Direction,
+    pub writing_mode: WritingMode,
+    pub background_color: Color,
+}
+
+impl Default for FragmentContext {
+    fn default() -> FragmentContext {
+        FragmentContext {
+            width: 800.0,
+            height: None,
+            direction: Direction::Ltr,
+            writing_mode: WritingMode::HorizontalTb,
+            background_color: Color { r: 0, g: 0, b: 0, a: 0 },
+        }
+    }
+}
+
+/// The tag of the element that stands in for the container, which the fragment's stylesheets
+/// are unlikely to select.
+const CONTAINER_TAG: &str = "chameleon-fragment";
+
+/// Parse a fragment of HTML, returning its nodes in a root element that stands in for its
+/// container.
+pub fn parse_fragment(html: &str) -> Node<'_> {
+    dom::elem(CONTAINER_TAG, HashMap::new(), html::parse_fragment(html))
+}
+
+/// Render a fragment of HTML, styled by the user agent stylesheet, the author stylesheet `css`,
+/// and the fragment's own `<style>` elements, in a container.
+///
+/// The canvas covers the container: it is as wide as `context.width`, and as tall as
+/// `context.height` or, without one, as the fragment.
+pub fn render_fragment(html: &str, css: &str, context: &FragmentContext)
+    -> Result<Canvas, LimitError>
+{
+    let root = parse_fragment(html);
+    let (embedded, _) = style::document_stylesheet(&root, &[]);
+    let stylesheet = Stylesheet::combine(vec![
+        css::user_agent_stylesheet(),
+        container_stylesheet(context),
+        css::parse(css),
+        embedded,
+    ]);
+    let layout_context = LayoutContext {
+        viewport_width: context.width,
+        viewport_height: context.height.unwrap_or(0.0),
+    };
+    let engine = Engine::with_limits(&root, &stylesheet, layout_context, Limits::default())?;
+    let height = context.height.unwrap_or_else(|| {
+        engine.layout_tree().root().map_or(0.0, |root| root.geometry().margin_box.max().y)
+    });
+    engine.render_region(Rect { x: 0.0, y: 0.0, width: context.width, height }, 1.0)
+}
+
+/// The styles of the container, as `!important` user agent declarations, which nothing
+/// overrides.
+fn container_stylesheet(context: &FragmentContext) -> Stylesheet<'static> {
+    let direction = match context.direction {
+        Direction::Ltr => "ltr",
+        Direction::Rtl => "rtl",
+    };
+    let writing_mode = match context.writing_mode {
+        WritingMode::HorizontalTb => "horizontal-tb",
+        WritingMode::VerticalRl => "vertical-rl",
+        WritingMode::VerticalLr => "vertical-lr",
+    };
+    let mut declarations = vec![
+        ("display", Value::Keyword("block".into())),
+        ("direction", Value::Keyword(direction.into())),
+        ("writing-mode", Value::Keyword(writing_mode.into())),
+        ("width", Value::Length(context.width, Unit::Px)),
+        ("background-color", Value::ColorValue(context.background_color)),
+    ];
+    if let Some(height) = context.height {
+        declarations.push(("height", Value::Length(height, Unit::Px)));
+    }
+    let selector = SimpleSelector {
+        tag: Some(CONTAINER_TAG.into()),
+        id: None,
+        class: Vec::new(),
+        pseudo_classes: Vec::new(),
+        pseudo_element: None,
+    };
+    Stylesheet::new(vec![Rule {
+        selectors: vec![Selector::Simple(selector)],
+        declarations: declarations.into_iter()
+            .map(|(name, value)| Declaration { name: name.into(), value, important: true })
+            .collect(),
+        origin: Origin::UserAgent,
+        media: Vec::new(),
+    }])
+}
@@ -0,0 +1,198 @@
+//! Points, sizes, and rectangles in document space, shared by layout and painting.
+//!
+//! All coordinates are in px, with the origin at the top left corner of the document.
+
+use style::{Edge, Pixels};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Point {
+    pub x: Pixels,
+    pub y: Pixels,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Size {
+    pub width: Pixels,
+    pub height: Pixels,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rect {
+    pub x: Pixels,
+    pub y: Pixels,
+    pub width: Pixels,
+    pub height: Pixels,
+}
+
+impl Rect {
+    pub fn new(origin: Point, size: Size) -> Rect {
+        Rect { x: origin.x, y: origin.y, width: size.width, height: size.height }
+    }
+
+    /// The top left corner.
+    pub fn origin(self) -> Point {
+        Point { x: self.x, y: self.y }
+    }
+
+    pub fn size(self) -> Size {
+        Size { width: self.width, height: self.height }
+    }
+
+    /// The bottom right corner.
+    pub fn max(self) -> Point {
+        Point { x: self.x + self.width, y: self.y + self.height }
+    }
+
+    /// Does the rectangle cover no area?
+    pub fn is_empty(self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    pub fn expanded_by(self, edge: Edge<Pixels>) -> Rect {
+        Rect {
+            x: self.x - edge.left,
+            y: self.y - edge.top,
+            width: self.width + edge.left + edge.right,
+            height: self.height + edge.top + edge.bottom,
+        }
+    }
+
+    /// Does the point `(x, y)` lie within this rectangle?
+    pub fn contains(self, x: Pixels, y: Pixels) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// Does `other` lie entirely within this rectangle?
+    pub fn contains_rect(self, other: Rect) -> bool {
+        other.x >= self.x && other.x + other.width <= self.x + self.width &&
+        other.y >= self.y && other.y + other.height <= self.y + self.height
+    }
+
+    /// The overlap of two rectangles, or `None` if they do not overlap.
+    pub fn intersection(self, other: Rect) -> Option<Rect> {
+        let (x0, y0) = (self.x.max(other.x), self.y.max(other.y));
+        let x1 = (self.x + self.width).min(other.x + other.width);
+        let y1 = (self.y + self.height).min(other.y + other.height);
+        if x1 > x0 && y1 > y0 {
+            Some(Rect { x: x0, y: y0, width: x1 - x0, height: y1 - y0 })
+        } else {
+            None
+        }
+    }
+
+    /// The smallest rectangle containing both rectangles. Empty rectangles are ignored.
+    pub fn union(self, other: Rect) -> Rect {
+        if other.is_empty() {
+            return self;
+        } else if self.is_empty() {
+            return other;
+        }
+        let (x0, y0) = (self.x.min(other.x), self.y.min(other.y));
+        let x1 = (self.x + self.width).max(other.x + other.width);
+        let y1 = (self.y + self.height).max(other.y + other.height);
+        Rect { x: x0, y: y0, width: x1 - x0, height: y1 - y0 }
+    }
+}
+
+/// A 2D affine transform, mapping `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: Pixels,
+    pub f: Pixels,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translation(x: Pixels, y: Pixels) -> Transform {
+        Transform { e: x, f: y, ..Transform::IDENTITY }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Transform {
+        Transform { a: x, d: y, ..Transform::IDENTITY }
+    }
+
+    /// A rotation by `degrees` clockwise (since the y axis points down).
+    pub fn rotation(degrees: f32) -> Transform {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        // Round away the error in the sine and cosine of right angles, so that rotations by them
+        // keep rectangles aligned with the axes.
+        let exact = |v: f32| if v.abs() < 1e-6 { 0.0 } else { v };
+        let (sin, cos) = (exact(sin), exact(cos));
+        Transform { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// The transform that applies this one and then `next`.
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// The transform that undoes this one, or `None` if it flattens the plane onto a line or a
+    /// point.
+    pub fn inverse(self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Transform { a, b, c, d, e: -(a * self.e + c * self.f), f: -(b * self.e + d * self.f) })
+    }
+
+    pub fn is_identity(self) -> bool {
+        self == Transform::IDENTITY
+    }
+
+    /// Does the transform map rectangles to rectangles, with edges along the axes?
+    pub fn is_axis_aligned(self) -> bool {
+        (self.b == 0.0 && self.c == 0.0) || (self.a == 0.0 && self.d == 0.0)
+    }
+
+    pub fn apply(self, point: Point) -> Point {
+        Point {
+            x: self.a * point.x + self.c * point.y + self.e,
+            y: self.b * point.x + self.d * point.y + self.f,
+        }
+    }
+
+    /// The smallest rectangle containing the image of `rect`.
+    pub fn bounding_rect(self, rect: Rect) -> Rect {
+        if self.is_identity() {
+            return rect;
+        }
+        let max = rect.max();
+        let corners = [rect.origin(), Point { x: max.x, y: rect.y }, Point { x: rect.x, y: max.y },
+                       max].map(|corner| self.apply(corner));
+        let (mut x0, mut y0) = (f32::INFINITY, f32::INFINITY);
+        let (mut x1, mut y1) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in &corners {
+            x0 = x0.min(corner.x);
+            y0 = y0.min(corner.y);
+            x1 = x1.max(corner.x);
+            y1 = y1.max(corner.y);
+        }
+        Rect { x: x0, y: y0, width: x1 - x0, height: y1 - y0 }
+    }
+}
@@ -0,0 +1,108 @@
+//! The geometry of a laid out document, for external renderers.
+//!
+//! Native UI toolkits can use chameleon for CSS layout alone and draw the boxes and text
+//! themselves. `layout_only` parses nothing and paints nothing: it styles and lays out a parsed
+//! document and returns only where every box and text run ended up.
+
+use css::Stylesheet;
+use dom::{Node, NodeId, NodeType};
+use geometry::{Point, Rect};
+use layout::{self, BoxGeometry, LayoutBox, LayoutContext, LayoutTree};
+use std::collections::HashMap;
+use std::fmt;
+use style::{self, Display};
+
+/// A box of the laid out document, with its position and size and those of its descendants.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GeometryNode {
+    /// The DOM node that generated the box, or `None` if the box is anonymous.
+    pub node: Option<NodeId>,
+    /// The content, padding, border, and margin boxes, relative to the document origin.
+    pub geometry: BoxGeometry,
+    /// The text of the box, if a text node generated it.
+    pub text: Option<TextRun>,
+    pub children: Vec<GeometryNode>,
+}
+
+/// A run of text and where it starts.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextRun {
+    pub text: String,
+    /// Where the run starts. Inline layout is not implemented yet, so every run starts at the top
+    /// left corner of the content box of the block that contains it.
+    pub origin: Point,
+}
+
+/// Style and lay out a document, returning the geometry of its boxes without building a display
+/// list. Returns `None` if the root element has `display: none`.
+pub fn layout_only(root: &Node, stylesheet: &Stylesheet, context: LayoutContext)
+    -> Option<GeometryNode>
+{
+    let style_root = style::style_tree(root, stylesheet);
+    geometry_tree(root, &layout::layout_tree(&style_root, context))
+}
+
+/// The geometry of the boxes of a layout tree, which must be laid out from `root`.
+pub fn geometry_tree(root: &Node, layout: &LayoutTree) -> Option<GeometryNode> {
+    let mut texts = HashMap::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter());
+        if let NodeType::Text(ref text) = node.node_type {
+            texts.insert(node.id, &**text);
+        }
+    }
+    layout.root().map(|root| geometry_node(root, &texts, Point::default()))
+}
+
+/// The geometry of a box, whose nearest block-level ancestor's content box starts at `block`.
+fn geometry_node(layout_box: &LayoutBox, texts: &HashMap<NodeId, &str>, block: Point)
+    -> GeometryNode
+{
+    let geometry = layout_box.geometry();
+    let block = match layout_box.style().display() {
+        Display::Inline => block,
+        _ => geometry.content_box.origin(),
+    };
+    let text = layout_box.node()
+        .and_then(|node| texts.get(&node))
+        .map(|text| TextRun { text: text.to_string(), origin: block });
+    GeometryNode {
+        node: layout_box.node(),
+        geometry,
+        text,
+        children: layout_box.children().iter()
+            .map(|child| geometry_node(child, texts, block))
+            .collect(),
+    }
+}
+
+/// An indented outline of the boxes, one per line, with their border and content boxes.
+impl fmt::Display for GeometryNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn rect(r: Rect) -> String {
+            format!("({}, {}) {}x{}", r.x, r.y, r.width, r.height)
+        }
+
+        fn write_node(f: &mut fmt::Formatter, node: &GeometryNode, depth: usize) -> fmt::Result {
+            let indent = "  ".repeat(depth);
+            match node.node {
+                Some(id) => write!(f, "{}{:?}", indent, id)?,
+                None => write!(f, "{}anonymous", indent)?,
+            }
+            let geometry = node.geometry;
+            write!(f, ": border {}, content {}", rect(geometry.border_box),
+                   rect(geometry.content_box))?;
+            if let Some(ref run) = node.text {
+                write!(f, ", text {:?} at ({}, {})", run.text, run.origin.x, run.origin.y)?;
+            }
+            writeln!(f)?;
+            for child in &node.children {
+                write_node(f, child, depth + 1)?;
+            }
+            Ok(())
+        }
+
+        write_node(f, self, 0)
+    }
+}
@@ -1,37 +1,149 @@
 //! A simple parser for a tiny subset of HTML.
 //!
-//! Can parse basic opening and closing tags, and text nodes.
+//! Can parse basic opening and closing tags, and text nodes. Tag and attribute names are ASCII
+//! case-insensitive, and are lowercased in the DOM.
+//!
+//! Malformed markup is reported as `ParseError`s. The parser recovers from it: a closing tag for
+//! an ancestor closes the elements still open inside it, a stray closing tag or malformed
+//! attribute is skipped, and elements still open at the end of the input are closed there.
+//!
+//! Void elements such as `<br>` have no contents or closing tag. Character references are decoded
+//! in text and attribute values, except in the text of `<style>` and `<script>` elements.
+//!
+//! Elements are nested at most `MAX_NESTING_DEPTH` deep, as in browsers: the contents of elements
+//! at that depth follow them as their siblings instead, so that no document is too deep to parse.
+//! `parse_with_limits` stops parsing documents that exceed tighter limits instead.
 //!
 //! Not yet supported:
 //!
 //! * Comments
 //! * Doctypes and processing instructions
-//! * Non-well-formed markup
-//! * Character entities
+//! * Unquoted attribute values
+//! * Named character references other than `&amp;`, `&lt;`, `&gt;`, `&quot;`, `&apos;`, and
+//!   `&nbsp;` (others are left as they are)
 
+use css::{ParseError, ParseMode};
 use dom;
+use limits::{LimitError, Limits};
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// The elements that have no contents or closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// The elements whose text is not escaped, nor has character references decoded.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// The deepest that elements are nested, counting top-level elements as depth 1. Blink stops
+/// nesting at the same depth.
+pub const MAX_NESTING_DEPTH: usize = 512;
+
 /// Parse an HTML document and return the root element.
-pub fn parse(source: String) -> dom::Node {
-    let mut nodes = Parser { pos: 0, input: source }.parse_nodes();
+///
+/// The DOM borrows tag names, attributes, and text from `source` rather than copying them.
+/// Malformed markup is recovered from; see `parse_with_errors` for the errors.
+pub fn parse(source: &str) -> dom::Node<'_> {
+    parse_with_errors(source).0
+}
+
+/// Parse an HTML document, also returning the errors in its markup, in order.
+pub fn parse_with_errors(source: &str) -> (dom::Node<'_>, Vec<ParseError>) {
+    let mut parser = Parser::new(source);
+    let nodes = parser.parse_document();
+    (document_root(nodes), parser.errors)
+}
+
+/// Parse an HTML document like `parse_with_errors`, unless it is nested more deeply or has more
+/// nodes than `limits` allow. Parsing stops at the first node past the limits, which is returned
+/// as the error.
+pub fn parse_with_limits<'a>(source: &'a str, limits: &Limits)
+    -> Result<(dom::Node<'a>, Vec<ParseError>), LimitError>
+{
+    let mut parser = Parser::new(source);
+    parser.limits = Some(*limits);
+    let nodes = parser.parse_document();
+    if let Some(error) = parser.exceeded {
+        return Err(error);
+    }
+    // The root created for several top-level nodes adds to both the depth and the nodes.
+    let root = document_root(nodes);
+    limits.check_dom(&root)?;
+    Ok((root, parser.errors))
+}
 
-    // If the document contains a root element, just return it. Otherwise, create one.
+/// The root element of a document with the given top-level nodes: the one element, if that is
+/// all there is, and otherwise a new `<html>` element around them.
+fn document_root(mut nodes: Vec<dom::Node<'_>>) -> dom::Node<'_> {
     if nodes.len() == 1 {
         nodes.swap_remove(0)
     } else {
-        dom::elem("html".to_string(), HashMap::new(), nodes)
+        dom::elem("html", HashMap::new(), nodes)
+    }
+}
+
+/// Parse an HTML document in the given mode. In `Strict` mode, the first error in the markup is
+/// returned instead of the document; in `Lenient` mode, this is `parse_with_errors`.
+pub fn parse_with_mode(source: &str, mode: ParseMode)
+    -> Result<(dom::Node<'_>, Vec<ParseError>), ParseError>
+{
+    let (root, errors) = parse_with_errors(source);
+    match (mode, errors.first()) {
+        (ParseMode::Strict, Some(error)) => Err(error.clone()),
+        _ => Ok((root, errors)),
     }
 }
 
-struct Parser {
+/// Parse a fragment of HTML, such as the contents of an element, returning its top-level nodes.
+pub fn parse_fragment(source: &str) -> Vec<dom::Node<'_>> {
+    Parser::new(source).parse_document()
+}
+
+struct Parser<'a> {
     pos: usize,
-    input: String,
+    input: &'a str,
+    /// The tags of the elements being parsed, from the outermost in.
+    open: Vec<&'a str>,
+    /// The tags of the elements too deep to nest anything that are not closed yet, from the
+    /// outermost in. Their closing tags are skipped.
+    flattened: Vec<&'a str>,
+    errors: Vec<ParseError>,
+    /// The limits to stop parsing at, if any.
+    limits: Option<Limits>,
+    /// The number of nodes parsed so far.
+    nodes: usize,
+    /// The limit that the document exceeded, if parsing stopped there.
+    exceeded: Option<LimitError>,
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser {
+            pos: 0,
+            input,
+            open: Vec::new(),
+            flattened: Vec::new(),
+            errors: Vec::new(),
+            limits: None,
+            nodes: 0,
+            exceeded: None,
+        }
+    }
+
+    /// Parse the top-level nodes of a document or fragment, skipping stray closing tags.
+    fn parse_document(&mut self) -> Vec<dom::Node<'a>> {
+        let mut nodes = self.parse_nodes();
+        while !self.eof() {
+            self.skip_closing_tag();
+            nodes.extend(self.parse_nodes());
+        }
+        nodes
+    }
+
     /// Parse a sequence of sibling nodes.
-    fn parse_nodes(&mut self) -> Vec<dom::Node> {
+    fn parse_nodes(&mut self) -> Vec<dom::Node<'a>> {
         let mut nodes = vec!();
         loop {
             self.consume_whitespace();
@@ -44,81 +156,213 @@ impl Parser {
     }
 
     /// Parse a single node.
-    fn parse_node(&mut self) -> dom::Node {
-        match self.next_char() {
-            '<' => self.parse_element(),
-            _   => self.parse_text()
+    fn parse_node(&mut self) -> dom::Node<'a> {
+        if !self.within_limits() {
+            // Parsing stops here, and the node is thrown away with the rest of the document.
+            self.pos = self.input.len();
+            return dom::text("");
+        }
+        if self.next_char() == '<' && self.starts_tag() {
+            self.parse_element()
+        } else {
+            self.parse_text()
         }
     }
 
     /// Parse a single element, including its open tag, contents, and closing tag.
-    fn parse_element(&mut self) -> dom::Node {
+    fn parse_element(&mut self) -> dom::Node<'a> {
         // Opening tag.
+        let start = self.pos;
         assert_eq!(self.consume_char(), '<');
         let tag = self.parse_identifier();
         let attrs = self.parse_attributes();
-        if self.next_char() == '/' {
+        if self.starts_with("/>") {
             // Self-closing tag.
-            assert_eq!(self.consume_char(), '/');
-            assert_eq!(self.consume_char(), '>');
-            return dom::elem(tag, attrs, vec![]);
+            self.pos += 2;
+            return dom::elem(dom::lowercase_name(tag), attrs, vec![]);
+        }
+        if !self.end_tag() || is_void_element(tag) {
+            return dom::elem(dom::lowercase_name(tag), attrs, vec![]);
         }
-        assert_eq!(self.consume_char(), '>');
 
-        // Contents.
-        let children = self.parse_nodes();
+        if self.open.len() + 1 >= MAX_NESTING_DEPTH {
+            // Leave the contents to follow the element, and skip its closing tag. Only the first
+            // of the elements that follow each other like this is reported.
+            if self.flattened.is_empty() {
+                self.error_at(start, format!("<{}> is nested too deeply to contain anything",
+                                             tag));
+            }
+            self.flattened.push(tag);
+            return dom::elem(dom::lowercase_name(tag), attrs, vec![]);
+        }
 
-        // Closing tag.
-        assert_eq!(self.consume_char(), '<');
-        assert_eq!(self.consume_char(), '/');
-        assert_eq!(self.parse_identifier(), tag);
-        assert_eq!(self.consume_char(), '>');
+        // Contents, up to the closing tag.
+        self.open.push(tag);
+        let mut children = self.parse_nodes();
+        loop {
+            if self.eof() {
+                self.error_at(start, format!("<{}> is never closed", tag));
+                break;
+            }
+            let close = self.pos;
+            let name = self.closing_tag_name();
+            if self.flattened.last().is_some_and(|last| last.eq_ignore_ascii_case(name)) {
+                self.flattened.pop();
+                self.consume_closing_tag();
+                children.extend(self.parse_nodes());
+                continue;
+            }
+            if name.eq_ignore_ascii_case(tag) {
+                self.skip_closing_tag();
+                break;
+            }
+            if self.open.iter().any(|open| open.eq_ignore_ascii_case(name)) {
+                // Leave the closing tag to the ancestor it belongs to.
+                self.error_at(close, format!("expected </{}>, found </{}>", tag, name));
+                break;
+            }
+            self.skip_closing_tag();
+            children.extend(self.parse_nodes());
+        }
+        self.open.pop();
+        // Elements too deep to nest anything can only be inside the innermost open element.
+        self.flattened.clear();
 
-        dom::elem(tag, attrs, children)
+        dom::elem(dom::lowercase_name(tag), attrs, children)
+    }
+
+    /// Count the node about to be parsed, returning whether it is within the limits (if any). If
+    /// not, the limit it exceeds is recorded.
+    fn within_limits(&mut self) -> bool {
+        self.nodes += 1;
+        let limits = match self.limits {
+            Some(limits) => limits,
+            None => return true,
+        };
+        if self.open.len() + 1 > limits.max_dom_depth {
+            self.exceeded = Some(LimitError::DomTooDeep(limits.max_dom_depth));
+        } else if self.nodes > limits.max_dom_nodes {
+            self.exceeded = Some(LimitError::TooManyNodes(limits.max_dom_nodes));
+        }
+        self.exceeded.is_none()
+    }
+
+    /// Does an opening tag start at the current `<`? Tag names start with a letter.
+    fn starts_tag(&self) -> bool {
+        self.input[self.pos + 1..].starts_with(|c: char| c.is_ascii_alphabetic())
+    }
+
+    /// Consume the `>` that ends an opening tag, skipping anything before it. Returns false if
+    /// the input ends first.
+    fn end_tag(&mut self) -> bool {
+        if !self.eof() && self.next_char() != '>' {
+            self.error(format!("unexpected {:?} in tag", self.next_char()));
+            self.consume_while(|c| c != '>');
+        }
+        if self.eof() {
+            self.error("unexpected end of input in tag".to_string());
+            return false;
+        }
+        self.consume_char();
+        true
+    }
+
+    /// The tag name of the closing tag at the current position, without consuming it.
+    fn closing_tag_name(&self) -> &'a str {
+        let rest = &self.input[self.pos + "</".len()..];
+        let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    /// Consume the closing tag at the current position, reporting it if it closes no open
+    /// element.
+    fn skip_closing_tag(&mut self) {
+        let name = self.closing_tag_name();
+        if !self.open.last().is_some_and(|open| open.eq_ignore_ascii_case(name)) {
+            self.error(format!("unexpected </{}>", name));
+        }
+        self.consume_closing_tag();
+    }
+
+    /// Consume the closing tag at the current position.
+    fn consume_closing_tag(&mut self) {
+        let name = self.closing_tag_name();
+        self.pos += "</".len() + name.len();
+        self.consume_whitespace();
+        self.end_tag();
     }
 
     /// Parse a tag or attribute name.
-    fn parse_identifier(&mut self) -> String {
-        self.consume_while(|c| match c {
-            'a'...'z' | 'A'...'Z' | '0'...'9' => true,
-            _ => false
-        })
+    fn parse_identifier(&mut self) -> &'a str {
+        self.consume_while(is_name_char)
     }
 
-    /// Parse a list of name="value" pairs, separated by whitespace.
-    fn parse_attributes(&mut self) -> dom::AttrMap {
+    /// Parse a list of name="value" pairs, separated by whitespace. Malformed pairs are skipped.
+    fn parse_attributes(&mut self) -> dom::AttrMap<'a> {
         let mut attributes = HashMap::new();
         loop {
             self.consume_whitespace();
-            if !self.next_char().is_alphanumeric() {
+            if self.eof() || !self.next_char().is_alphanumeric() {
                 break;
             }
-            let (name, value) = self.parse_attribute();
-            attributes.insert(name, value);
+            match self.parse_attribute() {
+                Some((name, value)) => {
+                    attributes.insert(dom::lowercase_name(name), decode(value));
+                }
+                None => {
+                    self.consume_while(|c| !c.is_whitespace() && c != '>' && c != '/');
+                }
+            }
         }
         attributes
     }
 
     /// Parse a single name="value" pair.
-    fn parse_attribute(&mut self) -> (String, String) {
+    fn parse_attribute(&mut self) -> Option<(&'a str, &'a str)> {
         let name = self.parse_identifier();
-        assert_eq!(self.consume_char(), '=');
-        let value = self.parse_quotation();
-        (name, value)
+        if !self.starts_with("=") {
+            self.error(format!("expected '=' after attribute {}", name));
+            return None;
+        }
+        self.consume_char();
+        let value = self.parse_quotation()?;
+        Some((name, value))
     }
 
     /// Parse a quoted value.
-    fn parse_quotation(&mut self) -> String {
-        let open_quote = self.consume_char();
-        assert!(open_quote == '"' || open_quote == '\'');
+    fn parse_quotation(&mut self) -> Option<&'a str> {
+        let open_quote = if self.eof() { '\0' } else { self.next_char() };
+        if open_quote != '"' && open_quote != '\'' {
+            self.error("expected a quoted attribute value".to_string());
+            return None;
+        }
+        let start = self.pos;
+        self.consume_char();
         let value = self.consume_while(|c| c != open_quote);
-        assert_eq!(self.consume_char(), open_quote);
-        value
+        if self.eof() {
+            self.error_at(start, "attribute value is never closed".to_string());
+            return None;
+        }
+        self.consume_char();
+        Some(value)
     }
 
-    /// Parse a text node.
-    fn parse_text(&mut self) -> dom::Node {
-        dom::text(self.consume_while(|c| c != '<'))
+    /// Parse a text node. A `<` that does not start a tag is part of the text.
+    fn parse_text(&mut self) -> dom::Node<'a> {
+        let start = self.pos;
+        loop {
+            self.consume_while(|c| c != '<');
+            if self.eof() || self.starts_with("</") || self.starts_tag() {
+                break;
+            }
+            self.error("'<' does not start a tag".to_string());
+            self.consume_char();
+        }
+        let text = &self.input[start..self.pos];
+        match self.open.last() {
+            Some(tag) if is_raw_text_element(tag) => dom::text(text),
+            _ => dom::text(decode(text)),
+        }
     }
 
     /// Consume and discard zero or more whitespace characters.
@@ -126,22 +370,20 @@ impl Parser {
         self.consume_while(char::is_whitespace);
     }
 
-    /// Consume characters until `test` returns false.
-    fn consume_while<F>(&mut self, test: F) -> String
+    /// Consume characters until `test` returns false, returning the consumed slice of the input.
+    fn consume_while<F>(&mut self, test: F) -> &'a str
             where F: Fn(char) -> bool {
-        let mut result = String::new();
+        let start = self.pos;
         while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
+            self.consume_char();
         }
-        result
+        &self.input[start..self.pos]
     }
 
     /// Return the current character, and advance self.pos to the next character.
     fn consume_char(&mut self) -> char {
-        let mut iter = self.input[self.pos..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
-        self.pos += next_pos;
+        let cur_char = self.next_char();
+        self.pos += cur_char.len_utf8();
         cur_char
     }
 
@@ -159,4 +401,94 @@ impl Parser {
     fn eof(&self) -> bool {
         self.pos >= self.input.len()
     }
+
+    /// Record an error at the current position.
+    fn error(&mut self, message: String) {
+        self.error_at(self.pos, message);
+    }
+
+    /// Record an error at the byte offset `pos`.
+    fn error_at(&mut self, pos: usize, message: String) {
+        let before = &self.input[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        self.errors.push(ParseError { line, column, message });
+    }
+}
+
+/// Can `c` appear in a tag or attribute name, such as `x-chart` or `data-id`?
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+/// Is `tag` the name of a void element, such as `br`, which has no contents or closing tag?
+pub fn is_void_element(tag: &str) -> bool {
+    VOID_ELEMENTS.iter().any(|void| void.eq_ignore_ascii_case(tag))
+}
+
+/// Is `tag` the name of an element, such as `style`, whose text is taken as it is?
+pub fn is_raw_text_element(tag: &str) -> bool {
+    RAW_TEXT_ELEMENTS.iter().any(|raw| raw.eq_ignore_ascii_case(tag))
+}
+
+/// Append `text` to `html`, escaping the characters that are special in text (or, if
+/// `attribute` is set, in a double-quoted attribute value).
+pub fn escape(html: &mut String, text: &str, attribute: bool) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '\u{a0}' => html.push_str("&nbsp;"),
+            '"' if attribute => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// Decode the character references in `text`, borrowing it if there are none. References that
+/// are unknown or malformed are left as they are.
+fn decode(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest.find(';').map(|end| (&rest[1..end], end));
+        match reference.and_then(|(name, end)| Some((character(name)?, end))) {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+/// The character a reference such as `amp` or `#x41` (without the `&` and `;`) stands for.
+fn character(name: &str) -> Option<char> {
+    let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(decimal) = name.strip_prefix('#') {
+        decimal.parse().ok()?
+    } else {
+        return match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => None,
+        };
+    };
+    char::from_u32(code).filter(|&c| c != '\0')
 }
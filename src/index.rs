@@ -0,0 +1,163 @@
+//! Indexes of the elements of a document by id, class, and type.
+//!
+//! An `ElementIndex` finds the elements a selector can match without walking the whole tree: a
+//! selector can only match elements with its id, its first class, or its type, so only the
+//! elements filed under that key are matched against it. It also records where each element is in
+//! the tree, to return elements in document order and to find them again quickly.
+//!
+//! The index must be told about changes to the DOM. Since a change to a subtree cannot move
+//! anything outside of it, `update` only indexes that subtree again.
+
+use css::{self, Selector};
+use dom::{self, Node, NodeId, NodeType};
+use std::collections::{HashMap, HashSet};
+use style;
+
+/// The elements of a document by id, class, and type, and their positions in the tree.
+#[derive(Clone, Debug, Default)]
+pub struct ElementIndex {
+    /// The child indices from the root to each element.
+    paths: HashMap<NodeId, Vec<usize>>,
+    by_id: HashMap<String, Vec<NodeId>>,
+    by_class: HashMap<String, Vec<NodeId>>,
+    /// Elements by lowercased type.
+    by_tag: HashMap<String, Vec<NodeId>>,
+}
+
+impl ElementIndex {
+    /// Index the elements of the document at `root`.
+    pub fn new(root: &Node) -> ElementIndex {
+        let mut index = ElementIndex::default();
+        index.add(root, &mut Vec::new());
+        index
+    }
+
+    /// Index the subtree of the node `changed` again, after it (or any of its descendants)
+    /// changed. `root` must be the document this index was built from, as changed, and the
+    /// children of nodes outside the subtree must not have changed.
+    pub fn update(&mut self, root: &Node, changed: NodeId) {
+        let path = match self.paths.get(&changed) {
+            Some(path) => path.clone(),
+            None => {
+                // Not an element, or not in the document: start over.
+                *self = ElementIndex::new(root);
+                return;
+            }
+        };
+        let removed: HashSet<NodeId> = self.paths.iter()
+            .filter(|&(_, other)| other.starts_with(&path))
+            .map(|(&id, _)| id)
+            .collect();
+        self.paths.retain(|id, _| !removed.contains(id));
+        for buckets in [&mut self.by_id, &mut self.by_class, &mut self.by_tag] {
+            for bucket in buckets.values_mut() {
+                bucket.retain(|id| !removed.contains(id));
+            }
+            buckets.retain(|_, bucket| !bucket.is_empty());
+        }
+        if let Some(node) = node_at(root, &path) {
+            self.add(node, &mut path.clone());
+        }
+    }
+
+    /// The node `id` of the document at `root`, if it is an indexed element.
+    pub fn get<'b, 'a>(&self, root: &'b Node<'a>, id: NodeId) -> Option<&'b Node<'a>> {
+        node_at(root, self.paths.get(&id)?)
+    }
+
+    /// The first element in document order with the given id attribute.
+    pub fn element_by_id(&self, id: &str) -> Option<NodeId> {
+        self.in_order(self.by_id.get(id).into_iter().flatten().cloned()).into_iter().next()
+    }
+
+    /// The elements with the given class, in document order.
+    pub fn elements_with_class(&self, class: &str) -> Vec<NodeId> {
+        self.in_order(self.by_class.get(class).into_iter().flatten().cloned())
+    }
+
+    /// The elements of the given type, in document order. HTML types are ASCII case-insensitive.
+    pub fn elements_with_tag(&self, tag: &str) -> Vec<NodeId> {
+        let ids = self.by_tag.get(&*dom::lowercase_name(tag)).into_iter().flatten().cloned();
+        self.in_order(ids)
+    }
+
+    /// The elements of the document at `root` that match a comma-separated list of selectors,
+    /// like `Node::query_selector_all`, but matching only the elements filed under each
+    /// selector's id, first class, or type.
+    pub fn query_selector_all<'b, 'a>(&self, root: &'b Node<'a>, selectors: &str)
+        -> Vec<&'b Node<'a>>
+    {
+        let selectors = match css::parse_selector_list(selectors) {
+            Ok(selectors) => selectors,
+            Err(_) => return Vec::new(),
+        };
+        let mut found = Vec::new();
+        for selector in &selectors {
+            found.extend(self.candidates(selector).into_iter().filter(|id| {
+                match self.get(root, *id).map(|node| &node.node_type) {
+                    Some(NodeType::Element(elem)) => style::matches_selector(elem, selector),
+                    _ => false,
+                }
+            }));
+        }
+        self.in_order(found).into_iter().filter_map(|id| self.get(root, id)).collect()
+    }
+
+    /// The first element of the document at `root` that matches a list of selectors, like
+    /// `query_selector_all`.
+    pub fn query_selector<'b, 'a>(&self, root: &'b Node<'a>, selectors: &str)
+        -> Option<&'b Node<'a>>
+    {
+        self.query_selector_all(root, selectors).into_iter().next()
+    }
+
+    /// The elements `selector` can match: those with its id, first class, or type.
+    fn candidates(&self, selector: &Selector) -> Vec<NodeId> {
+        let Selector::Simple(ref simple) = *selector;
+        let bucket = if let Some(ref id) = simple.id {
+            self.by_id.get(&**id)
+        } else if let Some(class) = simple.class.first() {
+            self.by_class.get(&**class)
+        } else if let Some(ref tag) = simple.tag {
+            self.by_tag.get(&*dom::lowercase_name(&**tag))
+        } else {
+            return self.paths.keys().cloned().collect();
+        };
+        bucket.cloned().unwrap_or_default()
+    }
+
+    /// Sort elements in document order, removing duplicates.
+    fn in_order<I: IntoIterator<Item = NodeId>>(&self, ids: I) -> Vec<NodeId> {
+        let mut ids: Vec<(&Vec<usize>, NodeId)> = ids.into_iter()
+            .filter_map(|id| self.paths.get(&id).map(|path| (path, id)))
+            .collect();
+        ids.sort_by(|a, b| a.0.cmp(b.0));
+        ids.dedup();
+        ids.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Index the elements of the subtree of `node`, which is at `path`.
+    fn add(&mut self, node: &Node, path: &mut Vec<usize>) {
+        let elem = match node.node_type {
+            NodeType::Element(ref elem) => elem,
+            NodeType::Text(_) => return,
+        };
+        self.paths.insert(node.id, path.clone());
+        if let Some(id) = elem.id() {
+            self.by_id.entry(id.to_string()).or_default().push(node.id);
+        }
+        for class in elem.classes().into_iter().filter(|class| !class.is_empty()) {
+            self.by_class.entry(class.to_string()).or_default().push(node.id);
+        }
+        self.by_tag.entry(dom::lowercase_name(&*elem.tag).into_owned()).or_default().push(node.id);
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            self.add(child, path);
+            path.pop();
+        }
+    }
+}
+
+fn node_at<'b, 'a>(root: &'b Node<'a>, path: &[usize]) -> Option<&'b Node<'a>> {
+    path.iter().try_fold(root, |node, &i| node.children.get(i))
+}
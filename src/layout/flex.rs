@@ -0,0 +1,317 @@
+//! Flex layout.
+//!
+//! http://www.w3.org/TR/css-flexbox-1/#layout-algorithm
+//!
+//! Auto margins, `min-*`/`max-*` constraints, and `align-self` are not supported.
+
+use super::LayoutBox;
+use geometry::Rect;
+use style::{AlignContent, AlignItems, Automatic, FlexWrap, JustifyContent, Pixels};
+use std::iter;
+use std::ops::Range;
+
+impl LayoutBox {
+    /// Lay out a flex container and its flex items.
+    pub(super) fn layout_flex(&mut self) {
+        // The container itself is sized and positioned like a block.
+        self.calculate_block_width();
+        self.calculate_block_position();
+
+        let row = self.style.flex_direction().is_row();
+        let align = self.style.align_items();
+        let wrap = self.style.flex_wrap();
+        let width = self.content_box.width;
+        let height = self.definite_height();
+        for item in &mut self.children {
+            item.containing_width = width;
+            item.containing_direction = self.style.direction();
+            item.containing_height = height;
+        }
+
+        // The main size of a row is always definite, but a column only has one if given a height.
+        let (main_size, cross_size) = if row {
+            (Some(width), height)
+        } else {
+            (height, Some(width))
+        };
+
+        // Determine the flex base size of every item, and collect the items into lines.
+        let bases: Vec<Pixels> = self.children.iter_mut()
+            .map(|item| item.flex_base_size(row, width))
+            .collect();
+        let lines = match (wrap, main_size) {
+            (FlexWrap::NoWrap, _) | (_, None) => iter::once(0..self.children.len()).collect(),
+            (_, Some(main_size)) => self.flex_lines(row, main_size, &bases),
+        };
+
+        // Resolve the flexible lengths on each line, and find the cross size of each line from the
+        // hypothetical cross sizes of its items.
+        let mut sizes = Vec::with_capacity(bases.len());
+        let mut crosses = Vec::with_capacity(lines.len());
+        for line in &lines {
+            let items = &mut self.children[line.clone()];
+            let line_sizes = resolve_flexible_lengths(items, row, main_size, &bases[line.clone()]);
+            let cross = items.iter_mut().zip(&line_sizes)
+                .map(|(item, &size)| item.flex_hypothetical_cross_size(row, size, width))
+                .fold(0.0, Pixels::max);
+            sizes.extend(line_sizes);
+            crosses.push(cross);
+        }
+
+        // A single line fills a definite cross size. Multiple lines are packed by `align-content`.
+        let multi_line = wrap != FlexWrap::NoWrap;
+        let container_cross = match cross_size {
+            Some(size) if !multi_line => {
+                crosses.fill(size);
+                size
+            }
+            Some(size) => size,
+            None => crosses.iter().sum(),
+        };
+        let (mut line_position, line_between) = if multi_line {
+            let free = container_cross - crosses.iter().sum::<Pixels>();
+            if self.style.align_content() == AlignContent::Stretch && free > 0.0 {
+                let share = free / crosses.len() as Pixels;
+                for cross in &mut crosses {
+                    *cross += share;
+                }
+            }
+            align_content(self.style.align_content(), free, crosses.len())
+        } else {
+            (0.0, 0.0)
+        };
+
+        // Place the items of every line.
+        let reverse = self.style.flex_direction().is_reverse();
+        let (x, y) = (self.content_box.x, self.content_box.y);
+        let mut container_main: Pixels = 0.0;
+        for (line, &line_cross) in lines.iter().zip(&crosses) {
+            let cross_start = if wrap == FlexWrap::WrapReverse {
+                container_cross - line_position - line_cross
+            } else {
+                line_position
+            };
+            line_position += line_cross + line_between;
+
+            // Distribute the remaining space along the main axis.
+            let items = &mut self.children[line.clone()];
+            let line_sizes = &sizes[line.clone()];
+            let outer: Vec<Pixels> = items.iter().zip(line_sizes)
+                .map(|(item, size)| size + item.main_edges(row))
+                .collect();
+            let used: Pixels = outer.iter().sum();
+            let line_main = main_size.unwrap_or(used);
+            container_main = container_main.max(line_main);
+            let justify_content = self.style.justify_content();
+            let (leading, between) = justify(justify_content, line_main - used, outer.len());
+
+            let mut position = leading;
+            for ((item, &size), &outer) in items.iter_mut().zip(line_sizes).zip(&outer) {
+                let main = if reverse { line_main - position - outer } else { position };
+                position += outer + between;
+
+                // Stretched items fill the line; others are aligned within it.
+                let cross = item.flex_cross_size(row, align, line_cross, width);
+                item.layout_flex_item(row, size, cross, 0.0, 0.0, width);
+                let offset = cross_start + match align {
+                    AlignItems::Stretch | AlignItems::FlexStart => 0.0,
+                    AlignItems::FlexEnd => line_cross - item.flex_outer_cross_size(row),
+                    AlignItems::Center => (line_cross - item.flex_outer_cross_size(row)) / 2.0,
+                };
+
+                if row {
+                    item.layout_flex_item(row, size, cross, x + main, y + offset, width);
+                } else {
+                    item.layout_flex_item(row, size, cross, x + offset, y + main, width);
+                }
+            }
+        }
+
+        self.intrinsic.height = if row { container_cross } else { container_main };
+        self.calculate_block_height();
+    }
+
+    /// Break the flex items into lines that fit within the main size.
+    fn flex_lines(&self, row: bool, main_size: Pixels, bases: &[Pixels]) -> Vec<Range<usize>> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut used = 0.0;
+        for (i, (item, base)) in self.children.iter().zip(bases).enumerate() {
+            let outer = base + item.main_edges(row);
+            // Every line holds at least one item.
+            if i > start && used + outer > main_size {
+                lines.push(start..i);
+                start = i;
+                used = 0.0;
+            }
+            used += outer;
+        }
+        lines.push(start..self.children.len());
+        lines
+    }
+
+    /// Determine the flex base size of a flex item's content box.
+    fn flex_base_size(&mut self, row: bool, width: Pixels) -> Pixels {
+        let specified = match self.style.flex_basis() {
+            Automatic::Auto if row => self.style.width().resolve(Some(width)),
+            Automatic::Auto => self.specified_height(),
+            basis => basis,
+        };
+        match specified {
+            Automatic::Given(size) => size,
+            Automatic::Auto if row => self.max_content_width() - self.horizontal_edges(),
+            Automatic::Auto => {
+                // Lay the item out to measure the height of its contents.
+                self.forced_height = None;
+                self.forced_width = None;
+                self.container = Rect { x: 0.0, y: 0.0, width, height: 0.0 };
+                self.layout();
+                self.content_box.height
+            }
+        }
+    }
+
+    /// Determine the outer cross size of a flex item if its main size is `size`, before any
+    /// stretching.
+    fn flex_hypothetical_cross_size(&mut self, row: bool, size: Pixels, width: Pixels) -> Pixels {
+        if row {
+            self.layout_flex_item(row, size, None, 0.0, 0.0, width);
+            self.margin_box.height
+        } else {
+            let specified = self.style.width().resolve(Some(width));
+            specified.take(self.fit_content_width(width)) + self.horizontal_edges()
+        }
+    }
+
+    /// The content width of a block that shrinks to fit its contents within `width`.
+    fn fit_content_width(&self, width: Pixels) -> Pixels {
+        let available = width - self.horizontal_edges();
+        (self.max_content_width() - self.horizontal_edges()).min(available).max(0.0)
+    }
+
+    /// The cross size of an item's content box on a line of the given cross size, if the item
+    /// does not simply take its specified (or, for rows, automatic) size.
+    fn flex_cross_size(&self, row: bool, align: AlignItems, line_cross: Pixels, width: Pixels)
+        -> Option<Pixels>
+    {
+        let stretch = align == AlignItems::Stretch;
+        if row {
+            if stretch && self.specified_height().is_auto() {
+                Some((line_cross - self.vertical_edges()).max(0.0))
+            } else {
+                None
+            }
+        } else if self.style.width().resolve(Some(width)).is_given() {
+            None
+        } else if stretch {
+            Some((line_cross - self.horizontal_edges()).max(0.0))
+        } else {
+            Some(self.fit_content_width(width))
+        }
+    }
+
+    /// The laid out size of a flex item's margin box along the cross axis.
+    fn flex_outer_cross_size(&self, row: bool) -> Pixels {
+        if row {
+            self.margin_box.height
+        } else {
+            // The margin box of a block absorbs any underflow, so measure from the border box.
+            let margin = self.specified_margin();
+            let margins = margin.left.value() + margin.right.value();
+            self.border_box.width + margins
+        }
+    }
+
+    /// Lay out a flex item with its content box sized `main` along the main axis and `cross`
+    /// (if given) along the cross axis, with its margin box placed at `(x, y)`.
+    fn layout_flex_item(&mut self, row: bool, main: Pixels, cross: Option<Pixels>,
+                        x: Pixels, y: Pixels, width: Pixels) {
+        if row {
+            self.forced_width = Some(main);
+            self.forced_height = cross;
+            self.container = Rect { x, y, width: main + self.horizontal_edges(), height: 0.0 };
+        } else {
+            self.forced_width = cross;
+            self.forced_height = Some(main);
+            let width = cross.map_or(width, |cross| cross + self.horizontal_edges());
+            self.container = Rect { x, y, width, height: 0.0 };
+        }
+        self.layout();
+    }
+
+    /// Total specified size of the margins, borders, and padding along the main axis.
+    fn main_edges(&self, row: bool) -> Pixels {
+        if row { self.horizontal_edges() } else { self.vertical_edges() }
+    }
+}
+
+/// Resolve the used main size of each item's content box on a line from its flex base size.
+fn resolve_flexible_lengths(items: &[LayoutBox], row: bool, main_size: Option<Pixels>,
+                            bases: &[Pixels]) -> Vec<Pixels> {
+    let outer: Pixels = items.iter().zip(bases)
+        .map(|(item, base)| base + item.main_edges(row))
+        .sum();
+    let free = main_size.map_or(0.0, |size| size - outer);
+
+    if free > 0.0 {
+        // Grow items in proportion to their grow factors. If the factors sum to less than one,
+        // only that fraction of the free space is distributed.
+        let total: f32 = items.iter().map(|item| item.style.flex_grow()).sum();
+        if total > 0.0 {
+            let free = free * total.min(1.0);
+            return items.iter().zip(bases)
+                .map(|(item, base)| base + free * item.style.flex_grow() / total)
+                .collect();
+        }
+    } else if free < 0.0 {
+        // Shrink items in proportion to their shrink factors scaled by their base sizes.
+        let total: f32 = items.iter().zip(bases)
+            .map(|(item, base)| item.style.flex_shrink() * base)
+            .sum();
+        if total > 0.0 {
+            return items.iter().zip(bases)
+                .map(|(item, base)| {
+                    (base + free * item.style.flex_shrink() * base / total).max(0.0)
+                })
+                .collect();
+        }
+    }
+    bases.to_vec()
+}
+
+/// Compute the leading space and the space between items for `justify-content`, given the
+/// remaining space on the line.
+fn justify(justify: JustifyContent, remaining: Pixels, count: usize) -> (Pixels, Pixels) {
+    let align = match justify {
+        JustifyContent::FlexStart => AlignContent::FlexStart,
+        JustifyContent::FlexEnd => AlignContent::FlexEnd,
+        JustifyContent::Center => AlignContent::Center,
+        JustifyContent::SpaceBetween => AlignContent::SpaceBetween,
+        JustifyContent::SpaceAround => AlignContent::SpaceAround,
+        JustifyContent::SpaceEvenly => AlignContent::SpaceEvenly,
+    };
+    align_content(align, remaining, count)
+}
+
+/// Compute the leading space and the space between lines (or items) for `align-content`, given
+/// the remaining space in the container.
+fn align_content(align: AlignContent, remaining: Pixels, count: usize) -> (Pixels, Pixels) {
+    if count == 0 {
+        return (0.0, 0.0);
+    }
+    let n = count as Pixels;
+    match align {
+        // Stretched lines have already absorbed any remaining space.
+        AlignContent::Stretch | AlignContent::FlexStart => (0.0, 0.0),
+        AlignContent::FlexEnd => (remaining, 0.0),
+        AlignContent::Center => (remaining / 2.0, 0.0),
+        // Overflowing containers fall back to `flex-start` or `center`.
+        AlignContent::SpaceBetween if count == 1 || remaining < 0.0 => (0.0, 0.0),
+        AlignContent::SpaceBetween => (0.0, remaining / (n - 1.0)),
+        AlignContent::SpaceAround | AlignContent::SpaceEvenly if remaining < 0.0 => {
+            (remaining / 2.0, 0.0)
+        }
+        AlignContent::SpaceAround => (remaining / n / 2.0, remaining / n),
+        AlignContent::SpaceEvenly => (remaining / (n + 1.0), remaining / (n + 1.0)),
+    }
+}
@@ -0,0 +1,315 @@
+//! Grid layout.
+//!
+//! http://www.w3.org/TR/css-grid-1/#layout-algorithm
+//!
+//! Tracks are sized in px, fr, or auto, and items are stretched to fill their grid areas. Named
+//! lines and areas, `repeat()`, `minmax()`, and dense packing are not supported.
+//!
+//! Like browsers, the grid has at most `MAX_TRACKS` tracks along each axis, explicit or implicit:
+//! lines past them are clamped to the last line, and spans to the whole grid.
+
+use super::LayoutBox;
+use geometry::Rect;
+use style::{GridLine, GridPlacement, Pixels, Track};
+use std::ops::Range;
+
+/// The maximum number of tracks along each axis of a grid.
+const MAX_TRACKS: usize = 1000;
+
+/// The tracks occupied by a grid item.
+struct Area {
+    column: Range<usize>,
+    row: Range<usize>,
+}
+
+/// The position of a grid item along one axis, before auto-placement.
+enum Position {
+    Definite(Range<usize>),
+    Auto(usize),
+}
+
+impl LayoutBox {
+    /// Lay out a grid container and its grid items.
+    pub(super) fn layout_grid(&mut self) {
+        // The container itself is sized and positioned like a block.
+        self.calculate_block_width();
+        self.calculate_block_position();
+
+        let areas = self.place_grid_items();
+        let columns = self.grid_columns(&areas, Some(self.content_box.width));
+
+        // Lay out every item in the width of its area to find the height of the rows.
+        let (column_gap, row_gap) = (self.style.column_gap(), self.style.row_gap());
+        let mut heights = Vec::with_capacity(areas.len());
+        for (item, area) in self.children.iter_mut().zip(&areas) {
+            let width = span_size(&columns, &area.column, column_gap);
+            item.forced_width = None;
+            item.forced_height = None;
+            item.containing_width = width;
+            item.containing_direction = self.style.direction();
+            item.containing_height = None;
+            item.container = Rect { x: 0.0, y: 0.0, width, height: 0.0 };
+            item.layout();
+            heights.push((area.row.clone(), item.margin_box.height));
+        }
+        let height = self.definite_height();
+        let row_count = areas.iter().map(|area| area.row.end)
+            .fold(self.style.grid_template_rows().len(), usize::max);
+        let templates = self.style.grid_template_rows();
+        let rows = size_tracks(templates, row_count, height, row_gap, &heights);
+
+        // Place every item in its area, stretching it to the height of the area.
+        for (item, area) in self.children.iter_mut().zip(&areas) {
+            let x = self.content_box.x + track_offset(&columns, area.column.start, column_gap);
+            let y = self.content_box.y + track_offset(&rows, area.row.start, row_gap);
+            let width = span_size(&columns, &area.column, column_gap);
+            let height = span_size(&rows, &area.row, row_gap);
+            item.containing_width = width;
+            item.containing_direction = self.style.direction();
+            item.containing_height = Some(height);
+            if item.specified_height().is_auto() {
+                item.forced_height = Some((height - item.vertical_edges()).max(0.0));
+            }
+            item.container = Rect { x, y, width, height: 0.0 };
+            item.layout();
+        }
+
+        self.intrinsic.height = span_size(&rows, &(0..rows.len()), row_gap);
+        self.calculate_block_height();
+    }
+
+    /// Width of the grid's content box when laid out without any constraint.
+    pub(super) fn grid_max_content_width(&self) -> Pixels {
+        let areas = self.place_grid_items();
+        let columns = self.grid_columns(&areas, None);
+        span_size(&columns, &(0..columns.len()), self.style.column_gap())
+    }
+
+    /// Size the columns of the grid, given the width available to them (if any).
+    fn grid_columns(&self, areas: &[Area], width: Option<Pixels>) -> Vec<Pixels> {
+        let widths: Vec<(Range<usize>, Pixels)> = self.children.iter().zip(areas)
+            .map(|(item, area)| (area.column.clone(), item.max_content_width()))
+            .collect();
+        let column_count = areas.iter().map(|area| area.column.end)
+            .fold(self.style.grid_template_columns().len(), usize::max);
+        size_tracks(self.style.grid_template_columns(), column_count, width,
+                    self.style.column_gap(), &widths)
+    }
+
+    /// Assign every grid item to an area of the grid, placing items without a definite position
+    /// into the first free cells in row-major order.
+    fn place_grid_items(&self) -> Vec<Area> {
+        let explicit_columns = self.style.grid_template_columns().len();
+        let explicit_rows = self.style.grid_template_rows().len();
+        let positions: Vec<(Position, Position)> = self.children.iter()
+            .map(|item| (resolve_placement(item.style.grid_column(), explicit_columns),
+                         resolve_placement(item.style.grid_row(), explicit_rows)))
+            .collect();
+
+        // Auto-placed items flow through the explicit columns, or through as many columns as the
+        // definitely placed items occupy.
+        let column_count = positions.iter()
+            .map(|position| match position.0 {
+                Position::Definite(ref columns) => columns.end,
+                Position::Auto(span) => span,
+            })
+            .fold(explicit_columns.max(1), usize::max);
+
+        // Items with a definite position are placed first.
+        let mut grid = Occupancy::default();
+        let mut areas: Vec<Option<Area>> = positions.iter().map(|position| match *position {
+            (Position::Definite(ref column), Position::Definite(ref row)) => {
+                grid.occupy(column, row);
+                Some(Area { column: column.clone(), row: row.clone() })
+            }
+            _ => None,
+        }).collect();
+
+        // Then the others are placed in document order, after the previous auto-placed item.
+        let mut cursor = (0, 0); // (row, column)
+        for (area, position) in areas.iter_mut().zip(&positions) {
+            if area.is_some() {
+                continue;
+            }
+            let placed = match *position {
+                (Position::Definite(ref column), Position::Auto(span)) => {
+                    let row = (0..).find(|&row| grid.is_free(column, &(row..row + span))).unwrap();
+                    Area { column: column.clone(), row: row..row + span }
+                }
+                (Position::Auto(span), Position::Definite(ref row)) => {
+                    let column = (0..).find(|&c| grid.is_free(&(c..c + span), row)).unwrap();
+                    Area { column: column..column + span, row: row.clone() }
+                }
+                (Position::Auto(column_span), Position::Auto(row_span)) => {
+                    let (mut row, mut column) = cursor;
+                    loop {
+                        if column + column_span > column_count {
+                            row += 1;
+                            column = 0;
+                        }
+                        let area = Area {
+                            column: column..column + column_span,
+                            row: row..row + row_span,
+                        };
+                        if grid.is_free(&area.column, &area.row) {
+                            break area;
+                        }
+                        column += 1;
+                    }
+                }
+                (Position::Definite(_), Position::Definite(_)) => unreachable!(),
+            };
+            grid.occupy(&placed.column, &placed.row);
+            if let Position::Auto(_) = position.0 {
+                cursor = (placed.row.start, placed.column.end);
+            }
+            *area = Some(placed);
+        }
+        areas.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+/// Resolve a grid item's placement along one axis to zero-based track indices.
+fn resolve_placement(placement: GridPlacement, explicit: usize) -> Position {
+    // Negative lines count back from the end of the explicit grid.
+    let line = |n: i32| if n > 0 {
+        (n as usize - 1).min(MAX_TRACKS)
+    } else {
+        (explicit as i64 + 1 + n as i64).max(0) as usize
+    };
+    let span = |span: usize| span.min(MAX_TRACKS);
+    let position = match (placement.start, placement.end) {
+        (GridLine::Line(start), GridLine::Line(end)) => {
+            let (start, end) = (line(start), line(end));
+            if end > start {
+                Position::Definite(start..end)
+            } else if start > end {
+                Position::Definite(end..start)
+            } else {
+                Position::Definite(start..start + 1)
+            }
+        }
+        (GridLine::Line(start), GridLine::Span(n)) => {
+            Position::Definite(line(start)..line(start) + span(n))
+        }
+        (GridLine::Line(start), GridLine::Auto) => {
+            Position::Definite(line(start)..line(start) + 1)
+        }
+        (GridLine::Span(n), GridLine::Line(end)) => {
+            let end = line(end).max(1);
+            Position::Definite(end.saturating_sub(span(n))..end)
+        }
+        (GridLine::Auto, GridLine::Line(end)) => {
+            let end = line(end).max(1);
+            Position::Definite(end - 1..end)
+        }
+        (GridLine::Span(n), _) | (GridLine::Auto, GridLine::Span(n)) => Position::Auto(span(n)),
+        (GridLine::Auto, GridLine::Auto) => Position::Auto(1),
+    };
+    // Items past the last track are moved back onto it.
+    match position {
+        Position::Definite(tracks) => {
+            let start = tracks.start.min(MAX_TRACKS - 1);
+            Position::Definite(start..tracks.end.clamp(start + 1, MAX_TRACKS))
+        }
+        auto => auto,
+    }
+}
+
+/// Cells of the grid that are taken by items, indexed by row and then column.
+#[derive(Default)]
+struct Occupancy(Vec<Vec<bool>>);
+
+impl Occupancy {
+    fn is_free(&self, columns: &Range<usize>, rows: &Range<usize>) -> bool {
+        let taken = |row: usize, column: usize| {
+            self.0.get(row).and_then(|cells| cells.get(column)).cloned().unwrap_or(false)
+        };
+        rows.clone().all(|row| columns.clone().all(|column| !taken(row, column)))
+    }
+
+    fn occupy(&mut self, columns: &Range<usize>, rows: &Range<usize>) {
+        for row in rows.clone() {
+            if self.0.len() <= row {
+                self.0.resize(row + 1, Vec::new());
+            }
+            let cells = &mut self.0[row];
+            if cells.len() < columns.end {
+                cells.resize(columns.end, false);
+            }
+            for column in columns.clone() {
+                cells[column] = true;
+            }
+        }
+    }
+}
+
+/// Size `count` tracks from their templates (implicit tracks are auto-sized), given the space
+/// available to them (if any) and the outer sizes of the items spanning each range of tracks.
+fn size_tracks(templates: &[Track], count: usize, available: Option<Pixels>, gap: Pixels,
+               items: &[(Range<usize>, Pixels)]) -> Vec<Pixels> {
+    let tracks: Vec<Track> = (0..count)
+        .map(|i| templates.get(i).cloned().unwrap_or(Track::Auto))
+        .collect();
+
+    // Without a definite size to divide, flexible tracks are sized like auto tracks.
+    let fraction_sized = available.is_some();
+    let is_auto = |track: &Track| match *track {
+        Track::Auto => true,
+        Track::Fraction(_) => !fraction_sized,
+        Track::Fixed(_) => false,
+    };
+
+    // Fixed tracks take their size, and auto tracks the size of their largest single-span item.
+    let mut sizes: Vec<Pixels> = tracks.iter().map(|track| match *track {
+        Track::Fixed(size) => size,
+        _ => 0.0,
+    }).collect();
+    for &(ref span, size) in items.iter().filter(|(span, _)| span.len() == 1) {
+        if is_auto(&tracks[span.start]) {
+            sizes[span.start] = sizes[span.start].max(size);
+        }
+    }
+
+    // Items spanning several tracks share any extra size they need among their auto tracks.
+    for &(ref span, size) in items.iter().filter(|(span, _)| span.len() > 1) {
+        let auto: Vec<usize> = span.clone().filter(|&i| is_auto(&tracks[i])).collect();
+        let extra = size - span_size(&sizes, span, gap);
+        if extra > 0.0 && !auto.is_empty() {
+            for &i in &auto {
+                sizes[i] += extra / auto.len() as Pixels;
+            }
+        }
+    }
+
+    // Flexible tracks share the space left over by all the others.
+    if let Some(available) = available {
+        let flex: f32 = tracks.iter().map(|track| match *track {
+            Track::Fraction(fr) => fr,
+            _ => 0.0,
+        }).sum();
+        if flex > 0.0 {
+            let leftover = (available - span_size(&sizes, &(0..count), gap)).max(0.0);
+            let unit = leftover / flex.max(1.0);
+            for (size, track) in sizes.iter_mut().zip(&tracks) {
+                if let Track::Fraction(fr) = *track {
+                    *size = fr * unit;
+                }
+            }
+        }
+    }
+    sizes
+}
+
+/// Distance from the start of the first track to the start of track `index`.
+fn track_offset(sizes: &[Pixels], index: usize, gap: Pixels) -> Pixels {
+    sizes[..index].iter().sum::<Pixels>() + gap * index as Pixels
+}
+
+/// Size of a range of tracks, including the gaps between them.
+fn span_size(sizes: &[Pixels], span: &Range<usize>, gap: Pixels) -> Pixels {
+    if span.start >= span.end {
+        return 0.0;
+    }
+    sizes[span.clone()].iter().sum::<Pixels>() + gap * (span.len() - 1) as Pixels
+}
@@ -0,0 +1,116 @@
+//! Markers of list items.
+//!
+//! Text cannot be painted yet, so markers are drawn with solid rectangles: a disc row by row, and
+//! numbers in a small built-in pixel font. They are black, since there is no `color` property, and
+//! sized for a 16px line. A marker sits outside the item's principal box, before the start of its
+//! first line: to the left of the content box, or to the right if the item is right-to-left.
+
+use super::LayoutBox;
+use css::Color;
+use paint::{DisplayCommand, DisplayList};
+use style::{Direction, ListStyleType, Pixels};
+
+const MARKER_COLOR: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+
+/// The height of the first line of a list item, which the marker is centered on.
+const LINE_HEIGHT: Pixels = 16.0;
+
+/// The space between the marker and the content box.
+const MARKER_GAP: Pixels = 8.0;
+
+/// The diameter of a disc.
+const DISC_SIZE: Pixels = 6.0;
+
+/// The size of a cell of the pixel font, whose glyphs are 3 cells wide and 5 tall.
+const CELL: Pixels = 2.0;
+
+/// The digits 0 to 9 in the pixel font, with `#` for a filled cell.
+const DIGITS: [[&str; 5]; 10] = [
+    ["###", "#.#", "#.#", "#.#", "###"],
+    [".#.", "##.", ".#.", ".#.", "###"],
+    ["###", "..#", "###", "#..", "###"],
+    ["###", "..#", "###", "..#", "###"],
+    ["#.#", "#.#", "###", "..#", "..#"],
+    ["###", "#..", "###", "..#", "###"],
+    ["###", "#..", "###", "#.#", "###"],
+    ["###", "..#", "..#", "..#", "..#"],
+    ["###", "#.#", "###", "#.#", "###"],
+    ["###", "#.#", "###", "..#", "###"],
+];
+
+/// The period after a number.
+const PERIOD: [&str; 5] = [".", ".", ".", ".", "#"];
+
+impl LayoutBox {
+    /// Paint the marker of a list item, whose number in its list is `ordinal`.
+    pub(super) fn render_marker(&self, ordinal: usize, list: &mut DisplayList) {
+        match self.style.list_style_type() {
+            ListStyleType::Disc => {
+                let x = self.marker_x(DISC_SIZE);
+                let y = self.content_box.y + (LINE_HEIGHT - DISC_SIZE) / 2.0;
+                render_disc(x, y, list);
+            }
+            ListStyleType::Decimal => {
+                let mut glyphs: Vec<&[&str; 5]> = ordinal.to_string().bytes()
+                    .map(|digit| &DIGITS[(digit - b'0') as usize])
+                    .collect();
+                glyphs.push(&PERIOD);
+                let width: Pixels = glyphs.iter()
+                    .map(|glyph| (glyph[0].len() as Pixels + 1.0) * CELL)
+                    .sum::<Pixels>() - CELL;
+                let mut x = self.marker_x(width);
+                let y = self.content_box.y + (LINE_HEIGHT - 5.0 * CELL) / 2.0;
+                for glyph in glyphs {
+                    render_glyph(glyph, x, y, list);
+                    x += (glyph[0].len() as Pixels + 1.0) * CELL;
+                }
+            }
+            ListStyleType::None => {}
+        }
+    }
+
+    /// The left edge of a marker of the given width.
+    fn marker_x(&self, width: Pixels) -> Pixels {
+        match self.style.direction() {
+            Direction::Ltr => self.content_box.x - MARKER_GAP - width,
+            Direction::Rtl => self.content_box.x + self.content_box.width + MARKER_GAP,
+        }
+    }
+}
+
+/// Paint a disc with its top left corner at `(x, y)`, one row of pixels at a time.
+fn render_disc(x: Pixels, y: Pixels, list: &mut DisplayList) {
+    let radius = DISC_SIZE / 2.0;
+    for row in 0..DISC_SIZE as usize {
+        // The width of the disc through the middle of the row.
+        let dy = row as Pixels + 0.5 - radius;
+        let half = (radius * radius - dy * dy).sqrt().round();
+        list.push(DisplayCommand::SolidColor {
+            color: MARKER_COLOR,
+            x: x + radius - half,
+            y: y + row as Pixels,
+            width: 2.0 * half,
+            height: 1.0,
+        });
+    }
+}
+
+/// Paint a glyph of the pixel font with its top left corner at `(x, y)`, one run of filled cells
+/// at a time.
+fn render_glyph(glyph: &[&str; 5], x: Pixels, y: Pixels, list: &mut DisplayList) {
+    for (row, cells) in glyph.iter().enumerate() {
+        let mut column = 0;
+        for run in cells.split('.') {
+            if !run.is_empty() {
+                list.push(DisplayCommand::SolidColor {
+                    color: MARKER_COLOR,
+                    x: x + column as Pixels * CELL,
+                    y: y + row as Pixels * CELL,
+                    width: run.len() as Pixels * CELL,
+                    height: CELL,
+                });
+            }
+            column += run.len() + 1;
+        }
+    }
+}
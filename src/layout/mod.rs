@@ -0,0 +1,1286 @@
+//! Basic CSS block layout.
+
+use dom::{Node, NodeId};
+use geometry::{Point, Rect, Transform};
+use style::{StyledNode, Style, Display, Edge, Pixels, Automatic};
+use style::{BackgroundImage, ColorStop, GradientDirection, LengthPercentage, RadialExtent};
+use style::{Cursor, Direction, PointerEvents, Visibility};
+use paint::{DisplayList, DisplayCommand, GradientShape, GradientStop};
+use limits::LimitError;
+use std::cell::Cell;
+use std::default::Default;
+use std::fmt::Write;
+use std::mem;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use widget::{Widget, Widgets};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeStruct;
+
+#[cfg(feature = "flexbox")]
+mod flex;
+#[cfg(feature = "grid")]
+mod grid;
+mod list;
+mod table;
+
+// CSS box model. All sizes are in px.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "kebab-case"))]
+enum BoxType {
+    Block, // display: block, list-item
+    Inline, // display: inline
+    Table, // display: table
+    TableRow, // display: table-row
+    TableCell, // display: table-cell
+    #[cfg(feature = "flexbox")]
+    FlexContainer, // display: flex
+    #[cfg(feature = "grid")]
+    GridContainer, // display: grid
+    //Absolute, // position: absolute && display: block
+    //Fixed, // position: fixed && display: block
+    //Float, // display: block && float: left|right
+    //Text, // literal text
+}
+
+/// Position and size of the nested boxes of a laid out box, relative to the document origin.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoxGeometry {
+    pub content_box: Rect,
+    pub padding_box: Rect,
+    pub border_box: Rect,
+    pub margin_box: Rect,
+}
+
+/// Identifies a box in a layout tree. A box keeps its id when the tree is laid out again.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct LayoutNodeId(usize);
+
+impl LayoutNodeId {
+    /// Allocate an identifier that no other box has.
+    fn next() -> LayoutNodeId {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        LayoutNodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A node in the layout tree.
+pub struct LayoutBox {
+    /// Identifier of this box.
+    id: LayoutNodeId,
+    /// Position and size of the container box (from the containing block).
+    container: Rect,
+    /// Position and size ignoring any adjustments due to style constraints.
+    intrinsic: Rect,
+    /// Position and size of the content box relative to the document origin.
+    content_box: Rect,
+    /// Position and size of the padding box relative to the document origin.
+    padding_box: Rect,
+    /// Position and size of the border box relative to the document origin.
+    border_box: Rect,
+    /// Position and size of the margin box relative to the document origin.
+    margin_box: Rect,
+    /// Edges of the padding box.
+    padding: Edge<Pixels>,
+    /// Edges of the border box.
+    border: Edge<Pixels>,
+    /// Edges of the margin box.
+    margin: Edge<Pixels>,
+    /// Excess (or missing) horizontal space.
+    underflow: Pixels,
+    /// Content width imposed by the containing formatting context (e.g., a table column).
+    forced_width: Option<Pixels>,
+    /// Content height imposed by the containing formatting context (e.g., a table row).
+    forced_height: Option<Pixels>,
+    /// Width of the containing block, which percentages of margins refer to.
+    containing_width: Pixels,
+    /// Direction of the containing block, whose end margin gives way when over-constrained.
+    containing_direction: Direction,
+    /// Height of the containing block, if it is definite.
+    containing_height: Option<Pixels>,
+    /// Specified values from styling.
+    style: Arc<Style>,
+    /// The DOM node that generated this box, or `None` if the box is anonymous.
+    node: Option<NodeId>,
+    /// Fundamental layout mode (e.g., block, inline, float, absolute, &c.).
+    box_type: BoxType,
+    /// Zero or more descendant (child) boxes.
+    children: Vec<LayoutBox>,
+    /// The embedder's widget, if it draws the box's element.
+    widget: Option<Arc<dyn Widget>>,
+    /// Whether the box must be laid out again, even if its constraints are unchanged.
+    dirty: bool,
+    /// Constraints from the last time the box was laid out.
+    laid_out: Option<Constraints>,
+}
+
+/// The inputs to the layout of a box from its containing formatting context.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Constraints {
+    container: Rect,
+    forced_width: Option<Pixels>,
+    forced_height: Option<Pixels>,
+    containing_width: Pixels,
+    containing_direction: Direction,
+    containing_height: Option<Pixels>,
+}
+
+impl Constraints {
+    /// The position of the box's margin box, given these constraints.
+    fn origin(&self) -> (Pixels, Pixels) {
+        (self.container.x, self.container.y + self.container.height)
+    }
+
+    /// Would a box laid out with these constraints only move with `other`, not change size?
+    fn same_size(&self, other: &Constraints) -> bool {
+        self.container.width == other.container.width &&
+        self.forced_width == other.forced_width &&
+        self.forced_height == other.forced_height &&
+        self.containing_width == other.containing_width &&
+        self.containing_direction == other.containing_direction &&
+        self.containing_height == other.containing_height
+    }
+}
+
+impl LayoutBox {
+    fn new(box_type: BoxType, style: Arc<Style>) -> Self {
+        LayoutBox {
+            id: LayoutNodeId::next(),
+            container: Rect::default(),
+            intrinsic: Rect::default(),
+            content_box: Rect::default(),
+            padding_box: Rect::default(),
+            border_box: Rect::default(),
+            margin_box: Rect::default(),
+            padding: Edge::default(),
+            border: Edge::default(),
+            margin: Edge::default(),
+            underflow: 0.0,
+            forced_width: None,
+            forced_height: None,
+            containing_width: 0.0,
+            containing_direction: Direction::Ltr,
+            containing_height: None,
+            style,
+            node: None,
+            box_type,
+            children: Vec::new(),
+            widget: None,
+            dirty: true,
+            laid_out: None,
+        }
+    }
+}
+
+/// Parameters of a layout pass that come from outside the document.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LayoutContext {
+    /// Width of the initial containing block (i.e., the viewport).
+    pub viewport_width: Pixels,
+    /// Height of the initial containing block (i.e., the viewport).
+    pub viewport_height: Pixels,
+}
+
+/// A laid out tree of boxes, which owns the computed styles of its boxes.
+///
+/// Boxes are identified by `LayoutNodeId`, and map back to the DOM nodes that generated them.
+pub struct LayoutTree {
+    /// The box of the root element, or `None` if it has `display: none`.
+    root: Option<LayoutBox>,
+    context: LayoutContext,
+    /// The widgets of the elements drawn by the embedder.
+    widgets: Widgets,
+}
+
+/// Transform a style tree into a layout tree.
+///
+/// The style tree may be any subtree of the document's, which is laid out as if its root were the
+/// root element. If the root has `display: none`, the layout tree is empty.
+pub fn layout_tree(node: &StyledNode, context: LayoutContext) -> LayoutTree {
+    layout_tree_with_widgets(node, context, Widgets::new())
+}
+
+/// Transform a style tree into a layout tree like `layout_tree`, drawing the elements that have
+/// widgets with them.
+pub fn layout_tree_with_widgets(node: &StyledNode, context: LayoutContext, widgets: Widgets)
+    -> LayoutTree
+{
+    let mut root = build_layout_tree(node, &widgets);
+    if let Some(ref mut root) = root {
+        root.layout_root(context);
+    }
+    LayoutTree { root, context, widgets }
+}
+
+/// Transform a style tree into a layout tree like `layout_tree`, but give up if boxes would be laid
+/// out more than `max_layouts` times in all.
+pub fn layout_tree_limited(node: &StyledNode, context: LayoutContext, max_layouts: usize)
+    -> Result<LayoutTree, LimitError>
+{
+    with_layout_budget(max_layouts, || layout_tree(node, context))
+}
+
+/// Run `layout`, which lays out boxes (by building a layout tree or laying one out again), but
+/// give up if boxes would be laid out more than `max_layouts` times in all.
+///
+/// The boxes that `layout` leaves when it gives up are only partly laid out, and should be thrown
+/// away.
+pub fn with_layout_budget<T, F>(max_layouts: usize, layout: F) -> Result<T, LimitError>
+    where F: FnOnce() -> T
+{
+    LAYOUT_BUDGET.with(|budget| budget.set(LayoutBudget::Remaining(max_layouts)));
+    let result = layout();
+    match LAYOUT_BUDGET.with(|budget| budget.replace(LayoutBudget::Unlimited)) {
+        LayoutBudget::Exhausted => Err(LimitError::TooManyLayouts(max_layouts)),
+        _ => Ok(result),
+    }
+}
+
+/// The number of box layouts left in the current call to `with_layout_budget`.
+#[derive(Clone, Copy)]
+enum LayoutBudget {
+    Unlimited,
+    Remaining(usize),
+    /// A box needed to be laid out after the budget ran out, so layout is being abandoned.
+    Exhausted,
+}
+
+thread_local! {
+    static LAYOUT_BUDGET: Cell<LayoutBudget> = const { Cell::new(LayoutBudget::Unlimited) };
+}
+
+/// Count a box layout against the budget, returning whether the box may be laid out.
+fn spend_layout_budget() -> bool {
+    LAYOUT_BUDGET.with(|budget| match budget.get() {
+        LayoutBudget::Unlimited => true,
+        LayoutBudget::Remaining(0) | LayoutBudget::Exhausted => {
+            budget.set(LayoutBudget::Exhausted);
+            false
+        }
+        LayoutBudget::Remaining(n) => {
+            budget.set(LayoutBudget::Remaining(n - 1));
+            true
+        }
+    })
+}
+
+/// Lay out the subtree for the DOM node `id` on its own, as if it were the root element.
+///
+/// Returns `None` if the node is not in the style tree.
+pub fn layout_subtree(style_root: &StyledNode, id: NodeId, context: LayoutContext)
+    -> Option<LayoutTree>
+{
+    style_root.find(id).map(|node| layout_tree(node, context))
+}
+
+impl LayoutTree {
+    /// The box of the root element, or `None` if the tree is empty.
+    pub fn root(&self) -> Option<&LayoutBox> {
+        self.root.as_ref()
+    }
+
+    /// The box with the given id, if it is still in the tree.
+    pub fn get(&self, id: LayoutNodeId) -> Option<&LayoutBox> {
+        self.root.as_ref()?.find(&|layout_box| layout_box.id == id)
+    }
+
+    /// The boxes generated by a DOM node, in tree order.
+    pub fn boxes_for_node(&self, node: NodeId) -> Vec<LayoutNodeId> {
+        let mut ids = Vec::new();
+        if let Some(ref root) = self.root {
+            root.collect(&|layout_box| layout_box.node == Some(node), &mut ids);
+        }
+        ids
+    }
+
+    /// The geometry of the (first) box generated by a DOM node, or `None` if it has no box.
+    pub fn box_for_node(&self, node: &Node) -> Option<BoxGeometry> {
+        let id = node.id;
+        let layout_box = self.root.as_ref()?.find(&|layout_box| layout_box.node == Some(id))?;
+        Some(layout_box.geometry())
+    }
+
+    /// The DOM nodes whose boxes lie under the point `(x, y)`, topmost first.
+    pub fn hit_test(&self, x: Pixels, y: Pixels) -> Vec<NodeId> {
+        self.root.as_ref().map_or_else(Vec::new, |root| root.hit_test(x, y))
+    }
+
+    /// The mouse cursor to show over the point `(x, y)`; see `LayoutBox::cursor_at`.
+    pub fn cursor_at(&self, x: Pixels, y: Pixels) -> Cursor {
+        self.root.as_ref().map_or(Cursor::Default, |root| root.cursor_at(x, y))
+    }
+
+    /// Remove every box, as if the root element had `display: none`.
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    /// Set the widgets of the elements drawn by the embedder. They apply to the boxes built by
+    /// later calls to `relayout`: relayout the root to apply them to the whole tree.
+    pub fn set_widgets(&mut self, widgets: Widgets) {
+        self.widgets = widgets;
+    }
+
+    /// Lay the tree out again in another viewport. Boxes whose constraints do not change are only
+    /// moved.
+    pub fn set_context(&mut self, context: LayoutContext) {
+        self.context = context;
+        if let Some(ref mut root) = self.root {
+            root.layout_root(context);
+        }
+    }
+
+    /// Update the tree after the styles of the subtree for node `id` have been recomputed, and
+    /// lay it out again.
+    ///
+    /// Only the boxes for that subtree are rebuilt. Other boxes are laid out again only if their
+    /// constraints changed (or they contain the changed subtree), and are otherwise just moved.
+    ///
+    /// `style_root` must be the style tree this layout tree was built from.
+    pub fn relayout(&mut self, style_root: &StyledNode, id: NodeId) {
+        if style_root.node == id {
+            self.root = build_layout_tree(style_root, &self.widgets);
+        } else if let (Some(root), Some(parent)) = (&mut self.root, find_parent(style_root, id)) {
+            root.rebuild_child(parent, id, &self.widgets);
+        }
+        if let Some(ref mut root) = self.root {
+            root.layout_root(self.context);
+        }
+    }
+}
+
+/// Serialize the tree as its root box, or as nothing (`null` in JSON) if it is empty.
+#[cfg(feature = "serde")]
+impl Serialize for LayoutTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.root.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl LayoutTree {
+    /// The tree as pretty-printed JSON: each box with its type, the DOM node that generated it
+    /// (as a number, or `null` for anonymous boxes), its content, padding, border, and margin
+    /// boxes, its padding, border, and margin edges, and its children.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("layout trees always serialize")
+    }
+}
+
+/// Serialize a box with its geometry and children, but not its style or layout state.
+#[cfg(feature = "serde")]
+impl Serialize for LayoutBox {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("LayoutBox", 10)?;
+        state.serialize_field("box_type", &self.box_type)?;
+        state.serialize_field("node", &self.node)?;
+        state.serialize_field("content_box", &self.content_box)?;
+        state.serialize_field("padding_box", &self.padding_box)?;
+        state.serialize_field("border_box", &self.border_box)?;
+        state.serialize_field("margin_box", &self.margin_box)?;
+        state.serialize_field("padding", &self.padding)?;
+        state.serialize_field("border", &self.border)?;
+        state.serialize_field("margin", &self.margin)?;
+        state.serialize_field("children", &self.children)?;
+        state.end()
+    }
+}
+
+impl LayoutBox {
+    /// Identifier of this box.
+    pub fn id(&self) -> LayoutNodeId {
+        self.id
+    }
+
+    /// The DOM node that generated this box, or `None` if the box is anonymous.
+    pub fn node(&self) -> Option<NodeId> {
+        self.node
+    }
+
+    /// Computed values from styling.
+    pub fn style(&self) -> &Arc<Style> {
+        &self.style
+    }
+
+    /// Child boxes, in tree order.
+    pub fn children(&self) -> &[LayoutBox] {
+        &self.children
+    }
+
+    /// The laid out content, padding, border, and margin boxes.
+    pub fn geometry(&self) -> BoxGeometry {
+        BoxGeometry {
+            content_box: self.content_box,
+            padding_box: self.padding_box,
+            border_box: self.border_box,
+            margin_box: self.margin_box,
+        }
+    }
+
+    /// An indented outline of this subtree, one box per line, with the box's type, its border box,
+    /// and its margin, border, and padding edges (top, right, bottom, left).
+    ///
+    /// DOM node ids are left out, so the outline of a document is the same every time it is laid
+    /// out, for golden-file tests.
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+        self.dump_into(&mut dump, 0);
+        dump
+    }
+
+    fn dump_into(&self, dump: &mut String, depth: usize) {
+        fn edge(edge: &Edge<Pixels>) -> String {
+            format!("{} {} {} {}", edge.top, edge.right, edge.bottom, edge.left)
+        }
+
+        let b = self.border_box;
+        let anonymous = if self.node.is_none() { " (anonymous)" } else { "" };
+        let _ = writeln!(dump, "{}{:?}{} ({}, {}) {}x{}; margin {}; border {}; padding {}",
+                         "  ".repeat(depth), self.box_type, anonymous, b.x, b.y, b.width, b.height,
+                         edge(&self.margin), edge(&self.border), edge(&self.padding));
+        for child in &self.children {
+            child.dump_into(dump, depth + 1);
+        }
+    }
+
+    /// The DOM nodes whose boxes in this subtree lie under the point `(x, y)`, topmost first.
+    ///
+    /// Boxes are hit within their border box, where it is painted after transforms. Without
+    /// stacking contexts or clipping, later boxes paint over earlier ones, so this is the reverse
+    /// of painting order. Boxes with `pointer-events: none` or `visibility: hidden` are never hit,
+    /// though their descendants can be.
+    pub fn hit_test(&self, x: Pixels, y: Pixels) -> Vec<NodeId> {
+        let mut hits = Vec::new();
+        self.collect_hits(x, y, &mut hits);
+        hits.iter().rev().filter_map(|layout_box| layout_box.node).collect()
+    }
+
+    /// The mouse cursor to show over the point `(x, y)`: the `cursor` of the topmost box hit
+    /// there, or `default` if there is none. Since text is not laid out yet, `auto` is `default`.
+    pub fn cursor_at(&self, x: Pixels, y: Pixels) -> Cursor {
+        let mut hits = Vec::new();
+        self.collect_hits(x, y, &mut hits);
+        match hits.last().map(|layout_box| layout_box.style.cursor()) {
+            Some(Cursor::Auto) | None => Cursor::Default,
+            Some(cursor) => cursor,
+        }
+    }
+
+    /// Collect the boxes of DOM nodes that lie under the point `(x, y)`, in painting order.
+    ///
+    /// The point is mapped back through the transform of each box, to where it would be without
+    /// it, before testing the box and its descendants. Nothing is hit in a box whose transform
+    /// flattens it.
+    fn collect_hits<'b>(&'b self, x: Pixels, y: Pixels, hits: &mut Vec<&'b LayoutBox>) {
+        let Point { x, y } = match self.transform().inverse() {
+            Some(inverse) => inverse.apply(Point { x, y }),
+            None => return,
+        };
+        if self.node.is_some() && self.style.pointer_events() != PointerEvents::None
+            && self.style.visibility() == Visibility::Visible && self.border_box.contains(x, y) {
+            hits.push(self);
+        }
+        for child in &self.children {
+            child.collect_hits(x, y, hits);
+        }
+    }
+
+    /// Find the first box in this subtree (in tree order) that satisfies `predicate`.
+    fn find(&self, predicate: &dyn Fn(&LayoutBox) -> bool) -> Option<&LayoutBox> {
+        if predicate(self) {
+            return Some(self);
+        }
+        self.children.iter().filter_map(|child| child.find(predicate)).next()
+    }
+
+    /// Collect the ids of the boxes in this subtree (in tree order) that satisfy `predicate`.
+    fn collect(&self, predicate: &dyn Fn(&LayoutBox) -> bool, ids: &mut Vec<LayoutNodeId>) {
+        if predicate(self) {
+            ids.push(self.id);
+        }
+        for child in &self.children {
+            child.collect(predicate, ids);
+        }
+    }
+}
+
+/// The offsets of the stops of a gradient that is `length` px long.
+///
+/// The first and last stops default to the ends of the gradient, a stop before an earlier one
+/// moves up to it, and the other stops without a position are spread evenly between their
+/// neighbours.
+fn resolve_color_stops(stops: &[ColorStop], length: Pixels) -> Vec<GradientStop> {
+    let mut offsets: Vec<Option<f32>> = stops.iter()
+        .map(|stop| stop.position.map(|position| match position {
+            LengthPercentage::Length(px) if length > 0.0 => px / length,
+            LengthPercentage::Length(_) => 0.0,
+            LengthPercentage::Percentage(p) => p / 100.0,
+            LengthPercentage::Calc(px, p) if length > 0.0 => px / length + p / 100.0,
+            LengthPercentage::Calc(_, p) => p / 100.0,
+        }))
+        .collect();
+    let last = offsets.len() - 1;
+    offsets[0] = offsets[0].or(Some(0.0));
+    offsets[last] = offsets[last].or(Some(1.0));
+    let mut max = Pixels::NEG_INFINITY;
+    for offset in offsets.iter_mut().flatten() {
+        max = max.max(*offset);
+        *offset = max;
+    }
+    let mut i = 0;
+    while i < last {
+        let start = i;
+        i += 1;
+        while offsets[i].is_none() {
+            i += 1;
+        }
+        let (from, to) = (offsets[start].unwrap(), offsets[i].unwrap());
+        let steps = (i - start) as f32;
+        for (step, offset) in offsets[start + 1..i].iter_mut().enumerate() {
+            *offset = Some(from + (to - from) * (step + 1) as f32 / steps);
+        }
+    }
+    stops.iter().zip(offsets)
+        .map(|(stop, offset)| GradientStop { offset: offset.unwrap(), color: stop.color })
+        .collect()
+}
+
+/// Find the style node whose child is the style node for node `id`.
+fn find_parent(style_node: &StyledNode, id: NodeId) -> Option<&StyledNode> {
+    if style_node.children.iter().any(|child| child.node == id) {
+        return Some(style_node);
+    }
+    style_node.children.iter().filter_map(|child| find_parent(child, id)).next()
+}
+
+/// Build the tree of LayoutBoxes, but don't perform any layout calculations yet.
+///
+/// The contents of an element drawn by a widget get no boxes.
+fn build_layout_tree(style_node: &StyledNode, widgets: &Widgets) -> Option<LayoutBox> {
+    // Create the root box.
+    let box_type = match style_node.specified.display() {
+        Display::Block | Display::ListItem => Some(BoxType::Block),
+        Display::Inline => Some(BoxType::Inline),
+        Display::Table => Some(BoxType::Table),
+        Display::TableRow => Some(BoxType::TableRow),
+        Display::TableCell => Some(BoxType::TableCell),
+        #[cfg(feature = "flexbox")]
+        Display::Flex => Some(BoxType::FlexContainer),
+        #[cfg(feature = "grid")]
+        Display::Grid => Some(BoxType::GridContainer),
+        // Without the layout modes built in, their containers are laid out as blocks.
+        #[cfg(not(feature = "flexbox"))]
+        Display::Flex => Some(BoxType::Block),
+        #[cfg(not(feature = "grid"))]
+        Display::Grid => Some(BoxType::Block),
+        Display::None => None,
+    }?;
+    let mut root = LayoutBox::new(box_type, style_node.specified.clone());
+    root.node = Some(style_node.node);
+    root.widget = widgets.get(&style_node.node).cloned();
+    if root.widget.is_some() {
+        return Some(root);
+    }
+
+    // Create the descendant boxes.
+    for child in &style_node.children {
+        if let Some(child) = build_layout_tree(child, widgets) {
+            root.push_child(child);
+        }
+    }
+    Some(root)
+}
+
+impl BoxType {
+    /// Does a box of this type participate in a block formatting context?
+    fn is_block_level(self) -> bool {
+        match self {
+            BoxType::Block | BoxType::Table => true,
+            #[cfg(feature = "flexbox")]
+            BoxType::FlexContainer => true,
+            #[cfg(feature = "grid")]
+            BoxType::GridContainer => true,
+            BoxType::Inline | BoxType::TableRow | BoxType::TableCell => false,
+        }
+    }
+
+    /// The type of anonymous box needed to hold a child of type `child` inside a box of this
+    /// type, or `None` if the child can be placed directly.
+    fn wrapper_for(self, child: BoxType) -> Option<BoxType> {
+        match (self, child) {
+            // Flex and grid items are blockified, with runs of inline content wrapped in anonymous
+            // blocks.
+            #[cfg(feature = "flexbox")]
+            (BoxType::FlexContainer, BoxType::Inline) => Some(BoxType::Block),
+            #[cfg(feature = "flexbox")]
+            (BoxType::FlexContainer, _) => None,
+            #[cfg(feature = "grid")]
+            (BoxType::GridContainer, BoxType::Inline) => Some(BoxType::Block),
+            #[cfg(feature = "grid")]
+            (BoxType::GridContainer, _) => None,
+            (BoxType::Table, BoxType::TableRow) => None,
+            (BoxType::Table, _) => Some(BoxType::TableRow),
+            (BoxType::TableRow, BoxType::TableCell) => None,
+            (BoxType::TableRow, _) => Some(BoxType::TableCell),
+            (_, BoxType::TableRow) | (_, BoxType::TableCell) => Some(BoxType::Table),
+            (BoxType::Block, child) | (BoxType::TableCell, child) if child.is_block_level() => None,
+            (BoxType::TableCell, _) => Some(BoxType::Block),
+            // TODO: The child sequence is really supposed to be restricted to the supremum of all
+            // real child box types, taking Text < Inline < Block.
+            // This hacky check effectively just follows the original toy layout algorithm.
+            (parent, child) if parent == child => None,
+            (parent, _) => Some(parent),
+        }
+    }
+}
+
+impl LayoutBox {
+    /// Append a child box, generating anonymous wrapper boxes as needed.
+    fn push_child(&mut self, child: LayoutBox) {
+        match self.box_type.wrapper_for(child.box_type) {
+            // An anonymous box of our own type already is the wrapper.
+            Some(wrapper) if self.node.is_some() || wrapper != self.box_type => {
+                // Consecutive children share the same wrapper.
+                let reuse = match self.children.last() {
+                    Some(last) => last.node.is_none() && last.box_type == wrapper,
+                    None => false,
+                };
+                if !reuse {
+                    self.children.push(LayoutBox::new(wrapper, anonymous_style()));
+                }
+                self.children.last_mut().unwrap().push_child(child);
+            }
+            _ => self.children.push(child),
+        }
+    }
+
+    /// Rebuild the box for the child `id` of the element styled by `parent`, reusing the boxes of
+    /// its other children, and mark the boxes containing it for layout.
+    ///
+    /// Returns whether the element's box was found in this subtree.
+    fn rebuild_child(&mut self, parent: &StyledNode, id: NodeId, widgets: &Widgets) -> bool {
+        if self.node == Some(parent.node) {
+            // Unwrap the boxes of the other children from any anonymous wrappers, and wrap them
+            // again around the new box.
+            let mut old = Vec::new();
+            for child in mem::take(&mut self.children) {
+                child.unwrap_anonymous(&mut old);
+            }
+            for style_child in &parent.children {
+                let node = Some(style_child.node);
+                let child = match old.iter().position(|child| child.node == node) {
+                    Some(i) if style_child.node != id => Some(old.swap_remove(i)),
+                    _ => build_layout_tree(style_child, widgets),
+                };
+                if let Some(child) = child {
+                    self.push_child(child);
+                }
+            }
+            self.dirty = true;
+            return true;
+        }
+        let found = self.children.iter_mut()
+            .any(|child| child.rebuild_child(parent, id, widgets));
+        self.dirty |= found;
+        found
+    }
+
+    /// Collect the boxes generated for elements, descending into anonymous boxes.
+    fn unwrap_anonymous(self, boxes: &mut Vec<LayoutBox>) {
+        if self.node.is_some() {
+            boxes.push(self);
+        } else {
+            for child in self.children {
+                child.unwrap_anonymous(boxes);
+            }
+        }
+    }
+}
+
+/// Style of anonymous boxes, which have the initial value of every property.
+fn anonymous_style() -> Arc<Style> {
+    static STYLE: OnceLock<Arc<Style>> = OnceLock::new();
+    STYLE.get_or_init(|| Arc::new(Style::default())).clone()
+}
+
+/// Fold the layout tree into a display list to render.
+pub fn display_list(layout_tree: &LayoutTree) -> DisplayList {
+    let mut list = DisplayList::new();
+    if let Some(ref root) = layout_tree.root {
+        root.render(&mut list, 1);
+    }
+    list
+}
+
+impl LayoutBox {
+    /// Lay out the root box in the initial containing block.
+    fn layout_root(&mut self, context: LayoutContext) {
+        self.container.width = context.viewport_width;
+        self.containing_width = context.viewport_width;
+        // The initial containing block takes the direction of the root element.
+        self.containing_direction = self.style.direction();
+        self.containing_height = Some(context.viewport_height);
+        self.layout();
+    }
+
+    /// Lay out a box and its descendants.
+    ///
+    /// A box that is not dirty and was last laid out with constraints of the same size is only
+    /// moved to its new position.
+    fn layout(&mut self) {
+        if !spend_layout_budget() {
+            return;
+        }
+        let constraints = Constraints {
+            container: self.container,
+            forced_width: self.forced_width,
+            forced_height: self.forced_height,
+            containing_width: self.containing_width,
+            containing_direction: self.containing_direction,
+            containing_height: self.containing_height,
+        };
+        match self.laid_out {
+            Some(last) if !self.dirty && last.same_size(&constraints) => {
+                let ((x, y), (last_x, last_y)) = (constraints.origin(), last.origin());
+                self.translate(x - last_x, y - last_y);
+                self.container = constraints.container;
+            }
+            _ if self.widget.is_some() => self.layout_widget(),
+            _ => match self.box_type {
+                BoxType::Block | BoxType::TableRow | BoxType::TableCell => self.layout_block(),
+                BoxType::Table => self.layout_table(),
+                #[cfg(feature = "flexbox")]
+                BoxType::FlexContainer => self.layout_flex(),
+                #[cfg(feature = "grid")]
+                BoxType::GridContainer => self.layout_grid(),
+                BoxType::Inline => {},
+            },
+        }
+        self.dirty = false;
+        self.laid_out = Some(constraints);
+    }
+
+    /// Move a laid out box and its descendants.
+    fn translate(&mut self, dx: Pixels, dy: Pixels) {
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        for rect in [&mut self.container, &mut self.intrinsic, &mut self.content_box,
+                     &mut self.padding_box, &mut self.border_box, &mut self.margin_box] {
+            rect.x += dx;
+            rect.y += dy;
+        }
+        if let Some(ref mut last) = self.laid_out {
+            last.container.x += dx;
+            last.container.y += dy;
+        }
+        for child in &mut self.children {
+            child.translate(dx, dy);
+        }
+    }
+
+    /// Lay out a block-level element and its descendants.
+    fn layout_block(&mut self) {
+        // Child width can depend on parent width, so we need to calculate this box's width before
+        // laying out its children.
+        self.calculate_block_width();
+
+        // Finish calculating the block's edge sizes, and position it within its containing block.
+        self.calculate_block_position();
+
+        // Recursively lay out the children of this box.
+        self.intrinsic.height = 0.0; // fold accumulator
+        let height = self.definite_height();
+        for child in &mut self.children {
+            // Give the child box the boundaries of its container.
+            child.container.x = self.intrinsic.x;
+            child.container.y = self.intrinsic.y;
+            child.container.height = self.intrinsic.height;
+            child.container.width = self.content_box.width;
+            child.containing_width = self.content_box.width;
+            child.containing_direction = self.style.direction();
+            child.containing_height = height;
+            // Lay out the child box.
+            child.layout();
+            // Increment the height so each child is laid out below the previous one.
+            self.intrinsic.height += child.margin_box.height;
+        }
+
+        // Parent height can depend on child height, so `calculate_height` must be called after the
+        // children are laid out.
+        self.calculate_block_height();
+    }
+
+    /// Lay out an element drawn by a widget, like a block-level replaced element: the widget's
+    /// intrinsic size stands in for the laid out contents.
+    ///
+    /// Without inline layout, widgets with `display: inline` are laid out in the same way.
+    fn layout_widget(&mut self) {
+        let widget = self.widget.clone().expect("box has no widget");
+        let node = self.node.expect("widget box is anonymous");
+        let width = self.forced_width.or(match self.specified_width() {
+            Automatic::Given(width) => Some(width),
+            Automatic::Auto => None,
+        });
+        let available = width.unwrap_or(self.container.width - self.horizontal_edges());
+        let size = widget.intrinsic_size(node, available.max(0.0));
+
+        self.calculate_width(Automatic::Given(width.unwrap_or(size.width)));
+        self.calculate_block_position();
+        self.intrinsic.height = size.height;
+        self.calculate_block_height();
+    }
+
+    /// Calculate the vertical edges of a block-level box and position it in its container.
+    ///
+    /// Must be called after the horizontal edges are known.
+    fn calculate_block_position(&mut self) {
+        let margin = self.specified_margin();
+        self.margin.top = margin.top.value(); // auto ==> 0
+        self.margin.bottom = margin.bottom.value(); // auto ==> 0
+
+        self.border.top = self.style.border_top_width();
+        self.border.bottom = self.style.border_bottom_width();
+
+        self.padding.top = self.style.padding_top();
+        self.padding.bottom = self.style.padding_bottom();
+
+        // Position the box flush (w.r.t. margin/border/padding) to the start edge of the
+        // container: its left edge, or its right edge in right-to-left flow.
+        self.intrinsic.x = match self.containing_direction {
+            Direction::Ltr => {
+                self.container.x + self.margin.left + self.border.left + self.padding.left
+            }
+            Direction::Rtl => {
+                self.container.x + self.container.width - self.margin.right - self.border.right
+                    - self.padding.right - self.content_box.width
+            }
+        };
+        self.content_box.x = self.intrinsic.x;
+
+        // Position the box below all the previous boxes in the container.
+        self.intrinsic.y = self.container.y + self.container.height +
+                           self.margin.top + self.border.top + self.padding.top;
+        self.content_box.y = self.intrinsic.y;
+    }
+
+    /// Calculate the height of a block-level box from its laid out contents (`intrinsic.height`),
+    /// and derive the outer boxes.
+    ///
+    /// Children with negative margins can overlap their siblings, or stick out of the top of the
+    /// box, so the contents can be shorter than the children, but never less than empty.
+    fn calculate_block_height(&mut self) {
+        let intrinsic = self.intrinsic.height.max(0.0);
+        let height = self.forced_height.unwrap_or(self.specified_height().take(intrinsic));
+        self.calculate_height(height);
+    }
+
+    /// Set the used height of the content box, and derive the outer boxes.
+    fn calculate_height(&mut self, height: Pixels) {
+        self.content_box.height = height;
+
+        self.padding_box = self.content_box.expanded_by(self.padding);
+        self.border_box = self.padding_box.expanded_by(self.border);
+        self.margin_box = self.border_box.expanded_by(self.margin);
+    }
+
+    /// Calculate the width of a block-level non-replaced element in normal flow.
+    ///
+    /// http://www.w3.org/TR/CSS2/visudet.html#blockwidth
+    ///
+    /// Sets the horizontal margin/padding/border dimensions, and the `width`.
+    fn calculate_block_width(&mut self) {
+        let width = self.forced_width.map(Automatic::Given).unwrap_or(self.specified_width());
+        self.calculate_width(width);
+    }
+
+    /// Calculate the horizontal edges and content width of a block-level box in normal flow,
+    /// given its width (as specified or as imposed by the formatting context).
+    fn calculate_width(&mut self, width: Automatic<Pixels>) {
+        let margin = self.specified_margin();
+        self.intrinsic.width = [
+            margin.left.value(), margin.right.value(),
+            self.style.border_left_width(), self.style.border_right_width(),
+            self.style.padding_left(), self.style.padding_right(),
+            width.value(),
+        ].iter().sum();
+
+        // Adjust used values so that the above sum equals `containing_block.width`.
+        // Each arm of the `match` should increase the total width by exactly `underflow`,
+        // and afterward all values should be absolute lengths in px.
+        self.underflow = self.container.width - self.intrinsic.width;
+
+        self.padding.left = self.style.padding_left();
+        self.padding.right = self.style.padding_right();
+
+        self.border.left = self.style.border_left_width();
+        self.border.right = self.style.border_right_width();
+
+        self.content_box.width = if width.is_auto() {
+            self.underflow.max(0.0)
+        } else {
+            width.value()
+        };
+
+        // Resolve the margins at the start and end of the line, which are the left and right
+        // margins in left-to-right flow, and the other way around in right-to-left flow.
+        let rtl = self.containing_direction == Direction::Rtl;
+        let (start_margin, end_margin) = if rtl {
+            (margin.right, margin.left)
+        } else {
+            (margin.left, margin.right)
+        };
+
+        let start = if start_margin.is_auto() {
+            if width.is_auto() || self.underflow < 0.0 {
+                0.0
+            } else if end_margin.is_auto() {
+                self.underflow / 2.0
+            } else {
+                self.underflow
+            }
+        } else {
+            start_margin.value()
+        };
+
+        let end = if width.is_auto() {
+            // The width takes up the underflow, unless that would make it negative.
+            end_margin.value() + self.underflow.min(0.0)
+        } else if start_margin.is_auto() && self.underflow >= 0.0 {
+            if end_margin.is_auto() {
+                self.underflow / 2.0
+            } else {
+                end_margin.value()
+            }
+        } else {
+            // Over-constrained (or with only the end margin auto), so the end margin takes up
+            // the underflow, even if that makes it negative.
+            end_margin.value() + self.underflow
+        };
+
+        let (left, right) = if rtl { (end, start) } else { (start, end) };
+        self.margin.left = left;
+        self.margin.right = right;
+    }
+
+    /// Width of the margin box when laid out without any constraint from the container.
+    ///
+    /// With no text to break into lines, this only depends on the specified widths of the box and
+    /// its descendants.
+    fn max_content_width(&self) -> Pixels {
+        // Percentages of the container's width are treated as `auto`.
+        let content = match self.style.width().resolve(None) {
+            Automatic::Given(width) => width,
+            Automatic::Auto if self.widget.is_some() => {
+                let widget = self.widget.as_ref().unwrap();
+                let node = self.node.expect("widget box is anonymous");
+                widget.intrinsic_size(node, Pixels::INFINITY).width
+            }
+            Automatic::Auto => match self.box_type {
+                BoxType::Table => self.table_max_content_width(),
+                #[cfg(feature = "grid")]
+                BoxType::GridContainer => self.grid_max_content_width(),
+                #[cfg(feature = "flexbox")]
+                BoxType::FlexContainer if self.style.flex_direction().is_row() => {
+                    self.children.iter().map(LayoutBox::max_content_width).sum()
+                }
+                BoxType::TableRow | BoxType::Inline => {
+                    self.children.iter().map(LayoutBox::max_content_width).sum()
+                }
+                // Column flex containers are as wide as their widest item, like blocks.
+                _ => {
+                    self.children.iter().map(LayoutBox::max_content_width).fold(0.0, Pixels::max)
+                }
+            },
+        };
+        content + self.horizontal_edges()
+    }
+
+    /// The specified content width, resolved against the width of the container.
+    fn specified_width(&self) -> Automatic<Pixels> {
+        self.style.width().resolve(Some(self.container.width))
+    }
+
+    /// The specified content height, resolved against the height of the containing block.
+    ///
+    /// Percentages of an indefinite height behave as `auto`.
+    fn specified_height(&self) -> Automatic<Pixels> {
+        self.style.height().resolve(self.containing_height)
+    }
+
+    /// The content height, if it is known before laying out the contents of the box.
+    fn definite_height(&self) -> Option<Pixels> {
+        self.forced_height.or(match self.specified_height() {
+            Automatic::Given(height) => Some(height),
+            Automatic::Auto => None,
+        })
+    }
+
+    /// The specified margins, with percentages resolved against the width of the containing
+    /// block (for the top and bottom margins too).
+    fn specified_margin(&self) -> Edge<Automatic<Pixels>> {
+        let base = Some(self.containing_width);
+        Edge {
+            left: self.style.margin_left().resolve(base),
+            right: self.style.margin_right().resolve(base),
+            top: self.style.margin_top().resolve(base),
+            bottom: self.style.margin_bottom().resolve(base),
+        }
+    }
+
+    /// Total specified width of the left and right margins, borders, and padding.
+    fn horizontal_edges(&self) -> Pixels {
+        let margin = self.specified_margin();
+        [
+            margin.left.value(), margin.right.value(),
+            self.style.border_left_width(), self.style.border_right_width(),
+            self.style.padding_left(), self.style.padding_right(),
+        ].iter().sum()
+    }
+
+    /// Total specified height of the top and bottom margins, borders, and padding.
+    #[cfg(any(feature = "flexbox", feature = "grid"))]
+    fn vertical_edges(&self) -> Pixels {
+        let margin = self.specified_margin();
+        [
+            margin.top.value(), margin.bottom.value(),
+            self.style.border_top_width(), self.style.border_bottom_width(),
+            self.style.padding_top(), self.style.padding_bottom(),
+        ].iter().sum()
+    }
+}
+
+impl LayoutBox {
+    /// Paint the box and its descendants. If the box is a list item, `ordinal` is its number in
+    /// its list. A box with `visibility: hidden` paints nothing of its own, but its descendants
+    /// can still be visible.
+    fn render(&self, list: &mut DisplayList, ordinal: usize) {
+        let transform = self.transform();
+        if !transform.is_identity() {
+            list.push(DisplayCommand::PushTransform { transform });
+        }
+        if self.style.visibility() == Visibility::Visible {
+            self.render_shadows(list);
+            self.render_background(list);
+            self.render_background_image(list);
+            self.render_borders(list);
+            if self.style.display() == Display::ListItem {
+                self.render_marker(ordinal, list);
+            }
+            if let (Some(ref widget), Some(node)) = (&self.widget, self.node) {
+                self.render_widget(&**widget, node, list);
+            }
+        }
+        let mut ordinal = 0;
+        for child in &self.children {
+            if child.style.display() == Display::ListItem {
+                ordinal += 1;
+            }
+            child.render(list, ordinal);
+        }
+        if !transform.is_identity() {
+            list.push(DisplayCommand::PopTransform);
+        }
+    }
+
+    /// The transform of the box, which maps where it and its descendants are laid out to where
+    /// they are painted. It applies around the center of the border box.
+    pub fn transform(&self) -> Transform {
+        let transform = self.style.transform();
+        if transform.is_identity() {
+            return transform;
+        }
+        let Rect { x, y, width, height } = self.border_box;
+        let (center_x, center_y) = (x + width / 2.0, y + height / 2.0);
+        Transform::translation(-center_x, -center_y).then(transform)
+            .then(Transform::translation(center_x, center_y))
+    }
+
+    /// Paint the box's shadows beneath its background, the first one in front.
+    fn render_shadows(&self, list: &mut DisplayList) {
+        for shadow in self.style.box_shadow().iter().rev() {
+            let border_box = self.border_box;
+            list.push(DisplayCommand::BoxShadow {
+                color: shadow.color,
+                x: border_box.x + shadow.offset_x - shadow.spread,
+                y: border_box.y + shadow.offset_y - shadow.spread,
+                width: (border_box.width + 2.0 * shadow.spread).max(0.0),
+                height: (border_box.height + 2.0 * shadow.spread).max(0.0),
+                blur: shadow.blur,
+                border_box,
+            });
+        }
+    }
+
+    /// Paint the background color across the border box, unless it is fully transparent.
+    fn render_background(&self, list: &mut DisplayList) {
+        if self.style.background_color().a == 0 {
+            return;
+        }
+        list.push(DisplayCommand::SolidColor {
+            color: self.style.background_color(),
+            x: self.border_box.x,
+            y: self.border_box.y,
+            width: self.border_box.width,
+            height: self.border_box.height,
+        });
+    }
+
+    /// Paint the background image across the border box.
+    fn render_background_image(&self, list: &mut DisplayList) {
+        let Rect { x, y, width, height } = self.border_box;
+        let center = Point { x: x + width / 2.0, y: y + height / 2.0 };
+        let (shape, length, stops) = match *self.style.background_image() {
+            BackgroundImage::None => return,
+            BackgroundImage::LinearGradient { direction, ref stops } => {
+                // The gradient runs through the center, far enough to reach the corners.
+                let (dx, dy) = match direction {
+                    GradientDirection::Angle(degrees) => {
+                        // Round away the error in the sine and cosine of right angles, so that
+                        // gradients along an axis stay exactly along it.
+                        let (sin, cos) = degrees.to_radians().sin_cos();
+                        let exact = |v: f32| if v.abs() < 1e-6 { 0.0 } else { v };
+                        (exact(sin), -exact(cos))
+                    }
+                    GradientDirection::Corner { right, bottom } => {
+                        let sign = |positive| if positive { 1.0 } else { -1.0 };
+                        let (dx, dy) = (sign(right) * height, sign(bottom) * width);
+                        let norm = dx.hypot(dy).max(Pixels::MIN_POSITIVE);
+                        (dx / norm, dy / norm)
+                    }
+                };
+                let length = (width * dx).abs() + (height * dy).abs();
+                let (half_x, half_y) = (dx * length / 2.0, dy * length / 2.0);
+                let start = Point { x: center.x - half_x, y: center.y - half_y };
+                let end = Point { x: center.x + half_x, y: center.y + half_y };
+                (GradientShape::Linear { start, end }, length, stops)
+            }
+            BackgroundImage::RadialGradient { circle, extent, ref stops } => {
+                let (half_width, half_height) = (width / 2.0, height / 2.0);
+                let (radius_x, radius_y) = match (circle, extent) {
+                    (true, RadialExtent::ClosestSide) => {
+                        let radius = half_width.min(half_height);
+                        (radius, radius)
+                    }
+                    (true, RadialExtent::FarthestSide) => {
+                        let radius = half_width.max(half_height);
+                        (radius, radius)
+                    }
+                    (true, _) => {
+                        let radius = half_width.hypot(half_height);
+                        (radius, radius)
+                    }
+                    (false, RadialExtent::ClosestSide) | (false, RadialExtent::FarthestSide) => {
+                        (half_width, half_height)
+                    }
+                    // The ellipse with the proportions of the box that passes through its
+                    // corners.
+                    (false, _) => {
+                        (half_width * std::f32::consts::SQRT_2,
+                         half_height * std::f32::consts::SQRT_2)
+                    }
+                };
+                (GradientShape::Radial { center, radius_x, radius_y }, radius_x, stops)
+            }
+        };
+        list.push(DisplayCommand::Gradient {
+            x,
+            y,
+            width,
+            height,
+            shape,
+            stops: resolve_color_stops(stops, length),
+        });
+    }
+
+    /// Paint the widget into a slot of its own, and move its items onto the content box.
+    fn render_widget(&self, widget: &dyn Widget, node: NodeId, list: &mut DisplayList) {
+        let mut slot = DisplayList::new();
+        let content = self.content_box;
+        widget.paint(node, content.size(), &mut slot);
+        // Transforms that the widget leaves pushed are popped after its items.
+        let mut depth = 0;
+        for item in slot.items() {
+            let mut item = item.clone();
+            item.translate(content.x, content.y);
+            let clipped = item.bounds().intersection(content);
+            match item {
+                DisplayCommand::SolidColor { color, .. } => {
+                    if let Some(Rect { x, y, width, height }) = clipped {
+                        list.push(DisplayCommand::SolidColor { color, x, y, width, height });
+                    }
+                }
+                DisplayCommand::Gradient { shape, stops, .. } => {
+                    if let Some(Rect { x, y, width, height }) = clipped {
+                        list.push(DisplayCommand::Gradient { x, y, width, height, shape, stops });
+                    }
+                }
+                // A blurred shadow cannot be cut off, so it is left out unless it fits.
+                DisplayCommand::BoxShadow { .. } => {
+                    if content.contains_rect(item.bounds()) {
+                        list.push(item);
+                    }
+                }
+                DisplayCommand::PushTransform { .. } => {
+                    depth += 1;
+                    list.push(item);
+                }
+                DisplayCommand::PopTransform if depth > 0 => {
+                    depth -= 1;
+                    list.push(item);
+                }
+                DisplayCommand::PopTransform => {}
+            }
+        }
+        for _ in 0..depth {
+            list.push(DisplayCommand::PopTransform);
+        }
+    }
+
+    /// Paint the borders, unless they are fully transparent.
+    fn render_borders(&self, list: &mut DisplayList) {
+        if self.style.border_color().a == 0 {
+            return;
+        }
+
+        // Left border
+        list.push(DisplayCommand::SolidColor {
+            color: self.style.border_color(),
+            x: self.border_box.x,
+            y: self.border_box.y,
+            width: self.border.left,
+            height: self.border_box.height,
+        });
+
+        // Right border
+        list.push(DisplayCommand::SolidColor {
+            color: self.style.border_color(),
+            x: self.border_box.x + self.border_box.width - self.border.right,
+            y: self.border_box.y,
+            width: self.border.right,
+            height: self.border_box.height,
+        });
+
+        // Top border
+        list.push(DisplayCommand::SolidColor{
+            color: self.style.border_color(),
+            x: self.border_box.x,
+            y: self.border_box.y,
+            width: self.border_box.width,
+            height: self.border.top,
+        });
+
+        // Bottom border
+        list.push(DisplayCommand::SolidColor {
+            color: self.style.border_color(),
+            x: self.border_box.x,
+            y: self.border_box.y + self.border_box.height - self.border.bottom,
+            width: self.border_box.width,
+            height: self.border.bottom,
+        });
+    }
+}
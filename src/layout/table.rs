@@ -0,0 +1,110 @@
+//! Automatic table layout.
+//!
+//! http://www.w3.org/TR/CSS2/tables.html#auto-table-layout
+//!
+//! Every child of a table box is treated as a row and every child of a row as a cell; anonymous
+//! rows and cells are generated while building the layout tree.
+
+use super::LayoutBox;
+use geometry::Rect;
+use style::{Automatic, Pixels};
+
+impl LayoutBox {
+    /// Lay out a table box, its rows, and their cells.
+    pub(super) fn layout_table(&mut self) {
+        let spacing = self.style.border_spacing();
+        let mut columns = self.column_widths();
+        let natural = table_width(&columns, spacing);
+
+        // A table with an automatic width shrinks to fit its columns. A specified width that is
+        // larger than the columns need is distributed over the columns in proportion to their
+        // widths (or evenly, if they are all empty).
+        let specified = self.specified_width();
+        if specified.is_given() {
+            let extra = specified.value() - natural;
+            if extra > 0.0 && !columns.is_empty() {
+                let total: Pixels = columns.iter().sum();
+                let count = columns.len() as Pixels;
+                for column in &mut columns {
+                    *column += if total > 0.0 { extra * *column / total } else { extra / count };
+                }
+            }
+        }
+        let width = table_width(&columns, spacing).max(specified.value());
+        self.calculate_width(Automatic::Given(self.forced_width.unwrap_or(width)));
+        self.calculate_block_position();
+
+        // Stack the rows, with `border-spacing` around and between them.
+        self.intrinsic.height = spacing;
+        let x = self.content_box.x + spacing;
+        for row in &mut self.children {
+            let y = self.content_box.y + self.intrinsic.height;
+            row.layout_table_row(&columns, spacing, x, y);
+            self.intrinsic.height += row.margin_box.height + spacing;
+        }
+
+        // The used height of a table is at least the height of its rows.
+        let height = self.intrinsic.height.max(self.specified_height().value());
+        self.calculate_height(self.forced_height.unwrap_or(height));
+    }
+
+    /// Lay out a row of cells at the given position, using the given column widths.
+    fn layout_table_row(&mut self, columns: &[Pixels], spacing: Pixels, x: Pixels, y: Pixels) {
+        // Lay out each cell in its column.
+        let mut cell_x = x;
+        let mut height = self.specified_height().value();
+        for (cell, &width) in self.children.iter_mut().zip(columns) {
+            cell.container = Rect { x: cell_x, y, width, height: 0.0 };
+            cell.containing_width = width;
+            cell.containing_direction = self.style.direction();
+            cell.forced_width = Some((width - cell.horizontal_edges()).max(0.0));
+            cell.forced_height = None;
+            cell.layout();
+            height = height.max(cell.margin_box.height);
+            cell_x += width + spacing;
+        }
+
+        // Stretch every cell to the height of the row.
+        for cell in &mut self.children {
+            let extra = height - cell.margin_box.height;
+            if extra > 0.0 {
+                cell.forced_height = Some(cell.content_box.height + extra);
+                cell.layout();
+            }
+        }
+
+        // Rows have no edges of their own; the row box spans its cells.
+        let width = (cell_x - spacing - x).max(0.0);
+        self.container = Rect { x, y, width, height: 0.0 };
+        self.content_box = Rect { x, y, width, height };
+        self.intrinsic = self.content_box;
+        self.padding_box = self.content_box;
+        self.border_box = self.content_box;
+        self.margin_box = self.content_box;
+    }
+
+    /// Compute the width of each column as the widest cell's margin box in that column.
+    fn column_widths(&self) -> Vec<Pixels> {
+        let mut columns: Vec<Pixels> = Vec::new();
+        for row in &self.children {
+            for (i, cell) in row.children.iter().enumerate() {
+                let width = cell.max_content_width();
+                match columns.get_mut(i) {
+                    Some(column) => *column = column.max(width),
+                    None => columns.push(width),
+                }
+            }
+        }
+        columns
+    }
+
+    /// Width of the table's content box when laid out without any constraint.
+    pub(super) fn table_max_content_width(&self) -> Pixels {
+        table_width(&self.column_widths(), self.style.border_spacing())
+    }
+}
+
+/// Width of a table's content box given its column widths and cell spacing.
+fn table_width(columns: &[Pixels], spacing: Pixels) -> Pixels {
+    columns.iter().sum::<Pixels>() + spacing * (columns.len() + 1) as Pixels
+}
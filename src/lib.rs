@@ -0,0 +1,56 @@
+//! A toy web rendering engine.
+
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "http")]
+extern crate ureq;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "gpu")]
+extern crate wgpu;
+#[cfg(feature = "gpu")]
+extern crate pollster;
+
+pub mod clipboard;
+pub mod content_hash;
+pub mod css;
+pub mod document;
+pub mod dom;
+pub mod editing;
+pub mod engine;
+pub mod events;
+pub mod focus;
+pub mod fragment;
+pub mod geometry;
+pub mod geometry_tree;
+pub mod html;
+pub mod index;
+pub mod layout;
+pub mod limits;
+pub mod links;
+pub mod metrics;
+pub mod observer;
+pub mod outline;
+pub mod reader;
+pub mod resource;
+pub mod script;
+pub mod style;
+pub mod paint;
+pub mod snapshot;
+pub mod terminal;
+pub mod widget;
+
+// Everything produced after parsing can be handed to (or shared with) other threads, so styling,
+// layout, and painting can each run on a worker thread.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<dom::Node>();
+    assert_send_sync::<css::Stylesheet>();
+    assert_send_sync::<style::StyledNode>();
+    assert_send_sync::<layout::LayoutTree>();
+    assert_send_sync::<paint::DisplayList>();
+    assert_send_sync::<paint::Canvas>();
+    assert_send_sync::<engine::Engine>();
+};
@@ -0,0 +1,117 @@
+//! Limits on the work and memory that rendering a document may take.
+//!
+//! Parsing, styling, layout, and painting recurse over the document tree, and layout may lay a box
+//! out several times to measure it, so a hostile document can exhaust the stack, the processor, or
+//! memory. `html::parse_with_limits` stops parsing such documents as soon as they grow too deep or
+//! too large. An `Engine` created with `Engine::with_limits` refuses them, and stops laying them
+//! out (then and whenever they are laid out again) once layout takes too long.
+
+use css::Stylesheet;
+use dom::Node;
+use std::fmt;
+
+/// Bounds on the size of a document and the work done to render it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Limits {
+    /// Maximum nesting depth of DOM nodes; the root is at depth 1.
+    pub max_dom_depth: usize,
+    /// Maximum number of DOM nodes, including text nodes.
+    pub max_dom_nodes: usize,
+    /// Maximum number of rules in the stylesheet.
+    pub max_stylesheet_rules: usize,
+    /// Maximum number of times boxes may be laid out during a layout pass.
+    pub max_layouts: usize,
+    /// Maximum number of pixels in a rendered canvas.
+    pub max_canvas_pixels: usize,
+}
+
+impl Limits {
+    /// No limits at all.
+    pub fn unlimited() -> Limits {
+        Limits {
+            max_dom_depth: usize::MAX,
+            max_dom_nodes: usize::MAX,
+            max_stylesheet_rules: usize::MAX,
+            max_layouts: usize::MAX,
+            max_canvas_pixels: usize::MAX,
+        }
+    }
+
+    /// Check the size of a document and its stylesheet.
+    pub fn check_document(&self, root: &Node, stylesheet: &Stylesheet) -> Result<(), LimitError> {
+        if stylesheet.rules().len() > self.max_stylesheet_rules {
+            return Err(LimitError::StylesheetTooLarge(self.max_stylesheet_rules));
+        }
+        self.check_dom(root)
+    }
+
+    /// Check the depth and the number of nodes of a document.
+    pub fn check_dom(&self, root: &Node) -> Result<(), LimitError> {
+        // Walk the tree with an explicit stack, since it may be too deep to recurse over.
+        let mut stack = vec![(root, 1)];
+        let mut count = 0;
+        while let Some((node, depth)) = stack.pop() {
+            if depth > self.max_dom_depth {
+                return Err(LimitError::DomTooDeep(self.max_dom_depth));
+            }
+            count += 1;
+            if count > self.max_dom_nodes {
+                return Err(LimitError::TooManyNodes(self.max_dom_nodes));
+            }
+            stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+        }
+        Ok(())
+    }
+
+    /// Check the size of a canvas before allocating it.
+    pub fn check_canvas(&self, width: usize, height: usize) -> Result<(), LimitError> {
+        match width.checked_mul(height) {
+            Some(pixels) if pixels <= self.max_canvas_pixels => Ok(()),
+            _ => Err(LimitError::CanvasTooLarge { width, height }),
+        }
+    }
+}
+
+impl Default for Limits {
+    /// Limits that ordinary documents stay well within.
+    fn default() -> Limits {
+        Limits {
+            max_dom_depth: 256,
+            max_dom_nodes: 1_000_000,
+            max_stylesheet_rules: 100_000,
+            max_layouts: 10_000_000,
+            max_canvas_pixels: 16384 * 16384,
+        }
+    }
+}
+
+/// The limit that a document exceeded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LimitError {
+    /// The DOM is nested more deeply than the given depth.
+    DomTooDeep(usize),
+    /// The DOM has more than the given number of nodes.
+    TooManyNodes(usize),
+    /// The stylesheet has more than the given number of rules.
+    StylesheetTooLarge(usize),
+    /// Layout needed to lay out more than the given number of boxes.
+    TooManyLayouts(usize),
+    /// A canvas of this size has too many pixels.
+    CanvasTooLarge { width: usize, height: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LimitError::DomTooDeep(max) => write!(f, "DOM is nested more than {} deep", max),
+            LimitError::TooManyNodes(max) => write!(f, "DOM has more than {} nodes", max),
+            LimitError::StylesheetTooLarge(max) => {
+                write!(f, "stylesheet has more than {} rules", max)
+            }
+            LimitError::TooManyLayouts(max) => write!(f, "layout took more than {} passes", max),
+            LimitError::CanvasTooLarge { width, height } => {
+                write!(f, "canvas of {}x{} pixels is too large", width, height)
+            }
+        }
+    }
+}
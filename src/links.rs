@@ -0,0 +1,160 @@
+//! The clickable regions of hyperlinks, for output formats that can annotate them.
+//!
+//! Paginated (PDF) and vector (SVG) output, and HTML image maps, can make parts of a rendering
+//! clickable. They need the rectangles of every `<a href>` element and the URL each one leads to,
+//! resolved against the document's base URL.
+
+use dom::{Node, NodeType};
+use geometry::Rect;
+use layout::LayoutTree;
+
+/// A hyperlink and the rectangles it covers in the laid out document.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Link {
+    /// The URL the link leads to, resolved against the base URL.
+    pub url: String,
+    /// The border boxes of the boxes the link generated, in px. A link with no box (for example,
+    /// one with `display: none`) is left out.
+    pub rects: Vec<Rect>,
+}
+
+/// Find the hyperlinks of a document, in document order.
+///
+/// `layout` must be laid out from `root`. Relative URLs are resolved against the `href` of the
+/// document's first `<base>` element, which is itself resolved against `base_url`. Without a base
+/// URL, relative URLs are left as they are.
+pub fn links(root: &Node, layout: &LayoutTree, base_url: Option<&str>) -> Vec<Link> {
+    let base = document_base_url(root, base_url);
+    let mut links = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        let href = match node.node_type {
+            NodeType::Element(ref elem) if elem.tag == "a" => attribute(node, "href"),
+            _ => None,
+        };
+        let href = match href {
+            Some(href) => href,
+            None => continue,
+        };
+        let rects: Vec<Rect> = layout.boxes_for_node(node.id).into_iter()
+            .filter_map(|id| layout.get(id))
+            .map(|layout_box| layout_box.geometry().border_box)
+            .filter(|rect| !rect.is_empty())
+            .collect();
+        if rects.is_empty() {
+            continue;
+        }
+        let url = match base {
+            Some(ref base) => resolve(base, href),
+            None => href.to_string(),
+        };
+        links.push(Link { url, rects });
+    }
+    links
+}
+
+/// The URL that relative URLs in a document are resolved against: the `href` of its first
+/// `<base>` element, resolved against `base_url`, or else `base_url` itself.
+pub fn document_base_url(root: &Node, base_url: Option<&str>) -> Option<String> {
+    let document_base = find_element(root, "base").and_then(|base| attribute(base, "href"));
+    match (base_url, document_base) {
+        (Some(base_url), Some(href)) => Some(resolve(base_url, href)),
+        (None, Some(href)) => Some(href.to_string()),
+        (base_url, None) => base_url.map(String::from),
+    }
+}
+
+/// The first element of type `tag` in `node`'s subtree, in document order.
+fn find_element<'a, 'b>(node: &'b Node<'a>, tag: &str) -> Option<&'b Node<'a>> {
+    match node.node_type {
+        NodeType::Element(ref elem) if elem.tag == tag => Some(node),
+        _ => node.children.iter().filter_map(|child| find_element(child, tag)).next(),
+    }
+}
+
+/// The value of an attribute of an element, with surrounding whitespace removed.
+fn attribute<'a>(node: &'a Node, name: &str) -> Option<&'a str> {
+    match node.node_type {
+        NodeType::Element(ref elem) => elem.attributes.get(name).map(|value| value.trim()),
+        NodeType::Text(_) => None,
+    }
+}
+
+/// Resolve a URL reference against a base URL, following RFC 3986 for hierarchical URLs.
+///
+/// A reference with a scheme is already absolute. A base without `://` (such as `mailto:`) can
+/// only take fragments.
+pub fn resolve(base: &str, reference: &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let base = base.split('#').next().unwrap_or("");
+    if reference.is_empty() {
+        return base.to_string();
+    }
+    if reference.starts_with('#') {
+        return format!("{}{}", base, reference);
+    }
+    let (scheme, rest) = match base.find("://") {
+        Some(i) => (&base[..i], &base[i + 3..]),
+        None => return reference.to_string(),
+    };
+    if let Some(network_path) = reference.strip_prefix("//") {
+        return format!("{}://{}", scheme, network_path);
+    }
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, base_path) = (&rest[..authority_end], &rest[authority_end..]);
+    let base_path = base_path.split('?').next().unwrap_or("");
+
+    // Split the query (and fragment) off the reference, and merge the paths.
+    let split = reference.find(['?', '#']).unwrap_or(reference.len());
+    let (path, suffix) = reference.split_at(split);
+    let merged = if path.is_empty() {
+        // Only a query: keep the base path.
+        base_path.to_string()
+    } else if path.starts_with('/') {
+        path.to_string()
+    } else {
+        let directory = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+        let directory = if directory.is_empty() { "/" } else { directory };
+        format!("{}{}", directory, path)
+    };
+    format!("{}://{}{}{}", scheme, authority, remove_dot_segments(&merged), suffix)
+}
+
+/// Does the URL start with a scheme, such as `https:`?
+fn has_scheme(url: &str) -> bool {
+    match url.find(':') {
+        Some(end) => {
+            let scheme = &url[..end];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+                scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        None => false,
+    }
+}
+
+/// Remove the `.` and `..` segments of an absolute path.
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, &segment) in parts.iter().enumerate() {
+        let last = i + 1 == parts.len();
+        match segment {
+            "." => {
+                if last {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                segments.pop();
+                if last {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
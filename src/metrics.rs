@@ -0,0 +1,78 @@
+//! Size metrics of the subtree of an element, for tools that flag oversized or overflowing
+//! components in generated documents.
+
+use dom::{Node, NodeType};
+use geometry::Rect;
+use layout::{LayoutBox, LayoutTree};
+use style::{Edge, Pixels};
+
+/// How large the subtree of an element is, in the DOM and in the laid out document.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SubtreeMetrics {
+    /// The number of DOM nodes in the subtree, including the element and text nodes.
+    pub nodes: usize,
+    /// The number of boxes the subtree generated, including anonymous and generated boxes.
+    pub boxes: usize,
+    /// The number of characters in the text nodes of the subtree.
+    pub text_length: usize,
+    /// The smallest rectangle containing the border boxes of all of the subtree's boxes, in px.
+    pub bounds: Rect,
+    /// How far the border boxes of the element's descendants extend past its own border box on
+    /// each side, in px, or zero where they do not.
+    pub overflow: Edge<Pixels>,
+}
+
+/// Measure the subtree of an element, or return `None` if it generated no box (for example,
+/// because it has `display: none`).
+///
+/// `layout` must be laid out from the document that `node` is in.
+pub fn subtree_metrics(node: &Node, layout: &LayoutTree) -> Option<SubtreeMetrics> {
+    let boxes: Vec<&LayoutBox> = layout.boxes_for_node(node.id).into_iter()
+        .filter_map(|id| layout.get(id))
+        .collect();
+    if boxes.is_empty() {
+        return None;
+    }
+
+    let mut nodes = 0;
+    let mut text_length = 0;
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter());
+        nodes += 1;
+        if let NodeType::Text(ref text) = node.node_type {
+            text_length += text.chars().count();
+        }
+    }
+
+    let own = boxes.iter().fold(Rect::default(), |bounds, b| bounds.union(b.geometry().border_box));
+    let mut descendants = Rect::default();
+    let mut box_count = 0;
+    let mut stack = boxes;
+    while let Some(layout_box) = stack.pop() {
+        box_count += 1;
+        for child in layout_box.children() {
+            descendants = descendants.union(child.geometry().border_box);
+            stack.push(child);
+        }
+    }
+
+    let overflow = if descendants.is_empty() {
+        Edge::default()
+    } else {
+        let (own_max, descendants_max) = (own.max(), descendants.max());
+        Edge {
+            left: (own.x - descendants.x).max(0.0),
+            right: (descendants_max.x - own_max.x).max(0.0),
+            top: (own.y - descendants.y).max(0.0),
+            bottom: (descendants_max.y - own_max.y).max(0.0),
+        }
+    };
+    Some(SubtreeMetrics {
+        nodes,
+        boxes: box_count,
+        text_length,
+        bounds: own.union(descendants),
+        overflow,
+    })
+}
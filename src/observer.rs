@@ -0,0 +1,122 @@
+//! Notifications of changes to the layout of particular elements.
+//!
+//! Hosts that draw native overlays (tooltips, popovers, text fields) over rendered elements need
+//! to move them when the elements move. An observer selects the elements it follows with a
+//! filter, and is called after every relayout with the old and new geometry of each of them that
+//! changed.
+
+use dom::NodeId;
+use layout::{BoxGeometry, LayoutTree};
+use std::collections::{HashMap, HashSet};
+
+/// A change to the geometry of an element's first box.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LayoutChange {
+    pub node: NodeId,
+    /// The geometry before the relayout, or `None` if the element had no box.
+    pub old: Option<BoxGeometry>,
+    /// The geometry after the relayout, or `None` if the element no longer has a box.
+    pub new: Option<BoxGeometry>,
+}
+
+/// Identifies an observer, to remove it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ObserverId(usize);
+
+struct Observer {
+    id: ObserverId,
+    filter: Box<dyn Fn(NodeId) -> bool + Send + Sync>,
+    callback: Box<dyn FnMut(&LayoutChange) + Send + Sync>,
+}
+
+/// The geometry of the first box of every observed element, in document order.
+pub type LayoutSnapshot = Vec<(NodeId, BoxGeometry)>;
+
+/// The layout observers of an engine.
+#[derive(Default)]
+pub struct LayoutObservers {
+    observers: Vec<Observer>,
+    next_id: usize,
+}
+
+impl LayoutObservers {
+    pub fn new() -> LayoutObservers {
+        LayoutObservers::default()
+    }
+
+    /// Call `callback` with every change to the geometry of the elements that `filter` accepts.
+    pub fn add<F, C>(&mut self, filter: F, callback: C) -> ObserverId
+        where F: Fn(NodeId) -> bool + Send + Sync + 'static,
+              C: FnMut(&LayoutChange) + Send + Sync + 'static
+    {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        let (filter, callback) = (Box::new(filter), Box::new(callback));
+        self.observers.push(Observer { id, filter, callback });
+        id
+    }
+
+    /// Remove an observer, returning whether it was there.
+    pub fn remove(&mut self, id: ObserverId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|observer| observer.id != id);
+        self.observers.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// The geometry of the elements that any observer follows, to compare with after a relayout.
+    /// Empty if there are no observers.
+    pub fn snapshot(&self, layout: &LayoutTree) -> LayoutSnapshot {
+        let mut snapshot = Vec::new();
+        if self.is_empty() {
+            return snapshot;
+        }
+        let mut seen = HashSet::new();
+        let mut stack: Vec<_> = layout.root().into_iter().collect();
+        while let Some(layout_box) = stack.pop() {
+            stack.extend(layout_box.children().iter().rev());
+            let node = match layout_box.node() {
+                Some(node) => node,
+                None => continue,
+            };
+            if seen.insert(node) && self.observers.iter().any(|observer| (observer.filter)(node)) {
+                snapshot.push((node, layout_box.geometry()));
+            }
+        }
+        snapshot
+    }
+
+    /// Tell the observers how the geometry of the elements they follow changed since `before`
+    /// was taken: first the elements that have a box now, in document order, then those that
+    /// lost theirs.
+    pub fn notify(&mut self, before: LayoutSnapshot, layout: &LayoutTree) {
+        if self.is_empty() {
+            return;
+        }
+        let after = self.snapshot(layout);
+        let mut old: HashMap<NodeId, BoxGeometry> = before.iter().cloned().collect();
+        let mut changes = Vec::new();
+        for &(node, geometry) in &after {
+            let previous = old.remove(&node);
+            if previous != Some(geometry) {
+                changes.push(LayoutChange { node, old: previous, new: Some(geometry) });
+            }
+        }
+        for (node, geometry) in before {
+            if old.contains_key(&node) {
+                changes.push(LayoutChange { node, old: Some(geometry), new: None });
+            }
+        }
+
+        for observer in &mut self.observers {
+            for change in &changes {
+                if (observer.filter)(change.node) {
+                    (observer.callback)(change);
+                }
+            }
+        }
+    }
+}
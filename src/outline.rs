@@ -0,0 +1,91 @@
+//! The outline of a document: its hierarchy of headings, for a table of contents.
+
+use dom::{Node, NodeId, NodeType};
+use geometry::Rect;
+use layout::LayoutTree;
+
+/// A heading of the document, and the headings of its section.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Heading {
+    /// 1 for `<h1>`, up to 6 for `<h6>`.
+    pub level: u8,
+    /// The text of the heading, with whitespace collapsed.
+    pub text: String,
+    /// The heading element, which `Engine::scroll_into_view` can jump to.
+    pub node: NodeId,
+    /// The border box of the heading's first box, in px.
+    pub rect: Rect,
+    /// The headings of lower rank (higher level) that follow this one, up to the next heading of
+    /// the same or higher rank.
+    pub children: Vec<Heading>,
+}
+
+/// Find the headings of a document, nested by rank. Headings without a box (for example, with
+/// `display: none`) are left out.
+///
+/// `layout` must be laid out from `root`. A heading that skips levels (an `<h3>` right after an
+/// `<h1>`) is nested directly under the previous heading of higher rank.
+pub fn outline(root: &Node, layout: &LayoutTree) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    // The open sections, from the outermost in.
+    let mut open: Vec<Heading> = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        let level = match node.node_type {
+            NodeType::Element(ref elem) => heading_level(&elem.tag),
+            NodeType::Text(_) => None,
+        };
+        let (level, geometry) = match (level, layout.box_for_node(node)) {
+            (Some(level), Some(geometry)) => (level, geometry),
+            _ => continue,
+        };
+        close_sections(&mut open, &mut headings, level);
+        let mut text = String::new();
+        collect_text(node, &mut text);
+        open.push(Heading {
+            level,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            node: node.id,
+            rect: geometry.border_box,
+            children: Vec::new(),
+        });
+    }
+    close_sections(&mut open, &mut headings, 1);
+    headings
+}
+
+/// The level of a heading element, or `None` if `tag` is not a heading.
+fn heading_level(tag: &str) -> Option<u8> {
+    match tag.as_bytes() {
+        [b'h' | b'H', digit @ b'1'..=b'6'] => Some(digit - b'0'),
+        _ => None,
+    }
+}
+
+/// Close the open sections whose headings have a level of `level` or more, nesting each in its
+/// parent section (or in `headings`, at the top).
+fn close_sections(open: &mut Vec<Heading>, headings: &mut Vec<Heading>, level: u8) {
+    while open.last().is_some_and(|heading| heading.level >= level) {
+        let heading = open.pop().unwrap();
+        match open.last_mut() {
+            Some(parent) => parent.children.push(heading),
+            None => headings.push(heading),
+        }
+    }
+}
+
+/// Append the text of `node`'s subtree to `text`.
+fn collect_text(node: &Node, text: &mut String) {
+    match node.node_type {
+        NodeType::Text(ref data) => {
+            text.push_str(data);
+            text.push(' ');
+        }
+        NodeType::Element(_) => {
+            for child in &node.children {
+                collect_text(child, text);
+            }
+        }
+    }
+}
@@ -1,23 +1,299 @@
-#![allow(unstable_name_collisions)]
 use css::Color;
+use geometry::{Point, Rect, Transform};
 use style::Pixels;
+use std::slice;
+use std::sync::OnceLock;
+use std::thread;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gpu")]
+pub mod gpu;
 
 pub struct Canvas {
     pub pixels: Vec<Color>,
     pub width: usize,
     pub height: usize,
+    /// The region of the document (in px) that the canvas shows.
+    pub region: Rect,
+    /// Pixels per px that the region is painted at.
+    pub scale: f32,
+    /// The options the canvas is painted with, which `repaint` paints with too.
+    pub options: PaintOptions,
 }
 
 /// Paint a display list to an array of pixels.
 pub fn paint_display_list(display_list: &DisplayList, width: usize, height: usize) -> Canvas {
-    let mut canvas = Canvas::new(width, height);
-    for item in display_list {
-        canvas.paint_item(&item);
+    let viewport = Rect { x: 0.0, y: 0.0, width: width as Pixels, height: height as Pixels };
+    paint_region(display_list, viewport, 1.0)
+}
+
+/// Paint the part of a display list within `region` to an array of pixels, scaled by `scale`
+/// pixels per px.
+pub fn paint_region(display_list: &DisplayList, region: Rect, scale: f32) -> Canvas {
+    paint_region_with_options(display_list, region, scale, PaintOptions::default())
+}
+
+/// Options for painting a display list.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PaintOptions {
+    /// Width and height of the square tiles that the canvas is split into, in pixels. Each tile
+    /// is painted on its own, by one of the painting threads.
+    pub tile_size: usize,
+    /// Number of threads to paint tiles on.
+    pub threads: usize,
+    /// How the edges of display items are snapped to pixel boundaries.
+    pub snap: SnapPolicy,
+    /// The color space that translucent items are blended in.
+    pub blending: ColorSpace,
+}
+
+/// How an edge of a display item that falls inside a pixel is moved to a pixel boundary.
+///
+/// Every edge is snapped on its own, after it is mapped onto the canvas, so boxes that share an
+/// edge in the document also share it on the canvas, whatever the scale: they never overlap or
+/// leave a gap between them. The one exception is an item thinner than a pixel whose edges snap
+/// to the same boundary: it still covers the pixel after that boundary, so that hairlines such as
+/// 1px borders at a small scale do not disappear.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SnapPolicy {
+    /// Move the edge to the nearest boundary.
+    Round,
+    /// Move the edge up or left.
+    #[default]
+    Floor,
+    /// Move the edge down or right.
+    Ceil,
+    /// Leave the edge where it is, and blend each pixel it crosses by the part of the pixel that
+    /// the item covers. Edges at fractional positions look smooth rather than jagged, but boxes
+    /// that share an edge inside a pixel both paint it in part, so a faint seam can show there.
+    Antialias,
+}
+
+impl SnapPolicy {
+    fn snap(self, coordinate: f32) -> f32 {
+        // Layout sums the same edge in different orders for the boxes on either side of it, so
+        // the sums may differ in their last bits. Round away such error before snapping, or it
+        // could push the edges to different pixels.
+        let coordinate = (coordinate * 64.0).round() / 64.0;
+        match self {
+            SnapPolicy::Round => coordinate.round(),
+            SnapPolicy::Floor => coordinate.floor(),
+            SnapPolicy::Ceil => coordinate.ceil(),
+            SnapPolicy::Antialias => coordinate,
+        }
+    }
+}
+
+impl Default for PaintOptions {
+    fn default() -> PaintOptions {
+        PaintOptions {
+            tile_size: 256,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            snap: SnapPolicy::default(),
+            blending: ColorSpace::default(),
+        }
+    }
+}
+
+/// A color space that translucent colors can be blended in.
+///
+/// Colors in display lists and canvases are always sRGB-encoded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ColorSpace {
+    /// Blend the sRGB-encoded values directly, as web browsers do.
+    #[default]
+    Srgb,
+    /// Blend in linear light: decode the colors to linear sRGB, blend them, and encode the result
+    /// again. Translucent edges and overlaps of contrasting colors keep their brightness, rather
+    /// than looking dark and muddy.
+    LinearSrgb,
+}
+
+impl ColorSpace {
+    /// Paint `color` over `below`.
+    fn blend(self, color: Color, below: Color) -> Color {
+        match self {
+            ColorSpace::Srgb => color.over(&below),
+            ColorSpace::LinearSrgb if color.a == 255 => color,
+            ColorSpace::LinearSrgb => {
+                let (alpha_a, alpha_b) = (color.alpha(), below.alpha());
+                let alpha_c = alpha_a + alpha_b * (1.0 - alpha_a);
+                if alpha_c == 0.0 {
+                    return Color::default();
+                }
+                let table = linear_table();
+                let compose = |a: u8, b: u8| {
+                    let (a, b) = (table[a as usize], table[b as usize]);
+                    encode_srgb((a * alpha_a + b * alpha_b * (1.0 - alpha_a)) / alpha_c)
+                };
+                Color {
+                    r: compose(color.r, below.r),
+                    g: compose(color.g, below.g),
+                    b: compose(color.b, below.b),
+                    a: (alpha_c * 255.0).round() as u8,
+                }
+            }
+        }
+    }
+}
+
+/// The linear light intensity of every sRGB-encoded channel value.
+fn linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (value, linear) in table.iter_mut().enumerate() {
+            let value = value as f32 / 255.0;
+            *linear = if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        table
+    })
+}
+
+/// Encode a linear light intensity as an sRGB channel value.
+fn encode_srgb(linear: f32) -> u8 {
+    let value = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Paint the part of a display list within `region`, like `paint_region`, with the given options.
+///
+/// Every display item is assigned to the tiles its bounds overlap, and the tiles are painted in
+/// parallel. Each pixel is painted by the same items in the same order as it would be on a single
+/// thread, so the result does not depend on the options.
+pub fn paint_region_with_options(display_list: &DisplayList, region: Rect, scale: f32,
+                                 options: PaintOptions) -> Canvas {
+    let (width, height) = canvas_size(region, scale);
+    let mut canvas = Canvas { region, scale, options, ..Canvas::new(width, height) };
+    let device = DeviceSpace {
+        region,
+        scale,
+        snap: options.snap,
+        blending: options.blending,
+        width,
+        height,
+    };
+    let tile_size = options.tile_size.max(1);
+    let (columns, rows) = (width.div_ceil(tile_size), height.div_ceil(tile_size));
+
+    // Assign the items to the tiles they overlap, in order.
+    let mut tiles: Vec<Tile> = (0..rows * columns).map(|i| {
+        let (x0, y0) = (i % columns * tile_size, i / columns * tile_size);
+        let (x1, y1) = ((x0 + tile_size).min(width), (y0 + tile_size).min(height));
+        Tile { bounds: PixelRect { x0, y0, x1, y1 }, items: Vec::new() }
+    }).collect();
+    let placed = device.place(display_list);
+    for item in &placed {
+        let bounds = item.bounds;
+        for row in bounds.y0 / tile_size..bounds.y1.div_ceil(tile_size) {
+            for column in bounds.x0 / tile_size..bounds.x1.div_ceil(tile_size) {
+                tiles[row * columns + column].items.push(item);
+            }
+        }
+    }
+
+    // Paint the tiles on a pool of threads, then copy them onto the canvas.
+    let threads = options.threads.max(1);
+    let chunk_size = tiles.len().div_ceil(threads).max(1);
+    let painted: Vec<(PixelRect, Vec<Color>)> = thread::scope(|scope| {
+        let workers: Vec<_> = tiles.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || {
+                chunk.iter().map(|tile| (tile.bounds, tile.paint(&device)))
+                    .collect::<Vec<_>>()
+            }))
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    for (bounds, pixels) in painted {
+        canvas.copy_from(bounds, &pixels);
     }
     canvas
 }
 
-#[derive(Debug)]
+/// The size in pixels of the canvas that `paint_region` paints.
+pub fn canvas_size(region: Rect, scale: f32) -> (usize, usize) {
+    let width = (region.width * scale).ceil().max(0.0) as usize;
+    let height = (region.height * scale).ceil().max(0.0) as usize;
+    (width, height)
+}
+
+/// Thumbnails of regions of a document, painted side by side on one canvas, for the scrubber
+/// previews of viewer applications.
+pub struct SpriteSheet {
+    pub canvas: Canvas,
+    /// The regions of the document (in px) that the thumbnails show, in order.
+    pub frames: Vec<Rect>,
+    /// The number of thumbnails in each row of the grid.
+    pub columns: usize,
+    /// Size of each cell of the grid, in pixels.
+    pub frame_width: usize,
+    pub frame_height: usize,
+}
+
+impl SpriteSheet {
+    /// The pixels of the canvas that show thumbnail `index`, as `(x, y, width, height)`.
+    pub fn frame_pixels(&self, index: usize) -> Option<(usize, usize, usize, usize)> {
+        if index >= self.frames.len() {
+            return None;
+        }
+        let x = index % self.columns * self.frame_width;
+        let y = index / self.columns * self.frame_height;
+        Some((x, y, self.frame_width, self.frame_height))
+    }
+}
+
+/// The size in pixels of the cells and of the whole canvas of a sprite sheet of `frames`, as
+/// `(frame_width, frame_height, width, height)`.
+pub fn sprite_sheet_size(frames: &[Rect], scale: f32, columns: usize)
+                         -> (usize, usize, usize, usize) {
+    let (frame_width, frame_height) = frames.iter()
+        .map(|&frame| canvas_size(frame, scale))
+        .fold((0, 0), |(w0, h0), (w1, h1)| (w0.max(w1), h0.max(h1)));
+    let columns = columns.clamp(1, frames.len().max(1));
+    let rows = frames.len().div_ceil(columns);
+    (frame_width, frame_height, frame_width * columns, frame_height * rows)
+}
+
+/// Paint each of `frames` (regions of the document, in px) scaled by `scale` pixels per px, into
+/// a grid with `columns` thumbnails per row, filled row by row.
+///
+/// Every cell is as large as the largest thumbnail; smaller ones sit in its top left corner.
+pub fn paint_sprite_sheet(display_list: &DisplayList, frames: Vec<Rect>, scale: f32,
+                          columns: usize, options: PaintOptions) -> SpriteSheet {
+    let (frame_width, frame_height, width, height) = sprite_sheet_size(&frames, scale, columns);
+    let columns = columns.clamp(1, frames.len().max(1));
+    let mut sheet = SpriteSheet {
+        canvas: Canvas::new(width, height),
+        frames,
+        columns,
+        frame_width,
+        frame_height,
+    };
+    for index in 0..sheet.frames.len() {
+        let frame = sheet.frames[index];
+        let thumbnail = paint_region_with_options(display_list, frame, scale, options);
+        let (x0, y0, _, _) = sheet.frame_pixels(index).unwrap();
+        let bounds = PixelRect { x0, y0, x1: x0 + thumbnail.width, y1: y0 + thumbnail.height };
+        if !bounds.is_empty() {
+            sheet.canvas.copy_from(bounds, &thumbnail.pixels);
+        }
+    }
+    sheet
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize),
+           serde(rename_all = "kebab-case"))]
 pub enum DisplayCommand {
     SolidColor {
         color: Color,
@@ -26,45 +302,772 @@ pub enum DisplayCommand {
         width: Pixels,
         height: Pixels
     },
+    /// A rectangle of `color` with edges blurred by a Gaussian blur, painted only outside
+    /// `border_box`, the box that casts it.
+    BoxShadow {
+        color: Color,
+        x: Pixels,
+        y: Pixels,
+        width: Pixels,
+        height: Pixels,
+        /// The blur radius, which is twice the standard deviation of the blur. Zero leaves the
+        /// edges sharp.
+        blur: Pixels,
+        border_box: Rect,
+    },
+    /// A rectangle filled with a gradient.
+    Gradient {
+        x: Pixels,
+        y: Pixels,
+        width: Pixels,
+        height: Pixels,
+        shape: GradientShape,
+        /// The colors along the gradient, by increasing offset.
+        stops: Vec<GradientStop>,
+    },
+    /// Paint the items up to the matching `PopTransform` through `transform`, which maps their
+    /// coordinates to those of the items around them.
+    PushTransform { transform: Transform },
+    PopTransform,
+}
+
+/// Where the colors of a gradient go, in px.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize),
+           serde(rename_all = "kebab-case"))]
+pub enum GradientShape {
+    /// The colors change along the line from `start` (offset 0) to `end` (offset 1), and stay
+    /// the same across it.
+    Linear { start: Point, end: Point },
+    /// The colors change along every ray from `center`, reaching offset 1 on the ellipse with the
+    /// given radii.
+    Radial { center: Point, radius_x: Pixels, radius_y: Pixels },
+}
+
+/// A color of a gradient, at an offset along it. Offsets may be past 0 or 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: Color,
+}
+
+impl GradientShape {
+    /// The offset along the gradient of a point.
+    fn offset(&self, x: Pixels, y: Pixels) -> f32 {
+        match *self {
+            GradientShape::Linear { start, end } => {
+                let (dx, dy) = (end.x - start.x, end.y - start.y);
+                let length = dx * dx + dy * dy;
+                if length == 0.0 {
+                    return 0.0;
+                }
+                ((x - start.x) * dx + (y - start.y) * dy) / length
+            }
+            GradientShape::Radial { center, radius_x, radius_y } => {
+                if radius_x <= 0.0 || radius_y <= 0.0 {
+                    return f32::INFINITY;
+                }
+                let (dx, dy) = ((x - center.x) / radius_x, (y - center.y) / radius_y);
+                (dx * dx + dy * dy).sqrt()
+            }
+        }
+    }
+
+    /// The shape moved by `(dx, dy)`.
+    fn translated(self, dx: Pixels, dy: Pixels) -> GradientShape {
+        let translate = |point: Point| Point { x: point.x + dx, y: point.y + dy };
+        match self {
+            GradientShape::Linear { start, end } => {
+                GradientShape::Linear { start: translate(start), end: translate(end) }
+            }
+            GradientShape::Radial { center, radius_x, radius_y } => {
+                GradientShape::Radial { center: translate(center), radius_x, radius_y }
+            }
+        }
+    }
 }
 
-pub type DisplayList = Vec<DisplayCommand>;
+/// The color of a gradient at `offset`, interpolated between the stops around it with
+/// premultiplied alpha, so that transparent stops do not darken their neighbours.
+fn gradient_color(stops: &[GradientStop], offset: f32) -> Color {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Color::default(),
+    };
+    if offset <= first.offset {
+        return first.color;
+    }
+    if offset >= last.offset {
+        return last.color;
+    }
+    // The last pair of stops that `offset` is between, so that stops at the same offset make a
+    // sharp transition.
+    let i = stops.windows(2).rposition(|pair| pair[0].offset <= offset && offset < pair[1].offset)
+        .unwrap_or(0);
+    let (a, b) = (stops[i], stops[i + 1]);
+    let t = (offset - a.offset) / (b.offset - a.offset);
+    let (alpha_a, alpha_b) = (a.color.alpha(), b.color.alpha());
+    let alpha = alpha_a + (alpha_b - alpha_a) * t;
+    if alpha == 0.0 {
+        return Color::default();
+    }
+    let mix = |ca: u8, cb: u8| {
+        let premultiplied = ca as f32 * alpha_a + (cb as f32 * alpha_b - ca as f32 * alpha_a) * t;
+        (premultiplied / alpha).round().clamp(0.0, 255.0) as u8
+    };
+    Color {
+        r: mix(a.color.r, b.color.r),
+        g: mix(a.color.g, b.color.g),
+        b: mix(a.color.b, b.color.b),
+        a: (alpha * 255.0).round() as u8,
+    }
+}
+
+/// Display items in painting order, from back to front.
+///
+/// With the `serde` feature, a display list serializes as the sequence of its items, so it can be
+/// saved, compared in tests, or sent to a rasterizer in another process, in JSON or any other
+/// serde format.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct DisplayList {
+    items: Vec<DisplayCommand>,
+}
+
+impl DisplayList {
+    pub fn new() -> DisplayList {
+        DisplayList::default()
+    }
+
+    /// Add an item in front of the others.
+    pub fn push(&mut self, item: DisplayCommand) {
+        self.items.push(item);
+    }
+
+    /// The items, from back to front.
+    pub fn items(&self) -> &[DisplayCommand] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Remove the items that cannot affect the pixels painted within `viewport`: items entirely
+    /// outside it, and items entirely hidden behind a later opaque item.
+    pub fn optimize(&mut self, viewport: Rect) {
+        self.optimize_with_snap(viewport, SnapPolicy::default());
+    }
+
+    /// Remove the items that cannot affect the pixels painted within `viewport` with `snap`, like
+    /// `optimize`. Under `SnapPolicy::Antialias`, opaque items only partly cover the pixels at
+    /// their edges, so the items behind them still show through and are kept.
+    pub fn optimize_with_snap(&mut self, viewport: Rect, snap: SnapPolicy) {
+        // Walk from front to back, collecting the opaque rectangles in front of each item. Only
+        // the parts of the items within the viewport matter.
+        let transforms = self.transforms();
+        let mut opaque: Vec<Rect> = Vec::new();
+        let mut kept = Vec::with_capacity(self.items.len());
+        for (item, transform) in self.items.drain(..).zip(transforms).rev() {
+            let transform = match transform {
+                Some(transform) => transform,
+                None => {
+                    kept.push(item);
+                    continue;
+                }
+            };
+            let bounds = match transform.bounding_rect(item.bounds()).intersection(viewport) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            if opaque.iter().any(|cover| cover.contains_rect(bounds)) {
+                continue;
+            }
+            if item.is_opaque() && transform.is_axis_aligned() && snap != SnapPolicy::Antialias {
+                opaque.push(bounds);
+            }
+            kept.push(item);
+        }
+        kept.reverse();
+        self.items = kept;
+    }
+
+    /// The transform that each item is painted through, from its coordinates to the document's,
+    /// or `None` for `PushTransform` and `PopTransform`. A `PopTransform` without a matching
+    /// `PushTransform` does nothing.
+    pub fn transforms(&self) -> Vec<Option<Transform>> {
+        let mut stack = vec![Transform::IDENTITY];
+        self.items.iter().map(|item| match *item {
+            DisplayCommand::PushTransform { transform } => {
+                let current = *stack.last().unwrap();
+                stack.push(transform.then(current));
+                None
+            }
+            DisplayCommand::PopTransform => {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                None
+            }
+            _ => stack.last().copied(),
+        }).collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl DisplayList {
+    /// The items as pretty-printed JSON: an array of objects like
+    /// `{"solid-color": {"color": {"r": 0, "g": 0, "b": 0, "a": 255}, "x": 0.0, ...}}`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("display lists always serialize")
+    }
+
+    /// Read a display list from JSON written by `to_json`.
+    pub fn from_json(json: &str) -> Result<DisplayList, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl<'a> IntoIterator for &'a DisplayList {
+    type Item = &'a DisplayCommand;
+    type IntoIter = slice::Iter<'a, DisplayCommand>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+/// Changes between two display lists, for embedders that keep the painted items in a retained
+/// scene of their own (a game engine's or a GUI toolkit's) and update it instead of rebuilding it
+/// every frame.
+pub struct DisplayListDiff;
+
+/// One change from an old display list to a new one, in terms of the indices of their items.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// The new item at `new` has no counterpart in the old list.
+    Added { new: usize },
+    /// The old item at `old` has no counterpart in the new list.
+    Removed { old: usize },
+    /// The old item at `old` is the new item at `new`, but in other bounds or out of its old
+    /// place in the painting order.
+    Moved { old: usize, new: usize },
+}
+
+impl DisplayListDiff {
+    /// The changes from `old` to `new`: first the removed items in old order, and then the added
+    /// and moved items in new order.
+    ///
+    /// An item that is painted in the same way at another position counts as moved. The items
+    /// that no change mentions are unchanged, and keep their order relative to each other.
+    /// Display lists have a single stacking context, so the lists are compared as a whole. The
+    /// changes are correct but not always the fewest possible.
+    pub fn compute(old: &DisplayList, new: &DisplayList) -> Vec<Change> {
+        let (old, new) = (old.items(), new.items());
+
+        // Most changes leave the start and the end of the list alone.
+        let prefix = old.iter().zip(new).take_while(|&(a, b)| a == b).count();
+        let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+            .take_while(|&(a, b)| a == b)
+            .count();
+        let old_range = prefix..old.len() - suffix;
+        let new_range = prefix..new.len() - suffix;
+
+        // Match the unchanged items in between, in order.
+        let mut old_matched = vec![false; old.len()];
+        let mut new_matched = vec![false; new.len()];
+        let mut cursor = old_range.start;
+        for j in new_range.clone() {
+            if let Some(i) = (cursor..old_range.end).find(|&i| old[i] == new[j]) {
+                old_matched[i] = true;
+                new_matched[j] = true;
+                cursor = i + 1;
+            }
+        }
+
+        // Match the moved items among the rest.
+        let mut moves = Vec::new();
+        for j in new_range.clone().filter(|&j| !new_matched[j]) {
+            let moved = old_range.clone()
+                .find(|&i| !old_matched[i] && old[i].is_moved(&new[j]));
+            if let Some(i) = moved {
+                old_matched[i] = true;
+                moves.push(Change::Moved { old: i, new: j });
+            } else {
+                moves.push(Change::Added { new: j });
+            }
+        }
+
+        old_range.filter(|&i| !old_matched[i])
+            .map(|i| Change::Removed { old: i })
+            .chain(moves)
+            .collect()
+    }
+}
+
+impl DisplayCommand {
+    /// The rectangle the item paints (in px), in its own coordinates. Transforms paint nothing
+    /// themselves, so their bounds are empty.
+    pub fn bounds(&self) -> Rect {
+        match *self {
+            DisplayCommand::SolidColor { x, y, width, height, .. } |
+            DisplayCommand::Gradient { x, y, width, height, .. } => Rect { x, y, width, height },
+            DisplayCommand::PushTransform { .. } | DisplayCommand::PopTransform => Rect::default(),
+            DisplayCommand::BoxShadow { x, y, width, height, blur, .. } => {
+                // Past three standard deviations, the shadow is too faint to show.
+                let extent = 1.5 * blur;
+                Rect {
+                    x: x - extent,
+                    y: y - extent,
+                    width: width + 2.0 * extent,
+                    height: height + 2.0 * extent,
+                }
+            }
+        }
+    }
+
+    /// Is `other` this item, painted in the same way but perhaps at another position?
+    fn is_moved(&self, other: &DisplayCommand) -> bool {
+        match (self, other) {
+            (&DisplayCommand::SolidColor { color, width, height, .. },
+             &DisplayCommand::SolidColor { color: other_color, width: other_width,
+                                           height: other_height, .. }) => {
+                color == other_color && width == other_width && height == other_height
+            }
+            (&DisplayCommand::BoxShadow { color, x, y, width, height, blur, border_box },
+             &DisplayCommand::BoxShadow { color: other_color, x: other_x, y: other_y,
+                                          width: other_width, height: other_height,
+                                          blur: other_blur, border_box: other_box }) => {
+                // The same shadow of the same box, with both moved by the same amount.
+                color == other_color && width == other_width && height == other_height
+                    && blur == other_blur && border_box.size() == other_box.size()
+                    && border_box.x - x == other_box.x - other_x
+                    && border_box.y - y == other_box.y - other_y
+            }
+            (&DisplayCommand::Gradient { x, y, width, height, shape, ref stops },
+             &DisplayCommand::Gradient { x: other_x, y: other_y, width: other_width,
+                                         height: other_height, shape: other_shape,
+                                         stops: ref other_stops }) => {
+                width == other_width && height == other_height && stops == other_stops
+                    && shape.translated(-x, -y) == other_shape.translated(-other_x, -other_y)
+            }
+            _ => false,
+        }
+    }
+
+    /// Move the item by `(dx, dy)`.
+    pub fn translate(&mut self, dx: Pixels, dy: Pixels) {
+        match *self {
+            DisplayCommand::SolidColor { ref mut x, ref mut y, .. } => {
+                *x += dx;
+                *y += dy;
+            }
+            DisplayCommand::BoxShadow { ref mut x, ref mut y, ref mut border_box, .. } => {
+                *x += dx;
+                *y += dy;
+                border_box.x += dx;
+                border_box.y += dy;
+            }
+            DisplayCommand::Gradient { ref mut x, ref mut y, ref mut shape, .. } => {
+                *x += dx;
+                *y += dy;
+                *shape = shape.translated(dx, dy);
+            }
+            DisplayCommand::PushTransform { ref mut transform } => {
+                // Move the items into place before transforming them, and back afterwards.
+                *transform = Transform::translation(-dx, -dy).then(*transform)
+                    .then(Transform::translation(dx, dy));
+            }
+            DisplayCommand::PopTransform => {}
+        }
+    }
+
+    /// Does the item completely hide whatever is behind it within its bounds?
+    pub fn is_opaque(&self) -> bool {
+        match *self {
+            DisplayCommand::SolidColor { color, .. } => color.a == 255,
+            // The shadow shows nothing within the box that casts it.
+            DisplayCommand::BoxShadow { .. } => false,
+            DisplayCommand::PushTransform { .. } | DisplayCommand::PopTransform => false,
+            DisplayCommand::Gradient { ref stops, .. } => {
+                stops.iter().all(|stop| stop.color.a == 255)
+            }
+        }
+    }
+}
+
+/// How two canvases of the same size differ.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CanvasDiff {
+    /// The number of pixels that differ in any channel.
+    pub differing_pixels: usize,
+    /// The largest difference in any channel of any pixel.
+    pub max_difference: u8,
+}
 
 impl Canvas {
-    /// Create a blank canvas
-    fn new(width: usize, height: usize) -> Canvas {
+    /// Create a white canvas, showing the document from the origin at one pixel per px with the
+    /// default options.
+    pub fn new(width: usize, height: usize) -> Canvas {
         let white = Color { r: 255, g: 255, b: 255, a: 255 };
         Canvas {
             pixels: vec![white; width * height],
-            width: width,
-            height: height,
-        }
-    }
-
-    fn paint_item(&mut self, item: &DisplayCommand) {
-        match *item {
-            DisplayCommand::SolidColor { color, x, y, width, height } => {
-                // Clip the rectangle to the canvas boundaries.
-                let x0 = x.clamp(0.0, self.width as f32) as usize;
-                let y0 = y.clamp(0.0, self.height as f32) as usize;
-                let x1 = (x + width).clamp(0.0, self.width as f32) as usize;
-                let y1 = (y + height).clamp(0.0, self.height as f32) as usize;
-                for y in y0 .. y1 {
-                    for x in x0 .. x1 {
-                        let i = y * self.width + x;
-                        self.pixels[i] = color.over(&self.pixels[i]);
+            width,
+            height,
+            region: Rect { x: 0.0, y: 0.0, width: width as Pixels, height: height as Pixels },
+            scale: 1.0,
+            options: PaintOptions::default(),
+        }
+    }
+
+    /// Compare the canvas with another of the same size, pixel by pixel. Returns `None` if the
+    /// sizes differ.
+    pub fn diff(&self, other: &Canvas) -> Option<CanvasDiff> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        let mut diff = CanvasDiff::default();
+        for (a, b) in self.pixels.iter().zip(&other.pixels) {
+            let difference = a.r.abs_diff(b.r).max(a.g.abs_diff(b.g))
+                .max(a.b.abs_diff(b.b)).max(a.a.abs_diff(b.a));
+            if difference > 0 {
+                diff.differing_pixels += 1;
+                diff.max_difference = diff.max_difference.max(difference);
+            }
+        }
+        Some(diff)
+    }
+
+    /// Repaint only the pixels within `damage` (in px) from an updated display list, leaving the
+    /// rest of the canvas as it is.
+    ///
+    /// The canvas is painted again with its own region, scale, and options, as painted by
+    /// `paint_region_with_options`. Every pixel that overlaps `damage` ends up as it would if the
+    /// whole canvas were painted again.
+    pub fn repaint(&mut self, display_list: &DisplayList, damage: Rect) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let x = |x: Pixels| (x - self.region.x) * self.scale;
+        let y = |y: Pixels| (y - self.region.y) * self.scale;
+        let bounds = PixelRect {
+            x0: x(damage.x).floor().clamp(0.0, width) as usize,
+            y0: y(damage.y).floor().clamp(0.0, height) as usize,
+            x1: x(damage.x + damage.width).ceil().clamp(0.0, width) as usize,
+            y1: y(damage.y + damage.height).ceil().clamp(0.0, height) as usize,
+        };
+        if bounds.is_empty() {
+            return;
+        }
+        let device = DeviceSpace {
+            region: self.region,
+            scale: self.scale,
+            snap: self.options.snap,
+            blending: self.options.blending,
+            width: self.width,
+            height: self.height,
+        };
+        let placed = device.place(display_list);
+        let items = placed.iter().filter(|item| item.bounds.overlaps(bounds)).collect();
+        let pixels = Tile { bounds, items }.paint(&device);
+        self.copy_from(bounds, &pixels);
+    }
+
+    /// Copy the rows of pixels of a tile onto the canvas.
+    fn copy_from(&mut self, bounds: PixelRect, pixels: &[Color]) {
+        let tile_width = bounds.x1 - bounds.x0;
+        for (y, row) in (bounds.y0..bounds.y1).zip(pixels.chunks(tile_width)) {
+            let start = y * self.width + bounds.x0;
+            self.pixels[start..start + tile_width].copy_from_slice(row);
+        }
+    }
+}
+
+/// The mapping from a region of the document onto the pixels of a canvas.
+#[derive(Clone, Copy)]
+struct DeviceSpace {
+    region: Rect,
+    /// Pixels per px.
+    scale: f32,
+    snap: SnapPolicy,
+    blending: ColorSpace,
+    /// Size of the canvas in pixels.
+    width: usize,
+    height: usize,
+}
+
+impl DeviceSpace {
+    /// The items of a display list that paint, with the transforms they are painted through
+    /// and the pixels they may cover. Items that cover no pixels are left out.
+    fn place<'a>(&self, display_list: &'a DisplayList) -> Vec<Placed<'a>> {
+        let mut placed = Vec::new();
+        for (item, transform) in display_list.items.iter().zip(display_list.transforms()) {
+            let transform = match transform {
+                Some(transform) => transform,
+                None => continue,
+            };
+            // A transform without an inverse flattens the item to a line or a point.
+            let inverse = match transform.inverse() {
+                Some(inverse) => inverse,
+                None => continue,
+            };
+            let rect = transform.bounding_rect(item.bounds());
+            // Axis-aligned items are snapped to whole pixels, unless anti-aliased; others may
+            // cover any pixel they touch in part.
+            let bounds = if transform.is_axis_aligned() && self.snap != SnapPolicy::Antialias {
+                self.snap_rect(rect)
+            } else {
+                self.cover_rect(rect)
+            };
+            let area = Rect {
+                x: (rect.x - self.region.x) * self.scale,
+                y: (rect.y - self.region.y) * self.scale,
+                width: rect.width * self.scale,
+                height: rect.height * self.scale,
+            };
+            if !bounds.is_empty() {
+                placed.push(Placed { item, transform, inverse, bounds, area });
+            }
+        }
+        placed
+    }
+
+    /// The pixels covered by a rectangle, snapped to pixel boundaries and clipped to the canvas.
+    fn snap_rect(&self, rect: Rect) -> PixelRect {
+        let (x0, x1) = self.snap_span(rect.x, rect.x + rect.width, self.region.x, self.width);
+        let (y0, y1) = self.snap_span(rect.y, rect.y + rect.height, self.region.y, self.height);
+        PixelRect { x0, y0, x1, y1 }
+    }
+
+    /// Map the span from `start` to `end` along an axis of the document onto the `size` pixels
+    /// of the canvas along that axis, which start at `origin`, and snap its edges to pixel
+    /// boundaries. A span that is not empty covers at least one pixel.
+    fn snap_span(&self, start: Pixels, end: Pixels, origin: Pixels, size: usize)
+        -> (usize, usize)
+    {
+        let snapped_start = self.snap.snap((start - origin) * self.scale);
+        let mut snapped_end = self.snap.snap((end - origin) * self.scale);
+        if end > start && snapped_end <= snapped_start {
+            snapped_end = snapped_start + 1.0;
+        }
+        let size = size as f32;
+        (snapped_start.clamp(0.0, size) as usize, snapped_end.clamp(0.0, size) as usize)
+    }
+
+    /// The pixels that a rectangle touches, clipped to the canvas.
+    fn cover_rect(&self, rect: Rect) -> PixelRect {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let x = |x: Pixels| ((x - self.region.x) * self.scale).clamp(0.0, width);
+        let y = |y: Pixels| ((y - self.region.y) * self.scale).clamp(0.0, height);
+        PixelRect {
+            x0: x(rect.x).floor() as usize,
+            y0: y(rect.y).floor() as usize,
+            x1: x(rect.x + rect.width).ceil() as usize,
+            y1: y(rect.y + rect.height).ceil() as usize,
+        }
+    }
+
+    /// The point of the document (in px) at the center of a pixel of the canvas.
+    fn pixel_center(&self, x: usize, y: usize) -> (Pixels, Pixels) {
+        let x = self.region.x + (x as f32 + 0.5) / self.scale;
+        let y = self.region.y + (y as f32 + 0.5) / self.scale;
+        (x, y)
+    }
+}
+
+/// A rectangle of pixels, from `(x0, y0)` inclusive to `(x1, y1)` exclusive.
+#[derive(Clone, Copy, Debug)]
+struct PixelRect {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+impl PixelRect {
+    fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x0..self.x1).contains(&x) && (self.y0..self.y1).contains(&y)
+    }
+
+    fn overlaps(&self, other: PixelRect) -> bool {
+        self.x0.max(other.x0) < self.x1.min(other.x1) &&
+        self.y0.max(other.y0) < self.y1.min(other.y1)
+    }
+}
+
+/// A display item that paints, placed on the canvas.
+struct Placed<'a> {
+    item: &'a DisplayCommand,
+    /// Maps the item's coordinates to the document's.
+    transform: Transform,
+    inverse: Transform,
+    /// The pixels the item may cover, clipped to the canvas.
+    bounds: PixelRect,
+    /// The bounding rectangle of the item on the canvas, in pixels, before snapping.
+    area: Rect,
+}
+
+/// The number of samples along each axis of a pixel, to find how much of it an item covers
+/// when the item is not aligned with the pixel grid.
+const SAMPLES: usize = 4;
+
+impl Placed<'_> {
+    /// The point in the item's coordinates at the center of a pixel.
+    fn local_center(&self, device: &DeviceSpace, x: usize, y: usize) -> Point {
+        let (x, y) = device.pixel_center(x, y);
+        self.inverse.apply(Point { x, y })
+    }
+
+    /// How much of a pixel within `bounds` the item's rectangle covers, from 0 to 1.
+    ///
+    /// Items aligned with the axes are snapped to whole pixels, and cover all of them, unless
+    /// they are anti-aliased: then the exact area of the pixel within the item is measured. Other
+    /// items are sampled at a grid of points within the pixel.
+    fn coverage(&self, device: &DeviceSpace, x: usize, y: usize) -> f32 {
+        if self.transform.is_axis_aligned() {
+            if device.snap != SnapPolicy::Antialias {
+                return 1.0;
+            }
+            let overlap = |pixel: usize, start: f32, length: f32| {
+                let pixel = pixel as f32;
+                ((start + length).min(pixel + 1.0) - start.max(pixel)).max(0.0)
+            };
+            let area = self.area;
+            return overlap(x, area.x, area.width) * overlap(y, area.y, area.height);
+        }
+        let rect = self.item.bounds();
+        let mut covered = 0;
+        for i in 0..SAMPLES * SAMPLES {
+            let sample_x = x as f32 + (i % SAMPLES) as f32 / SAMPLES as f32 + 0.5 / SAMPLES as f32;
+            let sample_y = y as f32 + (i / SAMPLES) as f32 / SAMPLES as f32 + 0.5 / SAMPLES as f32;
+            let point = Point {
+                x: device.region.x + sample_x / device.scale,
+                y: device.region.y + sample_y / device.scale,
+            };
+            let local = self.inverse.apply(point);
+            if rect.contains(local.x, local.y) {
+                covered += 1;
+            }
+        }
+        covered as f32 / (SAMPLES * SAMPLES) as f32
+    }
+}
+
+/// `color`, with its alpha reduced to the part of a pixel that it covers.
+fn with_coverage(color: Color, coverage: f32) -> Color {
+    if coverage >= 1.0 {
+        return color;
+    }
+    Color { a: (color.a as f32 * coverage).round() as u8, ..color }
+}
+
+/// A part of the canvas, and the display items that overlap it.
+struct Tile<'a> {
+    bounds: PixelRect,
+    items: Vec<&'a Placed<'a>>,
+}
+
+impl Tile<'_> {
+    /// Paint the tile of a canvas, returning its rows of pixels.
+    fn paint(&self, device: &DeviceSpace) -> Vec<Color> {
+        let bounds = self.bounds;
+        let tile_width = bounds.x1 - bounds.x0;
+        let white = Color { r: 255, g: 255, b: 255, a: 255 };
+        let mut pixels = vec![white; tile_width * (bounds.y1 - bounds.y0)];
+        for &placed in &self.items {
+            // Clip the item to the whole canvas first, exactly as if the canvas were painted in
+            // one piece, and then to the tile.
+            let clip = placed.bounds;
+            let rows = clip.y0.max(bounds.y0)..clip.y1.min(bounds.y1);
+            let columns = clip.x0.max(bounds.x0)..clip.x1.min(bounds.x1);
+            let mut paint = |x: usize, y: usize, color: Color| {
+                let i = (y - bounds.y0) * tile_width + (x - bounds.x0);
+                pixels[i] = device.blending.blend(color, pixels[i]);
+            };
+            match *placed.item {
+                DisplayCommand::SolidColor { color, .. } => {
+                    for y in rows {
+                        for x in columns.clone() {
+                            let coverage = placed.coverage(device, x, y);
+                            if coverage > 0.0 {
+                                paint(x, y, with_coverage(color, coverage));
+                            }
+                        }
                     }
                 }
+                DisplayCommand::Gradient { shape, ref stops, .. } => {
+                    for y in rows {
+                        for x in columns.clone() {
+                            let coverage = placed.coverage(device, x, y);
+                            if coverage == 0.0 {
+                                continue;
+                            }
+                            let point = placed.local_center(device, x, y);
+                            let color = gradient_color(stops, shape.offset(point.x, point.y));
+                            paint(x, y, with_coverage(color, coverage));
+                        }
+                    }
+                }
+                DisplayCommand::BoxShadow { color, x, y, width, height, blur, border_box } => {
+                    // The box is snapped like its background, so the two meet without a gap.
+                    let snapped = placed.transform.is_axis_aligned()
+                        && device.snap != SnapPolicy::Antialias;
+                    let hole = match snapped {
+                        true => Some(device.snap_rect(placed.transform.bounding_rect(border_box))),
+                        false => None,
+                    };
+                    let sigma = blur / 2.0;
+                    for py in rows {
+                        for px in columns.clone() {
+                            let point = placed.local_center(device, px, py);
+                            let in_box = match hole {
+                                Some(hole) => hole.contains(px, py),
+                                None => border_box.contains(point.x, point.y),
+                            };
+                            if in_box {
+                                continue;
+                            }
+                            // The blur of a rectangle is the product of the blurs of its extents
+                            // along each axis, which are differences of the normal CDF.
+                            let coverage = if sigma > 0.0 {
+                                blurred_extent(point.x, x, x + width, sigma)
+                                    * blurred_extent(point.y, y, y + height, sigma)
+                            } else {
+                                placed.coverage(device, px, py)
+                            };
+                            let color = with_coverage(color, coverage);
+                            if color.a > 0 {
+                                paint(px, py, color);
+                            }
+                        }
+                    }
+                }
+                DisplayCommand::PushTransform { .. } | DisplayCommand::PopTransform => {}
             }
         }
+        pixels
     }
 }
 
-trait Clamp {
-    fn clamp(self, lower: Self, upper: Self) -> Self;
+/// How much of the extent from `start` to `end` covers `point` once blurred by a Gaussian blur
+/// with standard deviation `sigma`, from 0 to 1.
+fn blurred_extent(point: f32, start: f32, end: f32, sigma: f32) -> f32 {
+    let scale = 1.0 / (sigma * std::f32::consts::SQRT_2);
+    0.5 * (erf((point - start) * scale) - erf((point - end) * scale))
 }
-impl Clamp for Pixels {
-    fn clamp(self, lower: Pixels, upper: Pixels) -> Pixels {
-        self.max(lower).min(upper)
-    }
+
+/// The error function, to within 1.5e-7 (Abramowitz and Stegun, formula 7.1.26).
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t * (0.254_829_6 + t * (-0.284_496_7 + t * (1.421_413_8
+        + t * (-1.453_152 + t * 1.061_405_4))));
+    let y = 1.0 - polynomial * (-x * x).exp();
+    if x < 0.0 { -y } else { y }
 }
@@ -0,0 +1,455 @@
+//! Painting display lists on the GPU, with wgpu.
+//!
+//! Every display item becomes a quad over the pixels it may cover, placed and snapped exactly as
+//! the CPU painter places it. A shader finds the color of each pixel: solid colors and shadows
+//! directly, and gradients from a texture holding a row of colors for each gradient. The pixels
+//! come out as the CPU painter would paint them, give or take rounding, so embedders can paint
+//! large pages every frame on the GPU and still test them against the CPU painter.
+
+use css::Color;
+use geometry::{Point, Rect};
+use paint::{gradient_color, Canvas, ColorSpace, DeviceSpace, DisplayCommand, DisplayList,
+            GradientShape, PaintOptions, Placed, SnapPolicy};
+use std::sync::mpsc;
+
+/// The number of colors in the row of each gradient.
+const RAMP_WIDTH: u32 = 256;
+
+/// The size of a vertex, in 32-bit words.
+const VERTEX_WORDS: usize = 22;
+
+/// Paints display lists into textures on a wgpu device.
+pub struct GpuPainter {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl GpuPainter {
+    /// Paint with the embedder's device and queue, into textures of `format`, such as the
+    /// textures of a window's surface.
+    ///
+    /// Translucent items are blended in the color space of the format: sRGB-encoded values are
+    /// blended directly in formats such as `Rgba8Unorm`, as the CPU painter blends them by
+    /// default, and in linear light in formats such as `Rgba8UnormSrgb`.
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue, format: wgpu::TextureFormat)
+        -> GpuPainter
+    {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("display items"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gradient colors"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("display items"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let attributes = wgpu::vertex_attr_array![
+            0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Uint32x2,
+            4 => Float32x4, 5 => Float32x4, 6 => Float32x4,
+        ];
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("display items"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: (VERTEX_WORDS * 4) as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &attributes,
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("gradient colors"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        GpuPainter { device, queue, format, pipeline, bind_group_layout, sampler }
+    }
+
+    /// Paint with a device of its own, into `Rgba8Unorm` textures, to paint off screen. Fails if
+    /// the system has no adapter or device to paint with.
+    pub fn headless() -> Result<GpuPainter, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))
+            .map_err(|error| error.to_string())?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("chameleon"),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        })).map_err(|error| error.to_string())?;
+        Ok(GpuPainter::new(device, queue, wgpu::TextureFormat::Rgba8Unorm))
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Paint the part of a display list within `region`, scaled by `scale` pixels per px, over
+    /// the whole of `target`, which must have the painter's format. The texture is cleared to
+    /// white first, like a new canvas.
+    pub fn paint_to_texture(&self, display_list: &DisplayList, region: Rect, scale: f32,
+                            snap: SnapPolicy, target: &wgpu::Texture) {
+        let size = target.size();
+        let device_space = DeviceSpace {
+            region,
+            scale,
+            snap,
+            blending: ColorSpace::default(),
+            width: size.width as usize,
+            height: size.height as usize,
+        };
+        let placed = device_space.place(display_list);
+        let batches = self.batches(&device_space, &placed);
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("display list"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            for batch in &batches {
+                pass.set_bind_group(0, &batch.bind_group, &[]);
+                pass.set_vertex_buffer(0, batch.vertices.slice(..));
+                pass.draw(0..batch.count, 0..1);
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Paint the part of a display list within `region` to an array of pixels, like
+    /// `paint_region_with_options`, and read them back from the GPU.
+    ///
+    /// Fails if the painter's format is neither `Rgba8Unorm` nor `Bgra8Unorm`, or if the pixels
+    /// cannot be read back.
+    pub fn paint_region(&self, display_list: &DisplayList, region: Rect, scale: f32,
+                        snap: SnapPolicy) -> Result<Canvas, String> {
+        let swap_red_and_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm => false,
+            wgpu::TextureFormat::Bgra8Unorm => true,
+            format => return Err(format!("cannot read pixels of format {:?}", format)),
+        };
+        let (width, height) = super::canvas_size(region, scale);
+        let options = PaintOptions { snap, ..PaintOptions::default() };
+        if width == 0 || height == 0 {
+            return Ok(Canvas { region, scale, options, ..Canvas::new(width, height) });
+        }
+        let size = wgpu::Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("canvas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        self.paint_to_texture(display_list, region, scale, snap, &texture);
+
+        // Rows of a buffer that a texture is copied to must be aligned.
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+        let row_bytes = (width * 4).div_ceil(alignment) * alignment;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("canvas pixels"),
+            size: (row_bytes * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_bytes as u32),
+                    rows_per_image: None,
+                },
+            },
+            size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::PollType::Wait).map_err(|error| error.to_string())?;
+        receiver.recv().map_err(|error| error.to_string())?.map_err(|error| error.to_string())?;
+
+        let bytes = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in bytes.chunks(row_bytes) {
+            for pixel in row[..width * 4].chunks(4) {
+                let (r, b) = match swap_red_and_blue {
+                    true => (pixel[2], pixel[0]),
+                    false => (pixel[0], pixel[2]),
+                };
+                pixels.push(Color { r, g: pixel[1], b, a: pixel[3] });
+            }
+        }
+        Ok(Canvas { pixels, width, height, region, scale, options })
+    }
+
+    /// Turn the placed items into vertices, in painting order. The items are split into batches
+    /// with as many gradients as rows fit in a texture.
+    fn batches(&self, device_space: &DeviceSpace, placed: &[Placed]) -> Vec<Batch> {
+        let max_rows = self.device.limits().max_texture_dimension_2d as usize;
+        let mut batches = Vec::new();
+        let mut start = 0;
+        while start < placed.len() || batches.is_empty() {
+            let mut gradients = 0;
+            let mut end = start;
+            while end < placed.len() {
+                if let DisplayCommand::Gradient { .. } = *placed[end].item {
+                    if gradients == max_rows {
+                        break;
+                    }
+                    gradients += 1;
+                }
+                end += 1;
+            }
+            batches.push(self.batch(device_space, &placed[start..end], gradients));
+            start = end;
+        }
+        batches
+    }
+
+    /// Upload the vertices of `placed`, and the colors of its `gradients`.
+    fn batch(&self, device_space: &DeviceSpace, placed: &[Placed], gradients: usize) -> Batch {
+        use wgpu::util::DeviceExt;
+
+        let rows = gradients.max(1);
+        let mut ramps = Vec::with_capacity(RAMP_WIDTH as usize * rows * 4);
+        let mut words = Vec::with_capacity(placed.len() * 6 * VERTEX_WORDS);
+        for item in placed {
+            let row = (ramps.len() / (RAMP_WIDTH as usize * 4)) as f32;
+            let quad = Quad::new(device_space, item, (row + 0.5) / rows as f32);
+            if let DisplayCommand::Gradient { ref stops, .. } = *item.item {
+                let (first, last) = (quad.extra[1], quad.extra[2]);
+                for i in 0..RAMP_WIDTH {
+                    let t = (i as f32 + 0.5) / RAMP_WIDTH as f32;
+                    let color = gradient_color(stops, first + (last - first) * t);
+                    let alpha = color.alpha();
+                    let premultiply = |channel: u8| (channel as f32 * alpha).round() as u8;
+                    ramps.extend([premultiply(color.r), premultiply(color.g),
+                                  premultiply(color.b), color.a]);
+                }
+            }
+            quad.write(device_space, &mut words);
+        }
+        ramps.resize(RAMP_WIDTH as usize * rows * 4, 0);
+
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        let vertices = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("display items"),
+            // Buffers may not be empty.
+            contents: if bytes.is_empty() { &[0; 4] } else { &bytes },
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let texture = self.device.create_texture_with_data(
+            &self.queue,
+            &wgpu::TextureDescriptor {
+                label: Some("gradient colors"),
+                size: wgpu::Extent3d { width: RAMP_WIDTH, height: rows as u32,
+                                       depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &ramps);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gradient colors"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        let count = (words.len() / VERTEX_WORDS) as u32;
+        Batch { vertices, bind_group, count }
+    }
+}
+
+/// Consecutive items painted by one draw call.
+struct Batch {
+    vertices: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    count: u32,
+}
+
+// The kinds of items, and the ways to find the coverage of a pixel, as the shader numbers them.
+const SOLID: u32 = 0;
+const LINEAR: u32 = 1;
+const RADIAL: u32 = 2;
+const SHADOW: u32 = 3;
+const SNAPPED: u32 = 0;
+const EXACT: u32 = 1;
+const SAMPLED: u32 = 2;
+
+/// The quad of a display item, with what the shader needs to paint it.
+struct Quad<'a> {
+    placed: &'a Placed<'a>,
+    color: Color,
+    kind: u32,
+    mode: u32,
+    rect: [f32; 4],
+    shape: [f32; 4],
+    extra: [f32; 4],
+}
+
+impl<'a> Quad<'a> {
+    /// The quad of `placed`, whose gradient colors (if any) are in the row at `row` of the
+    /// texture, from 0 to 1.
+    fn new(device_space: &DeviceSpace, placed: &'a Placed<'a>, row: f32) -> Quad<'a> {
+        let mode = if !placed.transform.is_axis_aligned() {
+            SAMPLED
+        } else if device_space.snap == SnapPolicy::Antialias {
+            EXACT
+        } else {
+            SNAPPED
+        };
+        let edges = |rect: Rect| [rect.x, rect.y, rect.x + rect.width, rect.y + rect.height];
+        let mut quad = Quad {
+            placed,
+            color: Color::default(),
+            kind: SOLID,
+            mode,
+            rect: edges(placed.item.bounds()),
+            shape: [0.0; 4],
+            extra: [0.0; 4],
+        };
+        match *placed.item {
+            DisplayCommand::SolidColor { color, .. } => quad.color = color,
+            DisplayCommand::Gradient { shape, ref stops, .. } => {
+                let (first, last) = match (stops.first(), stops.last()) {
+                    (Some(first), Some(last)) => (first.offset, last.offset),
+                    _ => (0.0, 0.0),
+                };
+                quad.extra = [row, first, last, 0.0];
+                match shape {
+                    GradientShape::Linear { start, end } => {
+                        quad.kind = LINEAR;
+                        quad.shape = [start.x, start.y, end.x, end.y];
+                    }
+                    GradientShape::Radial { center, radius_x, radius_y } => {
+                        quad.kind = RADIAL;
+                        quad.shape = [center.x, center.y, radius_x, radius_y];
+                    }
+                }
+            }
+            DisplayCommand::BoxShadow { color, x, y, width, height, blur, border_box } => {
+                quad.kind = SHADOW;
+                quad.color = color;
+                quad.rect = edges(Rect { x, y, width, height });
+                quad.extra = [0.0, 0.0, 0.0, blur / 2.0];
+                // The box is snapped like its background, so the two meet without a gap.
+                quad.shape = if mode == SNAPPED {
+                    let hole = device_space.snap_rect(placed.transform.bounding_rect(border_box));
+                    [hole.x0 as f32, hole.y0 as f32, hole.x1 as f32, hole.y1 as f32]
+                } else {
+                    edges(border_box)
+                };
+            }
+            DisplayCommand::PushTransform { .. } | DisplayCommand::PopTransform => {}
+        }
+        quad
+    }
+
+    /// Append the two triangles of the quad, over the pixels it may cover.
+    fn write(&self, device_space: &DeviceSpace, words: &mut Vec<u32>) {
+        let bounds = self.placed.bounds;
+        let (x0, y0, x1, y1) = (bounds.x0 as f32, bounds.y0 as f32, bounds.x1 as f32,
+                                bounds.y1 as f32);
+        let (width, height) = (device_space.width as f32, device_space.height as f32);
+        let color = [self.color.r, self.color.g, self.color.b, self.color.a]
+            .map(|channel| channel as f32 / 255.0);
+        for &(x, y) in &[(x0, y0), (x1, y0), (x0, y1), (x0, y1), (x1, y0), (x1, y1)] {
+            let document = Point {
+                x: device_space.region.x + x / device_space.scale,
+                y: device_space.region.y + y / device_space.scale,
+            };
+            let local = self.placed.inverse.apply(document);
+            let floats = [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0, local.x, local.y];
+            words.extend(floats.iter().chain(&color).map(|float| float.to_bits()));
+            words.extend([self.kind, self.mode]);
+            words.extend(self.rect.iter().chain(&self.shape).chain(&self.extra)
+                .map(|float| float.to_bits()));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Paints the quads of display items. Every vertex of a quad carries the whole item, so that the
+// fragments can find how much of their pixel the item covers and what color it paints there.
+
+// Kinds of items.
+const SOLID: u32 = 0u;
+const LINEAR: u32 = 1u;
+const RADIAL: u32 = 2u;
+const SHADOW: u32 = 3u;
+
+// Ways to find the coverage of a pixel.
+const SNAPPED: u32 = 0u;
+const EXACT: u32 = 1u;
+const SAMPLED: u32 = 2u;
+
+// The number of samples along each axis of a pixel, for items not aligned with the pixel grid.
+const SAMPLES: i32 = 4;
+
+struct Vertex {
+    // On the canvas, in clip space.
+    @location(0) position: vec2<f32>,
+    // In the item's coordinates, in px.
+    @location(1) local: vec2<f32>,
+    // Not premultiplied.
+    @location(2) color: vec4<f32>,
+    // The kind of item and the way to find coverage.
+    @location(3) kind: vec2<u32>,
+    // The rectangle the item fills, as `(x0, y0, x1, y1)` in its coordinates.
+    @location(4) rect: vec4<f32>,
+    // The line or the center and radii of a gradient, or the box that casts a shadow.
+    @location(5) shape: vec4<f32>,
+    // The row of a gradient's colors and the offsets it spans, or the blur of a shadow.
+    @location(6) extra: vec4<f32>,
+}
+
+struct Fragment {
+    @builtin(position) position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) @interpolate(flat, either) color: vec4<f32>,
+    @location(2) @interpolate(flat, either) kind: vec2<u32>,
+    @location(3) @interpolate(flat, either) rect: vec4<f32>,
+    @location(4) @interpolate(flat, either) shape: vec4<f32>,
+    @location(5) @interpolate(flat, either) extra: vec4<f32>,
+}
+
+@group(0) @binding(0) var ramps: texture_2d<f32>;
+@group(0) @binding(1) var ramp_sampler: sampler;
+
+@vertex
+fn vs_main(vertex: Vertex) -> Fragment {
+    var out: Fragment;
+    out.position = vec4<f32>(vertex.position, 0.0, 1.0);
+    out.local = vertex.local;
+    out.color = vertex.color;
+    out.kind = vertex.kind;
+    out.rect = vertex.rect;
+    out.shape = vertex.shape;
+    out.extra = vertex.extra;
+    return out;
+}
+
+// The length of the part of `[start, end)` that overlaps `[low, high)`.
+fn overlap(start: f32, end: f32, low: f32, high: f32) -> f32 {
+    return max(min(end, high) - max(start, low), 0.0);
+}
+
+// The part of the pixel at `local` within `rect`, for items aligned with the axes. `dx` and `dy`
+// step one pixel right and down, in the item's coordinates.
+fn exact_coverage(local: vec2<f32>, dx: vec2<f32>, dy: vec2<f32>, rect: vec4<f32>) -> f32 {
+    let half = 0.5 * (abs(dx) + abs(dy));
+    let low = local - half;
+    let high = local + half;
+    let x = overlap(low.x, high.x, rect.x, rect.z) / (high.x - low.x);
+    let y = overlap(low.y, high.y, rect.y, rect.w) / (high.y - low.y);
+    return x * y;
+}
+
+// The part of a grid of samples in the pixel at `local` that falls within `rect`.
+fn sampled_coverage(local: vec2<f32>, dx: vec2<f32>, dy: vec2<f32>, rect: vec4<f32>) -> f32 {
+    var covered = 0;
+    for (var i = 0; i < SAMPLES * SAMPLES; i++) {
+        let offset = (vec2<f32>(vec2<i32>(i % SAMPLES, i / SAMPLES)) + 0.5) / f32(SAMPLES) - 0.5;
+        let point = local + offset.x * dx + offset.y * dy;
+        if point.x >= rect.x && point.x < rect.z && point.y >= rect.y && point.y < rect.w {
+            covered += 1;
+        }
+    }
+    return f32(covered) / f32(SAMPLES * SAMPLES);
+}
+
+// The error function, to within 1.5e-7 (Abramowitz and Stegun, formula 7.1.26).
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.3275911 * abs(x));
+    let polynomial = t * (0.2548296 + t * (-0.2844967 + t * (1.4214138
+        + t * (-1.453152 + t * 1.0614054))));
+    return sign(x) * (1.0 - polynomial * exp(-x * x));
+}
+
+// How much of the extent from `start` to `end` covers `point` once blurred by a Gaussian blur
+// with standard deviation `sigma`.
+fn blurred_extent(point: f32, start: f32, end: f32, sigma: f32) -> f32 {
+    let scale = 1.0 / (sigma * sqrt(2.0));
+    return 0.5 * (erf((point - start) * scale) - erf((point - end) * scale));
+}
+
+// The offset along a gradient of a point.
+fn gradient_offset(kind: u32, shape: vec4<f32>, local: vec2<f32>) -> f32 {
+    if kind == LINEAR {
+        let direction = shape.zw - shape.xy;
+        let length = dot(direction, direction);
+        if length == 0.0 {
+            return 0.0;
+        }
+        return dot(local - shape.xy, direction) / length;
+    }
+    if shape.z <= 0.0 || shape.w <= 0.0 {
+        return 3.4e38;
+    }
+    return length((local - shape.xy) / shape.zw);
+}
+
+@fragment
+fn fs_main(in: Fragment) -> @location(0) vec4<f32> {
+    // Derivatives are only defined outside of branches.
+    let dx = dpdx(in.local);
+    let dy = dpdy(in.local);
+    let kind = in.kind.x;
+    let mode = in.kind.y;
+
+    var coverage = 1.0;
+    if mode == EXACT {
+        coverage = exact_coverage(in.local, dx, dy, in.rect);
+    } else if mode == SAMPLED {
+        coverage = sampled_coverage(in.local, dx, dy, in.rect);
+    }
+
+    if kind == LINEAR || kind == RADIAL {
+        // The colors are premultiplied, and spread over the offsets from the first stop to the
+        // last.
+        let offset = gradient_offset(kind, in.shape, in.local);
+        let span = in.extra.z - in.extra.y;
+        var t = select(0.0, 1.0, offset >= in.extra.y);
+        if span > 0.0 {
+            t = clamp((offset - in.extra.y) / span, 0.0, 1.0);
+        }
+        return textureSampleLevel(ramps, ramp_sampler, vec2<f32>(t, in.extra.x), 0.0) * coverage;
+    }
+
+    if kind == SHADOW {
+        // Snapped shadows have their box snapped like its background, on the canvas.
+        var point = in.local;
+        if mode == SNAPPED {
+            point = in.position.xy;
+        }
+        let hole = in.shape;
+        if point.x >= hole.x && point.x < hole.z && point.y >= hole.y && point.y < hole.w {
+            discard;
+        }
+        let sigma = in.extra.w;
+        if sigma > 0.0 {
+            coverage = blurred_extent(in.local.x, in.rect.x, in.rect.z, sigma)
+                * blurred_extent(in.local.y, in.rect.y, in.rect.w, sigma);
+        }
+    }
+
+    return vec4<f32>(in.color.rgb * in.color.a, in.color.a) * coverage;
+}
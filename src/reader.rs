@@ -0,0 +1,245 @@
+//! Reader views: the main content of a page, such as an article, without the rest of the page.
+//!
+//! Feed readers and archival tools want the text of an article, not the navigation, sidebars, and
+//! comments around it. `main_content` finds the element that holds it with the heuristics of
+//! Readability: every paragraph with enough text scores points for its parent and grandparent, by
+//! its length and number of commas, and every candidate's score is adjusted by its tag, by words in
+//! its id and classes such as `article` or `sidebar`, and by how much of its text is in links.
+//! `reader_document` copies that element into a document of its own, without the clutter, and
+//! `render` paints it with a built-in stylesheet.
+
+use css::{self, Stylesheet};
+use dom::{self, AttrMap, Node, NodeId, NodeType};
+use engine::Engine;
+use geometry::Rect;
+use layout::LayoutContext;
+use limits::{LimitError, Limits};
+use paint::Canvas;
+use std::collections::HashMap;
+use style::Pixels;
+
+/// Elements that never hold the main content, and are left out of reader views.
+const UNLIKELY_TAGS: &[&str] = &[
+    "aside", "button", "footer", "form", "head", "header", "iframe", "link", "meta", "nav",
+    "script", "style", "title",
+];
+
+/// Words in ids and classes of elements that hold the main content.
+const POSITIVE_NAMES: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "main", "page", "post", "story", "text",
+];
+
+/// Words in ids and classes of elements that hold anything else.
+const NEGATIVE_NAMES: &[&str] = &[
+    "ad", "banner", "comment", "footer", "footnote", "menu", "meta", "nav", "promo", "related",
+    "share", "sidebar", "social", "sponsor", "widget",
+];
+
+/// Paragraphs with less text than this many characters score nothing.
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// Containers whose text is more than this fraction links are left out of reader views.
+const MAX_LINK_DENSITY: f32 = 0.5;
+
+/// The amount of text in a subtree, in non-whitespace characters.
+#[derive(Clone, Copy, Default)]
+struct TextLength {
+    total: usize,
+    /// The part of `total` that is in links.
+    links: usize,
+}
+
+impl TextLength {
+    /// The fraction of the text that is in links.
+    fn link_density(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.links as f32 / self.total as f32
+        }
+    }
+}
+
+/// Find the element that holds the main content of the document at `root`, or `None` if no
+/// element has paragraphs with enough text.
+pub fn main_content<'n, 'a>(root: &'n Node<'a>) -> Option<&'n Node<'a>> {
+    let mut lengths = HashMap::new();
+    text_length(root, false, &mut lengths);
+
+    let mut candidates = HashMap::new();
+    score_paragraphs(root, &mut Vec::new(), &mut candidates);
+
+    // Visit the candidates in document order, so that the first of equal scores wins.
+    let mut best: Option<(&Node, f32)> = None;
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        if let Some(&score) = candidates.get(&node.id) {
+            let score = (score + tag_weight(node) + name_weight(node))
+                * (1.0 - lengths[&node.id].link_density());
+            if score > 0.0 && best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((node, score));
+            }
+        }
+    }
+    best.map(|(node, _)| node)
+}
+
+/// Copy the main content of the document at `root` into a document of its own, as the only child
+/// of an `<article>` in its `<body>`, or return `None` if it has no main content.
+///
+/// The copy leaves out elements that never hold the main content (such as `<nav>` and `<form>`),
+/// containers whose ids or classes name something else (such as `sidebar`) or whose text is
+/// mostly links, and every attribute but `href`, so that only the reader stylesheet applies.
+pub fn reader_document<'a>(root: &Node<'a>) -> Option<Node<'a>> {
+    let content = main_content(root)?;
+    let mut lengths = HashMap::new();
+    text_length(content, false, &mut lengths);
+    let article = dom::elem("article", AttrMap::new(), vec![copy_clean(content, &lengths)?]);
+    let body = dom::elem("body", AttrMap::new(), vec![article]);
+    Some(dom::elem("html", AttrMap::new(), vec![body]))
+}
+
+/// The stylesheet of reader views, with the user agent stylesheet before it.
+pub fn reader_stylesheet() -> Stylesheet<'static> {
+    Stylesheet::combine(vec![css::user_agent_stylesheet(), css::parse(include_str!("reader.css"))])
+}
+
+/// Render a document from `reader_document` with the reader stylesheet, in a viewport `width` px
+/// wide. The canvas is as tall as the document.
+pub fn render(reader: &Node, width: Pixels) -> Result<Canvas, LimitError> {
+    let context = LayoutContext { viewport_width: width, viewport_height: 0.0 };
+    let engine = Engine::with_limits(reader, &reader_stylesheet(), context, Limits::default())?;
+    let height = engine.layout_tree().root()
+        .map_or(0.0, |root| root.geometry().margin_box.max().y);
+    engine.render_region(Rect { x: 0.0, y: 0.0, width, height }, 1.0)
+}
+
+/// Measure the text of every element in the subtree of `node`, which is inside a link if
+/// `in_link` is true, recording it in `lengths`.
+fn text_length(node: &Node, in_link: bool, lengths: &mut HashMap<NodeId, TextLength>)
+    -> TextLength
+{
+    let mut length = TextLength::default();
+    match node.node_type {
+        NodeType::Text(ref text) => {
+            length.total = text.chars().filter(|c| !c.is_whitespace()).count();
+            if in_link {
+                length.links = length.total;
+            }
+        }
+        NodeType::Element(ref elem) => {
+            let in_link = in_link || elem.tag == "a";
+            for child in &node.children {
+                let child_length = text_length(child, in_link, lengths);
+                length.total += child_length.total;
+                length.links += child_length.links;
+            }
+            lengths.insert(node.id, length);
+        }
+    }
+    length
+}
+
+/// Score the paragraphs in the subtree of `node`, whose ancestors are `ancestors`, adding each
+/// paragraph's score to its parent's and half of it to its grandparent's in `candidates`.
+fn score_paragraphs(node: &Node, ancestors: &mut Vec<NodeId>,
+                    candidates: &mut HashMap<NodeId, f32>) {
+    let elem = match node.node_type {
+        NodeType::Element(ref elem) => elem,
+        NodeType::Text(_) => return,
+    };
+    if UNLIKELY_TAGS.contains(&&*elem.tag) {
+        return;
+    }
+    if matches!(&*elem.tag, "p" | "pre" | "blockquote" | "td") {
+        let mut text = String::new();
+        collect_text(node, &mut text);
+        let length = text.chars().filter(|c| !c.is_whitespace()).count();
+        if length >= MIN_PARAGRAPH_LENGTH {
+            let commas = text.matches(',').count();
+            let score = 1.0 + commas as f32 + (length / 100).min(3) as f32;
+            for (&ancestor, share) in ancestors.iter().rev().zip(&[1.0, 0.5]) {
+                *candidates.entry(ancestor).or_insert(0.0) += score * share;
+            }
+        }
+    }
+    ancestors.push(node.id);
+    for child in &node.children {
+        score_paragraphs(child, ancestors, candidates);
+    }
+    ancestors.pop();
+}
+
+/// The adjustment to the score of a candidate for its tag.
+fn tag_weight(node: &Node) -> f32 {
+    let tag = match node.node_type {
+        NodeType::Element(ref elem) => &*elem.tag,
+        NodeType::Text(_) => return 0.0,
+    };
+    match tag {
+        "article" | "main" => 10.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    }
+}
+
+/// The adjustment to the score of a candidate for the words in its id and classes.
+fn name_weight(node: &Node) -> f32 {
+    let elem = match node.node_type {
+        NodeType::Element(ref elem) => elem,
+        NodeType::Text(_) => return 0.0,
+    };
+    let mut weight = 0.0;
+    for name in elem.id().into_iter().chain(elem.classes()) {
+        let name = name.to_ascii_lowercase();
+        let words: Vec<&str> = name.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        if NEGATIVE_NAMES.iter().any(|word| words.contains(word)) {
+            weight -= 25.0;
+        } else if POSITIVE_NAMES.iter().any(|word| words.contains(word)) {
+            weight += 25.0;
+        }
+    }
+    weight
+}
+
+/// Copy the subtree of `node` for a reader view, or return `None` if it is left out.
+fn copy_clean<'a>(node: &Node<'a>, lengths: &HashMap<NodeId, TextLength>) -> Option<Node<'a>> {
+    let elem = match node.node_type {
+        NodeType::Element(ref elem) => elem,
+        NodeType::Text(ref text) => return Some(dom::text(text.clone())),
+    };
+    if UNLIKELY_TAGS.contains(&&*elem.tag) {
+        return None;
+    }
+    let is_container = matches!(&*elem.tag, "div" | "section" | "ul" | "ol" | "table");
+    if is_container
+        && (name_weight(node) < 0.0 || lengths[&node.id].link_density() > MAX_LINK_DENSITY)
+    {
+        return None;
+    }
+    let mut attributes = AttrMap::new();
+    if let Some(href) = elem.attributes.get("href") {
+        attributes.insert("href".into(), href.clone());
+    }
+    let children = node.children.iter().filter_map(|child| copy_clean(child, lengths)).collect();
+    Some(dom::elem(elem.tag.clone(), attributes, children))
+}
+
+/// Append the text of `node`'s subtree to `text`.
+fn collect_text(node: &Node, text: &mut String) {
+    match node.node_type {
+        NodeType::Text(ref data) => {
+            text.push_str(data);
+            text.push(' ');
+        }
+        NodeType::Element(_) => {
+            for child in &node.children {
+                collect_text(child, text);
+            }
+        }
+    }
+}
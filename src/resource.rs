@@ -0,0 +1,294 @@
+//! Fetching the resources a document refers to, such as its linked stylesheets.
+//!
+//! Fetching goes through a `ResourceLoader`, so the embedder decides where resources come from.
+//! `FileLoader` reads `file:` URLs (and plain paths) from the local filesystem, and, with the
+//! `http` feature, `HttpLoader` fetches `http:` and `https:` URLs.
+
+use dom::{Node, NodeId, NodeType};
+use links;
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// Fetches resources by URL.
+pub trait ResourceLoader {
+    /// Fetch the resource at an absolute URL (or, if the document has no base URL, at whatever
+    /// the document wrote).
+    fn load(&self, url: &str) -> Result<Vec<u8>, LoadError>;
+
+    /// Fetch a resource along with its final URL and its MIME type. By default, the URL is the
+    /// one given and the type is sniffed from the contents.
+    fn fetch(&self, url: &str) -> Result<Resource, LoadError> {
+        let bytes = self.load(url)?;
+        Ok(Resource { url: url.to_string(), mime_type: mime_type(None, &bytes), bytes })
+    }
+}
+
+/// A fetched resource.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Resource {
+    /// The URL the resource was fetched from, after any redirects. Relative URLs in the resource
+    /// are resolved against it.
+    pub url: String,
+    /// The MIME type of the resource, such as `text/html`, without parameters.
+    pub mime_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// A loader for a document at `url` and the resources it refers to: an `HttpLoader` for `http:`
+/// and `https:` URLs, and a `FileLoader` otherwise.
+#[cfg(feature = "http")]
+pub fn loader_for(url: &str) -> Box<dyn ResourceLoader> {
+    if is_http(url) {
+        Box::new(HttpLoader::new())
+    } else {
+        Box::new(FileLoader)
+    }
+}
+
+/// A loader for a document at `url` and the resources it refers to. Without the `http` feature,
+/// this is always a `FileLoader`.
+#[cfg(not(feature = "http"))]
+pub fn loader_for(_url: &str) -> Box<dyn ResourceLoader> {
+    Box::new(FileLoader)
+}
+
+/// Is `url` an `http:` or `https:` URL?
+pub fn is_http(url: &str) -> bool {
+    match url.find(':') {
+        Some(end) => {
+            let scheme = &url[..end];
+            scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+        }
+        None => false,
+    }
+}
+
+/// Why a resource could not be fetched.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The loader cannot fetch URLs of this scheme.
+    UnsupportedScheme(String),
+    /// Reading the resource failed.
+    Io(io::Error),
+    /// The server answered with an error status, such as 404.
+    Status(u16),
+    /// The server could not be reached, or the connection failed.
+    Network(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::UnsupportedScheme(ref scheme) => write!(f, "unsupported scheme {}:", scheme),
+            LoadError::Io(ref error) => write!(f, "{}", error),
+            LoadError::Status(status) => write!(f, "HTTP status {}", status),
+            LoadError::Network(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> LoadError {
+        LoadError::Io(error)
+    }
+}
+
+/// Loads `file:` URLs, and URLs without a scheme as paths, from the local filesystem.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FileLoader;
+
+impl ResourceLoader for FileLoader {
+    fn load(&self, url: &str) -> Result<Vec<u8>, LoadError> {
+        let path = match url.find(':') {
+            // A Windows drive letter is not a scheme.
+            Some(1) => url,
+            Some(end) if &url[..end] == "file" => {
+                // Drop the (empty or `localhost`) authority, keeping the absolute path.
+                let path = url["file:".len()..].trim_start_matches("//");
+                let path = path.strip_prefix("localhost").unwrap_or(path);
+                path.split(['?', '#']).next().unwrap_or("")
+            }
+            Some(end) => return Err(LoadError::UnsupportedScheme(url[..end].to_string())),
+            None => url,
+        };
+        Ok(fs::read(path)?)
+    }
+}
+
+/// Fetches `http:` and `https:` URLs, following redirects.
+#[cfg(feature = "http")]
+pub struct HttpLoader {
+    agent: ::ureq::Agent,
+    max_size: u64,
+}
+
+#[cfg(feature = "http")]
+impl HttpLoader {
+    /// A loader that follows up to 10 redirects and fetches resources of up to 64 MiB.
+    pub fn new() -> HttpLoader {
+        HttpLoader::with_limits(10, 64 << 20)
+    }
+
+    /// A loader that follows up to `max_redirects` redirects (and fails on the next one), and
+    /// fails on resources larger than `max_size` bytes.
+    pub fn with_limits(max_redirects: u32, max_size: u64) -> HttpLoader {
+        let agent = ::ureq::AgentBuilder::new()
+            .redirects(max_redirects)
+            .user_agent(concat!("chameleon/", env!("CARGO_PKG_VERSION")))
+            .build();
+        HttpLoader { agent, max_size }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Default for HttpLoader {
+    fn default() -> HttpLoader {
+        HttpLoader::new()
+    }
+}
+
+#[cfg(feature = "http")]
+impl ResourceLoader for HttpLoader {
+    fn load(&self, url: &str) -> Result<Vec<u8>, LoadError> {
+        Ok(self.fetch(url)?.bytes)
+    }
+
+    fn fetch(&self, url: &str) -> Result<Resource, LoadError> {
+        use std::io::Read;
+
+        if !is_http(url) {
+            let scheme = url.split(':').next().unwrap_or("");
+            return Err(LoadError::UnsupportedScheme(scheme.to_string()));
+        }
+        let response = match self.agent.get(url).call() {
+            Ok(response) => response,
+            Err(::ureq::Error::Status(status, _)) => return Err(LoadError::Status(status)),
+            Err(::ureq::Error::Transport(error)) => {
+                return Err(LoadError::Network(error.to_string()))
+            }
+        };
+        let final_url = response.get_url().to_string();
+        let content_type = response.header("Content-Type").map(String::from);
+        let mut bytes = Vec::new();
+        response.into_reader().take(self.max_size + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > self.max_size {
+            return Err(LoadError::Network(format!("larger than {} bytes", self.max_size)));
+        }
+        let mime_type = mime_type(content_type.as_deref(), &bytes);
+        Ok(Resource { url: final_url, mime_type, bytes })
+    }
+}
+
+/// The MIME type of a resource, without parameters: the one in its `Content-Type` header, if it
+/// has a meaningful one, or else one sniffed from its first bytes.
+///
+/// Sniffing follows the WHATWG MIME Sniffing standard for unknown types, recognizing HTML, XML,
+/// PDF, and the common image formats, and telling other text from binary data.
+pub fn mime_type(content_type: Option<&str>, bytes: &[u8]) -> String {
+    let essence = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|essence| essence.trim().to_ascii_lowercase())
+        .filter(|essence| essence.contains('/'));
+    match essence {
+        Some(ref essence) if !matches!(&**essence,
+                                       "unknown/unknown" | "application/unknown" | "*/*") => {
+            essence.clone()
+        }
+        _ => sniff(bytes).to_string(),
+    }
+}
+
+/// Sniff the MIME type of a resource of unknown type.
+fn sniff(bytes: &[u8]) -> &'static str {
+    const IMAGES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+    ];
+    const HTML_TAGS: &[&[u8]] = &[
+        b"<!DOCTYPE HTML", b"<HTML", b"<HEAD", b"<SCRIPT", b"<IFRAME", b"<H1", b"<DIV", b"<FONT",
+        b"<TABLE", b"<A", b"<STYLE", b"<TITLE", b"<B", b"<BODY", b"<BR", b"<P", b"<!--",
+    ];
+
+    let bytes = &bytes[..bytes.len().min(1445)];
+    for &(signature, mime_type) in IMAGES {
+        if bytes.starts_with(signature) {
+            return mime_type;
+        }
+    }
+    if bytes.len() >= 14 && &bytes[..4] == b"RIFF" && &bytes[8..14] == b"WEBPVP" {
+        return "image/webp";
+    }
+    let start = bytes.iter().position(|b| !b" \t\n\x0c\r".contains(b)).unwrap_or(bytes.len());
+    let text = &bytes[start..];
+    for &tag in HTML_TAGS {
+        // The tag must be followed by a space or the end of the tag.
+        if text.len() > tag.len() && text[..tag.len()].eq_ignore_ascii_case(tag) &&
+           (text[tag.len()] == b' ' || text[tag.len()] == b'>') {
+            return "text/html";
+        }
+    }
+    if text.starts_with(b"<?xml") {
+        return "text/xml";
+    }
+    let binary = |b: &u8| matches!(*b, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f);
+    if bytes.iter().any(binary) {
+        "application/octet-stream"
+    } else {
+        "text/plain"
+    }
+}
+
+/// A stylesheet linked from a document by a `<link rel="stylesheet">` element.
+#[derive(Debug)]
+pub struct LinkedStylesheet {
+    /// The `<link>` element, which places the stylesheet in the document's cascade order.
+    pub node: NodeId,
+    /// The URL of the stylesheet, resolved against the base URL.
+    pub url: String,
+    /// The text of the stylesheet, or why it could not be fetched.
+    pub source: Result<String, LoadError>,
+}
+
+/// Fetch the stylesheets linked from a document, in document order.
+///
+/// Relative URLs are resolved as `links::links` resolves them. Alternate stylesheets are not
+/// fetched, since they only apply when the user picks them. Stylesheets that are not valid UTF-8
+/// are decoded lossily.
+pub fn fetch_stylesheets(root: &Node, base_url: Option<&str>, loader: &dyn ResourceLoader)
+    -> Vec<LinkedStylesheet>
+{
+    let base = links::document_base_url(root, base_url);
+    let mut stylesheets = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        let elem = match node.node_type {
+            NodeType::Element(ref elem) if elem.tag == "link" => elem,
+            _ => continue,
+        };
+        let rel = elem.attributes.get("rel").map_or("", |rel| rel);
+        let is_rel = |keyword: &str| {
+            rel.split_whitespace().any(|token| token.eq_ignore_ascii_case(keyword))
+        };
+        if !is_rel("stylesheet") || is_rel("alternate") {
+            continue;
+        }
+        let href = match elem.attributes.get("href") {
+            Some(href) => href.trim(),
+            None => continue,
+        };
+        let url = match base {
+            Some(ref base) => links::resolve(base, href),
+            None => href.to_string(),
+        };
+        let source = loader.load(&url)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+        stylesheets.push(LinkedStylesheet { node: node.id, url, source });
+    }
+    stylesheets
+}
@@ -0,0 +1,326 @@
+//! A hook for embedders to run the scripts of a document with a script engine of their choice.
+//!
+//! Chameleon does not run scripts itself. An embedder implements `ScriptHost` over an engine (such
+//! as Boa or QuickJS), and `run_scripts` calls it for each `<script>` element in document order.
+//! The host changes the document through a `ScriptDom`, which refers to nodes by plain integer
+//! handles and takes and returns owned strings, so that it can be bound to another language
+//! without exposing references into the tree.
+
+use dom::{self, MutationError, Node, NodeId, NodeType};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A node, as a script engine refers to it: the raw value of its `NodeId`.
+pub type NodeHandle = u64;
+
+/// A `<script>` element to run.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Script {
+    /// The `<script>` element.
+    pub node: NodeHandle,
+    /// The text of the element: the script itself, for an inline script.
+    pub text: String,
+    /// The `src` attribute, naming an external script for the host to fetch, if any.
+    pub src: Option<String>,
+    /// The `type` attribute, if any. Hosts skip the types they cannot run.
+    pub script_type: Option<String>,
+}
+
+/// A script engine that runs the scripts of a document.
+pub trait ScriptHost {
+    /// Run `script`, changing the document through `dom`. An error is reported with the script,
+    /// and does not keep the scripts after it from running.
+    fn run(&mut self, script: &Script, dom: &mut ScriptDom) -> Result<(), String>;
+}
+
+/// A script that failed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ScriptError {
+    /// The `<script>` element.
+    pub node: NodeId,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "script {} failed: {}", self.node.to_raw(), self.message)
+    }
+}
+
+/// Run the scripts of the document at `root` with `host`, in document order, returning the errors
+/// of the ones that failed.
+///
+/// Each `<script>` element runs once. Scripts that a script inserts run after it, if they come
+/// later in the document than it; those it inserts before itself do not run. If a script removes
+/// itself, the next one to run is the first that has not run yet.
+pub fn run_scripts(root: &mut Node, host: &mut dyn ScriptHost) -> Vec<ScriptError> {
+    let mut ran = HashSet::new();
+    let mut errors = Vec::new();
+    let mut dom = ScriptDom { root, detached: HashMap::new() };
+    let mut last = None;
+    while let Some(script) = next_script(dom.root, last, &ran) {
+        let id = NodeId::from_raw(script.node);
+        ran.insert(id);
+        last = Some(id);
+        if let Err(message) = host.run(&script, &mut dom) {
+            errors.push(ScriptError { node: id, message });
+        }
+    }
+    errors
+}
+
+/// The first `<script>` element of the document at `root` that has not run, after the one that
+/// ran `last`, if it is still in the document.
+fn next_script(root: &Node, last: Option<NodeId>, ran: &HashSet<NodeId>) -> Option<Script> {
+    let mut after_last = last.is_none_or(|last| root.find(last).is_none());
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        let elem = match node.node_type {
+            NodeType::Element(ref elem) if elem.is_html() && elem.has_tag("script") => elem,
+            _ => continue,
+        };
+        if Some(node.id) == last {
+            after_last = true;
+        }
+        if !after_last || ran.contains(&node.id) {
+            continue;
+        }
+        let attribute = |name: &str| elem.attributes.get(name).map(|value| value.to_string());
+        return Some(Script {
+            node: node.id.to_raw(),
+            text: text_content(node),
+            src: attribute("src"),
+            script_type: attribute("type"),
+        });
+    }
+    None
+}
+
+/// The document, as a script sees it.
+///
+/// Nodes that scripts create, or remove from the document, are kept (with their descendants)
+/// until the scripts are done, so that they can be changed and inserted again.
+pub struct ScriptDom<'d, 'a: 'd> {
+    root: &'d mut Node<'a>,
+    /// Trees outside the document, by the handle of their root.
+    detached: HashMap<NodeId, Node<'a>>,
+}
+
+impl<'d, 'a> ScriptDom<'d, 'a> {
+    /// The root element of the document.
+    pub fn document_element(&self) -> NodeHandle {
+        self.root.id.to_raw()
+    }
+
+    /// Create an element with the given tag name, outside the document.
+    pub fn create_element(&mut self, tag: &str) -> NodeHandle {
+        self.add_detached(dom::elem(dom::lowercase_name(tag.to_string()), HashMap::new(), vec![]))
+    }
+
+    /// Create a text node, outside the document.
+    pub fn create_text_node(&mut self, text: &str) -> NodeHandle {
+        self.add_detached(dom::text(text.to_string()))
+    }
+
+    /// The tag name of an element, or `None` for a text node.
+    pub fn tag_name(&self, node: NodeHandle) -> Result<Option<String>, MutationError> {
+        match self.find(node)?.node_type {
+            NodeType::Element(ref elem) => Ok(Some(elem.tag.to_string())),
+            NodeType::Text(_) => Ok(None),
+        }
+    }
+
+    /// The parent of a node, if it has one.
+    pub fn parent(&self, node: NodeHandle) -> Result<Option<NodeHandle>, MutationError> {
+        let id = NodeId::from_raw(node);
+        let tree = self.tree(id).ok_or(MutationError::NotFound)?;
+        Ok(tree.parent(id).map(|parent| parent.id.to_raw()))
+    }
+
+    /// The children of a node, in order.
+    pub fn children(&self, node: NodeHandle) -> Result<Vec<NodeHandle>, MutationError> {
+        Ok(self.find(node)?.children.iter().map(|child| child.id.to_raw()).collect())
+    }
+
+    /// The value of an attribute of an element, if it is set.
+    pub fn attribute(&self, node: NodeHandle, name: &str)
+        -> Result<Option<String>, MutationError>
+    {
+        match self.find(node)?.node_type {
+            NodeType::Element(ref elem) => {
+                let name = if elem.is_html() { dom::lowercase_name(name) } else { name.into() };
+                Ok(elem.attributes.get(&*name).map(|value| value.to_string()))
+            }
+            NodeType::Text(_) => Err(MutationError::NotAnElement),
+        }
+    }
+
+    /// Set an attribute of an element.
+    pub fn set_attribute(&mut self, node: NodeHandle, name: &str, value: &str)
+        -> Result<(), MutationError>
+    {
+        let id = NodeId::from_raw(node);
+        let tree = self.tree_mut(id)?;
+        tree.set_attribute(id, name.to_string(), value.to_string()).map(|_| ())
+    }
+
+    /// Remove an attribute of an element, if it is set.
+    pub fn remove_attribute(&mut self, node: NodeHandle, name: &str)
+        -> Result<(), MutationError>
+    {
+        let id = NodeId::from_raw(node);
+        self.tree_mut(id)?.remove_attribute(id, name).map(|_| ())
+    }
+
+    /// The text of a node: its data, for a text node, or the text of its descendants in order.
+    pub fn text_content(&self, node: NodeHandle) -> Result<String, MutationError> {
+        Ok(text_content(self.find(node)?))
+    }
+
+    /// Set the text of a node, like `Node::set_text`. An element's old children are dropped.
+    pub fn set_text_content(&mut self, node: NodeHandle, text: &str)
+        -> Result<(), MutationError>
+    {
+        let id = NodeId::from_raw(node);
+        self.tree_mut(id)?.set_text(id, text.to_string())
+    }
+
+    /// Insert `child` into `parent` before `reference`, or after its other children if
+    /// `reference` is `None`. A child already in a tree is moved from its old place.
+    pub fn insert_before(&mut self, parent: NodeHandle, child: NodeHandle,
+                         reference: Option<NodeHandle>) -> Result<(), MutationError> {
+        let (parent, child) = (NodeId::from_raw(parent), NodeId::from_raw(child));
+        if self.find(child.to_raw())?.find(parent).is_some() {
+            return Err(MutationError::Cycle);
+        }
+        match self.find(parent.to_raw())?.node_type {
+            NodeType::Element(_) => {}
+            NodeType::Text(_) => return Err(MutationError::NotAnElement),
+        }
+        if let Some(reference) = reference {
+            if reference == child.to_raw() {
+                return Ok(());
+            }
+            let reference = NodeId::from_raw(reference);
+            if !self.find(parent.to_raw())?.children.iter().any(|node| node.id == reference) {
+                return Err(MutationError::NotAChild);
+            }
+        }
+        let node = self.take(child)?;
+        let tree = self.tree_mut(parent)?;
+        let parent_node = tree.find_mut(parent).ok_or(MutationError::NotFound)?;
+        let index = reference.and_then(|reference| {
+            let reference = NodeId::from_raw(reference);
+            parent_node.children.iter().position(|node| node.id == reference)
+        });
+        let index = index.unwrap_or(parent_node.children.len());
+        parent_node.children.insert(index, node);
+        Ok(())
+    }
+
+    /// Add `child` after the other children of `parent`, moving it from its old place if it is
+    /// already in a tree.
+    pub fn append_child(&mut self, parent: NodeHandle, child: NodeHandle)
+        -> Result<(), MutationError>
+    {
+        self.insert_before(parent, child, None)
+    }
+
+    /// Remove `child` from the children of `parent`. It is kept outside the document.
+    pub fn remove_child(&mut self, parent: NodeHandle, child: NodeHandle)
+        -> Result<(), MutationError>
+    {
+        let (parent, child) = (NodeId::from_raw(parent), NodeId::from_raw(child));
+        let node = self.tree_mut(parent)?.remove_child(parent, child)?;
+        self.detached.insert(child, node);
+        Ok(())
+    }
+
+    /// Put `new` in the place of `old` among the children of `parent`, keeping `old` outside the
+    /// document. `new` is moved from its old place if it is already in a tree.
+    pub fn replace_child(&mut self, parent: NodeHandle, new: NodeHandle, old: NodeHandle)
+        -> Result<(), MutationError>
+    {
+        if new == old {
+            return Ok(());
+        }
+        self.insert_before(parent, new, Some(old))?;
+        self.remove_child(parent, old)
+    }
+
+    /// The first element with the given id attribute in the document, in document order.
+    pub fn element_by_id(&self, id: &str) -> Option<NodeHandle> {
+        let mut stack = vec![&*self.root];
+        while let Some(node) = stack.pop() {
+            stack.extend(node.children.iter().rev());
+            if let NodeType::Element(ref elem) = node.node_type {
+                if elem.id() == Some(id) {
+                    return Some(node.id.to_raw());
+                }
+            }
+        }
+        None
+    }
+
+    /// The first element in the subtree of `node` that matches a list of selectors, like
+    /// `Node::query_selector`.
+    pub fn query_selector(&self, node: NodeHandle, selectors: &str)
+        -> Result<Option<NodeHandle>, MutationError>
+    {
+        Ok(self.find(node)?.query_selector(selectors).map(|found| found.id.to_raw()))
+    }
+
+    /// Every element in the subtree of `node` that matches a list of selectors, in document
+    /// order.
+    pub fn query_selector_all(&self, node: NodeHandle, selectors: &str)
+        -> Result<Vec<NodeHandle>, MutationError>
+    {
+        let found = self.find(node)?.query_selector_all(selectors);
+        Ok(found.into_iter().map(|found| found.id.to_raw()).collect())
+    }
+
+    fn add_detached(&mut self, node: Node<'a>) -> NodeHandle {
+        let id = node.id;
+        self.detached.insert(id, node);
+        id.to_raw()
+    }
+
+    /// The root of the tree (the document, or a detached one) that holds the node `id`.
+    fn tree(&self, id: NodeId) -> Option<&Node<'a>> {
+        if self.root.find(id).is_some() {
+            return Some(self.root);
+        }
+        self.detached.values().find(|tree| tree.find(id).is_some())
+    }
+
+    fn tree_mut(&mut self, id: NodeId) -> Result<&mut Node<'a>, MutationError> {
+        if self.root.find(id).is_some() {
+            return Ok(self.root);
+        }
+        self.detached.values_mut().find(|tree| tree.find(id).is_some())
+            .ok_or(MutationError::NotFound)
+    }
+
+    fn find(&self, node: NodeHandle) -> Result<&Node<'a>, MutationError> {
+        let id = NodeId::from_raw(node);
+        self.tree(id).and_then(|tree| tree.find(id)).ok_or(MutationError::NotFound)
+    }
+
+    /// Take the node `id` out of its tree. The root of the document cannot be taken.
+    fn take(&mut self, id: NodeId) -> Result<Node<'a>, MutationError> {
+        if let Some(node) = self.detached.remove(&id) {
+            return Ok(node);
+        }
+        let tree = self.tree_mut(id)?;
+        let parent = tree.parent(id).map(|parent| parent.id).ok_or(MutationError::NotAChild)?;
+        tree.remove_child(parent, id)
+    }
+}
+
+fn text_content(node: &Node) -> String {
+    match node.node_type {
+        NodeType::Text(ref text) => text.to_string(),
+        NodeType::Element(_) => node.children.iter().map(text_content).collect(),
+    }
+}
@@ -0,0 +1,514 @@
+//! Compact binary snapshots of a parsed document and its stylesheet.
+//!
+//! A snapshot lets a host save a rendering session and resume it later without parsing the HTML
+//! and CSS sources again. Restoring borrows every name, attribute, text node, and keyword from the
+//! snapshot itself, just as parsing borrows them from the sources.
+//!
+//! The format is a header (`CHMS` and a version byte) followed by the DOM, the stylesheet, and the
+//! state of the session: the scroll position, and the preorder index of the focused node plus one
+//! (or 0). Counts and lengths are LEB128 varints, numbers are little-endian `f32`s, and strings
+//! are length-prefixed UTF-8.
+
+use css::{Color, Declaration, Origin, PseudoClass, PseudoElement, Rule, Selector, SimpleSelector};
+use css::{MediaFeature, MediaQuery, MediaQueryList, MediaType, Stylesheet, Unit, Value};
+use dom::{self, Node, NodeType};
+use html::MAX_NESTING_DEPTH;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter;
+use std::str;
+use style::{ElementStates, Pixels};
+
+const MAGIC: &[u8] = b"CHMS";
+const VERSION: u8 = 8;
+
+/// The state of a rendering session besides its document and stylesheet, which
+/// `Engine::session_state` returns and `Engine::restore_session_state` applies.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct SessionState {
+    /// The offset of the viewport from the origin of the document.
+    pub scroll_position: (Pixels, Pixels),
+    /// The states of the elements, such as which one has the focus.
+    pub states: ElementStates,
+}
+
+/// Why a snapshot could not be restored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SnapshotError {
+    /// The snapshot does not start with the expected header, or is of another version.
+    BadHeader,
+    /// The snapshot ends in the middle of a value.
+    UnexpectedEnd,
+    /// A string in the snapshot is not valid UTF-8.
+    InvalidUtf8,
+    /// A byte identifying the kind of a value is out of range.
+    InvalidTag(u8),
+    /// Nodes or values are nested more than the given depth.
+    TooDeep(usize),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::BadHeader => write!(f, "not a snapshot of this version"),
+            SnapshotError::UnexpectedEnd => write!(f, "unexpected end of snapshot"),
+            SnapshotError::InvalidUtf8 => write!(f, "invalid UTF-8 in snapshot"),
+            SnapshotError::InvalidTag(tag) => write!(f, "invalid tag {} in snapshot", tag),
+            SnapshotError::TooDeep(depth) => {
+                write!(f, "snapshot is nested more than {} deep", depth)
+            }
+        }
+    }
+}
+
+/// Save a document and its stylesheet to a snapshot, with the default session state.
+pub fn save(root: &Node, stylesheet: &Stylesheet) -> Vec<u8> {
+    save_session(root, stylesheet, &SessionState::default())
+}
+
+/// Save a document, its stylesheet, and the state of a session rendering them to a snapshot.
+///
+/// A focused element that is not in the document is saved as no focus.
+pub fn save_session(root: &Node, stylesheet: &Stylesheet, state: &SessionState) -> Vec<u8> {
+    let mut writer = Writer(Vec::new());
+    writer.0.extend_from_slice(MAGIC);
+    writer.0.push(VERSION);
+    writer.node(root);
+    writer.stylesheet(stylesheet);
+    let (x, y) = state.scroll_position;
+    writer.number(x);
+    writer.number(y);
+    let focused = state.states.focused.and_then(|id| preorder(root).position(|n| n.id == id));
+    writer.count(focused.map_or(0, |index| index + 1));
+    writer.0
+}
+
+/// Restore a document and its stylesheet from a snapshot, dropping the session state.
+pub fn restore(snapshot: &[u8]) -> Result<(Node<'_>, Stylesheet<'_>), SnapshotError> {
+    restore_session(snapshot).map(|(root, stylesheet, _)| (root, stylesheet))
+}
+
+/// Restore a document, its stylesheet, and the state of a session from a snapshot.
+///
+/// The restored nodes have new `NodeId`s, which the focus refers to. Restoring fails, rather than
+/// overflowing the stack, if nodes or values are nested more deeply than the HTML parser ever
+/// nests elements (`html::MAX_NESTING_DEPTH`).
+pub fn restore_session(snapshot: &[u8])
+    -> Result<(Node<'_>, Stylesheet<'_>, SessionState), SnapshotError>
+{
+    let mut reader = Reader { pos: 0, input: snapshot, depth: 1 };
+    if reader.bytes(MAGIC.len())? != MAGIC || reader.byte()? != VERSION {
+        return Err(SnapshotError::BadHeader);
+    }
+    let root = reader.node()?;
+    let stylesheet = reader.stylesheet()?;
+    let scroll_position = (reader.number()?, reader.number()?);
+    let focused = match reader.count()? {
+        0 => None,
+        index => preorder(&root).nth(index - 1).map(|node| node.id),
+    };
+    let states = ElementStates { focused };
+    Ok((root, stylesheet, SessionState { scroll_position, states }))
+}
+
+/// The nodes of a tree in preorder, without recursing.
+fn preorder<'r, 'a>(root: &'r Node<'a>) -> impl Iterator<Item = &'r Node<'a>> {
+    let mut stack = vec![root];
+    iter::from_fn(move || {
+        let node = stack.pop()?;
+        stack.extend(node.children.iter().rev());
+        Some(node)
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn node(&mut self, node: &Node) {
+        match node.node_type {
+            NodeType::Element(ref elem) => {
+                self.0.push(0);
+                self.string(&elem.tag);
+                self.optional_string(&elem.namespace);
+                self.count(elem.attributes.len());
+                for (name, value) in &elem.attributes {
+                    self.string(name);
+                    self.string(value);
+                }
+            }
+            NodeType::Text(ref text) => {
+                self.0.push(1);
+                self.string(text);
+            }
+        }
+        self.count(node.children.len());
+        for child in &node.children {
+            self.node(child);
+        }
+    }
+
+    fn stylesheet(&mut self, stylesheet: &Stylesheet) {
+        self.count(stylesheet.rules().len());
+        for rule in stylesheet.rules() {
+            self.0.push(match rule.origin {
+                Origin::UserAgent => 0,
+                Origin::User => 1,
+                Origin::Author => 2,
+            });
+            self.count(rule.selectors.len());
+            for selector in &rule.selectors {
+                let Selector::Simple(ref simple) = *selector;
+                self.optional_string(&simple.tag);
+                self.optional_string(&simple.id);
+                self.count(simple.class.len());
+                for class in &simple.class {
+                    self.string(class);
+                }
+                self.count(simple.pseudo_classes.len());
+                for pseudo_class in &simple.pseudo_classes {
+                    self.0.push(match *pseudo_class {
+                        PseudoClass::Focus => 0,
+                    });
+                }
+                self.0.push(match simple.pseudo_element {
+                    None => 0,
+                    Some(PseudoElement::Before) => 1,
+                    Some(PseudoElement::After) => 2,
+                });
+            }
+            self.count(rule.declarations.len());
+            for declaration in &rule.declarations {
+                self.string(&declaration.name);
+                self.value(&declaration.value);
+                self.0.push(declaration.important as u8);
+            }
+            self.count(rule.media.len());
+            for list in &rule.media {
+                self.count(list.0.len());
+                for query in &list.0 {
+                    self.0.push(query.negated as u8);
+                    self.0.push(match query.media_type {
+                        None => 0,
+                        Some(MediaType::Screen) => 1,
+                        Some(MediaType::Print) => 2,
+                    });
+                    self.count(query.features.len());
+                    for feature in &query.features {
+                        let (tag, length) = match *feature {
+                            MediaFeature::MinWidth(length) => (0, length),
+                            MediaFeature::MaxWidth(length) => (1, length),
+                            MediaFeature::MinHeight(length) => (2, length),
+                            MediaFeature::MaxHeight(length) => (3, length),
+                        };
+                        self.0.push(tag);
+                        self.number(length);
+                    }
+                }
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match *value {
+            Value::Keyword(ref keyword) => {
+                self.0.push(0);
+                self.string(keyword);
+            }
+            Value::Number(n) => {
+                self.0.push(1);
+                self.number(n);
+            }
+            Value::Percentage(p) => {
+                self.0.push(2);
+                self.number(p);
+            }
+            Value::Length(n, unit) => {
+                self.0.push(3);
+                self.number(n);
+                self.0.push(match unit {
+                    Unit::Px => 0,
+                    Unit::Fr => 1,
+                    Unit::Deg => 2,
+                });
+            }
+            Value::ColorValue(color) => {
+                self.0.push(4);
+                self.0.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+            Value::Delimiter(c) => {
+                self.0.push(5);
+                self.count(c as usize);
+            }
+            Value::List(ref values) => {
+                self.0.push(6);
+                self.count(values.len());
+                for value in values {
+                    self.value(value);
+                }
+            }
+            Value::String(ref s) => {
+                self.0.push(7);
+                self.string(s);
+            }
+            Value::Function(ref name, ref arguments) => {
+                self.0.push(8);
+                self.string(name);
+                self.count(arguments.len());
+                for argument in arguments {
+                    self.value(argument);
+                }
+            }
+        }
+    }
+
+    /// Write an unsigned LEB128 varint.
+    fn count(&mut self, mut n: usize) {
+        while n >= 0x80 {
+            self.0.push((n as u8 & 0x7f) | 0x80);
+            n >>= 7;
+        }
+        self.0.push(n as u8);
+    }
+
+    fn number(&mut self, n: f32) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn string(&mut self, s: &str) {
+        self.count(s.len());
+        self.0.extend_from_slice(s.as_bytes());
+    }
+
+    fn optional_string(&mut self, s: &Option<Cow<str>>) {
+        match *s {
+            Some(ref s) => {
+                self.0.push(1);
+                self.string(s);
+            }
+            None => self.0.push(0),
+        }
+    }
+}
+
+struct Reader<'a> {
+    pos: usize,
+    input: &'a [u8],
+    /// The depth of the node or value being read; the root is at depth 1.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn node(&mut self) -> Result<Node<'a>, SnapshotError> {
+        let tag = self.byte()?;
+        let mut node = match tag {
+            0 => {
+                let name = self.string()?;
+                let namespace = self.optional_string()?;
+                let mut attributes = HashMap::new();
+                for _ in 0..self.count()? {
+                    attributes.insert(self.string()?, self.string()?);
+                }
+                match namespace {
+                    Some(namespace) => dom::elem_ns(namespace, name, attributes, Vec::new()),
+                    None => dom::elem(name, attributes, Vec::new()),
+                }
+            }
+            1 => dom::text(self.string()?),
+            _ => return Err(SnapshotError::InvalidTag(tag)),
+        };
+        for _ in 0..self.count()? {
+            node.children.push(self.nested(Reader::node)?);
+        }
+        Ok(node)
+    }
+
+    /// Read a node or value inside the current one with `read`, unless that nests them too deeply.
+    fn nested<T, F>(&mut self, read: F) -> Result<T, SnapshotError>
+        where F: FnOnce(&mut Reader<'a>) -> Result<T, SnapshotError>
+    {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(SnapshotError::TooDeep(MAX_NESTING_DEPTH));
+        }
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn stylesheet(&mut self) -> Result<Stylesheet<'a>, SnapshotError> {
+        let mut rules = Vec::new();
+        for _ in 0..self.count()? {
+            let origin = match self.byte()? {
+                0 => Origin::UserAgent,
+                1 => Origin::User,
+                2 => Origin::Author,
+                tag => return Err(SnapshotError::InvalidTag(tag)),
+            };
+            let mut selectors = Vec::new();
+            for _ in 0..self.count()? {
+                let tag = self.optional_string()?;
+                let id = self.optional_string()?;
+                let mut class = Vec::new();
+                for _ in 0..self.count()? {
+                    class.push(self.string()?);
+                }
+                let mut pseudo_classes = Vec::new();
+                for _ in 0..self.count()? {
+                    pseudo_classes.push(match self.byte()? {
+                        0 => PseudoClass::Focus,
+                        tag => return Err(SnapshotError::InvalidTag(tag)),
+                    });
+                }
+                let pseudo_element = match self.byte()? {
+                    0 => None,
+                    1 => Some(PseudoElement::Before),
+                    2 => Some(PseudoElement::After),
+                    tag => return Err(SnapshotError::InvalidTag(tag)),
+                };
+                selectors.push(Selector::Simple(SimpleSelector {
+                    tag,
+                    id,
+                    class,
+                    pseudo_classes,
+                    pseudo_element,
+                }));
+            }
+            let mut declarations = Vec::new();
+            for _ in 0..self.count()? {
+                let name = self.string()?;
+                let value = self.value()?;
+                let important = match self.byte()? {
+                    0 => false,
+                    1 => true,
+                    tag => return Err(SnapshotError::InvalidTag(tag)),
+                };
+                declarations.push(Declaration { name, value, important });
+            }
+            let mut media = Vec::new();
+            for _ in 0..self.count()? {
+                let mut queries = Vec::new();
+                for _ in 0..self.count()? {
+                    let negated = match self.byte()? {
+                        0 => false,
+                        1 => true,
+                        tag => return Err(SnapshotError::InvalidTag(tag)),
+                    };
+                    let media_type = match self.byte()? {
+                        0 => None,
+                        1 => Some(MediaType::Screen),
+                        2 => Some(MediaType::Print),
+                        tag => return Err(SnapshotError::InvalidTag(tag)),
+                    };
+                    let mut features = Vec::new();
+                    for _ in 0..self.count()? {
+                        let tag = self.byte()?;
+                        let length = self.number()?;
+                        features.push(match tag {
+                            0 => MediaFeature::MinWidth(length),
+                            1 => MediaFeature::MaxWidth(length),
+                            2 => MediaFeature::MinHeight(length),
+                            3 => MediaFeature::MaxHeight(length),
+                            tag => return Err(SnapshotError::InvalidTag(tag)),
+                        });
+                    }
+                    queries.push(MediaQuery { negated, media_type, features });
+                }
+                media.push(MediaQueryList(queries));
+            }
+            rules.push(Rule { selectors, declarations, origin, media });
+        }
+        Ok(Stylesheet::new(rules))
+    }
+
+    fn value(&mut self) -> Result<Value<'a>, SnapshotError> {
+        let tag = self.byte()?;
+        Ok(match tag {
+            0 => Value::Keyword(self.string()?),
+            1 => Value::Number(self.number()?),
+            2 => Value::Percentage(self.number()?),
+            3 => {
+                let n = self.number()?;
+                let unit = match self.byte()? {
+                    0 => Unit::Px,
+                    1 => Unit::Fr,
+                    2 => Unit::Deg,
+                    unit => return Err(SnapshotError::InvalidTag(unit)),
+                };
+                Value::Length(n, unit)
+            }
+            4 => {
+                let rgba = self.bytes(4)?;
+                Value::ColorValue(Color { r: rgba[0], g: rgba[1], b: rgba[2], a: rgba[3] })
+            }
+            5 => {
+                let c = self.count()?;
+                Value::Delimiter(char::from_u32(c as u32).ok_or(SnapshotError::InvalidTag(tag))?)
+            }
+            6 => {
+                let mut values = Vec::new();
+                for _ in 0..self.count()? {
+                    values.push(self.nested(Reader::value)?);
+                }
+                Value::List(values)
+            }
+            7 => Value::String(self.string()?),
+            8 => {
+                let name = self.string()?;
+                let mut arguments = Vec::new();
+                for _ in 0..self.count()? {
+                    arguments.push(self.nested(Reader::value)?);
+                }
+                Value::Function(name, arguments)
+            }
+            _ => return Err(SnapshotError::InvalidTag(tag)),
+        })
+    }
+
+    fn byte(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.input.len());
+        let end = end.ok_or(SnapshotError::UnexpectedEnd)?;
+        let bytes = &self.input[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Read an unsigned LEB128 varint.
+    fn count(&mut self) -> Result<usize, SnapshotError> {
+        let mut n = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift < usize::BITS {
+                n |= ((byte & 0x7f) as usize) << shift;
+            }
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, SnapshotError> {
+        let bytes = self.bytes(4)?;
+        Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<Cow<'a, str>, SnapshotError> {
+        let len = self.count()?;
+        let bytes = self.bytes(len)?;
+        str::from_utf8(bytes).map(Cow::Borrowed).map_err(|_| SnapshotError::InvalidUtf8)
+    }
+
+    fn optional_string(&mut self) -> Result<Option<Cow<'a, str>>, SnapshotError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.string()?)),
+            tag => Err(SnapshotError::InvalidTag(tag)),
+        }
+    }
+}
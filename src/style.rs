@@ -3,19 +3,49 @@
 //! This is not very interesting at the moment.  It will get much more
 //! complicated if I add support for compound selectors.
 
-use dom::{Node, NodeType, ElementData};
-use css::{Stylesheet, Rule, Selector, SimpleSelector, Value, Unit, Color, Specificity};
+use dom::{Node, NodeId, NodeType, ElementData};
+use css::{self, Stylesheet, Rule, Declaration, Origin, Selector, SimpleSelector, Value, Unit};
+use css::{Color, MediaContext, ParseError, PseudoClass, PseudoElement, Specificity};
+use geometry::Transform;
+use resource::LinkedStylesheet;
+use std::cmp::Reverse;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[cfg(feature = "parallel")]
+use html;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "parallel")]
+use std::cell::Cell;
+#[cfg(feature = "parallel")]
+use std::sync::OnceLock;
 
 /// A node with associated style data.
-pub struct StyledNode<'a> {
-    pub node: &'a Node,
-    pub specified: Style,
-    pub children: Vec<StyledNode<'a>>,
+///
+/// The style tree owns its data, so it can be retained across changes to the DOM and restyled
+/// incrementally.
+pub struct StyledNode {
+    pub node: NodeId,
+    /// The pseudo-element this node was generated for, as a child of its element. Generated nodes
+    /// are not in the DOM: each gets a new `NodeId` whenever its element is styled.
+    pub pseudo: Option<PseudoElement>,
+    pub specified: Arc<Style>,
+    /// Whether the styles of this subtree are out of date.
+    pub dirty: bool,
+    pub children: Vec<StyledNode>,
 }
 
 /// Bundled edge offsets.
 #[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Edge<T> {
     pub left: T,
     pub right: T,
@@ -23,27 +53,226 @@ pub struct Edge<T> {
     pub bottom: T,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub enum Display {
+    #[default]
     Inline,
     Block,
+    ListItem,
+    Table,
+    TableRow,
+    TableCell,
+    Flex,
+    Grid,
     None,
 }
 
-impl Default for Display {
-    fn default() -> Self { Display::Inline }
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FlexDirection {
+    #[default]
+    Row,
+    RowReverse,
+    Column,
+    ColumnReverse,
+}
+
+impl FlexDirection {
+    /// Is the main axis horizontal?
+    pub fn is_row(&self) -> bool {
+        matches!(self, FlexDirection::Row | FlexDirection::RowReverse)
+    }
+
+    /// Are flex items placed against the main axis?
+    pub fn is_reverse(&self) -> bool {
+        matches!(self, FlexDirection::RowReverse | FlexDirection::ColumnReverse)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FlexWrap {
+    #[default]
+    NoWrap,
+    Wrap,
+    WrapReverse,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum JustifyContent {
+    #[default]
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AlignContent {
+    #[default]
+    Stretch,
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AlignItems {
+    #[default]
+    Stretch,
+    FlexStart,
+    FlexEnd,
+    Center,
 }
 
 /// A length measured in standard pixels.
 pub type Pixels = f32;
 
 /// A potentially automatically calculated length.
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
 pub enum Automatic<V> {
+    #[default]
     Auto,
     Given(V),
 }
 
+/// A length that may be a percentage of a reference length, such as the size of the containing
+/// block, which is only known during layout.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LengthPercentage {
+    Length(Pixels),
+    Percentage(f32),
+    /// The sum of a length and a percentage, from a `calc()` expression such as
+    /// `calc(100% - 20px)`.
+    Calc(Pixels, f32),
+}
+
+impl LengthPercentage {
+    /// Resolve to an absolute length, given the reference length (if it is definite).
+    pub fn resolve(&self, base: Option<Pixels>) -> Option<Pixels> {
+        match *self {
+            LengthPercentage::Length(px) => Some(px),
+            LengthPercentage::Percentage(p) => base.map(|base| base * p / 100.0),
+            LengthPercentage::Calc(px, p) => base.map(|base| px + base * p / 100.0),
+        }
+    }
+
+    /// Evaluate the arguments of `calc()`, which add, subtract, multiply, and divide lengths,
+    /// percentages, and numbers, with parentheses (or nested `calc()`) for grouping.
+    ///
+    /// Lengths and percentages can only be multiplied and divided by numbers, so the result is
+    /// always some length plus some percentage.
+    fn calc(arguments: &[Value]) -> Result<LengthPercentage, String> {
+        match Calc::sum(arguments)? {
+            Calc::Number(_) => Err(String::from("expected a length or percentage from calc()")),
+            Calc::Dimension(px, 0.0) => Ok(LengthPercentage::Length(px)),
+            Calc::Dimension(0.0, p) => Ok(LengthPercentage::Percentage(p)),
+            Calc::Dimension(px, p) => Ok(LengthPercentage::Calc(px, p)),
+        }
+    }
+}
+
+/// An intermediate value of a `calc()` expression.
+#[derive(Clone, Copy)]
+enum Calc {
+    Number(f32),
+    /// A length in pixels plus a percentage.
+    Dimension(Pixels, f32),
+}
+
+impl Calc {
+    /// Evaluate terms separated by `+` and `-`.
+    fn sum(values: &[Value]) -> Result<Calc, String> {
+        let is_operator = |v: &Value| matches!(v, Value::Delimiter('+') | Value::Delimiter('-'));
+        let mut terms = values.split(|v| is_operator(v));
+        let mut operators = values.iter().filter(|v| is_operator(v));
+        let mut sum = Calc::product(terms.next().unwrap_or_default())?;
+        for term in terms {
+            let sign = match operators.next() {
+                Some(Value::Delimiter('-')) => -1.0,
+                _ => 1.0,
+            };
+            sum = match (sum, Calc::product(term)?) {
+                (Calc::Number(a), Calc::Number(b)) => Calc::Number(a + sign * b),
+                (Calc::Dimension(px, p), Calc::Dimension(px2, p2)) => {
+                    Calc::Dimension(px + sign * px2, p + sign * p2)
+                }
+                _ => return Err(String::from("cannot add a number to a length in calc()")),
+            };
+        }
+        Ok(sum)
+    }
+
+    /// Evaluate factors separated by `*` and `/`.
+    fn product(values: &[Value]) -> Result<Calc, String> {
+        let (first, rest) = match values.split_first() {
+            Some(split) => split,
+            None => return Err(String::from("expected a value in calc()")),
+        };
+        let mut product = Calc::factor(first)?;
+        for pair in rest.chunks(2) {
+            let (operator, factor) = match pair {
+                [operator, factor] => (operator, Calc::factor(factor)?),
+                _ => return Err(format!("expected a value after {} in calc()", pair[0])),
+            };
+            product = match (operator, product, factor) {
+                (Value::Delimiter('*'), Calc::Number(a), Calc::Number(b)) => Calc::Number(a * b),
+                (Value::Delimiter('*'), Calc::Number(n), Calc::Dimension(px, p)) |
+                (Value::Delimiter('*'), Calc::Dimension(px, p), Calc::Number(n)) => {
+                    Calc::Dimension(px * n, p * n)
+                }
+                (Value::Delimiter('/'), _, Calc::Number(0.0)) => {
+                    return Err(String::from("division by zero in calc()"));
+                }
+                (Value::Delimiter('/'), Calc::Number(a), Calc::Number(n)) => Calc::Number(a / n),
+                (Value::Delimiter('/'), Calc::Dimension(px, p), Calc::Number(n)) => {
+                    Calc::Dimension(px / n, p / n)
+                }
+                (Value::Delimiter('*'), _, _) | (Value::Delimiter('/'), _, _) => {
+                    return Err(String::from("cannot multiply or divide two lengths in calc()"));
+                }
+                _ => return Err(format!("unexpected {} in calc()", operator)),
+            };
+        }
+        Ok(product)
+    }
+
+    /// Evaluate a number, length, percentage, or a nested expression.
+    fn factor(value: &Value) -> Result<Calc, String> {
+        match value {
+            Value::Number(n) => Ok(Calc::Number(*n)),
+            Value::Length(px, Unit::Px) => Ok(Calc::Dimension(*px, 0.0)),
+            Value::Percentage(p) => Ok(Calc::Dimension(0.0, *p)),
+            Value::Function(name, arguments) if name.is_empty() || is_calc(name) => {
+                Calc::sum(arguments)
+            }
+            _ => Err(format!("unexpected {} in calc()", value)),
+        }
+    }
+}
+
+/// Whether a function is `calc()`.
+fn is_calc(name: &str) -> bool {
+    name.eq_ignore_ascii_case("calc")
+}
+
+impl Automatic<LengthPercentage> {
+    /// Resolve to an absolute length, given the reference length (if it is definite).
+    ///
+    /// Percentages of an indefinite length behave as `auto`.
+    pub fn resolve(&self, base: Option<Pixels>) -> Automatic<Pixels> {
+        match self {
+            Automatic::Auto => Automatic::Auto,
+            Automatic::Given(length) => {
+                length.resolve(base).map_or(Automatic::Auto, Automatic::Given)
+            }
+        }
+    }
+}
+
 impl<V: Default + Copy> Automatic<V> {
     /// Get the given value or the default for its type.
     pub fn value(&self) -> V {
@@ -86,57 +315,79 @@ impl<V: Default + Copy> Automatic<V> {
     }
 }
 
-impl<V> Default for Automatic<V> {
-    fn default() -> Self { Automatic::Auto }
-}
-
 impl<V> From<V> for Automatic<V> {
     fn from(v: V) -> Self { Automatic::Given(v) }
 }
 
-impl TryFrom<&Value> for Color {
+impl TryFrom<&Value<'_>> for Color {
     type Error = String;
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
             Value::ColorValue(v) => Ok(*v),
+            Value::Keyword(kw) if kw.eq_ignore_ascii_case("transparent") => Ok(Color::default()),
             _ => Err(format!("expected color but found {}", v)),
         }
     }
 }
 
-impl TryFrom<&Value> for Automatic<Pixels> {
+impl TryFrom<&Value<'_>> for Automatic<Pixels> {
     type Error = String;
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
             Value::Length(px, Unit::Px) => Ok(Automatic::Given(*px)),
+            Value::Number(n) if *n == 0.0 => Ok(Automatic::Given(0.0)),
             Value::Keyword(kw) if kw == "auto" => Ok(Automatic::Auto),
             _ => Err(format!("expected auto/length but found {}", v)),
         }
     }
 }
 
-impl TryFrom<&Value> for Pixels {
+impl TryFrom<&Value<'_>> for Automatic<LengthPercentage> {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Length(px, Unit::Px) => Ok(Automatic::Given(LengthPercentage::Length(*px))),
+            Value::Percentage(p) => Ok(Automatic::Given(LengthPercentage::Percentage(*p))),
+            Value::Number(n) if *n == 0.0 => Ok(Automatic::Given(LengthPercentage::Length(0.0))),
+            Value::Function(name, arguments) if is_calc(name) => {
+                LengthPercentage::calc(arguments).map(Automatic::Given)
+            }
+            Value::Keyword(kw) if kw == "auto" => Ok(Automatic::Auto),
+            _ => Err(format!("expected auto/length/percentage but found {}", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value<'_>> for Pixels {
     type Error = String;
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
             Value::Length(l, Unit::Px) => Ok(*l),
-            _ => Err(format!("expected auto/length but found {}", v)),
+            Value::Number(n) if *n == 0.0 => Ok(0.0),
+            _ => Err(format!("expected length but found {}", v)),
         }
     }
 }
 
-impl TryFrom<&Value> for Display {
+impl TryFrom<&Value<'_>> for Display {
     type Error = String;
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
             Value::Keyword(kw) => {
-                match kw.as_str() {
+                match &**kw {
                     "inline" => Ok(Display::Inline),
                     "block" => Ok(Display::Block),
+                    "list-item" => Ok(Display::ListItem),
+                    "table" => Ok(Display::Table),
+                    "table-row" => Ok(Display::TableRow),
+                    "table-cell" => Ok(Display::TableCell),
+                    "flex" => Ok(Display::Flex),
+                    "grid" => Ok(Display::Grid),
                     "none" => Ok(Display::None),
                     _ => Err(format!("invalid display mode \"{}\"", kw)),
                 }
@@ -146,180 +397,2172 @@ impl TryFrom<&Value> for Display {
     }
 }
 
-/// Computed style values
-#[derive(Clone, PartialEq, Debug)]
-pub struct Style {
-    // layout mode
-    pub display: Display,
+impl TryFrom<&Value<'_>> for FlexDirection {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "row" => Ok(FlexDirection::Row),
+                    "row-reverse" => Ok(FlexDirection::RowReverse),
+                    "column" => Ok(FlexDirection::Column),
+                    "column-reverse" => Ok(FlexDirection::ColumnReverse),
+                    _ => Err(format!("invalid flex direction \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected flex direction but found {}", v)),
+        }
+    }
+}
 
-    // box colors
-    pub background_color: Color,
-    pub border_color: Color,
+impl TryFrom<&Value<'_>> for FlexWrap {
+    type Error = String;
 
-    // content dimensions (None ~ auto)
-    pub width: Automatic<Pixels>,
-    pub height: Automatic<Pixels>,
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "nowrap" => Ok(FlexWrap::NoWrap),
+                    "wrap" => Ok(FlexWrap::Wrap),
+                    "wrap-reverse" => Ok(FlexWrap::WrapReverse),
+                    _ => Err(format!("invalid flex wrapping \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected flex wrapping but found {}", v)),
+        }
+    }
+}
 
-    // content edge in pixels (None ~ auto)
-    //pub content: Edge<Automatic<f32>>,
+impl TryFrom<&Value<'_>> for JustifyContent {
+    type Error = String;
 
-    // margin edge in pixels (None ~ auto)
-    pub margin: Edge<Automatic<Pixels>>,
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "flex-start" | "start" => Ok(JustifyContent::FlexStart),
+                    "flex-end" | "end" => Ok(JustifyContent::FlexEnd),
+                    "center" => Ok(JustifyContent::Center),
+                    "space-between" => Ok(JustifyContent::SpaceBetween),
+                    "space-around" => Ok(JustifyContent::SpaceAround),
+                    "space-evenly" => Ok(JustifyContent::SpaceEvenly),
+                    _ => Err(format!("invalid content distribution \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected content distribution but found {}", v)),
+        }
+    }
+}
 
-    // padding edge in pixels
-    pub padding: Edge<Pixels>,
+impl TryFrom<&Value<'_>> for AlignContent {
+    type Error = String;
 
-    // border edge in pixels
-    pub border: Edge<Pixels>,
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "stretch" | "normal" => Ok(AlignContent::Stretch),
+                    "flex-start" | "start" => Ok(AlignContent::FlexStart),
+                    "flex-end" | "end" => Ok(AlignContent::FlexEnd),
+                    "center" => Ok(AlignContent::Center),
+                    "space-between" => Ok(AlignContent::SpaceBetween),
+                    "space-around" => Ok(AlignContent::SpaceAround),
+                    "space-evenly" => Ok(AlignContent::SpaceEvenly),
+                    _ => Err(format!("invalid content distribution \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected content distribution but found {}", v)),
+        }
+    }
 }
 
-impl Default for Style {
-    fn default() -> Self {
-        Style {
-            display: Display::default(),
+impl TryFrom<&Value<'_>> for AlignItems {
+    type Error = String;
 
-            background_color: Color::default(),
-            border_color: Color::default(),
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "stretch" | "normal" => Ok(AlignItems::Stretch),
+                    // Without text there is no baseline, so fall back to the start edge.
+                    "flex-start" | "start" | "baseline" => Ok(AlignItems::FlexStart),
+                    "flex-end" | "end" => Ok(AlignItems::FlexEnd),
+                    "center" => Ok(AlignItems::Center),
+                    _ => Err(format!("invalid item alignment \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected item alignment but found {}", v)),
+        }
+    }
+}
 
-            width: Automatic::Auto,
-            height: Automatic::Auto,
+/// The inline base direction, which orders the start and end of a line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
 
-            margin: Edge {
-                left: Automatic::Given(0.0),
-                right: Automatic::Given(0.0),
-                top: Automatic::Given(0.0),
-                bottom: Automatic::Given(0.0),
-            },
+/// The orientation of lines, and the direction in which blocks are stacked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum WritingMode {
+    #[default]
+    HorizontalTb,
+    VerticalRl,
+    VerticalLr,
+}
 
-            padding: Edge::default(),
+impl TryFrom<&Value<'_>> for Direction {
+    type Error = String;
 
-            border: Edge::default(),
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "ltr" => Ok(Direction::Ltr),
+                    "rtl" => Ok(Direction::Rtl),
+                    _ => Err(format!("invalid direction \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected direction but found {}", v)),
         }
     }
 }
 
-/// Apply a stylesheet to an entire DOM tree, returning a StyledNode tree.
-///
-/// This finds only the specified values at the moment. Eventually it should be extended to find the
-/// computed values too, including inherited values.
-pub fn style_tree<'a>(root: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
-    StyledNode {
-        node: root,
-        specified: match root.node_type {
-            NodeType::Element(ref elem) => specified_values(elem, stylesheet),
-            NodeType::Text(_) => Style::default(),
-        },
-        children: root.children.iter().map(|child| style_tree(child, stylesheet)).collect(),
+impl TryFrom<&Value<'_>> for WritingMode {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "horizontal-tb" => Ok(WritingMode::HorizontalTb),
+                    "vertical-rl" => Ok(WritingMode::VerticalRl),
+                    "vertical-lr" => Ok(WritingMode::VerticalLr),
+                    _ => Err(format!("invalid writing mode \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected writing mode but found {}", v)),
+        }
     }
 }
 
-/// Apply styles to a single element, returning the specified styles.
-///
-/// To do: Allow multiple UA/author/user stylesheets, and implement the cascade.
-fn specified_values(elem: &ElementData, stylesheet: &Stylesheet) -> Style {
-    let mut style = Style::default();
-    let mut rules = matching_rules(elem, stylesheet);
-
-    // Go through the rules from lowest to highest specificity.
-    rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
-    for (_, rule) in rules {
-        for declaration in &rule.declarations {
-            let property = declaration.name.as_str();
-            let value = &declaration.value;
-            match property {
-                "display" => { style.display = value.try_into().expect(property); },
-
-                "width" => { style.width = value.try_into().expect(property); },
-                "height" => { style.height = value.try_into().expect(property); },
-
-                "background-color" => { style.background_color = value.try_into().expect(property); },
-                "border-color" => { style.border_color = value.try_into().expect(property); },
-
-                "margin-left" => { style.margin.left = value.try_into().expect(property); },
-                "margin-right" => { style.margin.right = value.try_into().expect(property); },
-                "margin-top" => { style.margin.top = value.try_into().expect(property); },
-                "margin-bottom" => { style.margin.bottom = value.try_into().expect(property); },
-                "margin" => {
-                    let specified = value.try_into().expect(property);
-                    style.margin.left = specified;
-                    style.margin.right = specified;
-                    style.margin.top = specified;
-                    style.margin.bottom = specified;
-                },
+/// The kind of marker of a list item.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ListStyleType {
+    /// A filled circle.
+    #[default]
+    Disc,
+    /// The item's number, counting from 1, followed by a period.
+    Decimal,
+    /// No marker.
+    None,
+}
 
-                "padding-left" => { style.padding.left = value.try_into().expect(property); },
-                "padding-right" => { style.padding.right = value.try_into().expect(property); },
-                "padding-top" => { style.padding.top = value.try_into().expect(property); },
-                "padding-bottom" => { style.padding.bottom = value.try_into().expect(property); },
-                "padding" => {
-                    let specified = value.try_into().expect(property);
-                    style.padding.left = specified;
-                    style.padding.right = specified;
-                    style.padding.top = specified;
-                    style.padding.bottom = specified;
-                },
+impl TryFrom<&Value<'_>> for ListStyleType {
+    type Error = String;
 
-                "border-left-width" => { style.border.left = value.try_into().expect(property); },
-                "border-right-width" => { style.border.right = value.try_into().expect(property); },
-                "border-top-width" => { style.border.top = value.try_into().expect(property); },
-                "border-bottom-width" => { style.border.bottom = value.try_into().expect(property); },
-                "border-width" => {
-                    let specified = value.try_into().expect(property);
-                    style.border.left = specified;
-                    style.border.right = specified;
-                    style.border.top = specified;
-                    style.border.bottom = specified;
-                },
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "disc" => Ok(ListStyleType::Disc),
+                    "decimal" => Ok(ListStyleType::Decimal),
+                    "none" => Ok(ListStyleType::None),
+                    _ => Err(format!("invalid list style type \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected list style type but found {}", v)),
+        }
+    }
+}
+
+impl ListStyleType {
+    /// The text of a counter with the value `value` in this style, as `counter()` generates it.
+    pub fn counter_text(self, value: i32) -> String {
+        match self {
+            ListStyleType::Disc => "\u{2022}".to_string(),
+            ListStyleType::Decimal => value.to_string(),
+            ListStyleType::None => String::new(),
+        }
+    }
+}
 
-                _ => { /* XXX: Ignore any unsupported styling property! */ }
+/// Whether the box of an element is painted. Unlike `display: none`, a hidden box still takes up
+/// space in the layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    /// The box itself is not painted, though its descendants are if they are visible.
+    Hidden,
+}
+
+impl TryFrom<&Value<'_>> for Visibility {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "visible" => Ok(Visibility::Visible),
+                    // Without table rows and columns to collapse, `collapse` is `hidden`.
+                    "hidden" | "collapse" => Ok(Visibility::Hidden),
+                    _ => Err(format!("invalid visibility \"{}\"", kw)),
+                }
             }
+            _ => Err(format!("expected visibility but found {}", v)),
         }
     }
-    style
 }
 
-/// A single CSS rule and the specificity of its most specific matching selector.
-type MatchedRule<'a> = (Specificity, &'a Rule);
+/// Whether the box of an element can be the target of pointer events, and be hit tested.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PointerEvents {
+    #[default]
+    Auto,
+    /// The box is never hit, though its descendants can be.
+    None,
+}
+
+impl TryFrom<&Value<'_>> for PointerEvents {
+    type Error = String;
 
-/// Find all CSS rules that match the given element.
-fn matching_rules<'a>(elem: &ElementData, stylesheet: &'a Stylesheet) -> Vec<MatchedRule<'a>> {
-    // For now, we just do a linear scan of all the rules.  For large
-    // documents, it would be more efficient to store the rules in hash tables
-    // based on tag name, id, class, etc.
-    stylesheet.rules.iter().filter_map(|rule| match_rule(elem, rule)).collect()
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "auto" => Ok(PointerEvents::Auto),
+                    "none" => Ok(PointerEvents::None),
+                    _ => Err(format!("invalid pointer events \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected pointer events but found {}", v)),
+        }
+    }
 }
 
-/// If `rule` matches `elem`, return a `MatchedRule`. Otherwise return `None`.
-fn match_rule<'a>(elem: &ElementData, rule: &'a Rule) -> Option<MatchedRule<'a>> {
-    // Find the first (most specific) matching selector.
-    rule.selectors.iter().find(|selector| matches(elem, *selector))
-        .map(|selector| (selector.specificity(), rule))
+/// The mouse cursor to show over a box.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Cursor {
+    /// Chosen by the content under the pointer: `text` over text, and `default` elsewhere.
+    #[default]
+    Auto,
+    /// The platform's default cursor, usually an arrow.
+    Default,
+    /// No cursor.
+    None,
+    /// A link.
+    Pointer,
+    Text,
+    Wait,
+    Progress,
+    Help,
+    Crosshair,
+    Move,
+    NotAllowed,
+    Grab,
+    Grabbing,
+    /// Resizing a column, or anything horizontally.
+    ColResize,
+    /// Resizing a row, or anything vertically.
+    RowResize,
 }
 
-/// Selector matching:
-fn matches(elem: &ElementData, selector: &Selector) -> bool {
-    match *selector {
-        Selector::Simple(ref simple_selector) => matches_simple_selector(elem, simple_selector)
+impl TryFrom<&Value<'_>> for Cursor {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => {
+                match &**kw {
+                    "auto" => Ok(Cursor::Auto),
+                    "default" => Ok(Cursor::Default),
+                    "none" => Ok(Cursor::None),
+                    "pointer" => Ok(Cursor::Pointer),
+                    "text" => Ok(Cursor::Text),
+                    "wait" => Ok(Cursor::Wait),
+                    "progress" => Ok(Cursor::Progress),
+                    "help" => Ok(Cursor::Help),
+                    "crosshair" => Ok(Cursor::Crosshair),
+                    "move" => Ok(Cursor::Move),
+                    "not-allowed" => Ok(Cursor::NotAllowed),
+                    "grab" => Ok(Cursor::Grab),
+                    "grabbing" => Ok(Cursor::Grabbing),
+                    "col-resize" | "ew-resize" => Ok(Cursor::ColResize),
+                    "row-resize" | "ns-resize" => Ok(Cursor::RowResize),
+                    _ => Err(format!("invalid cursor \"{}\"", kw)),
+                }
+            }
+            _ => Err(format!("expected cursor but found {}", v)),
+        }
     }
 }
 
-fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
-    // Check type selector
-    if selector.tag.iter().any(|name| elem.tag != *name) {
-        return false
+/// One shadow of `box-shadow`: a blurred copy of the border box, painted beneath the box.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoxShadow {
+    pub offset_x: Pixels,
+    pub offset_y: Pixels,
+    /// The blur radius. The edges of the shadow fade out over twice this distance.
+    pub blur: Pixels,
+    /// How far the shadow extends past the border box on every side, before blurring.
+    pub spread: Pixels,
+    pub color: Color,
+}
+
+impl TryFrom<&[Value<'_>]> for BoxShadow {
+    type Error = String;
+
+    fn try_from(values: &[Value]) -> Result<Self, Self::Error> {
+        // `<offset-x> <offset-y> [<blur> [<spread>]]` and a color, before or after the lengths.
+        let mut lengths = Vec::new();
+        let mut color = None;
+        for value in values {
+            match value {
+                Value::ColorValue(c) if color.is_none() => color = Some(*c),
+                Value::Keyword(kw) if kw == "inset" => {
+                    return Err(String::from("inset box shadows are not supported"));
+                }
+                _ => lengths.push(Pixels::try_from(value)?),
+            }
+        }
+        let (offset_x, offset_y, blur, spread) = match lengths[..] {
+            [x, y] => (x, y, 0.0, 0.0),
+            [x, y, blur] => (x, y, blur, 0.0),
+            [x, y, blur, spread] => (x, y, blur, spread),
+            _ => {
+                let value = Value::List(values.to_vec());
+                return Err(format!("expected two to four lengths in box shadow {}", value));
+            }
+        };
+        if blur < 0.0 {
+            return Err(format!("negative blur radius {}px", blur));
+        }
+        // Without a `color` property, a shadow without a color is black.
+        let color = color.unwrap_or(Color { r: 0, g: 0, b: 0, a: 255 });
+        Ok(BoxShadow { offset_x, offset_y, blur, spread, color })
     }
+}
 
-    // Check ID selector
-    if selector.id.iter().any(|id| elem.id() != Some(id)) {
-        return false;
+impl TryFrom<&Value<'_>> for Vec<BoxShadow> {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) if kw == "none" => Ok(Vec::new()),
+            Value::List(values) => {
+                values.split(|value| *value == Value::Delimiter(','))
+                    .map(BoxShadow::try_from)
+                    .collect()
+            }
+            _ => Err(format!("expected box shadow but found {}", v)),
+        }
     }
+}
 
-    // Check class selectors
-    let elem_classes = elem.classes();
-    if selector.class.iter().any(|class| !elem_classes.contains(&**class)) {
-        return false;
+impl TryFrom<&Value<'_>> for Transform {
+    type Error = String;
+
+    /// Parse a list of transform functions. The last one applies first, around the center of
+    /// the border box.
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        let functions = match v {
+            Value::Keyword(kw) if kw == "none" => return Ok(Transform::IDENTITY),
+            Value::List(values) => values.as_slice(),
+            _ => std::slice::from_ref(v),
+        };
+        let mut transform = Transform::IDENTITY;
+        for function in functions {
+            let (name, arguments) = match function {
+                Value::Function(name, arguments) => (name, arguments),
+                _ => return Err(format!("expected transform function but found {}", function)),
+            };
+            let arguments: Vec<&Value> = arguments.iter()
+                .filter(|argument| **argument != Value::Delimiter(','))
+                .collect();
+            let angle = |v: &Value| match v {
+                Value::Length(deg, Unit::Deg) => Ok(*deg),
+                Value::Number(n) if *n == 0.0 => Ok(0.0),
+                _ => Err(format!("expected angle but found {}", v)),
+            };
+            let next = match (&**name, arguments.as_slice()) {
+                ("translate", [x]) => Transform::translation(Pixels::try_from(*x)?, 0.0),
+                ("translate", [x, y]) => {
+                    Transform::translation(Pixels::try_from(*x)?, Pixels::try_from(*y)?)
+                }
+                ("translateX", [x]) => Transform::translation(Pixels::try_from(*x)?, 0.0),
+                ("translateY", [y]) => Transform::translation(0.0, Pixels::try_from(*y)?),
+                ("scale", [s]) => Transform::scale(number(s)?, number(s)?),
+                ("scale", [x, y]) => Transform::scale(number(x)?, number(y)?),
+                ("scaleX", [x]) => Transform::scale(number(x)?, 1.0),
+                ("scaleY", [y]) => Transform::scale(1.0, number(y)?),
+                ("rotate", [a]) => Transform::rotation(angle(a)?),
+                ("matrix", [a, b, c, d, e, f]) => Transform {
+                    a: number(a)?,
+                    b: number(b)?,
+                    c: number(c)?,
+                    d: number(d)?,
+                    e: number(e)?,
+                    f: number(f)?,
+                },
+                _ => return Err(format!("unsupported transform function {}", function)),
+            };
+            transform = next.then(transform);
+        }
+        Ok(transform)
     }
+}
 
-    // We didn't find any non-matching selector components.
-    true
+/// An image painted over the background color of a box, across its border box.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum BackgroundImage {
+    #[default]
+    None,
+    /// `linear-gradient(...)`: colors that change along a line through the center of the box.
+    LinearGradient { direction: GradientDirection, stops: Vec<ColorStop> },
+    /// `radial-gradient(...)`: colors that change with the distance from the center of the box.
+    RadialGradient { circle: bool, extent: RadialExtent, stops: Vec<ColorStop> },
+}
+
+/// The direction of a linear gradient.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GradientDirection {
+    /// An angle in degrees, clockwise from pointing up (`to top`).
+    Angle(f32),
+    /// Towards a corner of the box, such as `to bottom right`. The angle depends on the shape of
+    /// the box: the other two corners are halfway along the gradient.
+    Corner { right: bool, bottom: bool },
+}
+
+/// How far a radial gradient extends from the center of the box before its last color stop.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RadialExtent {
+    ClosestSide,
+    ClosestCorner,
+    FarthestSide,
+    #[default]
+    FarthestCorner,
+}
+
+/// A color of a gradient, and where along the gradient it is (as a length or a percentage of
+/// the gradient's length), if given.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorStop {
+    pub color: Color,
+    pub position: Option<LengthPercentage>,
+}
+
+impl TryFrom<&[Value<'_>]> for ColorStop {
+    type Error = String;
+
+    fn try_from(values: &[Value]) -> Result<Self, Self::Error> {
+        let position = |v: &Value| match v {
+            Value::Length(px, Unit::Px) => Ok(LengthPercentage::Length(*px)),
+            Value::Percentage(p) => Ok(LengthPercentage::Percentage(*p)),
+            Value::Number(n) if *n == 0.0 => Ok(LengthPercentage::Length(0.0)),
+            Value::Function(name, arguments) if is_calc(name) => LengthPercentage::calc(arguments),
+            _ => Err(format!("expected color stop position but found {}", v)),
+        };
+        match values {
+            [color] => Ok(ColorStop { color: color.try_into()?, position: None }),
+            [color, at] => {
+                Ok(ColorStop { color: color.try_into()?, position: Some(position(at)?) })
+            }
+            _ => Err(format!("expected color stop but found {}", Value::List(values.to_vec()))),
+        }
+    }
+}
+
+impl TryFrom<&[Value<'_>]> for GradientDirection {
+    type Error = String;
+
+    fn try_from(values: &[Value]) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid gradient direction {}", Value::List(values.to_vec()));
+        match values {
+            [Value::Length(deg, Unit::Deg)] => Ok(GradientDirection::Angle(*deg)),
+            [Value::Number(n)] if *n == 0.0 => Ok(GradientDirection::Angle(0.0)),
+            [Value::Keyword(to), sides @ ..] if to == "to" && sides.len() <= 2 => {
+                // Whether the direction is to the right or left, and to the bottom or top.
+                let (mut horizontal, mut vertical) = (None, None);
+                for side in sides {
+                    match side {
+                        Value::Keyword(kw) if (kw == "left" || kw == "right")
+                            && horizontal.is_none() => horizontal = Some(kw == "right"),
+                        Value::Keyword(kw) if (kw == "top" || kw == "bottom")
+                            && vertical.is_none() => vertical = Some(kw == "bottom"),
+                        _ => return Err(invalid()),
+                    }
+                }
+                match (horizontal, vertical) {
+                    (Some(right), Some(bottom)) => Ok(GradientDirection::Corner { right, bottom }),
+                    (Some(right), None) => {
+                        Ok(GradientDirection::Angle(if right { 90.0 } else { 270.0 }))
+                    }
+                    (None, Some(bottom)) => {
+                        Ok(GradientDirection::Angle(if bottom { 180.0 } else { 0.0 }))
+                    }
+                    (None, None) => Err(invalid()),
+                }
+            }
+            _ => Err(format!("expected gradient direction but found {}",
+                             Value::List(values.to_vec()))),
+        }
+    }
+}
+
+impl BackgroundImage {
+    /// Parse the arguments of `linear-gradient()` or `radial-gradient()`.
+    fn gradient(name: &str, arguments: &[Value]) -> Result<BackgroundImage, String> {
+        let mut groups: Vec<&[Value]> = arguments.split(|v| *v == Value::Delimiter(','))
+            .collect();
+        // The first argument is the direction or shape, unless it is a color stop.
+        let first = match groups.first() {
+            Some(first) if ColorStop::try_from(*first).is_err() => Some(groups.remove(0)),
+            _ => None,
+        };
+        let stops = groups.into_iter().map(ColorStop::try_from).collect::<Result<Vec<_>, _>>()?;
+        if stops.len() < 2 {
+            return Err(format!("{}() needs at least two color stops", name));
+        }
+        match name {
+            "linear-gradient" => {
+                let direction = match first {
+                    Some(first) => first.try_into()?,
+                    None => GradientDirection::Angle(180.0),
+                };
+                Ok(BackgroundImage::LinearGradient { direction, stops })
+            }
+            "radial-gradient" => {
+                let (mut circle, mut extent) = (false, RadialExtent::default());
+                for value in first.unwrap_or(&[]) {
+                    match value {
+                        Value::Keyword(kw) => match &**kw {
+                            "circle" => circle = true,
+                            "ellipse" => circle = false,
+                            "closest-side" => extent = RadialExtent::ClosestSide,
+                            "closest-corner" => extent = RadialExtent::ClosestCorner,
+                            "farthest-side" => extent = RadialExtent::FarthestSide,
+                            "farthest-corner" => extent = RadialExtent::FarthestCorner,
+                            "at" => {
+                                return Err(String::from("gradient positions are not supported"));
+                            }
+                            _ => return Err(format!("invalid radial gradient shape \"{}\"", kw)),
+                        },
+                        _ => return Err(format!("expected radial gradient shape but found {}",
+                                                value)),
+                    }
+                }
+                Ok(BackgroundImage::RadialGradient { circle, extent, stops })
+            }
+            _ => Err(format!("unsupported image {}()", name)),
+        }
+    }
+}
+
+impl TryFrom<&Value<'_>> for BackgroundImage {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) if kw == "none" => Ok(BackgroundImage::None),
+            Value::Function(name, arguments) => BackgroundImage::gradient(name, arguments),
+            _ => Err(format!("expected image but found {}", v)),
+        }
+    }
+}
+
+/// The content of a `::before` or `::after` pseudo-element.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub enum Content {
+    /// No content, so no pseudo-element is generated. `normal` computes to this too.
+    #[default]
+    None,
+    /// Text, which is the concatenation of the quoted strings of the value.
+    Text(String),
+    /// Strings and counters, and the text they generate, which is only known once the counters
+    /// of the whole document are (and is empty until then).
+    Counters(Vec<ContentItem>, String),
+}
+
+impl Content {
+    /// The generated text, if any.
+    pub fn text(&self) -> Option<&str> {
+        match *self {
+            Content::None => None,
+            Content::Text(ref text) | Content::Counters(_, ref text) => Some(text),
+        }
+    }
+}
+
+/// A part of the value of `content`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ContentItem {
+    /// A quoted string.
+    String(String),
+    /// `counter(name, style)`: the value of the innermost counter of the name.
+    Counter(String, ListStyleType),
+    /// `counters(name, separator, style)`: the values of all the counters of the name in scope,
+    /// from the outermost in, joined by the separator.
+    Counters(String, String, ListStyleType),
+}
+
+impl TryFrom<&Value<'_>> for ContentItem {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::String(s) => Ok(ContentItem::String(s.to_string())),
+            Value::Function(function, arguments) => {
+                let arguments: Vec<&[Value]> =
+                    arguments.split(|argument| *argument == Value::Delimiter(',')).collect();
+                let style = |argument: Option<&&[Value]>| match argument {
+                    None => Ok(ListStyleType::Decimal),
+                    Some([style]) => ListStyleType::try_from(style),
+                    Some(_) => Err(format!("invalid arguments of {}()", function)),
+                };
+                match (&**function, &arguments[..]) {
+                    ("counter", [[Value::Keyword(name)], ..]) if arguments.len() <= 2 => {
+                        Ok(ContentItem::Counter(name.to_string(), style(arguments.get(1))?))
+                    }
+                    ("counters", [[Value::Keyword(name)], [Value::String(separator)], ..])
+                        if arguments.len() <= 3 => {
+                        Ok(ContentItem::Counters(name.to_string(), separator.to_string(),
+                                                 style(arguments.get(2))?))
+                    }
+                    ("counter", _) | ("counters", _) => {
+                        Err(format!("invalid arguments of {}()", function))
+                    }
+                    _ => Err(format!("unsupported function {}()", function)),
+                }
+            }
+            _ => Err(format!("expected a string or counter but found {}", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value<'_>> for Content {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        let values = match v {
+            Value::Keyword(kw) if kw == "none" || kw == "normal" => return Ok(Content::None),
+            Value::List(values) => values.as_slice(),
+            _ => std::slice::from_ref(v),
+        };
+        let items = values.iter().map(ContentItem::try_from).collect::<Result<Vec<_>, _>>()?;
+        let mut text = String::new();
+        for item in &items {
+            match item {
+                ContentItem::String(s) => text.push_str(s),
+                _ => return Ok(Content::Counters(items, String::new())),
+            }
+        }
+        Ok(Content::Text(text))
+    }
+}
+
+/// A change to a counter by `counter-reset` or `counter-increment`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CounterChange {
+    pub name: String,
+    /// The value to reset the counter to, or to add to it. Without one, `counter-reset` resets
+    /// the counter to 0, and `counter-increment` adds 1 to it.
+    pub value: Option<i32>,
+}
+
+impl TryFrom<&Value<'_>> for Vec<CounterChange> {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        let values = match v {
+            Value::Keyword(kw) if kw == "none" => return Ok(Vec::new()),
+            Value::List(values) => values.as_slice(),
+            _ => std::slice::from_ref(v),
+        };
+        let mut changes: Vec<CounterChange> = Vec::new();
+        for value in values {
+            match (value, changes.last_mut()) {
+                (Value::Keyword(name), _) if name != "none" => {
+                    changes.push(CounterChange { name: name.to_string(), value: None });
+                }
+                (&Value::Number(n), Some(change)) if change.value.is_none() && n.fract() == 0.0 => {
+                    change.value = Some(n as i32);
+                }
+                _ => return Err(format!("expected a counter name or integer but found {}", value)),
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// The size of a grid track.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Track {
+    /// A fixed size in pixels.
+    Fixed(Pixels),
+    /// A share of the space left over by the other tracks.
+    Fraction(f32),
+    /// The size of the largest item in the track.
+    Auto,
+}
+
+impl TryFrom<&Value<'_>> for Track {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Length(px, Unit::Px) => Ok(Track::Fixed(*px)),
+            Value::Length(fr, Unit::Fr) => Ok(Track::Fraction(*fr)),
+            Value::Number(n) if *n == 0.0 => Ok(Track::Fixed(0.0)),
+            Value::Keyword(kw) if kw == "auto" => Ok(Track::Auto),
+            _ => Err(format!("expected track size but found {}", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value<'_>> for Vec<Track> {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::List(values) => values.iter().map(Track::try_from).collect(),
+            Value::Keyword(kw) if kw == "none" => Ok(Vec::new()),
+            _ => Ok(vec![v.try_into()?]),
+        }
+    }
+}
+
+/// One edge of a grid item's placement.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GridLine {
+    /// Placed automatically.
+    #[default]
+    Auto,
+    /// A numbered grid line, counting from 1 (or from the end if negative).
+    Line(i32),
+    /// A number of tracks spanned from the other edge.
+    Span(usize),
+}
+
+impl TryFrom<&[Value<'_>]> for GridLine {
+    type Error = String;
+
+    fn try_from(values: &[Value]) -> Result<Self, Self::Error> {
+        match values {
+            [Value::Keyword(kw)] if kw == "auto" => Ok(GridLine::Auto),
+            [Value::Number(n)] if n.fract() == 0.0 && *n != 0.0 => Ok(GridLine::Line(*n as i32)),
+            [Value::Keyword(kw), Value::Number(n)] if kw == "span" => {
+                if n.fract() == 0.0 && *n >= 1.0 {
+                    Ok(GridLine::Span(*n as usize))
+                } else {
+                    Err(format!("invalid grid span {}", n))
+                }
+            }
+            _ => Err(format!("expected grid line but found {}", Value::List(values.to_vec()))),
+        }
+    }
+}
+
+impl TryFrom<&Value<'_>> for GridLine {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::List(values) => values.as_slice().try_into(),
+            _ => std::slice::from_ref(v).try_into(),
+        }
+    }
+}
+
+/// The placement of a grid item along one axis.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GridPlacement {
+    pub start: GridLine,
+    pub end: GridLine,
+}
+
+impl TryFrom<&Value<'_>> for GridPlacement {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        let values = match v {
+            Value::List(values) => values.as_slice(),
+            _ => std::slice::from_ref(v),
+        };
+        // `<start> [ / <end> ]`
+        match values.iter().position(|v| *v == Value::Delimiter('/')) {
+            Some(i) => Ok(GridPlacement {
+                start: values[..i].try_into()?,
+                end: values[i + 1..].try_into()?,
+            }),
+            None => Ok(GridPlacement { start: values.try_into()?, end: GridLine::Auto }),
+        }
+    }
+}
+
+/// Parse a unitless number.
+fn number(v: &Value) -> Result<f32, String> {
+    match v {
+        Value::Number(n) => Ok(*n),
+        _ => Err(format!("expected number but found {}", v)),
+    }
+}
+
+/// Declare the longhand properties and generate the `Style` struct from them.
+///
+/// Each property is listed with its name, the `Style` method returning its computed value, the
+/// type and initial value of that value, and optionally the function parsing it from a specified
+/// value (by default, its `TryFrom<&Value>` implementation). Properties whose values are not
+/// `Copy` are listed under `by_ref`, and their accessors return a reference.
+macro_rules! longhands {
+    (
+        by_value { $($name:literal => $field:ident: $ty:ty = $initial:expr $(, $parse:path)?;)* }
+        by_ref { $($ref_name:literal => $ref_field:ident: $ref_ty:ty = $ref_initial:expr;)* }
+    ) => {
+        /// Computed style values
+        #[derive(Clone, PartialEq, Debug)]
+        pub struct Style {
+            $($field: $ty,)*
+            $($ref_field: $ref_ty,)*
+            custom_properties: Arc<CustomProperties>,
+        }
+
+        impl Default for Style {
+            fn default() -> Self {
+                Style {
+                    $($field: $initial,)*
+                    $($ref_field: $ref_initial,)*
+                    custom_properties: Arc::default(),
+                }
+            }
+        }
+
+        impl Style {
+            $(
+                #[doc = concat!("Computed value of `", $name, "`.")]
+                pub fn $field(&self) -> $ty {
+                    self.$field
+                }
+            )*
+
+            $(
+                #[doc = concat!("Computed value of `", $ref_name, "`.")]
+                pub fn $ref_field(&self) -> &$ref_ty {
+                    &self.$ref_field
+                }
+            )*
+
+            /// The properties whose values differ from their initial values, in the order above,
+            /// with the values formatted for debugging.
+            pub fn non_initial_values(&self) -> Vec<(&'static str, String)> {
+                let initial = Style::default();
+                let mut values = Vec::new();
+                $(
+                    if self.$field != initial.$field {
+                        values.push(($name, format!("{:?}", self.$field)));
+                    }
+                )*
+                $(
+                    if self.$ref_field != initial.$ref_field {
+                        values.push(($ref_name, format!("{:?}", self.$ref_field)));
+                    }
+                )*
+                values
+            }
+
+            /// Set a longhand property from its specified value, or return `None` if there is no
+            /// such longhand property.
+            fn set_longhand(&mut self, name: &str, value: &Value) -> Option<Result<(), String>> {
+                match name {
+                    $($name => Some(longhands!(@parse value $(, $parse)?)
+                        .map(|computed| self.$field = computed)),)*
+                    $($ref_name => Some(value.try_into()
+                        .map(|computed| self.$ref_field = computed)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+    (@parse $value:expr) => { $value.try_into() };
+    (@parse $value:expr, $parse:path) => { $parse($value) };
+}
+
+longhands! {
+    by_value {
+        // layout mode
+        "display" => display: Display = Display::Inline;
+
+        // whether the box is painted, while still taking up space
+        "visibility" => visibility: Visibility = Visibility::Visible;
+
+        // writing modes, which map logical properties to physical ones
+        "direction" => direction: Direction = Direction::Ltr;
+        "writing-mode" => writing_mode: WritingMode = WritingMode::HorizontalTb;
+
+        // list item markers
+        "list-style-type" => list_style_type: ListStyleType = ListStyleType::Disc;
+
+        // interaction with the pointer
+        "pointer-events" => pointer_events: PointerEvents = PointerEvents::Auto;
+        "cursor" => cursor: Cursor = Cursor::Auto;
+
+        // transform of the box and its descendants when painted
+        "transform" => transform: Transform = Transform::IDENTITY;
+
+        // box colors
+        "background-color" => background_color: Color = Color::default();
+        "border-color" => border_color: Color = Color::default();
+
+        // content dimensions
+        "width" => width: Automatic<LengthPercentage> = Automatic::Auto;
+        "height" => height: Automatic<LengthPercentage> = Automatic::Auto;
+
+        // margin edge, with percentages of the width of the containing block
+        "margin-left" => margin_left: Automatic<LengthPercentage> =
+            Automatic::Given(LengthPercentage::Length(0.0));
+        "margin-right" => margin_right: Automatic<LengthPercentage> =
+            Automatic::Given(LengthPercentage::Length(0.0));
+        "margin-top" => margin_top: Automatic<LengthPercentage> =
+            Automatic::Given(LengthPercentage::Length(0.0));
+        "margin-bottom" => margin_bottom: Automatic<LengthPercentage> =
+            Automatic::Given(LengthPercentage::Length(0.0));
+
+        // padding edge in pixels
+        "padding-left" => padding_left: Pixels = 0.0;
+        "padding-right" => padding_right: Pixels = 0.0;
+        "padding-top" => padding_top: Pixels = 0.0;
+        "padding-bottom" => padding_bottom: Pixels = 0.0;
+
+        // border edge in pixels
+        "border-left-width" => border_left_width: Pixels = 0.0;
+        "border-right-width" => border_right_width: Pixels = 0.0;
+        "border-top-width" => border_top_width: Pixels = 0.0;
+        "border-bottom-width" => border_bottom_width: Pixels = 0.0;
+
+        // spacing between table cells in pixels
+        "border-spacing" => border_spacing: Pixels = 0.0;
+
+        // flex container properties
+        "flex-direction" => flex_direction: FlexDirection = FlexDirection::default();
+        "flex-wrap" => flex_wrap: FlexWrap = FlexWrap::default();
+        "justify-content" => justify_content: JustifyContent = JustifyContent::default();
+        "align-content" => align_content: AlignContent = AlignContent::default();
+        "align-items" => align_items: AlignItems = AlignItems::default();
+
+        // flex item properties
+        "flex-grow" => flex_grow: f32 = 0.0, number;
+        "flex-shrink" => flex_shrink: f32 = 1.0, number;
+        "flex-basis" => flex_basis: Automatic<Pixels> = Automatic::Auto;
+
+        // grid container properties
+        "column-gap" => column_gap: Pixels = 0.0;
+        "row-gap" => row_gap: Pixels = 0.0;
+
+        // grid item properties
+        "grid-column-start" => grid_column_start: GridLine = GridLine::Auto;
+        "grid-column-end" => grid_column_end: GridLine = GridLine::Auto;
+        "grid-row-start" => grid_row_start: GridLine = GridLine::Auto;
+        "grid-row-end" => grid_row_end: GridLine = GridLine::Auto;
+    }
+    by_ref {
+        // generated content, and the counters it can show
+        "content" => content: Content = Content::None;
+        "counter-reset" => counter_reset: Vec<CounterChange> = Vec::new();
+        "counter-increment" => counter_increment: Vec<CounterChange> = Vec::new();
+
+        // background image, over the background color
+        "background-image" => background_image: BackgroundImage = BackgroundImage::None;
+
+        // box shadows, from front to back
+        "box-shadow" => box_shadow: Vec<BoxShadow> = Vec::new();
+
+        // grid container properties
+        "grid-template-columns" => grid_template_columns: Vec<Track> = Vec::new();
+        "grid-template-rows" => grid_template_rows: Vec<Track> = Vec::new();
+    }
+}
+
+/// Computed values of custom properties, by name (with the leading `--`).
+pub type CustomProperties = BTreeMap<String, Value<'static>>;
+
+impl Style {
+    /// The style of an element (or text) before any declarations apply: the initial values of
+    /// every property, except the inherited ones (`visibility`, `direction`, `writing-mode`,
+    /// `list-style-type`, `pointer-events`, `cursor`, and custom properties), which take the
+    /// values of the parent's style.
+    fn inherited_from(parent: &Style) -> Style {
+        Style {
+            visibility: parent.visibility,
+            direction: parent.direction,
+            writing_mode: parent.writing_mode,
+            list_style_type: parent.list_style_type,
+            pointer_events: parent.pointer_events,
+            cursor: parent.cursor,
+            custom_properties: parent.custom_properties.clone(),
+            ..Style::default()
+        }
+    }
+
+    /// Computed value of the custom property `name` (e.g., `--accent`), with the `var()`
+    /// references in it substituted, or `None` if it is not defined or its value is invalid.
+    pub fn custom_property(&self, name: &str) -> Option<&Value<'static>> {
+        self.custom_properties.get(name)
+    }
+
+    /// Computed values of every custom property defined for the element, by name.
+    pub fn custom_properties(&self) -> &CustomProperties {
+        &self.custom_properties
+    }
+
+    /// Computed values of the `grid-column-start` and `grid-column-end` properties.
+    pub fn grid_column(&self) -> GridPlacement {
+        GridPlacement { start: self.grid_column_start, end: self.grid_column_end }
+    }
+
+    /// Computed values of the `grid-row-start` and `grid-row-end` properties.
+    pub fn grid_row(&self) -> GridPlacement {
+        GridPlacement { start: self.grid_row_start, end: self.grid_row_end }
+    }
+
+    /// The physical longhands that a logical property sets, given this style's writing mode and
+    /// direction, or `None` if `name` is not a logical property.
+    ///
+    /// A property for one side (e.g., `margin-inline-start`) sets one longhand. A property for
+    /// both sides of an axis (e.g., `padding-block`) sets the start side and then the end side.
+    fn physical_longhands(&self, name: &str) -> Option<Vec<String>> {
+        let vertical = self.writing_mode != WritingMode::HorizontalTb;
+        match name {
+            "inline-size" => return Some(vec![if vertical { "height" } else { "width" }.into()]),
+            "block-size" => return Some(vec![if vertical { "width" } else { "height" }.into()]),
+            _ => {}
+        }
+
+        // `<box>-<axis>[-<side>]`, where borders also end with `-width`.
+        let (name, suffix) = match name.strip_suffix("-width") {
+            Some(name) => (name, "-width"),
+            None => (name, ""),
+        };
+        let mut parts = name.splitn(3, '-');
+        let prefix = parts.next()?;
+        match (prefix, suffix) {
+            ("margin", "") | ("padding", "") | ("border", "-width") => {}
+            _ => return None,
+        }
+        let (start, end) = match (parts.next()?, self.writing_mode, self.direction) {
+            ("inline", WritingMode::HorizontalTb, Direction::Ltr) => ("left", "right"),
+            ("inline", WritingMode::HorizontalTb, Direction::Rtl) => ("right", "left"),
+            ("inline", _, Direction::Ltr) => ("top", "bottom"),
+            ("inline", _, Direction::Rtl) => ("bottom", "top"),
+            ("block", WritingMode::HorizontalTb, _) => ("top", "bottom"),
+            ("block", WritingMode::VerticalRl, _) => ("right", "left"),
+            ("block", WritingMode::VerticalLr, _) => ("left", "right"),
+            _ => return None,
+        };
+        let sides = match parts.next() {
+            Some("start") => vec![start],
+            Some("end") => vec![end],
+            None => vec![start, end],
+            Some(_) => return None,
+        };
+        Some(sides.into_iter().map(|side| format!("{}-{}{}", prefix, side, suffix)).collect())
+    }
+}
+
+/// Apply a stylesheet to an entire DOM tree, returning a StyledNode tree.
+///
+/// This finds only the specified values at the moment. Eventually it should be extended to find the
+/// computed values too. Of the inherited properties, only `visibility`, `direction`,
+/// `writing-mode`, `list-style-type`, `pointer-events`, `cursor`, and custom properties are
+/// supported, and they are inherited as CSS specifies. The `var()` references to custom
+/// properties are substituted in the cascade.
+///
+/// The stylesheet may hold rules from every origin (see `Stylesheet::combine`), which cascade in
+/// the order CSS specifies, `!important` declarations included.
+///
+/// Rules in `@media` rules apply if they match the default `MediaContext`; see
+/// `style_tree_with_media` for others.
+///
+/// With the `parallel` feature, the children of every node are styled in parallel, on threads
+/// with stacks deep enough for trees as deep as `html::MAX_NESTING_DEPTH`. A node is always styled
+/// before its children, and the children are collected in document order, so the result is the
+/// same either way.
+pub fn style_tree(root: &Node, stylesheet: &Stylesheet) -> StyledNode {
+    style_document(root, stylesheet, Matching::default())
+}
+
+/// Apply a stylesheet to an entire DOM tree like `style_tree`, but style every node on the calling
+/// thread, as without the `parallel` feature: for embedders that style on threads of their own.
+#[cfg(feature = "parallel")]
+pub fn style_tree_sequential(root: &Node, stylesheet: &Stylesheet) -> StyledNode {
+    STYLING_SEQUENTIALLY.with(|sequential| sequential.set(true));
+    let styled = style_tree(root, stylesheet);
+    STYLING_SEQUENTIALLY.with(|sequential| sequential.set(false));
+    styled
+}
+
+#[cfg(feature = "parallel")]
+thread_local! {
+    /// Whether `style_tree_sequential` is styling on this thread.
+    static STYLING_SEQUENTIALLY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Apply a stylesheet to an entire DOM tree like `style_tree`, looking up the rules that match
+/// each element in `cache` first, and adding them to it if they are not there.
+pub fn style_tree_cached(root: &Node, stylesheet: &Stylesheet, cache: &MatchCache)
+    -> StyledNode
+{
+    let matching = Matching { cache: Some(cache), ..Matching::default() };
+    style_document(root, stylesheet, matching)
+}
+
+/// Apply a stylesheet to an entire DOM tree like `style_tree_cached`, with elements in the given
+/// states.
+pub fn style_tree_with_states(root: &Node, stylesheet: &Stylesheet, cache: &MatchCache,
+                              states: ElementStates) -> StyledNode {
+    style_tree_with_media(root, stylesheet, cache, states, MediaContext::default())
+}
+
+/// Apply a stylesheet to an entire DOM tree like `style_tree_with_states`, for the given media:
+/// only the rules in `@media` rules that match it apply.
+pub fn style_tree_with_media(root: &Node, stylesheet: &Stylesheet, cache: &MatchCache,
+                             states: ElementStates, media: MediaContext) -> StyledNode {
+    let matching = Matching { cache: Some(cache), states, media };
+    style_document(root, stylesheet, matching)
+}
+
+/// The states of elements that selectors can test.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ElementStates {
+    /// The element with the keyboard focus, which `:focus` matches.
+    pub focused: Option<NodeId>,
+}
+
+/// What selector matching depends on besides the DOM and the stylesheet.
+#[derive(Clone, Copy, Default)]
+struct Matching<'c> {
+    cache: Option<&'c MatchCache>,
+    states: ElementStates,
+    media: MediaContext,
+}
+
+impl Matching<'_> {
+    /// Find all CSS rules that match the element `id`, or its pseudo-element `pseudo`.
+    fn matching_rules<'a>(&self, id: NodeId, elem: &ElementData, stylesheet: &'a Stylesheet,
+                          pseudo: Option<PseudoElement>) -> Vec<MatchedRule<'a>> {
+        let focused = self.states.focused == Some(id);
+        let mut rules = match self.cache {
+            Some(cache) => cache.matching_rules(elem, focused, stylesheet, pseudo),
+            None => matching_rules(elem, focused, stylesheet, pseudo),
+        };
+        rules.retain(|&(_, rule)| rule.matches_media(&self.media));
+        rules
+    }
+}
+
+/// Style the tree of the root node `root`, and generate the text of its counters.
+fn style_document(root: &Node, stylesheet: &Stylesheet, matching: Matching) -> StyledNode {
+    let mut styled = style_subtree(root, stylesheet, &Style::default(), matching);
+    styled.update_counters(stylesheet);
+    styled
+}
+
+/// Style the subtree of a node whose parent has the style `parent`.
+fn style_subtree(root: &Node, stylesheet: &Stylesheet, parent: &Style,
+                 matching: Matching) -> StyledNode {
+    let specified = Arc::new(match root.node_type {
+        NodeType::Element(ref elem) => {
+            specified_values(root.id, elem, stylesheet, parent, matching)
+        }
+        NodeType::Text(_) => Style::inherited_from(parent),
+    });
+    let mut children = style_children(root, stylesheet, &specified, matching);
+    if let NodeType::Element(ref elem) = root.node_type {
+        add_pseudo_elements(root.id, elem, stylesheet, &specified, matching, &mut children);
+    }
+    StyledNode { node: root.id, pseudo: None, children, specified, dirty: false }
+}
+
+/// Generate the `::before` and `::after` children of the element `id` with the style `style`, for
+/// the pseudo-elements whose `content` is not `none`.
+fn add_pseudo_elements(id: NodeId, elem: &ElementData, stylesheet: &Stylesheet, style: &Style,
+                       matching: Matching, children: &mut Vec<StyledNode>) {
+    if !stylesheet.has_pseudo_elements() {
+        return;
+    }
+    for &pseudo in &[PseudoElement::Before, PseudoElement::After] {
+        let rules = matching.matching_rules(id, elem, stylesheet, Some(pseudo));
+        if rules.is_empty() {
+            continue;
+        }
+        let specified = cascade(rules, style);
+        if specified.content() == &Content::None {
+            continue;
+        }
+        let node = StyledNode {
+            node: NodeId::next(),
+            pseudo: Some(pseudo),
+            specified: Arc::new(specified),
+            dirty: false,
+            children: Vec::new(),
+        };
+        match pseudo {
+            PseudoElement::Before => children.insert(0, node),
+            PseudoElement::After => children.push(node),
+        }
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn style_children(node: &Node, stylesheet: &Stylesheet, style: &Style,
+                  matching: Matching) -> Vec<StyledNode> {
+    node.children.iter().map(|child| style_subtree(child, stylesheet, style, matching)).collect()
+}
+
+#[cfg(feature = "parallel")]
+fn style_children(node: &Node, stylesheet: &Stylesheet, style: &Style,
+                  matching: Matching) -> Vec<StyledNode> {
+    if STYLING_SEQUENTIALLY.with(Cell::get) {
+        return node.children.iter()
+            .map(|child| style_subtree(child, stylesheet, style, matching))
+            .collect();
+    }
+    style_threads().install(|| {
+        node.children.par_iter()
+            .map(|child| style_subtree(child, stylesheet, style, matching))
+            .collect()
+    })
+}
+
+/// The stack that styling may use for each level of the tree on a styling thread, where rayon
+/// adds frames of its own to every level.
+#[cfg(feature = "parallel")]
+const STACK_PER_LEVEL: usize = 64 * 1024;
+
+/// The threads that style subtrees in parallel, with stacks deep enough to style trees as deep as
+/// the HTML parser nests elements (`html::MAX_NESTING_DEPTH`).
+#[cfg(feature = "parallel")]
+fn style_threads() -> &'static rayon::ThreadPool {
+    static THREADS: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    THREADS.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .stack_size(html::MAX_NESTING_DEPTH * STACK_PER_LEVEL)
+            .thread_name(|i| format!("chameleon-style-{}", i))
+            .build()
+            .expect("cannot start the styling threads")
+    })
+}
+
+impl StyledNode {
+    /// Find the style node for the node `id` in this subtree.
+    pub fn find(&self, id: NodeId) -> Option<&StyledNode> {
+        if self.node == id {
+            return Some(self);
+        }
+        self.children.iter().filter_map(|child| child.find(id)).next()
+    }
+
+    /// Mark the subtree for the node `id` as needing to be restyled, returning whether the node
+    /// was found.
+    pub fn mark_dirty(&mut self, id: NodeId) -> bool {
+        if self.node == id {
+            self.dirty = true;
+            return true;
+        }
+        self.children.iter_mut().any(|child| child.mark_dirty(id))
+    }
+
+    /// Restyle the subtree for the node `id` after it (or any of its descendants) changed.
+    ///
+    /// `root` must be the DOM node this style tree was built from.
+    pub fn restyle(&mut self, root: &Node, stylesheet: &Stylesheet, id: NodeId) {
+        if self.mark_dirty(id) {
+            self.restyle_dirty(root, stylesheet);
+        }
+    }
+
+    /// Restyle the subtree for the node `id` like `restyle`, matching elements through `cache`.
+    pub fn restyle_cached(&mut self, root: &Node, stylesheet: &Stylesheet, id: NodeId,
+                          cache: &MatchCache) {
+        if self.mark_dirty(id) {
+            let matching = Matching { cache: Some(cache), ..Matching::default() };
+            self.restyle_dirty_within(root, stylesheet, &Style::default(), matching);
+            self.update_counters(stylesheet);
+        }
+    }
+
+    /// Recompute the styles of every dirty subtree, leaving the rest of the tree untouched.
+    ///
+    /// The children of a node that is not dirty must not have been added, removed, or reordered.
+    pub fn restyle_dirty(&mut self, node: &Node, stylesheet: &Stylesheet) {
+        self.restyle_dirty_within(node, stylesheet, &Style::default(), Matching::default());
+        self.update_counters(stylesheet);
+    }
+
+    /// Recompute the styles of every dirty subtree like `restyle_dirty`, matching elements
+    /// through `cache`, in the given states.
+    pub fn restyle_dirty_with_states(&mut self, node: &Node, stylesheet: &Stylesheet,
+                                     cache: &MatchCache, states: ElementStates) {
+        self.restyle_dirty_with_media(node, stylesheet, cache, states, MediaContext::default());
+    }
+
+    /// Recompute the styles of every dirty subtree like `restyle_dirty_with_states`, for the
+    /// given media.
+    pub fn restyle_dirty_with_media(&mut self, node: &Node, stylesheet: &Stylesheet,
+                                    cache: &MatchCache, states: ElementStates,
+                                    media: MediaContext) {
+        let matching = Matching { cache: Some(cache), states, media };
+        self.restyle_dirty_within(node, stylesheet, &Style::default(), matching);
+        self.update_counters(stylesheet);
+    }
+
+    /// Generate the text of the pseudo-elements whose `content` shows counters, applying the
+    /// `counter-reset` and `counter-increment` of every node in document order.
+    ///
+    /// Counters depend on the whole tree before a node, so this runs over the whole tree after
+    /// every restyle, unless no pseudo-element (and so no counter) can be generated.
+    fn update_counters(&mut self, stylesheet: &Stylesheet) {
+        if stylesheet.has_pseudo_elements() {
+            resolve_counters(self, 0, &mut Vec::new());
+        }
+    }
+
+    /// Restyle the dirty subtrees of a node whose parent has the style `parent`.
+    fn restyle_dirty_within(&mut self, node: &Node, stylesheet: &Stylesheet, parent: &Style,
+                            matching: Matching) {
+        debug_assert_eq!(self.node, node.id, "style tree does not match the DOM");
+        if self.dirty {
+            *self = style_subtree(node, stylesheet, parent, matching);
+            return;
+        }
+        let style = self.specified.clone();
+        let children = self.children.iter_mut().filter(|child| child.pseudo.is_none());
+        for (child, node) in children.zip(&node.children) {
+            child.restyle_dirty_within(node, stylesheet, &style, matching);
+        }
+    }
+}
+
+/// A counter, while counters are resolved in document order.
+struct CounterInstance {
+    name: String,
+    value: i32,
+    /// The depth of the node that created the counter. Its scope is that node, the node's
+    /// following siblings, and their descendants.
+    depth: usize,
+}
+
+/// Apply the counter changes of `node` (at depth `depth` in the tree), and generate the text of
+/// the counters in its subtree, with the counters `counters` in scope before it.
+fn resolve_counters(node: &mut StyledNode, depth: usize, counters: &mut Vec<CounterInstance>) {
+    let style = &node.specified;
+    for change in style.counter_reset() {
+        reset_counter(counters, &change.name, change.value.unwrap_or(0), depth);
+    }
+    // List items count themselves in the `list-item` counter, unless they say otherwise.
+    let increments = style.counter_increment();
+    if style.display() == Display::ListItem
+        && !increments.iter().any(|change| change.name == "list-item") {
+        increment_counter(counters, "list-item", 1, depth);
+    }
+    for change in increments {
+        increment_counter(counters, &change.name, change.value.unwrap_or(1), depth);
+    }
+
+    if let (Some(_), Content::Counters(items, text)) = (node.pseudo, style.content()) {
+        let generated = counter_text(items, counters);
+        if generated != *text {
+            let content = Content::Counters(items.clone(), generated);
+            Arc::make_mut(&mut node.specified).content = content;
+        }
+    }
+
+    let len = counters.len();
+    for child in &mut node.children {
+        resolve_counters(child, depth + 1, counters);
+    }
+    counters.truncate(len);
+}
+
+/// Reset the counter `name` to `value` for a node at depth `depth`. This creates a counter,
+/// unless a previous sibling (or the node itself) created one already, which it resets instead.
+fn reset_counter(counters: &mut Vec<CounterInstance>, name: &str, value: i32, depth: usize) {
+    match counters.iter_mut().rev().find(|counter| counter.name == name) {
+        Some(counter) if counter.depth == depth => counter.value = value,
+        _ => counters.push(CounterInstance { name: name.to_string(), value, depth }),
+    }
+}
+
+/// Add `value` to the innermost counter `name`, creating one at 0 for the node at depth `depth`
+/// if there is none in scope.
+fn increment_counter(counters: &mut Vec<CounterInstance>, name: &str, value: i32, depth: usize) {
+    if !counters.iter().any(|counter| counter.name == name) {
+        counters.push(CounterInstance { name: name.to_string(), value: 0, depth });
+    }
+    let counter = counters.iter_mut().rev().find(|counter| counter.name == name).unwrap();
+    counter.value = counter.value.wrapping_add(value);
+}
+
+/// The text of generated content, with the counters `counters` in scope. A counter that is not
+/// in scope shows as 0.
+fn counter_text(items: &[ContentItem], counters: &[CounterInstance]) -> String {
+    let mut text = String::new();
+    for item in items {
+        match item {
+            ContentItem::String(s) => text.push_str(s),
+            ContentItem::Counter(name, style) => {
+                let counter = counters.iter().rev().find(|counter| counter.name == *name);
+                text.push_str(&style.counter_text(counter.map_or(0, |counter| counter.value)));
+            }
+            ContentItem::Counters(name, separator, style) => {
+                let values: Vec<String> = counters.iter()
+                    .filter(|counter| counter.name == *name)
+                    .map(|counter| style.counter_text(counter.value))
+                    .collect();
+                if values.is_empty() {
+                    text.push_str(&style.counter_text(0));
+                } else {
+                    text.push_str(&values.join(separator));
+                }
+            }
+        }
+    }
+    text
+}
+
+/// Apply styles to the single element `id`, returning the specified styles.
+fn specified_values(id: NodeId, elem: &ElementData, stylesheet: &Stylesheet, parent: &Style,
+                    matching: Matching) -> Style {
+    let inline = inline_style(elem);
+    let mut rules = matching.matching_rules(id, elem, stylesheet, None);
+    rules.extend(inline.as_ref().map(|rule| (INLINE_SPECIFICITY, rule)));
+    cascade(rules, parent)
+}
+
+/// Declarations in a `style` attribute take precedence over those of every selector.
+const INLINE_SPECIFICITY: Specificity = (usize::MAX, usize::MAX, usize::MAX);
+
+/// The declarations of an element's `style` attribute, as an author rule without selectors.
+fn inline_style<'a>(elem: &'a ElementData) -> Option<Rule<'a>> {
+    let (declarations, _) = css::parse_inline_style(elem.attributes.get("style")?);
+    Some(Rule { selectors: Vec::new(), declarations, origin: Origin::Author, media: Vec::new() })
+}
+
+/// Collect the styles embedded in and linked from a document: the contents of its `<style>`
+/// elements and its fetched `linked` stylesheets, in document order, as one author stylesheet.
+/// `style` attributes are applied by `style_tree`.
+///
+/// Also returns the syntax errors found in these styles and in `style` attributes, each with the
+/// URL of the linked stylesheet it is in, if any. Lines and columns count from the start of the
+/// stylesheet, the element's text, or the attribute's value. Linked stylesheets that could not be
+/// fetched are skipped.
+pub fn document_stylesheet<'a>(root: &'a Node, linked: &'a [LinkedStylesheet])
+    -> (Stylesheet<'a>, Vec<(Option<&'a str>, ParseError)>)
+{
+    let mut stylesheets = Vec::new();
+    let mut errors = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter().rev());
+        let elem = match node.node_type {
+            NodeType::Element(ref elem) => elem,
+            NodeType::Text(_) => continue,
+        };
+        if let Some(source) = elem.attributes.get("style") {
+            errors.extend(css::parse_inline_style(source).1.into_iter().map(|error| (None, error)));
+        }
+        if elem.tag == "link" {
+            for stylesheet in linked.iter().filter(|stylesheet| stylesheet.node == node.id) {
+                if let Ok(ref source) = stylesheet.source {
+                    let (sheet, sheet_errors) = css::parse_with_errors(source);
+                    stylesheets.push(sheet);
+                    let url = &*stylesheet.url;
+                    errors.extend(sheet_errors.into_iter().map(|error| (Some(url), error)));
+                }
+            }
+        }
+        if elem.tag != "style" {
+            continue;
+        }
+        for child in &node.children {
+            if let NodeType::Text(ref source) = child.node_type {
+                let (stylesheet, sheet_errors) = css::parse_with_errors(source);
+                stylesheets.push(stylesheet);
+                errors.extend(sheet_errors.into_iter().map(|error| (None, error)));
+            }
+        }
+    }
+    (Stylesheet::combine(stylesheets), errors)
+}
+
+/// Apply the declarations of the rules that match an element, whose parent has the style
+/// `parent`, returning the specified styles.
+///
+/// As CSS requires, a declaration with an unsupported property or an invalid value is ignored,
+/// leaving the values of earlier declarations in place. `check_stylesheet` reports such
+/// declarations.
+fn cascade(rules: Vec<MatchedRule>, parent: &Style) -> Style {
+    let mut style = Style::inherited_from(parent);
+
+    // Go through the declarations from lowest to highest precedence: by origin and importance,
+    // then by specificity, then in order of appearance (which the stable sort keeps).
+    let mut declarations: Vec<_> = rules.iter()
+        .flat_map(|&(specificity, rule)| rule.declarations.iter().map(move |declaration| {
+            ((precedence(rule.origin, declaration.important), specificity), declaration)
+        }))
+        .collect();
+    declarations.sort_by_key(|&(key, _)| key);
+
+    // Other declarations may refer to custom properties, so those are found first. Their values
+    // replace the inherited ones before any references among them are substituted.
+    let mut custom = declarations.iter()
+        .map(|&(_, declaration)| declaration)
+        .filter(|declaration| is_custom_property(&declaration.name))
+        .peekable();
+    if custom.peek().is_some() {
+        let mut declared = (*style.custom_properties).clone();
+        for declaration in custom {
+            declared.insert(declaration.name.to_string(), declaration.value.clone().into_owned());
+        }
+        let mut substitution = Substitution::new(&declared);
+        let computed = declared.keys()
+            .filter_map(|name| Some((name.clone(), substitution.lookup(name)?)))
+            .collect();
+        style.custom_properties = Arc::new(computed);
+    }
+
+    // Logical properties are mapped to physical ones by the element's writing mode and direction,
+    // so those are found next.
+    for &(_, declaration) in &declarations {
+        let property = &*declaration.name;
+        if property == "direction" || property == "writing-mode" {
+            let _ = apply_substituted(&mut style, declaration);
+        }
+    }
+
+    for &(_, declaration) in &declarations {
+        let _ = apply_substituted(&mut style, declaration);
+    }
+    style
+}
+
+/// Whether `name` is the name of a custom property, which starts with `--`.
+fn is_custom_property(name: &str) -> bool {
+    name.starts_with("--")
+}
+
+/// Whether a value refers to custom properties with `var()`.
+fn has_references(value: &Value) -> bool {
+    match value {
+        Value::Function(name, _) if name.eq_ignore_ascii_case("var") => true,
+        Value::Function(_, values) | Value::List(values) => values.iter().any(has_references),
+        _ => false,
+    }
+}
+
+/// Apply one declaration to a style, after substituting the custom properties it refers to.
+///
+/// A declaration that refers to an undefined custom property without a fallback is ignored, as
+/// is one whose value is invalid once substituted. (CSS would unset the property instead.)
+fn apply_substituted(style: &mut Style, declaration: &Declaration) -> Result<(), String> {
+    if !has_references(&declaration.value) || is_custom_property(&declaration.name) {
+        return apply_declaration(style, declaration);
+    }
+    let value = Substitution::new(&style.custom_properties).substitute(&declaration.value)?;
+    let name = Cow::Borrowed(&*declaration.name);
+    apply_declaration(style, &Declaration { name, value, important: declaration.important })
+}
+
+/// Substitutes the `var()` references in values with the values of custom properties.
+///
+/// `var(--name)` is replaced by the computed value of `--name`, and `var(--name, <fallback>)` by
+/// the fallback values instead if `--name` is undefined or invalid. A custom property whose value
+/// refers to itself, directly or through others, is invalid.
+struct Substitution<'a> {
+    /// Custom properties as declared, whose values may have references.
+    declared: &'a CustomProperties,
+    /// The computed values of the custom properties looked up so far, or `None` if invalid.
+    computed: HashMap<String, Option<Value<'static>>>,
+    /// Custom properties whose values are being substituted, innermost last.
+    pending: Vec<String>,
+    /// Custom properties found to be in reference cycles.
+    cyclic: HashSet<String>,
+}
+
+impl<'a> Substitution<'a> {
+    fn new(declared: &'a CustomProperties) -> Self {
+        Substitution {
+            declared,
+            computed: HashMap::new(),
+            pending: Vec::new(),
+            cyclic: HashSet::new(),
+        }
+    }
+
+    /// The computed value of the custom property `name`, or `None` if it is undefined or
+    /// invalid.
+    fn lookup(&mut self, name: &str) -> Option<Value<'static>> {
+        if let Some(value) = self.computed.get(name) {
+            return value.clone();
+        }
+        if let Some(i) = self.pending.iter().position(|pending| pending == name) {
+            self.cyclic.extend(self.pending[i..].iter().cloned());
+            return None;
+        }
+        let declared = self.declared.get(name)?;
+        let value = if has_references(declared) {
+            self.pending.push(name.to_string());
+            let value = self.substitute(declared).ok();
+            self.pending.pop();
+            value.filter(|_| !self.cyclic.contains(name))
+        } else {
+            Some(declared.clone())
+        };
+        self.computed.insert(name.to_string(), value.clone());
+        value
+    }
+
+    /// A value with its references substituted, or why they cannot be.
+    fn substitute(&mut self, value: &Value) -> Result<Value<'static>, String> {
+        let mut values = Vec::new();
+        match value {
+            Value::List(list) => {
+                for value in list {
+                    self.substitute_into(value, &mut values)?;
+                }
+            }
+            _ => self.substitute_into(value, &mut values)?,
+        }
+        match values.len() {
+            0 => Err(String::from("no value after substituting var()")),
+            1 => Ok(values.swap_remove(0)),
+            _ => Ok(Value::List(values)),
+        }
+    }
+
+    /// Substitute the references in a component value, and append the resulting component
+    /// values to `values`. The components of a custom property's value take the place of a
+    /// reference to it, so they can fill several component values or function arguments.
+    fn substitute_into(&mut self, value: &Value, values: &mut Vec<Value<'static>>)
+        -> Result<(), String>
+    {
+        match value {
+            Value::Function(name, arguments) if name.eq_ignore_ascii_case("var") => {
+                let (property, fallback) = match arguments.split_first() {
+                    Some((Value::Keyword(property), rest)) if is_custom_property(property) => {
+                        match rest.split_first() {
+                            None => (property, None),
+                            Some((Value::Delimiter(','), fallback)) => (property, Some(fallback)),
+                            Some((value, _)) => {
+                                return Err(format!("unexpected {} in var()", value));
+                            }
+                        }
+                    }
+                    _ => return Err(String::from("expected a custom property name in var()")),
+                };
+                match (self.lookup(property), fallback) {
+                    (Some(Value::List(list)), _) => values.extend(list),
+                    (Some(value), _) => values.push(value),
+                    (None, Some(fallback)) => {
+                        for value in fallback {
+                            self.substitute_into(value, values)?;
+                        }
+                    }
+                    (None, None) => return Err(format!("{} is not defined", property)),
+                }
+            }
+            Value::Function(name, arguments) => {
+                let mut substituted = Vec::new();
+                for argument in arguments {
+                    self.substitute_into(argument, &mut substituted)?;
+                }
+                values.push(Value::Function(Cow::Owned(name.to_string()), substituted));
+            }
+            Value::List(list) => {
+                let mut substituted = Vec::new();
+                for value in list {
+                    self.substitute_into(value, &mut substituted)?;
+                }
+                values.push(Value::List(substituted));
+            }
+            _ => values.push(value.clone().into_owned()),
+        }
+        Ok(())
+    }
+}
+
+/// The precedence in the cascade of declarations from `origin`, from 0 (lowest) up.
+///
+/// Normal declarations from the author override the user's, which override the user agent's.
+/// Important declarations override all normal ones, and their origins go the other way around.
+fn precedence(origin: Origin, important: bool) -> u8 {
+    match (important, origin) {
+        (false, Origin::UserAgent) => 0,
+        (false, Origin::User) => 1,
+        (false, Origin::Author) => 2,
+        (true, Origin::Author) => 3,
+        (true, Origin::User) => 4,
+        (true, Origin::UserAgent) => 5,
+    }
+}
+
+/// Apply one declaration to a style. If the declaration is invalid, leave the style as it was and
+/// return why.
+fn apply_declaration(style: &mut Style, declaration: &Declaration) -> Result<(), String> {
+    let property = &*declaration.name;
+    let value = &declaration.value;
+    // Custom properties are set by `cascade`, before any other declarations.
+    if is_custom_property(property) {
+        return Ok(());
+    }
+    if let Some(result) = style.set_longhand(property, value) {
+        return result;
+    }
+    if let Some(longhands) = style.physical_longhands(property) {
+        // A property for both sides takes one value for both, or the start and end values.
+        let values = match value {
+            Value::List(values) if values.len() == longhands.len() => values.iter().collect(),
+            _ => vec![value; longhands.len()],
+        };
+        // Check every value before setting any, so that an invalid one leaves all sides alone.
+        for (longhand, value) in longhands.iter().zip(&values) {
+            Style::default().set_longhand(longhand, value).unwrap()?;
+        }
+        for (longhand, value) in longhands.iter().zip(values) {
+            style.set_longhand(longhand, value).unwrap()?;
+        }
+        return Ok(());
+    }
+    match property {
+        "margin" => {
+            let specified = value.try_into()?;
+            style.margin_left = specified;
+            style.margin_right = specified;
+            style.margin_top = specified;
+            style.margin_bottom = specified;
+        },
+
+        "padding" => {
+            let specified = value.try_into()?;
+            style.padding_left = specified;
+            style.padding_right = specified;
+            style.padding_top = specified;
+            style.padding_bottom = specified;
+        },
+
+        "background" => {
+            // `background: [<color> || <image>]`, resetting whichever is left out.
+            let values = match value {
+                Value::List(values) => values.as_slice(),
+                _ => std::slice::from_ref(value),
+            };
+            let (mut color, mut image) = (None, None);
+            for value in values {
+                match value {
+                    Value::ColorValue(c) if color.is_none() => color = Some(*c),
+                    _ if image.is_none() => image = Some(value.try_into()?),
+                    _ => return Err(format!("unexpected {} in background", value)),
+                }
+            }
+            style.background_color = color.unwrap_or_default();
+            style.background_image = image.unwrap_or_default();
+        },
+
+        "border-width" => {
+            let specified = value.try_into()?;
+            style.border_left_width = specified;
+            style.border_right_width = specified;
+            style.border_top_width = specified;
+            style.border_bottom_width = specified;
+        },
+
+        "flex" => {
+            // Only the single-number form, e.g. `flex: 1`.
+            style.flex_grow = number(value)?;
+            style.flex_shrink = 1.0;
+            style.flex_basis = Automatic::Given(0.0);
+        },
+
+        "gap" => {
+            // `gap: <row> [<column>]`
+            let (row, column) = match value {
+                Value::List(values) if values.len() == 2 => (&values[0], &values[1]),
+                _ => (value, value),
+            };
+            let (row, column) = (row.try_into()?, column.try_into()?);
+            style.row_gap = row;
+            style.column_gap = column;
+        },
+
+        "grid-column" => {
+            let placement: GridPlacement = value.try_into()?;
+            style.grid_column_start = placement.start;
+            style.grid_column_end = placement.end;
+        },
+        "grid-row" => {
+            let placement: GridPlacement = value.try_into()?;
+            style.grid_row_start = placement.start;
+            style.grid_row_end = placement.end;
+        },
+
+        _ => return Err(String::from("unsupported property")),
+    }
+    Ok(())
+}
+
+/// A declaration in a stylesheet that styling ignores.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StyleError {
+    /// Index of the declaration's rule in the stylesheet.
+    pub rule: usize,
+    /// Index of the declaration within its rule.
+    pub declaration: usize,
+    /// Why the declaration is ignored, starting with its property.
+    pub message: String,
+}
+
+impl fmt::Display for StyleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rule {}, declaration {}: {}", self.rule + 1, self.declaration + 1, self.message)
+    }
+}
+
+/// Find the declarations in a stylesheet that styling ignores, because their properties are
+/// unsupported or their values are invalid.
+///
+/// Whether a declaration is valid does not depend on the element it applies to, so every
+/// ignored declaration is reported once, in stylesheet order. The exception is a declaration that
+/// refers to custom properties, which depends on their values, so it is not checked.
+pub fn check_stylesheet(stylesheet: &Stylesheet) -> Vec<StyleError> {
+    let mut errors = Vec::new();
+    for (i, rule) in stylesheet.rules().iter().enumerate() {
+        for (j, declaration) in rule.declarations.iter().enumerate() {
+            if has_references(&declaration.value) {
+                continue;
+            }
+            if let Err(message) = apply_declaration(&mut Style::default(), declaration) {
+                errors.push(StyleError {
+                    rule: i,
+                    declaration: j,
+                    message: format!("{}: {}", declaration.name, message),
+                });
+            }
+        }
+    }
+    errors
+}
+
+/// A single CSS rule and the specificity of its most specific matching selector.
+type MatchedRule<'a> = (Specificity, &'a Rule<'a>);
+
+/// Find all CSS rules that match the given element (which has the focus if `focused` is true),
+/// or its pseudo-element `pseudo`.
+fn matching_rules<'a>(elem: &ElementData, focused: bool, stylesheet: &'a Stylesheet,
+                      pseudo: Option<PseudoElement>) -> Vec<MatchedRule<'a>> {
+    // Only the rules indexed under the element's id, classes, or type can match it.
+    stylesheet.candidate_rules(&elem.tag, elem.id(), &elem.classes()).into_iter()
+        .filter_map(|rule| match_rule(elem, focused, rule, pseudo))
+        .collect()
+}
+
+/// The rules that matched elements, by the parts of the elements that selectors can test, for
+/// one revision of a stylesheet.
+///
+/// Elements with the same type, namespace, id, and classes match the same rules, so documents
+/// with many such elements (list items, table cells, and the like) only match each kind once. The
+/// cache is cleared when it is used with another stylesheet, or with the same one after it
+/// changed.
+///
+/// Selectors cannot test attributes yet, so these are not part of the key. Of the states, only
+/// whether the element has the focus is.
+#[derive(Debug, Default)]
+pub struct MatchCache {
+    inner: Mutex<MatchCacheInner>,
+}
+
+#[derive(Debug, Default)]
+struct MatchCacheInner {
+    /// The revision of the stylesheet the entries were matched against.
+    revision: Option<u64>,
+    /// The specificity and index of every rule that matched elements with the key, in order.
+    entries: HashMap<ElementKey, Vec<(Specificity, usize)>>,
+    hits: usize,
+    misses: usize,
+}
+
+/// The type, id, (sorted) classes, and focus of an element, and the pseudo-element being
+/// matched.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct ElementKey {
+    tag: String,
+    namespace: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    focused: bool,
+    pseudo: Option<PseudoElement>,
+}
+
+impl MatchCache {
+    pub fn new() -> MatchCache {
+        MatchCache::default()
+    }
+
+    /// The number of elements whose rules were found in the cache.
+    pub fn hits(&self) -> usize {
+        self.inner.lock().unwrap().hits
+    }
+
+    /// The number of elements whose rules had to be matched.
+    pub fn misses(&self) -> usize {
+        self.inner.lock().unwrap().misses
+    }
+
+    /// Find all CSS rules that match the given element, like `matching_rules`.
+    fn matching_rules<'a>(&self, elem: &ElementData, focused: bool, stylesheet: &'a Stylesheet,
+                          pseudo: Option<PseudoElement>) -> Vec<MatchedRule<'a>>
+    {
+        let mut classes: Vec<String> = elem.classes().into_iter().map(String::from).collect();
+        classes.sort_unstable();
+        let key = ElementKey {
+            tag: elem.tag.to_string(),
+            namespace: elem.namespace.as_ref().map(|namespace| namespace.to_string()),
+            id: elem.id().map(String::from),
+            classes,
+            focused,
+            pseudo,
+        };
+        let rules = stylesheet.rules();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.revision != Some(stylesheet.revision()) {
+                inner.revision = Some(stylesheet.revision());
+                inner.entries.clear();
+            }
+            if let Some(matched) = inner.entries.get(&key) {
+                let matched = matched.iter().map(|&(specificity, i)| (specificity, &rules[i]))
+                    .collect();
+                inner.hits += 1;
+                return matched;
+            }
+            inner.misses += 1;
+        }
+
+        // Match without holding the lock, so other threads can use the cache meanwhile.
+        let matched: Vec<(Specificity, usize)> = stylesheet
+            .candidate_rule_indices(&elem.tag, elem.id(), &elem.classes()).into_iter()
+            .filter_map(|i| {
+                let (specificity, _) = match_rule(elem, focused, &rules[i], pseudo)?;
+                Some((specificity, i))
+            })
+            .collect();
+        let mut inner = self.inner.lock().unwrap();
+        if inner.revision == Some(stylesheet.revision()) {
+            inner.entries.insert(key, matched.clone());
+        }
+        matched.into_iter().map(|(specificity, i)| (specificity, &rules[i])).collect()
+    }
+}
+
+/// If `rule` matches `elem` (or its pseudo-element `pseudo`), return a `MatchedRule`. Otherwise
+/// return `None`.
+fn match_rule<'a>(elem: &ElementData, focused: bool, rule: &'a Rule,
+                  pseudo: Option<PseudoElement>) -> Option<MatchedRule<'a>> {
+    // Find the first (most specific) matching selector.
+    rule.selectors.iter().find(|selector| matches(elem, focused, selector, pseudo))
+        .map(|selector| (selector.specificity(), rule))
+}
+
+/// Does `selector` match `elem` itself, rather than one of its pseudo-elements? The element is
+/// taken not to have the focus.
+pub fn matches_selector(elem: &ElementData, selector: &Selector) -> bool {
+    matches(elem, false, selector, None)
+}
+
+/// Selector matching. A selector with a pseudo-element only matches that pseudo-element of the
+/// elements it matches, and a selector without one only matches the elements themselves.
+fn matches(elem: &ElementData, focused: bool, selector: &Selector,
+           pseudo: Option<PseudoElement>) -> bool {
+    match *selector {
+        Selector::Simple(ref simple_selector) => {
+            simple_selector.pseudo_element == pseudo
+                && matches_simple_selector(elem, focused, simple_selector)
+        }
+    }
+}
+
+fn matches_simple_selector(elem: &ElementData, focused: bool, selector: &SimpleSelector) -> bool {
+    // Check type selector
+    if selector.tag.iter().any(|name| !elem.has_tag(name)) {
+        return false
+    }
+
+    // Check ID selector
+    if selector.id.iter().any(|id| elem.id() != Some(id)) {
+        return false;
+    }
+
+    // Check class selectors
+    let elem_classes = elem.classes();
+    if selector.class.iter().any(|class| !elem_classes.contains(&**class)) {
+        return false;
+    }
+
+    // Check pseudo-classes
+    if selector.pseudo_classes.iter().any(|&PseudoClass::Focus| !focused) {
+        return false;
+    }
+
+    // We didn't find any non-matching selector components.
+    true
+}
+
+/// Apply a stylesheet to an entire DOM tree like `style_tree`, recording in `profile` the work
+/// that selector matching takes for every selector and element.
+///
+/// Every selector is timed separately, which slows styling down considerably, so this is only
+/// meant for finding expensive selectors.
+pub fn style_tree_profiled(root: &Node, stylesheet: &Stylesheet, profile: &mut StyleProfile)
+    -> StyledNode
+{
+    let mut styled = style_subtree_profiled(root, stylesheet, &Style::default(), profile);
+    styled.update_counters(stylesheet);
+    styled
+}
+
+fn style_subtree_profiled(root: &Node, stylesheet: &Stylesheet, parent: &Style,
+                          profile: &mut StyleProfile) -> StyledNode {
+    let specified = match root.node_type {
+        NodeType::Element(ref elem) => {
+            let start = Instant::now();
+            let (candidates, mut rules) = profile.matching_rules(elem, stylesheet);
+            rules.retain(|&(_, rule)| rule.matches_media(&MediaContext::default()));
+            let matches = rules.len();
+            let inline = inline_style(elem);
+            rules.extend(inline.as_ref().map(|rule| (INLINE_SPECIFICITY, rule)));
+            let style = cascade(rules, parent);
+            profile.elements.push(ElementStats {
+                node: root.id,
+                name: element_name(elem),
+                candidates,
+                matches,
+                time: start.elapsed(),
+            });
+            style
+        }
+        NodeType::Text(_) => Style::inherited_from(parent),
+    };
+    let mut children: Vec<StyledNode> = root.children.iter()
+        .map(|child| style_subtree_profiled(child, stylesheet, &specified, profile))
+        .collect();
+    if let NodeType::Element(ref elem) = root.node_type {
+        let matching = Matching::default();
+        add_pseudo_elements(root.id, elem, stylesheet, &specified, matching, &mut children);
+    }
+    let specified = Arc::new(specified);
+    StyledNode { node: root.id, pseudo: None, children, specified, dirty: false }
+}
+
+/// Selector matching statistics gathered by `style_tree_profiled`.
+#[derive(Debug, Default)]
+pub struct StyleProfile {
+    /// Statistics for every selector that was tried, by the index of its rule in the stylesheet
+    /// and its index within the rule.
+    pub selectors: HashMap<(usize, usize), SelectorStats>,
+    /// Statistics for every element, in document order.
+    pub elements: Vec<ElementStats>,
+}
+
+/// Matching statistics for one selector.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SelectorStats {
+    /// Number of elements the selector was matched against.
+    pub attempts: usize,
+    /// Number of elements the selector matched.
+    pub matches: usize,
+    /// Total time spent matching the selector.
+    pub time: Duration,
+}
+
+/// Styling statistics for one element.
+#[derive(Debug, Clone)]
+pub struct ElementStats {
+    pub node: NodeId,
+    /// The element's type, id, and classes, written as a selector.
+    pub name: String,
+    /// Number of rules that were matched against the element.
+    pub candidates: usize,
+    /// Number of rules that matched the element.
+    pub matches: usize,
+    /// Time spent finding the element's matching rules and applying their declarations.
+    pub time: Duration,
+}
+
+impl StyleProfile {
+    /// Find all CSS rules that match the given element, like `matching_rules`, recording the work
+    /// done for each selector. Also returns the number of candidate rules.
+    fn matching_rules<'a>(&mut self, elem: &ElementData, stylesheet: &'a Stylesheet)
+        -> (usize, Vec<MatchedRule<'a>>)
+    {
+        let candidates = stylesheet.candidate_rule_indices(&elem.tag, elem.id(), &elem.classes());
+        let mut matched = Vec::new();
+        for &i in &candidates {
+            let rule = &stylesheet.rules()[i];
+            for (j, selector) in rule.selectors.iter().enumerate() {
+                let start = Instant::now();
+                let found = matches(elem, false, selector, None);
+                let stats = self.selectors.entry((i, j)).or_default();
+                stats.time += start.elapsed();
+                stats.attempts += 1;
+                if found {
+                    stats.matches += 1;
+                    matched.push((selector.specificity(), rule));
+                    break;
+                }
+            }
+        }
+        (candidates.len(), matched)
+    }
+
+    /// Report the `limit` selectors with the most total matching time, and the `limit` elements
+    /// that took the longest to style.
+    ///
+    /// `stylesheet` must be the stylesheet that was profiled.
+    pub fn report(&self, stylesheet: &Stylesheet, limit: usize) -> String {
+        let mut selectors: Vec<_> = self.selectors.iter().collect();
+        selectors.sort_by_key(|&(&key, stats)| (Reverse(stats.time), key));
+        let mut elements: Vec<_> = self.elements.iter().collect();
+        elements.sort_by_key(|stats| Reverse(stats.time));
+
+        let mut report = String::new();
+        writeln!(report, "Most expensive selectors:").unwrap();
+        writeln!(report, "{:>12} {:>9} {:>9}  selector", "time", "attempts", "matches").unwrap();
+        for (&(rule, selector), stats) in selectors.into_iter().take(limit) {
+            let selector = &stylesheet.rules()[rule].selectors[selector];
+            writeln!(report, "{:>12.3?} {:>9} {:>9}  {}",
+                     stats.time, stats.attempts, stats.matches, selector).unwrap();
+        }
+        writeln!(report, "Most expensive elements:").unwrap();
+        writeln!(report, "{:>12} {:>9} {:>9}  element", "time", "rules", "matches").unwrap();
+        for stats in elements.into_iter().take(limit) {
+            writeln!(report, "{:>12.3?} {:>9} {:>9}  {}",
+                     stats.time, stats.candidates, stats.matches, stats.name).unwrap();
+        }
+        report
+    }
+}
+
+/// Name an element by its type, id, and classes, as a selector would.
+fn element_name(elem: &ElementData) -> String {
+    let mut name = elem.tag.to_string();
+    if let Some(id) = elem.id() {
+        name = name + "#" + id;
+    }
+    if let Some(classes) = elem.attributes.get("class") {
+        for class in classes.split(' ').filter(|class| !class.is_empty()) {
+            name = name + "." + class;
+        }
+    }
+    name
 }
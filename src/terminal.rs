@@ -0,0 +1,117 @@
+//! Rendering canvases as colored text, for terminals and CI logs.
+//!
+//! Each character is an upper half block (`▀`) whose foreground color is the average of a square
+//! of pixels and whose background color is the average of the square below it. Terminal cells
+//! are about twice as tall as they are wide, so the page keeps its proportions.
+
+use css::Color;
+use paint::Canvas;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// The colors that a terminal can show.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AnsiColors {
+    /// Any 24-bit color, as most modern terminals (and CI logs) show.
+    TrueColor,
+    /// The 256 colors of the xterm palette, for older terminals.
+    Palette256,
+}
+
+/// Render `canvas` as lines of text at most `columns` characters wide, with ANSI escape sequences
+/// for the colors. Every line ends by resetting the colors. The canvas is scaled down to fit, but
+/// never up; its alpha channel is ignored.
+pub fn to_ansi(canvas: &Canvas, columns: usize, colors: AnsiColors) -> String {
+    let mut text = String::new();
+    let columns = columns.min(canvas.width);
+    if columns == 0 || canvas.height == 0 {
+        return text;
+    }
+    // The size of the square of pixels in each half of a character.
+    let block = canvas.width as f32 / columns as f32;
+    let rows = (canvas.height as f32 / block).ceil() as usize;
+    let span = |i: usize, size: usize| {
+        let start = ((i as f32 * block) as usize).min(size - 1);
+        let end = (((i + 1) as f32 * block) as usize).clamp(start + 1, size);
+        start..end
+    };
+
+    for row in (0..rows).step_by(2) {
+        let (mut foreground, mut background) = (None, None);
+        for column in 0..columns {
+            let xs = span(column, canvas.width);
+            // Colors are only set when they change.
+            let top = escape(average(canvas, xs.clone(), span(row, canvas.height)), 38, colors);
+            if foreground.as_ref() != Some(&top) {
+                text.push_str(&top);
+                foreground = Some(top);
+            }
+            // The last row of blocks may have nothing below it.
+            let bottom = match row + 1 < rows {
+                true => escape(average(canvas, xs, span(row + 1, canvas.height)), 48, colors),
+                false => "\x1b[49m".to_string(),
+            };
+            if background.as_ref() != Some(&bottom) {
+                text.push_str(&bottom);
+                background = Some(bottom);
+            }
+            text.push('▀');
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
+
+/// The average color of the pixels in the given columns and rows of the canvas.
+fn average(canvas: &Canvas, xs: Range<usize>, ys: Range<usize>) -> Color {
+    let (mut r, mut g, mut b, mut count) = (0, 0, 0, 0);
+    for y in ys {
+        for pixel in &canvas.pixels[y * canvas.width + xs.start..y * canvas.width + xs.end] {
+            r += pixel.r as usize;
+            g += pixel.g as usize;
+            b += pixel.b as usize;
+            count += 1;
+        }
+    }
+    let mean = |sum: usize| ((sum + count / 2) / count) as u8;
+    Color { r: mean(r), g: mean(g), b: mean(b), a: 255 }
+}
+
+/// The escape sequence that sets the foreground (`layer` 38) or background (`layer` 48) color.
+fn escape(color: Color, layer: u8, colors: AnsiColors) -> String {
+    let mut escape = format!("\x1b[{};", layer);
+    match colors {
+        AnsiColors::TrueColor => write!(escape, "2;{};{};{}m", color.r, color.g, color.b),
+        AnsiColors::Palette256 => write!(escape, "5;{}m", palette_index(color)),
+    }.unwrap();
+    escape
+}
+
+/// The levels of each channel in the 6×6×6 color cube of the xterm palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The entry of the xterm palette nearest to `color`: one of the color cube (16 to 231) or of
+/// the gray ramp (232 to 255). The first 16 entries vary between terminals, so they are not used.
+fn palette_index(color: Color) -> u8 {
+    let nearest_level = |channel: u8| {
+        (0..6).min_by_key(|&i| CUBE_LEVELS[i].abs_diff(channel)).unwrap()
+    };
+    let (r, g, b) = (nearest_level(color.r), nearest_level(color.g), nearest_level(color.b));
+    let cube = Color { r: CUBE_LEVELS[r], g: CUBE_LEVELS[g], b: CUBE_LEVELS[b], a: 255 };
+
+    // The grays go from 8 to 238 in steps of 10.
+    let mean = (color.r as usize + color.g as usize + color.b as usize) / 3;
+    let step = (mean.saturating_sub(3) / 10).min(23);
+    let level = (8 + step * 10) as u8;
+    let gray = Color { r: level, g: level, b: level, a: 255 };
+
+    let distance = |other: Color| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(color.r, other.r) + d(color.g, other.g) + d(color.b, other.b)
+    };
+    if distance(gray) < distance(cube) {
+        232 + step as u8
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
@@ -0,0 +1,81 @@
+//! Elements drawn by the embedder, such as charts or native controls, inside the layout.
+//!
+//! The embedder registers a `Widget` for a tag name, such as `x-chart`. Each element with that
+//! tag is laid out as a replaced element: its contents are ignored, and its content box takes the
+//! widget's intrinsic size wherever the style leaves the size `auto`. When the document is
+//! painted, the widget paints into a display list of its own, which is moved onto the element's
+//! content box and clipped to it.
+
+use dom::{Node, NodeId, NodeType};
+use geometry::Size;
+use paint::DisplayList;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use style::Pixels;
+
+/// An element drawn by the embedder.
+pub trait Widget: Send + Sync {
+    /// The natural size of the element's content box, in px, when it may be up to
+    /// `available_width` px wide. `available_width` is infinite when the widest possible size is
+    /// asked for.
+    fn intrinsic_size(&self, node: NodeId, available_width: Pixels) -> Size;
+
+    /// Paint the element into `slot`, whose origin is the top left corner of its content box.
+    /// Items outside of `size` are clipped away, and shadows that do not fit in it are left out.
+    fn paint(&self, node: NodeId, size: Size, slot: &mut DisplayList);
+}
+
+/// The widgets of particular elements, by node.
+pub type Widgets = HashMap<NodeId, Arc<dyn Widget>>;
+
+/// Widgets by the tag name of the elements they draw.
+#[derive(Clone, Default)]
+pub struct WidgetRegistry {
+    by_tag: HashMap<String, Arc<dyn Widget>>,
+}
+
+impl WidgetRegistry {
+    pub fn new() -> WidgetRegistry {
+        WidgetRegistry::default()
+    }
+
+    /// Draw every element with the tag name `tag` (in any case) with `widget`, replacing any
+    /// widget registered for it before.
+    pub fn register<W: Widget + 'static>(&mut self, tag: &str, widget: W) {
+        self.by_tag.insert(tag.to_ascii_lowercase(), Arc::new(widget));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_tag.is_empty()
+    }
+
+    /// The widget for the elements with the tag name `tag`, if any.
+    pub fn get(&self, tag: &str) -> Option<&Arc<dyn Widget>> {
+        self.by_tag.get(&tag.to_ascii_lowercase())
+    }
+
+    /// The widgets of the elements in the document at `root`.
+    pub fn widgets_for(&self, root: &Node) -> Widgets {
+        let mut widgets = Widgets::new();
+        if self.is_empty() {
+            return widgets;
+        }
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            stack.extend(node.children.iter());
+            if let NodeType::Element(ref elem) = node.node_type {
+                if let Some(widget) = self.get(&elem.tag) {
+                    widgets.insert(node.id, widget.clone());
+                }
+            }
+        }
+        widgets
+    }
+}
+
+impl fmt::Debug for WidgetRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.by_tag.keys()).finish()
+    }
+}
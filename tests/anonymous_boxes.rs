@@ -0,0 +1,70 @@
+extern crate chameleon;
+
+use chameleon::paint::DisplayCommand;
+use chameleon::{css, html, layout, style};
+
+const STYLESHEET: &str = "
+    div, p { display: block; }
+    div { padding: 5px; border-width: 1px; margin: 3px; background: #ff0000; }
+    p { height: 10px; }
+";
+
+fn layout(source: &str, stylesheet: &str) -> layout::LayoutTree {
+    let root = html::parse(source);
+    let stylesheet = css::parse(stylesheet);
+    let context = layout::LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    layout::layout_tree(&style::style_tree(&root, &stylesheet), context)
+}
+
+fn dump(source: &str, stylesheet: &str) -> String {
+    layout(source, stylesheet).root().map_or_else(String::new, |root| root.dump())
+}
+
+#[test]
+fn anonymous_boxes_do_not_take_the_style_of_their_parent() {
+    // The wrapper of the span has no edges or background of its own, so the div's are neither
+    // doubled nor painted twice.
+    assert_eq!(dump("<div><p></p><span></span></div>", STYLESHEET), "\
+Block (3, 3) 94x22; margin 3 3 3 3; border 1 1 1 1; padding 5 5 5 5
+  Block (9, 9) 82x10; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+  Block (anonymous) (9, 19) 82x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+    Inline (0, 0) 0x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+");
+    let display_list = layout::display_list(&layout("<div><p></p><span></span></div>",
+                                                    STYLESHEET));
+    let backgrounds = display_list.items().iter()
+        .filter(|item| match **item {
+            DisplayCommand::SolidColor { color, .. } => (color.r, color.g) == (255, 0),
+            _ => false,
+        })
+        .count();
+    assert_eq!(backgrounds, 1);
+}
+
+#[test]
+fn inline_children_before_and_after_blocks_are_wrapped_and_kept() {
+    // Consecutive inline children share a wrapper, and the last run is kept like the others.
+    let stylesheet = "div, p { display: block; } p { height: 10px; }";
+    assert_eq!(dump("<div><span></span><span></span><p></p><span></span></div>", stylesheet), "\
+Block (0, 0) 100x10; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+  Block (anonymous) (0, 0) 100x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+    Inline (0, 0) 0x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+    Inline (0, 0) 0x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+  Block (0, 0) 100x10; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+  Block (anonymous) (0, 10) 100x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+    Inline (0, 0) 0x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+");
+}
+
+#[test]
+fn table_parts_outside_a_table_are_wrapped_in_anonymous_tables_and_rows() {
+    let stylesheet = "div { display: block; padding: 5px; } \
+                      span { display: table-cell; width: 20px; height: 10px; }";
+    assert_eq!(dump("<div><span></span><span></span></div>", stylesheet), "\
+Block (0, 0) 100x20; margin 0 0 0 0; border 0 0 0 0; padding 5 5 5 5
+  Table (anonymous) (5, 5) 40x10; margin 0 50 0 0; border 0 0 0 0; padding 0 0 0 0
+    TableRow (anonymous) (5, 5) 40x10; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+      TableCell (5, 5) 20x10; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+      TableCell (25, 5) 20x10; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+");
+}
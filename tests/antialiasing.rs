@@ -0,0 +1,76 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::geometry::Rect;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::{self, Canvas, DisplayCommand, DisplayList, PaintOptions, SnapPolicy};
+
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+
+fn paint(list: &DisplayList, scale: f32, snap: SnapPolicy) -> Canvas {
+    let region = Rect { x: 0.0, y: 0.0, width: 8.0, height: 8.0 };
+    let options = PaintOptions { snap, ..PaintOptions::default() };
+    paint::paint_region_with_options(list, region, scale, options)
+}
+
+fn rect(x: f32, y: f32, width: f32, height: f32) -> DisplayList {
+    let mut list = DisplayList::new();
+    list.push(DisplayCommand::SolidColor { color: BLACK, x, y, width, height });
+    list
+}
+
+/// Black at `alpha` over the white canvas.
+fn blend(alpha: u8) -> Color {
+    Color { a: alpha, ..BLACK }.over(&WHITE)
+}
+
+#[test]
+fn edges_blend_the_pixels_they_cross_by_coverage() {
+    let canvas = paint(&rect(1.5, 2.0, 1.75, 1.5), 1.0, SnapPolicy::Antialias);
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    let row: Vec<Color> = (0..5).map(|x| pixel(x, 2)).collect();
+    assert_eq!(row, [WHITE, blend(128), BLACK, blend(64), WHITE]);
+    let column: Vec<Color> = (1..5).map(|y| pixel(2, y)).collect();
+    assert_eq!(column, [WHITE, BLACK, blend(128), WHITE]);
+    // Corner pixels are covered in both directions at once.
+    assert_eq!(pixel(1, 3), blend(64));
+    assert_eq!(pixel(3, 3), blend(32));
+
+    // At scale 2 the same edges fall on pixel boundaries.
+    let canvas = paint(&rect(1.5, 2.0, 1.75, 1.5), 2.0, SnapPolicy::Antialias);
+    let row: Vec<Color> = (2..8).map(|x| canvas.pixels[5 * canvas.width + x]).collect();
+    assert_eq!(row, [WHITE, BLACK, BLACK, BLACK, blend(128), WHITE]);
+}
+
+#[test]
+fn whole_pixel_edges_paint_as_when_snapped() {
+    for &scale in &[1.0, 2.0] {
+        let list = rect(1.0, 2.0, 3.0, 4.0);
+        let antialiased = paint(&list, scale, SnapPolicy::Antialias);
+        assert_eq!(antialiased.pixels, paint(&list, scale, SnapPolicy::Floor).pixels);
+        assert!(antialiased.pixels.iter().all(|pixel| *pixel == WHITE || *pixel == BLACK));
+    }
+}
+
+#[test]
+fn items_show_through_the_antialiased_edges_of_later_items() {
+    // The blue div covers the red one, except for half of each pixel at its edges.
+    let root = html::parse("<html><div id=\"red\"></div><div id=\"blue\"></div></html>");
+    let stylesheet = css::parse("html, div { display: block; } \
+                                 div { width: 4.5px; height: 4px; margin-left: 0.5px; } \
+                                 #red { background: #ff0000; } \
+                                 #blue { background: #0000ff; margin-top: -4px; }");
+    let context = LayoutContext { viewport_width: 8.0, viewport_height: 8.0 };
+    let mut engine = Engine::new(&root, &stylesheet, context);
+    let options = PaintOptions { snap: SnapPolicy::Antialias, ..PaintOptions::default() };
+    engine.set_paint_options(options);
+
+    let rendered = engine.render().unwrap();
+    let region = Rect { x: 0.0, y: 0.0, width: 8.0, height: 8.0 };
+    let painted = paint::paint_region_with_options(engine.display_list(), region, 1.0, options);
+    assert_eq!(rendered.pixels, painted.pixels);
+    assert_eq!(rendered.pixels[0], Color { r: 127, g: 63, b: 191, a: 255 });
+}
@@ -0,0 +1,66 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::geometry::Rect;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::DisplayCommand;
+
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+
+fn engine(css: &str) -> Engine {
+    let root = html::parse("<html><div></div></html>");
+    let css = format!("html, div {{ display: block; }} div {{ width: 20px; height: 20px; \
+                       margin-left: 20px; margin-top: 20px; }} {}", css);
+    let stylesheet = css::parse(&css);
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    Engine::new(&root, &stylesheet, context)
+}
+
+#[test]
+fn shadows_are_painted_beneath_the_background_first_one_in_front() {
+    let engine = engine("div { box-shadow: 5px 5px #ff0000, -2px 0 4px 1px #0000ff; \
+                         background-color: #ffffff; }");
+    let items = engine.display_list().items();
+    let div = Rect { x: 20.0, y: 20.0, width: 20.0, height: 20.0 };
+    let position = |item: &DisplayCommand| items.iter().position(|i| i == item).unwrap();
+    let red = DisplayCommand::BoxShadow {
+        color: RED, x: 25.0, y: 25.0, width: 20.0, height: 20.0, blur: 0.0, border_box: div,
+    };
+    let blue = DisplayCommand::BoxShadow {
+        color: BLUE, x: 17.0, y: 19.0, width: 22.0, height: 22.0, blur: 4.0, border_box: div,
+    };
+    let background = DisplayCommand::SolidColor {
+        color: WHITE, x: 20.0, y: 20.0, width: 20.0, height: 20.0,
+    };
+    assert!(position(&blue) < position(&red));
+    assert!(position(&red) < position(&background));
+    assert_eq!(blue.bounds(), Rect { x: 11.0, y: 13.0, width: 34.0, height: 34.0 });
+}
+
+#[test]
+fn shadows_are_blurred_and_not_painted_within_their_box() {
+    let canvas = engine("div { box-shadow: 10px 0 #ff0000; }").render().unwrap();
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    // The box is transparent, but does not show its shadow through.
+    assert_eq!(pixel(35, 30), WHITE);
+    assert_eq!(pixel(45, 30), RED);
+    assert_eq!(pixel(51, 30), WHITE);
+
+    let canvas = engine("div { box-shadow: 10px 0 8px #ff0000; }").render().unwrap();
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    let red_at = |x: usize| 255 - pixel(x, 30).g;
+    // Half covered at the edge of the shadow, fading out past it.
+    assert!(red_at(49) > 128 && red_at(50) < 128);
+    assert!(red_at(45) > red_at(50) && red_at(50) > red_at(54) && red_at(54) > 0);
+    assert_eq!(pixel(35, 30), WHITE);
+    assert_eq!(pixel(70, 30), WHITE);
+
+    // Inset shadows are not supported, so the declaration is ignored.
+    let engine = engine("div { box-shadow: inset 1px 1px #000000; }");
+    let items = engine.display_list().items();
+    assert!(!items.iter().any(|item| matches!(item, DisplayCommand::BoxShadow { .. })));
+}
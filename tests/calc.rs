@@ -0,0 +1,51 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Value};
+use chameleon::geometry::Rect;
+use chameleon::html;
+use chameleon::layout::{self, LayoutContext};
+use chameleon::style::{self, Automatic, LengthPercentage};
+use std::convert::TryFrom;
+
+#[test]
+fn calc_expressions_reduce_to_a_length_and_a_percentage() {
+    let width = |expression: &str| {
+        let source = format!("p {{ width: {}; }}", expression);
+        let stylesheet = css::parse(&source);
+        let value = &stylesheet.rules()[0].declarations[0].value;
+        Automatic::<LengthPercentage>::try_from(value).map(|width| match width {
+            Automatic::Given(width) => width,
+            Automatic::Auto => panic!("auto width"),
+        })
+    };
+    assert_eq!(width("calc(100% - 20px)"), Ok(LengthPercentage::Calc(-20.0, 100.0)));
+    assert_eq!(width("calc((50% + 10px) * 2 - 5px / 5)"), Ok(LengthPercentage::Calc(19.0, 100.0)));
+    assert_eq!(width("calc(2 * calc(3px + 1px))"), Ok(LengthPercentage::Length(8.0)));
+    assert_eq!(width("calc(50% / 2)"), Ok(LengthPercentage::Percentage(25.0)));
+    assert!(width("calc(10px * 10px)").is_err());
+    assert!(width("calc(10px + 1)").is_err());
+    assert!(width("calc(10px / 0)").is_err());
+    assert!(width("calc(1 + 2)").is_err());
+
+    let stylesheet = css::parse("p { width: calc(100% - 20px); }");
+    assert_eq!(stylesheet.rules()[0].declarations[0].value, Value::Function("calc".into(), vec![
+        Value::Percentage(100.0), Value::Delimiter('-'), Value::Length(20.0, css::Unit::Px),
+    ]));
+}
+
+#[test]
+fn calc_percentages_resolve_against_the_containing_block() {
+    let root = html::parse("<div><p></p></div>");
+    let stylesheet = css::parse(
+        "div, p { display: block; } \
+         div { width: calc(50% + 100px); height: 40px; margin-left: calc(10% - 5px); } \
+         p { width: calc(100% - 2 * 20px); height: calc(50% + 1px); margin: calc(5% + 5px); }");
+    let context = LayoutContext { viewport_width: 400.0, viewport_height: 300.0 };
+    let layout = layout::layout_tree(&style::style_tree(&root, &stylesheet), context);
+    let div = layout.root().unwrap();
+    assert_eq!(div.geometry().content_box, Rect { x: 35.0, y: 0.0, width: 300.0, height: 40.0 });
+
+    // Vertical margins are percentages of the containing block's width too.
+    let p = div.children()[0].geometry();
+    assert_eq!(p.content_box, Rect { x: 55.0, y: 20.0, width: 260.0, height: 21.0 });
+}
@@ -0,0 +1,48 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::DisplayCommand;
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+    Color { r, g, b, a }
+}
+
+#[test]
+fn transparent_boxes_paint_nothing() {
+    let root = html::parse("<html><div></div><p></p></html>");
+    let stylesheet = css::parse("html, div, p { display: block; height: 10px; } \
+                                 html { background-color: TRANSPARENT; } \
+                                 div { background-color: #ff0000; border-width: 1px; } \
+                                 p { border-width: 2px; border-color: #0000ff; \
+                                     background-color: transparent; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+    let colors: Vec<Color> = engine.display_list().items().iter().filter_map(|item| match *item {
+        DisplayCommand::SolidColor { color, .. } => Some(color),
+        _ => None,
+    }).collect();
+    // The div's borders are transparent, and the paragraph has four blue ones.
+    assert_eq!(colors, [rgba(255, 0, 0, 255), rgba(0, 0, 255, 255), rgba(0, 0, 255, 255),
+                        rgba(0, 0, 255, 255), rgba(0, 0, 255, 255)]);
+}
+
+#[test]
+fn colors_composite_over_translucent_colors() {
+    let red = rgba(255, 0, 0, 255);
+    let half_blue = rgba(0, 0, 255, 128);
+    assert_eq!(half_blue.over(&red), rgba(127, 0, 128, 255));
+    assert_eq!(red.over(&half_blue), red);
+
+    // Transparent colors add nothing, whichever side they are on.
+    assert_eq!(half_blue.over(&Color::default()), half_blue);
+    assert_eq!(Color::default().over(&half_blue), half_blue);
+    assert_eq!(Color::default().over(&Color::default()), Color::default());
+
+    // Over a translucent color, the result is more opaque, but not darker.
+    let half_white = rgba(255, 255, 255, 128);
+    assert_eq!(half_white.over(&half_white), rgba(255, 255, 255, 192));
+    assert_eq!(rgba(255, 0, 0, 64).over(&rgba(255, 0, 0, 64)), rgba(255, 0, 0, 112));
+}
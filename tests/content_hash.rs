@@ -0,0 +1,32 @@
+extern crate chameleon;
+
+use chameleon::content_hash::Stage;
+use chameleon::engine::Engine;
+use chameleon::layout::LayoutContext;
+use chameleon::{css, html};
+
+const CONTEXT: LayoutContext = LayoutContext { viewport_width: 200.0, viewport_height: 100.0 };
+
+fn hashes(source: &str, stylesheet: &str) -> [u64; 3] {
+    let root = html::parse(source);
+    let engine = Engine::new(&root, &css::parse(stylesheet), CONTEXT);
+    [Stage::Style, Stage::Layout, Stage::DisplayList].map(|stage| engine.content_hash(stage))
+}
+
+#[test]
+fn hashes_change_only_with_their_stage() {
+    let html = "<div><p class=\"a\"></p><p></p></div>";
+    let css = "div, p { display: block; } p { height: 10px; background-color: #0000ff; }";
+    let base = hashes(html, css);
+    // Parsing again gives new node ids, but the same hashes.
+    assert_eq!(hashes(html, css), base);
+
+    // A new color changes the style and the display list, but not the layout.
+    let recolored = hashes(html, &format!("{} .a {{ background-color: #ff0000; }}", css));
+    assert!(recolored[0] != base[0] && recolored[2] != base[2]);
+    assert_eq!(recolored[1], base[1]);
+
+    // A new height changes every stage.
+    let resized = hashes(html, &format!("{} .a {{ height: 20px; }}", css));
+    assert!(resized.iter().zip(&base).all(|(a, b)| a != b));
+}
@@ -0,0 +1,61 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::dom;
+use chameleon::html;
+use chameleon::style::{self, StyledNode};
+use std::collections::HashMap;
+
+/// The text generated for every pseudo-element, in document order.
+fn generated(node: &StyledNode) -> Vec<String> {
+    let text = node.pseudo.map(|_| node.specified.content().text().unwrap_or("").to_string());
+    text.into_iter().chain(node.children.iter().flat_map(generated)).collect()
+}
+
+fn counters(source: &str, stylesheet: &str) -> Vec<String> {
+    let root = html::parse(source);
+    let stylesheet = css::Stylesheet::combine(vec![css::user_agent_stylesheet(),
+                                                   css::parse(stylesheet)]);
+    generated(&style::style_tree(&root, &stylesheet))
+}
+
+#[test]
+fn headings_are_numbered_by_counters_in_document_order() {
+    let texts = counters("<body><h1></h1><h2></h2><h2></h2><h1></h1><h2></h2></body>",
+                         "body { counter-reset: chapter section 10; } \
+                          h1 { counter-increment: chapter; counter-reset: section; } \
+                          h2 { counter-increment: section 2; } \
+                          h1::before { content: counter(chapter, decimal) \". \"; } \
+                          h2::before { content: counter(chapter) \".\" counter(section); } \
+                          body::after { content: counter(missing) counter(chapter, none); }");
+    assert_eq!(texts, ["1. ", "1.2", "1.4", "2. ", "2.2", "0"]);
+
+    let errors = style::check_stylesheet(&css::parse("p::before { content: counter(a, b, c); } \
+                                                       p::after { content: attr(title); } \
+                                                       p { counter-reset: a 1 2; }"));
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn nested_lists_count_their_items_in_separate_counters() {
+    let texts = counters("<ol><li></li><li><ol class='inner'><li></li><li></li></ol></li><li></li>\
+                          </ol>",
+                         "li::before { content: counters(list-item, \".\") \" \"; } \
+                          .inner { counter-reset: list-item 4; }");
+    assert_eq!(texts, ["1 ", "2 ", "2.5 ", "2.6 ", "3 "]);
+
+    // Items added later are counted when the list is restyled, and so are the items after them.
+    let mut root = html::parse("<ol><li></li><li></li></ol>");
+    let stylesheet = css::Stylesheet::combine(vec![
+        css::user_agent_stylesheet(),
+        css::parse("li::before { content: counter(list-item) \".\"; }"),
+    ]);
+    let mut styled = style::style_tree(&root, &stylesheet);
+    let (ol, first) = (root.id, root.children[0].id);
+    root.insert_child(ol, 0, dom::elem("li", HashMap::new(), Vec::new())).unwrap();
+    styled.restyle(&root, &stylesheet, ol);
+    assert_eq!(generated(&styled), ["1.", "2.", "3."]);
+    root.remove_child(ol, first).unwrap();
+    styled.restyle(&root, &stylesheet, ol);
+    assert_eq!(generated(&styled), ["1.", "2."]);
+}
@@ -0,0 +1,46 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color, Unit, Value};
+use chameleon::style::{self, Automatic, LengthPercentage};
+use chameleon::html;
+
+#[test]
+fn custom_properties_are_inherited_and_substituted() {
+    let root = html::parse("<div><p><span></span></p></div>");
+    let stylesheet = css::parse(
+        "div { --size: 10px; --edges: 1px 2px; --color: #ff0000; } \
+         p { --size: 20px; --double: var(--size) var(--size); gap: var(--edges); \
+             background-color: var(--missing, var(--color)); width: var(--size); } \
+         span { height: var(--size); width: var(--missing); --loop: var(--back, 1px); \
+                --back: var(--loop); margin: var(--loop, 3px); }");
+    let styled = style::style_tree(&root, &stylesheet);
+    let div = &styled.specified;
+    let p = &styled.children[0].specified;
+    let span = &styled.children[0].children[0].specified;
+
+    assert_eq!(div.custom_property("--size"), Some(&Value::Length(10.0, Unit::Px)));
+    assert_eq!(p.custom_property("--size"), Some(&Value::Length(20.0, Unit::Px)));
+    let twenty = Value::Length(20.0, Unit::Px);
+    assert_eq!(p.custom_property("--double"), Some(&Value::List(vec![twenty.clone(), twenty])));
+    assert_eq!(p.width(), Automatic::Given(LengthPercentage::Length(20.0)));
+    assert_eq!((p.row_gap(), p.column_gap()), (1.0, 2.0));
+    assert_eq!(p.background_color(), Color { r: 255, g: 0, b: 0, a: 255 });
+
+    // Inherited values are already substituted, and undefined references are ignored.
+    assert_eq!(span.height(), Automatic::Given(LengthPercentage::Length(20.0)));
+    assert_eq!(span.width(), Automatic::Auto);
+    // Properties in a cycle are invalid, even with fallbacks.
+    assert_eq!(span.custom_property("--loop"), None);
+    assert_eq!(span.custom_property("--back"), None);
+    assert_eq!(span.margin_left(), Automatic::Given(LengthPercentage::Length(3.0)));
+    assert_eq!(span.custom_property("--edges"), div.custom_property("--edges"));
+}
+
+#[test]
+fn declarations_with_references_are_not_checked_ahead_of_time() {
+    let stylesheet = css::parse("p { --x: anything; width: var(--x); height: nonsense; }");
+    let errors = style::check_stylesheet(&stylesheet);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message,
+               "height: expected auto/length/percentage but found \"nonsense\"");
+}
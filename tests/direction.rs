@@ -0,0 +1,49 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+
+/// The left edge and width of the border box of each child of the `div`, which is 100px wide and
+/// 20px from the left of the page.
+fn placement(direction: &str, stylesheet: &str) -> Vec<(f32, f32)> {
+    let root = html::parse("<html><div><p id='a'></p><p id='b'></p><p id='c'></p></div></html>");
+    let css = format!("html, div, p {{ display: block; margin: 0; }} p {{ height: 10px; }} \
+                       div {{ width: 100px; margin-left: 20px; direction: {}; }} {}",
+                      direction, stylesheet);
+    let stylesheet = css::parse(&css);
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 200.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+    let layout = engine.layout_tree();
+    root.children[0].children.iter().map(|child| {
+        let border_box = layout.box_for_node(child).unwrap().border_box;
+        (border_box.x, border_box.width)
+    }).collect()
+}
+
+#[test]
+fn over_constrained_boxes_are_flush_with_the_start_edge() {
+    let stylesheet = "#a { width: 50px; margin-left: 10px; margin-right: 10px; } \
+                      #b { width: 50px; } \
+                      #c { width: 150px; }";
+    assert_eq!(placement("ltr", stylesheet), [(30.0, 50.0), (20.0, 50.0), (20.0, 150.0)]);
+    // The left margins give way instead of the right ones, so wide boxes stick out to the left.
+    // The div itself is still placed from the left, by the direction of its own container.
+    assert_eq!(placement("rtl", stylesheet), [(60.0, 50.0), (70.0, 50.0), (-30.0, 150.0)]);
+}
+
+#[test]
+fn auto_margins_resolve_the_same_way_in_both_directions() {
+    let stylesheet = "#a { width: 50px; margin-left: auto; margin-right: 10px; } \
+                      #b { width: 50px; margin-left: 10px; margin-right: auto; } \
+                      #c { width: 40px; margin-left: auto; margin-right: auto; }";
+    for direction in &["ltr", "rtl"] {
+        assert_eq!(placement(direction, stylesheet), [(60.0, 50.0), (30.0, 50.0), (50.0, 40.0)],
+                   "{}", direction);
+    }
+
+    // Auto widths fill the container, and negative margins widen them past either edge.
+    let stylesheet = "#a { margin-right: -10px; } #b { margin-left: -10px; }";
+    assert_eq!(placement("rtl", stylesheet), [(20.0, 110.0), (10.0, 110.0), (20.0, 100.0)]);
+}
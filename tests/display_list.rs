@@ -0,0 +1,140 @@
+extern crate chameleon;
+
+use chameleon::css::Color;
+use chameleon::geometry::Rect;
+use chameleon::paint::{self, Change, DisplayCommand, DisplayList, DisplayListDiff};
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const GREEN: Color = Color { r: 0, g: 255, b: 0, a: 255 };
+const TRANSLUCENT: Color = Color { r: 0, g: 0, b: 255, a: 128 };
+
+const VIEWPORT: Rect = Rect { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+
+fn rect(color: Color, x: f32, y: f32, width: f32, height: f32) -> DisplayCommand {
+    DisplayCommand::SolidColor { color, x, y, width, height }
+}
+
+fn list(items: Vec<DisplayCommand>) -> DisplayList {
+    let mut list = DisplayList::new();
+    for item in items {
+        list.push(item);
+    }
+    list
+}
+
+#[test]
+fn culls_items_outside_the_viewport() {
+    let mut display_list = list(vec![
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+        rect(RED, 100.0, 10.0, 20.0, 20.0),
+        rect(RED, -50.0, -50.0, 50.0, 50.0),
+        rect(RED, 10.0, 500.0, 20.0, 20.0),
+        rect(RED, 90.0, 90.0, 20.0, 20.0),
+    ]);
+    display_list.optimize(VIEWPORT);
+    assert_eq!(display_list.items(), &[
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+        rect(RED, 90.0, 90.0, 20.0, 20.0),
+    ]);
+}
+
+#[test]
+fn drops_items_covered_by_a_later_opaque_item() {
+    let mut display_list = list(vec![
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+        rect(RED, 15.0, 15.0, 5.0, 5.0),
+        rect(GREEN, 0.0, 0.0, 50.0, 50.0),
+    ]);
+    display_list.optimize(VIEWPORT);
+    assert_eq!(display_list.items(), &[rect(GREEN, 0.0, 0.0, 50.0, 50.0)]);
+}
+
+#[test]
+fn keeps_items_behind_translucent_or_partial_covers() {
+    let mut display_list = list(vec![
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+        rect(TRANSLUCENT, 0.0, 0.0, 50.0, 50.0),
+        rect(RED, 60.0, 60.0, 20.0, 20.0),
+        rect(GREEN, 65.0, 55.0, 20.0, 20.0),
+    ]);
+    let before = display_list.clone();
+    display_list.optimize(VIEWPORT);
+    assert_eq!(display_list, before);
+}
+
+#[test]
+fn drops_items_covered_within_the_viewport() {
+    // The first item sticks out of the viewport, but is hidden wherever it is visible.
+    let mut display_list = list(vec![
+        rect(RED, 50.0, 50.0, 100.0, 100.0),
+        rect(GREEN, 40.0, 40.0, 60.0, 60.0),
+    ]);
+    display_list.optimize(VIEWPORT);
+    assert_eq!(display_list.items(), &[rect(GREEN, 40.0, 40.0, 60.0, 60.0)]);
+}
+
+#[test]
+fn does_not_change_the_painted_pixels() {
+    let mut display_list = list(vec![
+        rect(GREEN, 0.0, 0.0, 100.0, 100.0),
+        rect(RED, 10.5, 10.5, 20.0, 20.0),
+        rect(TRANSLUCENT, 5.0, 5.0, 40.0, 40.0),
+        rect(RED, 80.0, 80.0, 40.0, 40.0),
+        rect(GREEN, 70.25, 70.25, 50.0, 50.0),
+        rect(RED, 200.0, 0.0, 10.0, 10.0),
+    ]);
+    let before = paint::paint_region(&display_list, VIEWPORT, 1.0);
+    display_list.optimize(VIEWPORT);
+    assert_eq!(display_list.len(), 4);
+    let after = paint::paint_region(&display_list, VIEWPORT, 1.0);
+    assert!(before.pixels == after.pixels);
+}
+
+#[test]
+fn diff_finds_added_removed_and_moved_items() {
+    let old = list(vec![
+        rect(GREEN, 0.0, 0.0, 100.0, 100.0),
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+        rect(TRANSLUCENT, 5.0, 5.0, 40.0, 40.0),
+        rect(RED, 80.0, 80.0, 10.0, 10.0),
+    ]);
+    let new = list(vec![
+        rect(GREEN, 0.0, 0.0, 100.0, 100.0),
+        rect(RED, 15.0, 10.0, 20.0, 20.0),
+        rect(GREEN, 50.0, 50.0, 5.0, 5.0),
+        rect(RED, 80.0, 80.0, 10.0, 10.0),
+    ]);
+    assert_eq!(DisplayListDiff::compute(&old, &new), vec![
+        Change::Removed { old: 2 },
+        Change::Moved { old: 1, new: 1 },
+        Change::Added { new: 2 },
+    ]);
+    assert_eq!(DisplayListDiff::compute(&old, &old), vec![]);
+}
+
+#[test]
+fn diff_reports_reordered_items_as_moved() {
+    let old = list(vec![
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+        rect(GREEN, 20.0, 20.0, 20.0, 20.0),
+        rect(TRANSLUCENT, 30.0, 30.0, 20.0, 20.0),
+    ]);
+    let new = list(vec![
+        rect(GREEN, 20.0, 20.0, 20.0, 20.0),
+        rect(TRANSLUCENT, 30.0, 30.0, 20.0, 20.0),
+        rect(RED, 10.0, 10.0, 20.0, 20.0),
+    ]);
+    assert_eq!(DisplayListDiff::compute(&old, &new), vec![Change::Moved { old: 0, new: 2 }]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    let original = list(vec![
+        rect(GREEN, 0.0, 0.0, 100.0, 100.0),
+        rect(TRANSLUCENT, 10.5, 20.0, 30.0, 40.25),
+    ]);
+    let json = original.to_json();
+    assert!(json.contains("\"solid-color\""));
+    assert_eq!(DisplayList::from_json(&json).unwrap(), original);
+}
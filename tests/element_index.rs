@@ -0,0 +1,59 @@
+extern crate chameleon;
+
+use chameleon::document::Document;
+use chameleon::dom::{self, NodeId};
+use chameleon::html;
+use chameleon::index::ElementIndex;
+use std::collections::HashMap;
+
+fn ids(nodes: Vec<&dom::Node>) -> Vec<NodeId> {
+    nodes.into_iter().map(|node| node.id).collect()
+}
+
+#[test]
+fn the_index_finds_elements_in_document_order() {
+    let root = html::parse("<main><section class='a b'><p id='x' class='b'></p></section>\
+                            <p class='a'></p><P id='x'></P><span></span></main>");
+    let index = ElementIndex::new(&root);
+    let section = root.children[0].id;
+    let (first, second, third) = (root.children[0].children[0].id, root.children[1].id,
+                                  root.children[2].id);
+
+    assert_eq!(index.element_by_id("x"), Some(first));
+    assert_eq!(index.elements_with_class("a"), [section, second]);
+    assert_eq!(index.elements_with_class("b"), [section, first]);
+    assert_eq!(index.elements_with_tag("P"), [first, second, third]);
+    assert!(index.element_by_id("missing").is_none());
+
+    for selectors in &["p", ".b", "#x, span", "p.a, section.b", "*", "span::after", "p,"] {
+        assert_eq!(ids(index.query_selector_all(&root, selectors)),
+                   ids(root.query_selector_all(selectors)), "{}", selectors);
+    }
+    assert_eq!(index.query_selector(&root, ".b").map(|node| node.id), Some(section));
+}
+
+#[test]
+fn mutating_a_document_updates_its_index() {
+    let mut document = Document::load_html("<div><ul id='list'><li class='item'></li></ul>\
+                                            <p id='note'></p></div>", "");
+    let list = document.element_by_id("list").unwrap().id;
+    let note = document.element_by_id("note").unwrap().id;
+    let div = document.root().id;
+
+    let mut attributes = HashMap::new();
+    attributes.insert("class".into(), "item new".into());
+    let item = dom::elem("li", attributes, Vec::new());
+    let item_id = item.id;
+    document.mutate(list, |root| root.insert_child(list, 0, item)).unwrap();
+    assert_eq!(ids(document.query_selector_all(".item")).first(), Some(&item_id));
+    assert_eq!(document.query_selector(".new").map(|node| node.id), Some(item_id));
+
+    document.mutate(div, |root| root.remove_child(div, list)).unwrap();
+    assert!(document.element_by_id("list").is_none());
+    assert!(document.query_selector_all("li").is_empty());
+    assert_eq!(document.element_by_id("note").map(|node| node.id), Some(note));
+
+    document.mutate(note, |root| root.set_attribute(note, "id", "aside")).unwrap();
+    assert!(document.element_by_id("note").is_none());
+    assert_eq!(document.index().element_by_id("aside"), Some(note));
+}
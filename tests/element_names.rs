@@ -0,0 +1,62 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::dom::{self, NodeType, SVG_NAMESPACE};
+use chameleon::html;
+use chameleon::snapshot;
+use chameleon::style::{self, Automatic, LengthPercentage};
+use std::collections::HashMap;
+
+#[test]
+fn html_names_are_case_insensitive() {
+    let (root, errors) = html::parse_with_errors(
+        "<DIV ID='main' Class='wide'><P>one</p><Span DATA-X='1'/></Div>");
+    assert!(errors.is_empty(), "{:?}", errors);
+    match root.node_type {
+        NodeType::Element(ref elem) => {
+            assert_eq!(elem.tag, "div");
+            assert_eq!(elem.id(), Some("main"));
+            assert!(elem.classes().contains("wide"));
+            assert!(elem.is_html());
+        }
+        NodeType::Text(_) => panic!("expected an element"),
+    }
+    match root.children[1].node_type {
+        NodeType::Element(ref elem) => {
+            assert_eq!(elem.tag, "span");
+            assert_eq!(elem.attributes.get("data-x").map(|value| &**value), Some("1"));
+        }
+        NodeType::Text(_) => panic!("expected an element"),
+    }
+
+    // Type selectors match HTML elements whatever the case of either.
+    let upper = dom::elem("P", HashMap::new(), Vec::new());
+    let stylesheet = css::parse("DIV { width: 10px; } p { width: 20px; }");
+    let styled = style::style_tree(&root, &stylesheet);
+    assert_eq!(styled.specified.width(), Automatic::Given(LengthPercentage::Length(10.0)));
+    let styled = style::style_tree(&upper, &stylesheet);
+    assert_eq!(styled.specified.width(), Automatic::Given(LengthPercentage::Length(20.0)));
+}
+
+#[test]
+fn foreign_elements_keep_their_namespace_and_case() {
+    let root = dom::elem("div", HashMap::new(), vec![
+        dom::elem_ns(SVG_NAMESPACE, "foreignObject", HashMap::new(), Vec::new()),
+    ]);
+    let stylesheet = css::parse("foreignobject { width: 10px; } foreignObject { height: 20px; }");
+    let styled = style::style_tree(&root, &stylesheet);
+    let object = &styled.children[0].specified;
+    assert_eq!(object.width(), Automatic::Auto);
+    assert_eq!(object.height(), Automatic::Given(LengthPercentage::Length(20.0)));
+
+    let saved = snapshot::save(&root, &stylesheet);
+    let (restored, _) = snapshot::restore(&saved).unwrap();
+    match restored.children[0].node_type {
+        NodeType::Element(ref elem) => {
+            assert_eq!(elem.tag, "foreignObject");
+            assert_eq!(elem.namespace.as_deref(), Some(SVG_NAMESPACE));
+            assert!(!elem.has_tag("foreignobject"));
+        }
+        NodeType::Text(_) => panic!("expected an element"),
+    }
+}
@@ -0,0 +1,101 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::engine::Engine;
+use chameleon::events::{self, Event, EventListeners, EventPhase, Hover};
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use std::sync::{Arc, Mutex};
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// Log each call of the listener as `name phase`.
+fn logger(log: &Log, name: &'static str) -> impl FnMut(&mut Event) + Send + Sync + 'static {
+    let log = log.clone();
+    move |event: &mut Event| {
+        let phase = match event.phase() {
+            EventPhase::None => "none",
+            EventPhase::Capturing => "capture",
+            EventPhase::AtTarget => "target",
+            EventPhase::Bubbling => "bubble",
+        };
+        log.lock().unwrap().push(format!("{} {}", name, phase));
+    }
+}
+
+#[test]
+fn events_are_captured_then_bubble() {
+    let root = html::parse("<div><p><span></span></p></div>");
+    let (div, p, span) = (root.id, root.children[0].id, root.children[0].children[0].id);
+    let log = Log::default();
+    let mut listeners = EventListeners::new();
+    listeners.add(div, "click", true, logger(&log, "div"));
+    listeners.add(div, "click", false, logger(&log, "div"));
+    listeners.add(p, "click", false, logger(&log, "p"));
+    listeners.add(span, "click", false, logger(&log, "span"));
+    listeners.add(span, "click", true, logger(&log, "span"));
+    listeners.add(span, "keydown", false, logger(&log, "other"));
+
+    let mut click = Event::new("click", true, true);
+    assert!(events::dispatch_event(&root, &mut listeners, span, &mut click));
+    assert_eq!(*log.lock().unwrap(), ["div capture", "span target", "span target", "p bubble",
+                                      "div bubble"]);
+    assert_eq!((click.target(), click.current_target()), (Some(span), None));
+
+    // Events that do not bubble stop at the target.
+    log.lock().unwrap().clear();
+    events::dispatch_event(&root, &mut listeners, p, &mut Event::new("click", false, true));
+    assert_eq!(*log.lock().unwrap(), ["div capture", "p target"]);
+
+    // Stopping propagation at the target skips the ancestors, and canceling is reported.
+    log.lock().unwrap().clear();
+    let stopper = listeners.add(p, "click", false, |event: &mut Event| {
+        event.stop_propagation();
+        event.prevent_default();
+    });
+    let mut click = Event::new("click", true, true);
+    assert!(!events::dispatch_event(&root, &mut listeners, p, &mut click));
+    assert_eq!(*log.lock().unwrap(), ["div capture", "p target"]);
+    assert!(listeners.remove(stopper));
+    assert!(!listeners.remove(stopper));
+
+    // Non-cancelable events cannot be canceled, and immediate stops skip later listeners.
+    log.lock().unwrap().clear();
+    listeners.add(div, "click", true, |event: &mut Event| {
+        event.prevent_default();
+        event.stop_immediate_propagation();
+    });
+    let mut uncancelable = Event::new("click", true, false);
+    assert!(events::dispatch_event(&root, &mut listeners, span, &mut uncancelable));
+    assert_eq!(*log.lock().unwrap(), ["div capture"]);
+}
+
+#[test]
+fn hit_testing_targets_the_topmost_element() {
+    let root = html::parse("<html><div id='a'><p></p></div><div id='b'></div></html>");
+    let stylesheet = css::parse("html, div, p { display: block; margin: 0; } \
+                                 div { height: 20px; } p { height: 10px; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+    let (a, p, b) = (root.children[0].id, root.children[0].children[0].id, root.children[1].id);
+    let log = Log::default();
+    let mut listeners = EventListeners::new();
+    for &(node, name) in &[(a, "a"), (p, "p"), (b, "b")] {
+        listeners.add(node, "mouseover", false, logger(&log, name));
+        listeners.add(node, "mouseout", false, logger(&log, name));
+    }
+
+    let mut click = Event::new("click", true, true);
+    let layout = engine.layout_tree();
+    let hit = events::dispatch_at(&root, layout, &mut listeners, 5.0, 15.0, &mut click);
+    assert_eq!(hit, Some((a, true)));
+    let miss = events::dispatch_at(&root, layout, &mut listeners, 5.0, 50.0, &mut click);
+    assert_eq!(miss, None);
+
+    let mut hover = Hover::new();
+    assert!(hover.pointer_moved(&root, layout, &mut listeners, 5.0, 5.0));
+    assert!(!hover.pointer_moved(&root, layout, &mut listeners, 6.0, 6.0));
+    assert!(hover.pointer_moved(&root, layout, &mut listeners, 5.0, 25.0));
+    assert_eq!(hover.hovered(), Some(b));
+    assert_eq!(*log.lock().unwrap(), ["p target", "a bubble", "p target", "a bubble", "b target"]);
+}
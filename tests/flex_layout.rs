@@ -0,0 +1,86 @@
+#![cfg(feature = "flexbox")]
+
+extern crate chameleon;
+
+use chameleon::document::{Document, Viewport};
+use chameleon::geometry::Rect;
+
+/// Lay out `html` with `css` in a 100px-wide viewport, and return the border boxes of the
+/// elements with the given ids.
+fn border_boxes(html: &str, css: &str, ids: &[&str]) -> Vec<Rect> {
+    let mut document = Document::load_html(html, css);
+    document.layout(Viewport { width: 100.0, height: 100.0 }).unwrap();
+    let layout = document.engine().unwrap().layout_tree();
+    ids.iter()
+        .map(|id| layout.box_for_node(document.element_by_id(id).unwrap()).unwrap().border_box)
+        .collect()
+}
+
+const ROW: &str = "<div id=\"row\"><span id=\"a\"></span><span id=\"b\"></span></div>";
+
+#[test]
+fn free_space_is_distributed_in_proportion_to_flex_grow() {
+    let css = "div { display: flex; width: 100px; } span { display: block; height: 10px; }";
+    let grow = format!("{} span {{ flex-grow: 1; }}", css);
+    assert_eq!(border_boxes(ROW, &grow, &["a", "b"]), [
+        Rect { x: 0.0, y: 0.0, width: 50.0, height: 10.0 },
+        Rect { x: 50.0, y: 0.0, width: 50.0, height: 10.0 },
+    ]);
+
+    // Growth is added to the flex basis, here the specified width.
+    let uneven = format!("{} #a {{ width: 10px; flex-grow: 1; }} \
+                          #b {{ width: 30px; flex-grow: 3; }}", css);
+    assert_eq!(border_boxes(ROW, &uneven, &["a", "b"]), [
+        Rect { x: 0.0, y: 0.0, width: 25.0, height: 10.0 },
+        Rect { x: 25.0, y: 0.0, width: 75.0, height: 10.0 },
+    ]);
+}
+
+#[test]
+fn items_are_justified_along_the_main_axis_and_aligned_across_it() {
+    let css = "div { display: flex; width: 100px; height: 40px; } \
+               span { display: block; width: 20px; } #a { height: 10px; } #b { height: 20px; }";
+    let boxes = |extra: &str| {
+        border_boxes(ROW, &format!("{} div {{ {} }}", css, extra), &["a", "b"])
+    };
+
+    // Items that have a height are not stretched across the line.
+    assert_eq!(boxes(""), [
+        Rect { x: 0.0, y: 0.0, width: 20.0, height: 10.0 },
+        Rect { x: 20.0, y: 0.0, width: 20.0, height: 20.0 },
+    ]);
+    assert_eq!(boxes("justify-content: space-between; align-items: center;"), [
+        Rect { x: 0.0, y: 15.0, width: 20.0, height: 10.0 },
+        Rect { x: 80.0, y: 10.0, width: 20.0, height: 20.0 },
+    ]);
+    assert_eq!(boxes("justify-content: center; align-items: flex-end;"), [
+        Rect { x: 30.0, y: 30.0, width: 20.0, height: 10.0 },
+        Rect { x: 50.0, y: 20.0, width: 20.0, height: 20.0 },
+    ]);
+}
+
+#[test]
+fn items_overflowing_a_wrapping_row_move_to_the_next_line() {
+    let html = "<div id=\"row\"><span id=\"a\"></span><span id=\"b\"></span>\
+                <span id=\"c\"></span></div>";
+    let css = "div { display: flex; width: 100px; } \
+               span { display: block; width: 40px; height: 10px; } #b { height: 20px; }";
+    let boxes = |extra: &str| {
+        border_boxes(html, &format!("{} div {{ {} }}", css, extra), &["row", "a", "b", "c"])
+    };
+
+    // A single line shrinks its items to fit instead.
+    assert_eq!(boxes(""), [
+        Rect { x: 0.0, y: 0.0, width: 100.0, height: 20.0 },
+        Rect { x: 0.0, y: 0.0, width: 100.0 / 3.0, height: 10.0 },
+        Rect { x: 100.0 / 3.0, y: 0.0, width: 100.0 / 3.0, height: 20.0 },
+        Rect { x: 200.0 / 3.0, y: 0.0, width: 100.0 / 3.0, height: 10.0 },
+    ]);
+    // The second line starts below the tallest item of the first.
+    assert_eq!(boxes("flex-wrap: wrap;"), [
+        Rect { x: 0.0, y: 0.0, width: 100.0, height: 30.0 },
+        Rect { x: 0.0, y: 0.0, width: 40.0, height: 10.0 },
+        Rect { x: 40.0, y: 0.0, width: 40.0, height: 20.0 },
+        Rect { x: 0.0, y: 20.0, width: 40.0, height: 10.0 },
+    ]);
+}
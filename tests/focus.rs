@@ -0,0 +1,56 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+
+#[test]
+fn tab_order_puts_positive_tabindex_first_and_skips_the_unfocusable() {
+    let root = html::parse("<html>\
+        <a href=\"/\">Home</a>\
+        <a>No link</a>\
+        <button disabled=\"\">Off</button>\
+        <div tabindex=\"2\">Two</div>\
+        <input type=\"hidden\"/>\
+        <p tabindex=\"-1\">Script only</p>\
+        <textarea></textarea>\
+        <div tabindex=\"1\">One</div>\
+        <button tabindex=\"1\" style=\"display: none\">Hidden</button>\
+        </html>");
+    let stylesheet = css::user_agent_stylesheet();
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 200.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+
+    let order: Vec<_> = engine.tab_order(&root).iter().map(|entry| entry.node).collect();
+    let children = &root.children;
+    assert_eq!(order, [children[7].id, children[3].id, children[0].id, children[6].id]);
+}
+
+#[test]
+fn focus_moves_in_tab_order_and_restyles_focus_selectors() {
+    let root = html::parse("<html><div tabindex=\"0\"></div><div tabindex=\"0\"></div></html>");
+    let stylesheet = css::parse("html, div { display: block; } div { height: 10px; } \
+                                 div:focus { height: 30px; }");
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 200.0 };
+    let mut engine = Engine::new(&root, &stylesheet, context);
+    let (first, second) = (root.children[0].id, root.children[1].id);
+
+    let rect = engine.focus_next(&root, &stylesheet).unwrap().unwrap();
+    assert_eq!(engine.focused(), Some(first));
+    assert_eq!((rect.y, rect.height), (0.0, 30.0));
+
+    let rect = engine.focus_next(&root, &stylesheet).unwrap().unwrap();
+    assert_eq!(engine.focused(), Some(second));
+    assert_eq!((rect.y, rect.height), (10.0, 30.0));
+
+    // Past the last element, the focus wraps around; Shift+Tab goes back.
+    engine.focus_next(&root, &stylesheet).unwrap();
+    assert_eq!(engine.focused(), Some(first));
+    engine.focus_prev(&root, &stylesheet).unwrap();
+    assert_eq!(engine.focused(), Some(second));
+
+    engine.focus(&root, &stylesheet, None).unwrap();
+    let html = engine.layout_tree().root().unwrap();
+    assert_eq!(html.geometry().content_box.height, 20.0);
+}
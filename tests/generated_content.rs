@@ -0,0 +1,49 @@
+extern crate chameleon;
+
+use chameleon::css::{self, PseudoElement, Value};
+use chameleon::layout::{self, LayoutContext};
+use chameleon::{html, style};
+
+#[test]
+fn pseudo_elements_are_parsed_with_the_specificity_of_a_type() {
+    let (stylesheet, errors) = css::parse_with_errors(
+        "p.note::before, p:after { content: \"\\\"x\\\"\" 'y'; } \
+         p::first-line { color: #000000; }");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "unsupported pseudo-element ::first-line");
+
+    let rule = &stylesheet.rules()[0];
+    let selectors: Vec<String> = rule.selectors.iter().map(ToString::to_string).collect();
+    assert_eq!(selectors, ["p.note::before", "p::after"]);
+    assert_eq!(rule.selectors[0].specificity(), (0, 1, 2));
+    let css::Selector::Simple(ref simple) = rule.selectors[1];
+    assert_eq!(simple.pseudo_element, Some(PseudoElement::After));
+    assert_eq!(rule.declarations[0].value,
+               Value::List(vec![Value::String("\"x\"".into()), Value::String("y".into())]));
+}
+
+#[test]
+fn generated_content_is_laid_out_as_the_first_and_last_children() {
+    let root = html::parse("<div><p></p></div>");
+    let stylesheet = css::parse(
+        "div, p { display: block; } \
+         div::before { content: \"\"; display: block; height: 10px; } \
+         div::after { content: none; display: block; height: 5px; } \
+         p::after { content: \"!\"; display: block; height: 5px; }");
+    let styled = style::style_tree(&root, &stylesheet);
+
+    // The div's `::after` has no content, so it is not generated.
+    assert_eq!(styled.children.len(), 2);
+    assert_eq!(styled.children[0].pseudo, Some(PseudoElement::Before));
+    assert_eq!(styled.children[1].node, root.children[0].id);
+    let after = &styled.children[1].children[0];
+    assert_eq!(after.pseudo, Some(PseudoElement::After));
+    assert_eq!(after.specified.content().text(), Some("!"));
+
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let layout = layout::layout_tree(&styled, context);
+    let div = layout.root().unwrap();
+    assert_eq!(div.geometry().content_box.height, 15.0);
+    let p = div.children()[1].geometry().content_box;
+    assert_eq!((p.y, p.height), (10.0, 5.0));
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "gpu")]
+
+extern crate chameleon;
+
+use chameleon::css::Color;
+use chameleon::geometry::{Point, Rect, Transform};
+use chameleon::paint::gpu::GpuPainter;
+use chameleon::paint::{self, DisplayCommand, DisplayList, GradientShape, GradientStop,
+                       PaintOptions, SnapPolicy};
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+const TRANSLUCENT: Color = Color { r: 0, g: 128, b: 0, a: 96 };
+
+/// The painter, or `None` (after saying so) on systems without a GPU or a software adapter.
+fn painter() -> Option<GpuPainter> {
+    match GpuPainter::headless() {
+        Ok(painter) => Some(painter),
+        Err(error) => {
+            eprintln!("skipping: no GPU to paint with ({})", error);
+            None
+        }
+    }
+}
+
+fn solid(color: Color, x: f32, y: f32, width: f32, height: f32) -> DisplayCommand {
+    DisplayCommand::SolidColor { color, x, y, width, height }
+}
+
+/// Boxes at fractional positions, overlapping translucent boxes, a gradient, a shadow, and a
+/// rotated box.
+fn scene() -> DisplayList {
+    let mut list = DisplayList::new();
+    list.push(DisplayCommand::BoxShadow {
+        color: Color { a: 160, ..BLUE }, x: 6.0, y: 6.0, width: 20.0, height: 12.0, blur: 4.0,
+        border_box: Rect { x: 4.0, y: 4.0, width: 20.0, height: 12.0 },
+    });
+    list.push(solid(RED, 4.0, 4.0, 20.0, 12.0));
+    list.push(solid(TRANSLUCENT, 10.3, 8.6, 17.45, 9.2));
+    list.push(solid(TRANSLUCENT, 20.5, 12.25, 0.4, 15.0));
+    list.push(DisplayCommand::Gradient {
+        x: 30.0, y: 2.5, width: 24.0, height: 20.0,
+        shape: GradientShape::Linear { start: Point { x: 30.0, y: 0.0 },
+                                       end: Point { x: 54.0, y: 0.0 } },
+        stops: vec![GradientStop { offset: 0.0, color: RED },
+                    GradientStop { offset: 1.0, color: Color { a: 0, ..BLUE } }],
+    });
+    list.push(DisplayCommand::Gradient {
+        x: 4.0, y: 24.0, width: 20.0, height: 20.0,
+        shape: GradientShape::Radial { center: Point { x: 14.0, y: 34.0 }, radius_x: 10.0,
+                                       radius_y: 6.0 },
+        stops: vec![GradientStop { offset: 0.25, color: BLUE },
+                    GradientStop { offset: 0.75, color: RED }],
+    });
+    let rotation = Transform::translation(-40.0, -34.0).then(Transform::rotation(30.0))
+        .then(Transform::translation(40.0, 34.0));
+    list.push(DisplayCommand::PushTransform { transform: rotation });
+    list.push(solid(BLUE, 32.0, 28.0, 16.0, 12.0));
+    list.push(DisplayCommand::PopTransform);
+    list
+}
+
+#[test]
+fn the_gpu_paints_what_the_cpu_paints() {
+    let painter = match painter() {
+        Some(painter) => painter,
+        None => return,
+    };
+    let list = scene();
+    let region = Rect { x: 0.0, y: 0.0, width: 60.0, height: 50.0 };
+    for &snap in &[SnapPolicy::Floor, SnapPolicy::Round, SnapPolicy::Antialias] {
+        for &scale in &[1.0, 1.5, 2.0] {
+            let options = PaintOptions { snap, ..PaintOptions::default() };
+            let cpu = paint::paint_region_with_options(&list, region, scale, options);
+            let gpu = painter.paint_region(&list, region, scale, snap).unwrap();
+            let diff = gpu.diff(&cpu).unwrap();
+            assert!(diff.max_difference <= 2, "{:?} at scale {}: {:?}", snap, scale, diff);
+        }
+    }
+}
+
+#[test]
+fn snapped_opaque_boxes_paint_exactly_the_same_pixels() {
+    let painter = match painter() {
+        Some(painter) => painter,
+        None => return,
+    };
+    let mut list = DisplayList::new();
+    for i in 0..8 {
+        let offset = 0.7 + i as f32 * 5.3;
+        let color = if i % 2 == 0 { RED } else { BLUE };
+        list.push(solid(color, offset, 1.2, 5.3, 0.3 + i as f32));
+    }
+    let region = Rect { x: 0.5, y: 0.0, width: 40.0, height: 12.0 };
+    for &scale in &[1.0, 1.25, 3.0] {
+        let cpu = paint::paint_region(&list, region, scale);
+        let gpu = painter.paint_region(&list, region, scale, SnapPolicy::default()).unwrap();
+        assert_eq!(gpu.diff(&cpu).unwrap().differing_pixels, 0, "at scale {}", scale);
+    }
+}
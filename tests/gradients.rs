@@ -0,0 +1,86 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color, Value};
+use chameleon::engine::Engine;
+use chameleon::geometry::Point;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::{DisplayCommand, GradientShape, GradientStop};
+use chameleon::snapshot;
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+fn with_background(background: &str) -> Engine {
+    let root = html::parse("<html><div></div></html>");
+    let css = format!("html, div {{ display: block; }} \
+                       div {{ width: 100px; height: 50px; background: {}; }}", background);
+    let stylesheet = css::parse(&css);
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    Engine::new(&root, &stylesheet, context)
+}
+
+fn gradient(engine: &Engine) -> (GradientShape, Vec<GradientStop>) {
+    engine.display_list().items().iter()
+        .find_map(|item| match *item {
+            DisplayCommand::Gradient { shape, ref stops, .. } => Some((shape, stops.clone())),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn gradients_are_laid_out_across_the_box_with_their_stops_resolved() {
+    let stylesheet = css::parse("div { background: linear-gradient(to right, #ff0000 20px, \
+                                 #ffffff, #0000ff 40%, #ffffff 10%, #0000ff); }");
+    let value = &stylesheet.rules()[0].declarations[0].value;
+    assert!(matches!(value, Value::Function(name, _) if name == "linear-gradient"));
+    let root = html::parse("<html></html>");
+    let saved = snapshot::save(&root, &stylesheet);
+    let (_, restored) = snapshot::restore(&saved).unwrap();
+    assert_eq!(restored.rules()[0].declarations[0].value, *value);
+
+    let engine = with_background("linear-gradient(to right, #ff0000 20px, #ffffff, #0000ff 40%, \
+                         #ffffff 10%, #0000ff)");
+    let (shape, stops) = gradient(&engine);
+    let start = Point { x: 0.0, y: 25.0 };
+    let end = Point { x: 100.0, y: 25.0 };
+    assert_eq!(shape, GradientShape::Linear { start, end });
+    // The unpositioned stop is halfway between its neighbours, and the stop before an earlier
+    // one moves up to it.
+    let offsets: Vec<f32> = stops.iter().map(|stop| stop.offset).collect();
+    assert_eq!(offsets, [0.2, 0.3, 0.4, 0.4, 1.0]);
+
+    let engine = with_background("radial-gradient(circle closest-side, #ff0000, #0000ff)");
+    let center = Point { x: 50.0, y: 25.0 };
+    let shape = GradientShape::Radial { center, radius_x: 25.0, radius_y: 25.0 };
+    assert_eq!(gradient(&engine).0, shape);
+}
+
+#[test]
+fn gradients_are_painted_per_pixel() {
+    let engine = with_background("linear-gradient(90deg, #ff0000 50%, #0000ff 50%)");
+    let canvas = engine.render().unwrap();
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    assert_eq!((pixel(49, 10), pixel(50, 10)), (RED, BLUE));
+
+    let engine = with_background("radial-gradient(closest-side, #ff0000, #0000ff)");
+    let canvas = engine.render().unwrap();
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    // Red at the center, blue past the ellipse that touches the sides, and a blend in between.
+    let center = pixel(50, 25);
+    assert!(center.r > 240 && center.b < 15);
+    assert_eq!(pixel(0, 0), BLUE);
+    let between = pixel(75, 25);
+    assert!(between.r > 0 && between.b > 0 && between.g == 0);
+    assert_eq!(pixel(50, 75), WHITE);
+
+    // The shorthand sets the color beneath the gradient too.
+    let engine = with_background("#0000ff linear-gradient(#ff0000, #ff0000)");
+    let items = engine.display_list().items();
+    assert!(items.iter().any(|item| match *item {
+        DisplayCommand::SolidColor { color, .. } => color == BLUE,
+        _ => false,
+    }));
+}
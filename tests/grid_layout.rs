@@ -0,0 +1,58 @@
+#![cfg(feature = "grid")]
+
+extern crate chameleon;
+
+use chameleon::document::{Document, Viewport};
+use chameleon::geometry::Rect;
+
+/// Lay out `html` with `css` in a 100px-wide viewport, and return the border boxes of the
+/// elements with the given ids.
+fn border_boxes(html: &str, css: &str, ids: &[&str]) -> Vec<Rect> {
+    let mut document = Document::load_html(html, css);
+    document.layout(Viewport { width: 100.0, height: 100.0 }).unwrap();
+    let layout = document.engine().unwrap().layout_tree();
+    ids.iter()
+        .map(|id| layout.box_for_node(document.element_by_id(id).unwrap()).unwrap().border_box)
+        .collect()
+}
+
+const GRID: &str = "<div id=\"grid\"><span id=\"a\"></span><span id=\"b\"></span>\
+                    <span id=\"c\"></span></div>";
+
+#[test]
+fn fractions_share_the_width_left_after_gaps() {
+    // 100px less a 10px gap leaves 90px, split one part to two.
+    let css = "div { display: grid; width: 100px; grid-template-columns: 1fr 2fr; gap: 5px 10px; } \
+               span { display: block; height: 10px; }";
+    assert_eq!(border_boxes(GRID, css, &["grid", "a", "b", "c"]), [
+        Rect { x: 0.0, y: 0.0, width: 100.0, height: 25.0 },
+        Rect { x: 0.0, y: 0.0, width: 30.0, height: 10.0 },
+        Rect { x: 40.0, y: 0.0, width: 60.0, height: 10.0 },
+        Rect { x: 0.0, y: 15.0, width: 30.0, height: 10.0 },
+    ]);
+}
+
+#[test]
+fn items_spanning_several_tracks_cover_them_and_the_gaps_between() {
+    let css = "div { display: grid; width: 100px; grid-template-columns: 20px 30px 40px; \
+                     column-gap: 5px; } \
+               span { display: block; height: 10px; } #a { grid-column: 1 / span 2; }";
+    assert_eq!(border_boxes(GRID, css, &["a", "b", "c"]), [
+        Rect { x: 0.0, y: 0.0, width: 55.0, height: 10.0 },
+        Rect { x: 60.0, y: 0.0, width: 40.0, height: 10.0 },
+        Rect { x: 0.0, y: 10.0, width: 20.0, height: 10.0 },
+    ]);
+}
+
+#[test]
+fn oversized_spans_are_clamped_instead_of_allocating_their_tracks() {
+    // Spanning a billion columns would allocate billions of tracks; the span is clamped to the
+    // largest grid, whose implicit tracks are empty.
+    let css = "div { display: grid; width: 100px; grid-template-columns: 1fr 2fr; } \
+               span { display: block; height: 10px; } #a { grid-column: span 1000000000; }";
+    assert_eq!(border_boxes(GRID, css, &["a", "b", "c"]), [
+        Rect { x: 0.0, y: 0.0, width: 100.0, height: 10.0 },
+        Rect { x: 0.0, y: 10.0, width: 100.0 / 3.0, height: 10.0 },
+        Rect { x: 100.0 / 3.0, y: 10.0, width: 200.0 / 3.0, height: 10.0 },
+    ]);
+}
@@ -0,0 +1,27 @@
+extern crate chameleon;
+
+use chameleon::{css, html, layout, style};
+
+fn dump(source: &str, stylesheet: &str, viewport_width: f32) -> String {
+    let root = html::parse(source);
+    let stylesheet = css::parse(stylesheet);
+    let context = layout::LayoutContext { viewport_width, viewport_height: 100.0 };
+    let layout = layout::layout_tree(&style::style_tree(&root, &stylesheet), context);
+    layout.root().map_or_else(String::new, |root| root.dump())
+}
+
+#[test]
+fn dumps_box_types_and_geometry() {
+    let source = "<div><p></p><span></span></div>";
+    let stylesheet = "
+        div, p { display: block; }
+        div { padding: 5px; border-width: 1px; }
+        p { height: 10px; margin-top: 2px; margin-left: 4px; width: 50px; }
+    ";
+    assert_eq!(dump(source, stylesheet, 100.0), "\
+Block (0, 0) 100x24; margin 0 0 0 0; border 1 1 1 1; padding 5 5 5 5
+  Block (10, 8) 50x10; margin 2 34 0 4; border 0 0 0 0; padding 0 0 0 0
+  Block (anonymous) (6, 18) 88x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+    Inline (0, 0) 0x0; margin 0 0 0 0; border 0 0 0 0; padding 0 0 0 0
+");
+}
@@ -0,0 +1,167 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::document::{Document, Viewport};
+use chameleon::dom::{self, Node};
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::limits::{LimitError, Limits};
+use chameleon::snapshot::{self, SnapshotError};
+use std::collections::HashMap;
+
+/// `depth` `<div>`s, each inside the last, inside `<html>`.
+fn nested_divs(depth: usize) -> String {
+    format!("<html>{}{}</html>", "<div>".repeat(depth), "</div>".repeat(depth))
+}
+
+/// The depth of the tree, without recursing over it.
+fn depth(root: &Node) -> usize {
+    let mut stack = vec![(root, 1)];
+    let mut deepest = 0;
+    while let Some((node, depth)) = stack.pop() {
+        deepest = deepest.max(depth);
+        stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+    }
+    deepest
+}
+
+#[test]
+fn deeply_nested_documents_are_parsed_without_recursing_too_deep() {
+    let source = nested_divs(100_000);
+    let limits = Limits::default();
+    assert_eq!(html::parse_with_limits(&source, &limits).err(),
+               Some(LimitError::DomTooDeep(limits.max_dom_depth)));
+    let many = "<p></p>".repeat(1000);
+    let few = Limits { max_dom_nodes: 100, ..Limits::default() };
+    assert_eq!(html::parse_with_limits(&many, &few).err(), Some(LimitError::TooManyNodes(100)));
+    assert!(html::parse_with_limits(&nested_divs(10), &limits).is_ok());
+
+    // Without limits, elements past the maximum depth follow each other instead of nesting, and
+    // their closing tags close nothing else.
+    let source = format!("<html>{}<span></span></html>", nested_divs(100_000));
+    let (root, errors) = html::parse_with_errors(&source);
+    assert_eq!(depth(&root), html::MAX_NESTING_DEPTH);
+    assert_eq!(root.children.len(), 2);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "<div> is nested too deeply to contain anything");
+
+    let stylesheet = css::parse("div { display: block; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    assert_eq!(Engine::with_limits(&root, &stylesheet, context, limits).err(),
+               Some(LimitError::DomTooDeep(limits.max_dom_depth)));
+    Engine::new(&root, &stylesheet, context);
+}
+
+#[test]
+fn deeply_nested_snapshots_are_restored_without_recursing_too_deep() {
+    // Whatever the HTML parser nests can be restored.
+    let source = nested_divs(100_000);
+    let (root, _) = html::parse_with_errors(&source);
+    let stylesheet = css::parse("div { display: block; }");
+    let saved = snapshot::save(&root, &stylesheet);
+    let (restored, _) = snapshot::restore(&saved).unwrap();
+    assert_eq!(depth(&restored), html::MAX_NESTING_DEPTH);
+
+    // An `<a>` in each `<a>`, six bytes a level, after the header of a real snapshot.
+    let mut crafted = snapshot::save(&html::parse("<a></a>"), &css::parse(""))[..5].to_vec();
+    for _ in 0..100_000 {
+        crafted.extend_from_slice(&[0, 1, b'a', 0, 0, 1]);
+    }
+    assert_eq!(snapshot::restore(&crafted).err(),
+               Some(SnapshotError::TooDeep(html::MAX_NESTING_DEPTH)));
+}
+
+#[test]
+fn relayouts_are_limited_like_the_first_layout() {
+    let mut root = html::parse("<html><p></p></html>");
+    let stylesheet = css::parse("html, p { display: block; height: 10px; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let limits = Limits { max_layouts: 50, ..Limits::default() };
+    let mut engine = Engine::with_limits(&root, &stylesheet, context, limits).unwrap();
+
+    let html = root.id;
+    for _ in 0..100 {
+        root.append_child(html, dom::elem("p", HashMap::new(), Vec::new())).unwrap();
+    }
+    assert_eq!(engine.update(&root, &stylesheet, html), Err(LimitError::TooManyLayouts(50)));
+    assert!(engine.layout_tree().root().is_none());
+    assert!(engine.display_list().is_empty());
+
+    // The whole document is laid out again once it is small enough.
+    root.children.truncate(1);
+    engine.update(&root, &stylesheet, html).unwrap();
+    assert!(engine.layout_tree().box_for_node(&root.children[0]).is_some());
+
+    let mut document = Document::load_html("<p></p>", "p { display: block; }");
+    document.set_limits(limits);
+    document.layout(Viewport::default()).unwrap();
+    let p = document.root().id;
+    document.mutate(p, |root| {
+        for _ in 0..100 {
+            root.append_child(p, dom::elem("p", HashMap::new(), Vec::new())).unwrap();
+        }
+    });
+    assert!(document.engine().is_none());
+    assert_eq!(document.paint().err(), Some(LimitError::TooManyLayouts(50)));
+}
+
+#[test]
+#[cfg(feature = "grid")]
+fn grid_lines_and_spans_are_clamped_to_the_largest_grid() {
+    use chameleon::geometry::Rect;
+
+    let root = html::parse("<html><p id=\"a\"></p><p id=\"b\"></p><p id=\"c\"></p></html>");
+    let stylesheet = css::parse("html { display: grid; grid-template-columns: 10px; } \
+                                 p { display: block; height: 10px; } \
+                                 #a { grid-row: span 1000000000; } \
+                                 #b { grid-column: 1000000000; } \
+                                 #c { grid-column: -1000000000 / 2000000000; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+    let layout = engine.layout_tree();
+    let boxes: Vec<Rect> = root.children.iter()
+        .map(|p| layout.box_for_node(p).unwrap().border_box)
+        .collect();
+    // The first item spans every row, the second is in the last column, and the third spans
+    // every column, below the first. The implicit tracks are empty.
+    assert_eq!(boxes, [
+        Rect { x: 0.0, y: 0.0, width: 10.0, height: 10.0 },
+        Rect { x: 10.0, y: 0.0, width: 0.0, height: 10.0 },
+        Rect { x: 0.0, y: 10.0, width: 10.0, height: 10.0 },
+    ]);
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn parallel_styling_matches_sequential_styling_of_deep_trees() {
+    use chameleon::style::{self, StyledNode};
+
+    /// Whether the trees have the same styles, with the same nodes (apart from pseudo-elements,
+    /// which get new ids whenever they are styled).
+    fn same_styles(a: &StyledNode, b: &StyledNode) -> bool {
+        let mut stack = vec![(a, b)];
+        while let Some((a, b)) = stack.pop() {
+            if a.specified != b.specified || a.pseudo != b.pseudo
+                || (a.pseudo.is_none() && a.node != b.node)
+                || a.children.len() != b.children.len() {
+                return false;
+            }
+            stack.extend(a.children.iter().zip(&b.children));
+        }
+        true
+    }
+
+    let stylesheet = css::parse("div { display: block; padding: 1px; direction: rtl; } \
+                                 p:first-child { background: #ff0000; } \
+                                 p::before { content: \"-\"; } \
+                                 .wide { width: 50px; }");
+    let wide = format!("<html>{}</html>", "<p class=\"wide\">a</p><p>b</p>".repeat(500));
+    let deep = nested_divs(100_000);
+    for source in &[wide, deep] {
+        let (root, _) = html::parse_with_errors(source);
+        let parallel = style::style_tree(&root, &stylesheet);
+        assert!(same_styles(&parallel, &style::style_tree_sequential(&root, &stylesheet)));
+    }
+}
+
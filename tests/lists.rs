@@ -0,0 +1,41 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::geometry::Rect;
+use chameleon::layout::{self, LayoutContext};
+use chameleon::paint::DisplayCommand;
+use chameleon::{html, style};
+
+const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+
+/// The bounds of the marker items painted for a document, in order.
+fn markers(source: &str) -> Vec<Rect> {
+    let root = html::parse(source);
+    let stylesheet = css::Stylesheet::combine(vec![
+        css::user_agent_stylesheet(),
+        css::parse("body { margin: 0px; } ul, ol { margin-top: 0px; } li { height: 20px; }"),
+    ]);
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 100.0 };
+    let layout = layout::layout_tree(&style::style_tree(&root, &stylesheet), context);
+    layout::display_list(&layout).items().iter()
+        .filter(|item| matches!(item, DisplayCommand::SolidColor { color: BLACK, .. }))
+        .map(DisplayCommand::bounds)
+        .collect()
+}
+
+#[test]
+fn markers_sit_outside_the_item_before_its_first_line() {
+    // A disc is 6px across, 8px left of the content box (at 40px), centered on a 16px line.
+    let discs = markers("<html><body><ul><li></li><li></li></ul></body></html>");
+    assert_eq!(discs.len(), 12);
+    let first = discs[..6].iter().fold(discs[0], |bounds, &row| bounds.union(row));
+    assert_eq!(first, Rect { x: 26.0, y: 5.0, width: 6.0, height: 6.0 });
+    assert_eq!(discs[6].y, 25.0);
+
+    // "1." is painted one run per row of the 1, plus the period; `none` has no marker.
+    let numbers = markers("<html><body><ol><li></li><li style=\"list-style-type: none\"></li>\
+                           </ol></body></html>");
+    assert_eq!(numbers.len(), 6);
+    let right = numbers.iter().map(|rect| rect.x + rect.width).fold(0.0, f32::max);
+    assert_eq!(right, 32.0);
+}
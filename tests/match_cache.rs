@@ -0,0 +1,33 @@
+extern crate chameleon;
+
+use chameleon::style::{self, MatchCache, StyledNode};
+use chameleon::{css, html};
+
+fn styles(node: &StyledNode, out: &mut Vec<style::Style>) {
+    out.push((*node.specified).clone());
+    for child in &node.children {
+        styles(child, out);
+    }
+}
+
+#[test]
+fn cache_matches_like_elements_once_per_stylesheet() {
+    let root = html::parse("<ul><li class=\"a b\">1</li><li class=\"b a\">2</li>\
+                            <li class=\"a\">3</li><li id=\"x\" class=\"a\">4</li></ul>");
+    let sheet = css::parse("li { padding-left: 1px; } .a { height: 2px; } .a.b { width: 3px; } \
+                            #x { margin-top: 4px; }");
+
+    let cache = MatchCache::new();
+    let cached = style::style_tree_cached(&root, &sheet, &cache);
+    let (mut expected, mut actual) = (Vec::new(), Vec::new());
+    styles(&style::style_tree(&root, &sheet), &mut expected);
+    styles(&cached, &mut actual);
+    assert_eq!(actual, expected);
+    // `ul`, `li.a.b`, `li.a`, and `li#x.a` are matched; the second `li.a.b` is not.
+    assert_eq!((cache.hits(), cache.misses()), (1, 4));
+
+    // A changed stylesheet is a new revision, which clears the cache.
+    let sheet = sheet.with_origin(css::Origin::User);
+    style::style_tree_cached(&root, &sheet, &cache);
+    assert_eq!((cache.hits(), cache.misses()), (2, 8));
+}
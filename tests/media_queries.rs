@@ -0,0 +1,69 @@
+extern crate chameleon;
+
+use chameleon::css::{self, MediaContext, MediaType};
+use chameleon::engine::Engine;
+use chameleon::geometry::Size;
+use chameleon::{html, snapshot};
+use chameleon::layout::LayoutContext;
+
+fn media(width: f32, media_type: MediaType) -> MediaContext {
+    MediaContext { viewport: Size { width, height: 600.0 }, media_type }
+}
+
+#[test]
+fn rules_in_media_rules_apply_where_their_queries_match() {
+    let (stylesheet, errors) = css::parse_with_errors(
+        "p { width: 1px; } \
+         @media screen and (min-width: 600px), print { p { width: 2px; } \
+             @media (max-width: 900px) { p { width: 3px; } } } \
+         @media not tv { p { height: 1px; } } \
+         @media tv, (min-height: 1000px) { p { height: 2px; } } \
+         @media screen and (orientation: landscape) { p { height: 3px; } } \
+         @import url(x.css);");
+    let messages: Vec<&str> = errors.iter().map(|error| &*error.message).collect();
+    assert_eq!(messages, ["unsupported media feature orientation", "unsupported at-rule @import"]);
+
+    let applies = |context: MediaContext| -> Vec<usize> {
+        let rules = stylesheet.rules().iter().enumerate();
+        rules.filter(|(_, rule)| rule.matches_media(&context)).map(|(i, _)| i).collect()
+    };
+    assert_eq!(applies(media(500.0, MediaType::Screen)), [0, 3]);
+    assert_eq!(applies(media(700.0, MediaType::Screen)), [0, 1, 2, 3]);
+    assert_eq!(applies(media(1000.0, MediaType::Screen)), [0, 1, 3]);
+    assert_eq!(applies(media(500.0, MediaType::Print)), [0, 1, 2, 3]);
+    assert!(stylesheet.media_changes(&media(500.0, MediaType::Screen),
+                                     &media(700.0, MediaType::Screen)));
+    assert!(!stylesheet.media_changes(&media(1000.0, MediaType::Screen),
+                                      &media(1200.0, MediaType::Screen)));
+
+    let root = html::parse("<p></p>");
+    let saved = snapshot::save(&root, &stylesheet);
+    let (_, restored) = snapshot::restore(&saved).unwrap();
+    let media_of = |stylesheet: &css::Stylesheet| -> Vec<Vec<css::MediaQueryList>> {
+        stylesheet.rules().iter().map(|rule| rule.media.clone()).collect()
+    };
+    assert_eq!(media_of(&restored), media_of(&stylesheet));
+}
+
+#[test]
+fn engine_restyles_when_the_viewport_crosses_a_breakpoint() {
+    let root = html::parse("<html><div></div></html>");
+    let stylesheet = css::parse(
+        "html, div { display: block; } div { height: 10px; } \
+         @media (max-width: 400px) { div { height: 20px; } } \
+         @media print { div { height: 30px; } }");
+    let context = LayoutContext { viewport_width: 300.0, viewport_height: 200.0 };
+    let mut engine = Engine::new(&root, &stylesheet, context);
+    let height = |engine: &Engine| {
+        engine.layout_tree().box_for_node(&root.children[0]).unwrap().content_box.height
+    };
+    assert_eq!(height(&engine), 20.0);
+
+    engine.set_media(&root, &stylesheet, media(500.0, MediaType::Screen)).unwrap();
+    assert_eq!(engine.viewport().width, 500.0);
+    assert_eq!(engine.layout_tree().root().unwrap().geometry().content_box.width, 500.0);
+    assert_eq!(height(&engine), 10.0);
+
+    engine.set_media(&root, &stylesheet, media(500.0, MediaType::Print)).unwrap();
+    assert_eq!(height(&engine), 30.0);
+}
@@ -0,0 +1,31 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::engine::Engine;
+use chameleon::geometry::Rect;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::style::Edge;
+
+#[test]
+fn subtree_metrics_count_nodes_boxes_and_overflow() {
+    let root = html::parse("<html><div style=\"width: 100px; height: 50px\">\
+                            <p style=\"width: 150px; height: 80px\"></p>Hello</div>\
+                            <p style=\"display: none\">Hidden</p></html>");
+    let stylesheet = css::Stylesheet::combine(vec![
+        css::user_agent_stylesheet(),
+        css::parse("p { margin-top: 0px; margin-bottom: 0px; }"),
+    ]);
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 200.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+
+    let metrics = engine.subtree_metrics(&root.children[0]).unwrap();
+    assert_eq!(metrics.nodes, 3);
+    // The div, the p, and the text with the anonymous block that wraps it.
+    assert_eq!(metrics.boxes, 4);
+    assert_eq!(metrics.text_length, 5);
+    assert_eq!(metrics.bounds, Rect { x: 0.0, y: 0.0, width: 150.0, height: 80.0 });
+    assert_eq!(metrics.overflow, Edge { left: 0.0, right: 50.0, top: 0.0, bottom: 30.0 });
+
+    assert_eq!(engine.subtree_metrics(&root.children[1]), None);
+}
@@ -0,0 +1,71 @@
+extern crate chameleon;
+
+use chameleon::document::{Document, Viewport};
+use chameleon::dom::{self, MutationError, NodeType};
+use chameleon::html;
+use std::collections::HashMap;
+
+fn text_of(node: &dom::Node) -> String {
+    match node.node_type {
+        NodeType::Text(ref text) => text.to_string(),
+        NodeType::Element(_) => node.children.iter().map(text_of).collect(),
+    }
+}
+
+#[test]
+fn children_attributes_and_text_can_be_changed() {
+    let mut root = html::parse("<ul><li>one</li><li>two</li></ul>");
+    let (ul, first, second) = (root.id, root.children[0].id, root.children[1].id);
+
+    let third = dom::elem("li", HashMap::new(), vec![dom::text("three")]);
+    let third_id = third.id;
+    root.append_child(ul, third).unwrap();
+    root.insert_child(ul, 0, dom::elem("li", HashMap::new(), Vec::new())).unwrap();
+    assert_eq!(root.insert_child(ul, 9, dom::text("x")), Err(MutationError::InvalidIndex));
+    let removed = root.remove_child(ul, first).unwrap();
+    assert_eq!(text_of(&removed), "one");
+    assert_eq!(root.remove_child(ul, first).err(), Some(MutationError::NotAChild));
+    let old = root.replace_child(ul, second, dom::text("2")).unwrap();
+    assert_eq!(old.id, second);
+    assert_eq!(text_of(&root), "2three");
+
+    assert_eq!(root.set_attribute(third_id, "Class", "last").unwrap(), None);
+    assert_eq!(root.set_attribute(third_id, "class", "end").unwrap().as_deref(), Some("last"));
+    match root.find(third_id).unwrap().node_type {
+        NodeType::Element(ref elem) => assert!(elem.classes().contains("end")),
+        NodeType::Text(_) => panic!("expected an element"),
+    }
+    assert_eq!(root.remove_attribute(third_id, "CLASS").unwrap().as_deref(), Some("end"));
+
+    let text_id = root.find(third_id).unwrap().children[0].id;
+    assert_eq!(root.parent(text_id).map(|parent| parent.id), Some(third_id));
+    root.set_text(text_id, "3").unwrap();
+    assert_eq!(text_of(&root), "23");
+    root.set_text(third_id, "three").unwrap();
+    assert_eq!(root.append_child(text_id, dom::text("x")), Err(MutationError::NotFound));
+    let text_id = root.find(third_id).unwrap().children[0].id;
+    assert_eq!(root.set_attribute(text_id, "a", "b"), Err(MutationError::NotAnElement));
+}
+
+#[test]
+fn mutating_a_document_updates_its_layout() {
+    let mut document = Document::load_html("<div><style></style><p></p></div>",
+                                           "p { height: 10px; margin: 0; }");
+    document.layout(Viewport::default()).unwrap();
+    let div = document.root().id;
+    let height = |document: &Document| {
+        let layout = document.engine().unwrap().layout_tree();
+        layout.box_for_node(document.root()).unwrap().border_box.height
+    };
+    assert_eq!(height(&document), 10.0);
+
+    document.mutate(div, |root| {
+        root.append_child(div, dom::elem("p", HashMap::new(), Vec::new()))
+    }).unwrap();
+    assert_eq!(height(&document), 20.0);
+
+    // Changing a style element restyles the whole document.
+    let style = document.root().children[0].id;
+    document.mutate(style, |root| root.set_text(style, "p { height: 5px; }")).unwrap();
+    assert_eq!(height(&document), 10.0);
+}
@@ -0,0 +1,57 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::engine::Engine;
+use chameleon::geometry::Rect;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+
+/// The border boxes of the children of the `div`, and of the `div` itself.
+fn boxes(children: &str, stylesheet: &str) -> (Vec<Rect>, Rect) {
+    let source = format!("<html><div>{}</div></html>", children);
+    let root = html::parse(&source);
+    let css = format!("html, div, p {{ display: block; margin: 0; }} div {{ width: 100px; }} {}",
+                      stylesheet);
+    let stylesheet = css::parse(&css);
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 200.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+    let layout = engine.layout_tree();
+    let div = &root.children[0];
+    let border_box = |node| layout.box_for_node(node).unwrap().border_box;
+    (div.children.iter().map(border_box).collect(), border_box(div))
+}
+
+#[test]
+fn negative_margins_shift_boxes_over_their_neighbors() {
+    let (children, div) = boxes("<p id='a'></p><p id='b'></p><p id='c'></p>",
+                                "p { height: 20px; } \
+                                 #b { margin-top: -10px; margin-left: -15px; width: 50px; } \
+                                 #c { margin-top: -30px; }");
+    assert_eq!(children, [
+        Rect { x: 0.0, y: 0.0, width: 100.0, height: 20.0 },
+        Rect { x: -15.0, y: 10.0, width: 50.0, height: 20.0 },
+        Rect { x: 0.0, y: 0.0, width: 100.0, height: 20.0 },
+    ]);
+    // The div ends at the bottom of its last child.
+    assert_eq!(div.height, 20.0);
+
+    // Contents pulled up past the top of the div leave it empty, not negatively tall.
+    let (children, div) = boxes("<p></p>", "p { height: 10px; margin-top: -25px; }");
+    assert_eq!(children[0].y, -25.0);
+    assert_eq!(div.height, 0.0);
+}
+
+#[test]
+fn negative_horizontal_margins_widen_auto_width_boxes() {
+    let (children, _) = boxes("<p id='a'></p><p id='b'></p><p id='c'></p>",
+                              "p { height: 10px; } \
+                               #a { margin-left: -10px; margin-right: -20px; } \
+                               #b { margin-left: -10px; width: 150px; } \
+                               #c { margin-left: auto; margin-right: auto; width: 150px; }");
+    assert_eq!(children, [
+        Rect { x: -10.0, y: 0.0, width: 130.0, height: 10.0 },
+        Rect { x: -10.0, y: 10.0, width: 150.0, height: 10.0 },
+        // Auto margins are zero when the box is wider than its container.
+        Rect { x: 0.0, y: 20.0, width: 150.0, height: 10.0 },
+    ]);
+}
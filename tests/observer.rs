@@ -0,0 +1,37 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn observers_see_the_old_and_new_geometry_of_the_elements_they_follow() {
+    let root = html::parse("<html><div tabindex=\"0\"></div><div></div></html>");
+    let stylesheet = css::parse("html, div { display: block; } div { height: 10px; } \
+                                 div:focus { height: 30px; }");
+    let context = LayoutContext { viewport_width: 200.0, viewport_height: 200.0 };
+    let mut engine = Engine::new(&root, &stylesheet, context);
+    let (first, second) = (root.children[0].id, root.children[1].id);
+
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let seen = changes.clone();
+    let observer = engine.on_layout_changed(move |node| node == second, move |change| {
+        seen.lock().unwrap().push(*change);
+    });
+
+    // Focusing the first div makes it taller, which moves the second one down.
+    engine.focus(&root, &stylesheet, Some(first)).unwrap();
+    {
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].node, second);
+        assert_eq!(changes[0].old.unwrap().border_box.y, 10.0);
+        assert_eq!(changes[0].new.unwrap().border_box.y, 30.0);
+    }
+
+    assert!(engine.remove_layout_observer(observer));
+    engine.focus(&root, &stylesheet, None).unwrap();
+    assert_eq!(changes.lock().unwrap().len(), 1);
+}
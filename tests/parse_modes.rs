@@ -0,0 +1,37 @@
+extern crate chameleon;
+
+use chameleon::css::{self, ParseMode};
+use chameleon::document::{Document, SyntaxError};
+use chameleon::dom::NodeType;
+use chameleon::html;
+
+#[test]
+fn lenient_html_closes_elements_left_open() {
+    let (root, errors) = html::parse_with_errors("<div><p>one</div>\n<q>two</r></q>");
+    let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+    assert_eq!(messages, ["1:12: expected </p>, found </div>", "2:7: unexpected </r>"]);
+
+    let tags: Vec<&str> = root.children.iter()
+        .map(|child| match child.node_type {
+            NodeType::Element(ref elem) => &*elem.tag,
+            NodeType::Text(_) => "#text",
+        })
+        .collect();
+    assert_eq!(tags, ["div", "q"]);
+}
+
+#[test]
+fn strict_mode_returns_the_first_error() {
+    let error = css::parse_with_mode("p { color: }\nq { : }", ParseMode::Strict).err().unwrap();
+    assert_eq!((error.line, error.column), (1, 12));
+    assert!(css::parse_with_mode("p { color: }", ParseMode::Lenient).is_ok());
+
+    match Document::load_html_with_mode("<p a=b></p>", "p {", ParseMode::Strict) {
+        Err(SyntaxError::Html(error)) => assert_eq!((error.line, error.column), (1, 6)),
+        _ => panic!("expected an HTML error"),
+    }
+    match Document::load_html_with_mode("<p></p>", "p {", ParseMode::Strict) {
+        Err(SyntaxError::Css(_)) => {}
+        _ => panic!("expected a CSS error"),
+    }
+}
@@ -0,0 +1,46 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::dom::Node;
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::style::Cursor;
+
+fn engine<'a>(source: &'a str, stylesheet: &str) -> (Node<'a>, Engine) {
+    let root = html::parse(source);
+    let stylesheet = css::parse(stylesheet);
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+    (root, engine)
+}
+
+#[test]
+fn hit_testing_skips_boxes_without_pointer_events() {
+    let (root, engine) = engine("<html><div></div><div id='overlay'><p></p></div></html>",
+                                "html, div, p { display: block; margin: 0; } \
+                                 div { height: 20px; } p { height: 5px; pointer-events: auto; } \
+                                 #overlay { margin-top: -20px; pointer-events: none; }");
+    let (under, overlay) = (root.children[0].id, root.children[1].id);
+    let p = root.children[1].children[0].id;
+    let layout = engine.layout_tree();
+
+    assert_eq!(layout.hit_test(5.0, 2.0), [p, under, root.id]);
+    assert_eq!(layout.hit_test(5.0, 10.0), [under, root.id]);
+    assert!(!layout.hit_test(5.0, 10.0).contains(&overlay));
+}
+
+#[test]
+fn the_cursor_is_that_of_the_topmost_box() {
+    let (_, engine) = engine("<html><a><span></span></a><div></div></html>",
+                             "html, a, span, div { display: block; margin: 0; height: 10px; } \
+                              a { cursor: pointer; } div { cursor: not-allowed; } \
+                              div { cursor: bogus; } html { height: 50px; }");
+    let layout = engine.layout_tree();
+
+    // The span inherits the cursor of the link, and `auto` is the default cursor.
+    assert_eq!(layout.cursor_at(5.0, 5.0), Cursor::Pointer);
+    assert_eq!(layout.cursor_at(5.0, 15.0), Cursor::NotAllowed);
+    assert_eq!(layout.cursor_at(5.0, 30.0), Cursor::Default);
+    assert_eq!(layout.cursor_at(500.0, 500.0), Cursor::Default);
+}
@@ -0,0 +1,41 @@
+extern crate chameleon;
+
+use chameleon::css;
+use chameleon::dom::{Node, NodeType};
+use chameleon::html;
+
+fn tag<'b>(node: &'b Node) -> &'b str {
+    match node.node_type {
+        NodeType::Element(ref elem) => &elem.tag,
+        NodeType::Text(_) => "#text",
+    }
+}
+
+#[test]
+fn query_selector_finds_elements_in_document_order() {
+    let root = html::parse("<main class='page'><h1 id='title'>Title</h1>\
+                            <p class='note'>one</p><div><P CLASS='note wide'>two</P></div>\
+                            <span class='note'></span></main>");
+
+    assert_eq!(root.query_selector("#title").map(tag), Some("h1"));
+    assert_eq!(root.query_selector(".page").map(|node| node.id), Some(root.id));
+    let notes: Vec<&str> = root.query_selector_all(".note").into_iter().map(tag).collect();
+    assert_eq!(notes, ["p", "p", "span"]);
+    let list: Vec<&str> = root.query_selector_all("span, h1").into_iter().map(tag).collect();
+    assert_eq!(list, ["h1", "span"]);
+    let wide = root.query_selector("P.note.wide").unwrap();
+    assert_eq!(wide.id, root.children[2].children[0].id);
+    assert!(root.query_selector("section").is_none());
+}
+
+#[test]
+fn invalid_selectors_and_pseudo_elements_match_nothing() {
+    let root = html::parse("<div><p></p></div>");
+    assert!(root.query_selector("p::before").is_none());
+    assert!(root.query_selector_all("p, ").is_empty());
+    assert!(root.query_selector("p {").is_none());
+
+    let error = css::parse_selector_list("p, #").err().unwrap();
+    assert_eq!((error.line, error.column), (1, 5));
+    assert_eq!(css::parse_selector_list(" p , .a ").map(|selectors| selectors.len()), Ok(2));
+}
@@ -0,0 +1,55 @@
+extern crate chameleon;
+
+use chameleon::dom::{Node, NodeType};
+use chameleon::{html, reader};
+
+const PAGE: &str = "<html><body>\
+    <nav><a href=\"/\">Home</a><a href=\"/news\">News, sports, and weather</a></nav>\
+    <div class=\"layout\">\
+      <div id=\"story\" class=\"post-body\" style=\"width: 10px\">\
+        <h1>Rivers</h1>\
+        <p>Rivers carry water, sediment, and nutrients from the hills to the sea.</p>\
+        <p>Along the way, they shape valleys, feed wetlands, and water cities.</p>\
+        <div class=\"related\"><a href=\"/lakes\">Lakes, ponds, and reservoirs</a></div>\
+      </div>\
+      <div class=\"sidebar\"><p>Subscribe to our newsletter, for news, tips, and deals.</p></div>\
+    </div>\
+    <footer><p>Copyright, trademarks, and other legal notices apply.</p></footer>\
+    </body></html>";
+
+/// The tags of the elements in a subtree, in document order, checking that only `href` attributes
+/// are left.
+fn tags(node: &Node) -> Vec<String> {
+    let mut found = Vec::new();
+    if let NodeType::Element(ref elem) = node.node_type {
+        found.push(elem.tag.to_string());
+        assert!(elem.attributes.keys().all(|name| name == "href"));
+    }
+    for child in &node.children {
+        found.extend(tags(child));
+    }
+    found
+}
+
+#[test]
+fn main_content_is_the_element_with_the_most_prose() {
+    let root = html::parse(PAGE);
+    let content = reader::main_content(&root).expect("no main content");
+    match content.node_type {
+        NodeType::Element(ref elem) => assert_eq!(elem.id(), Some("story")),
+        NodeType::Text(_) => panic!("main content is text"),
+    }
+
+    assert!(reader::main_content(&html::parse("<div><p>Too short.</p></div>")).is_none());
+}
+
+#[test]
+fn reader_documents_leave_out_clutter_and_attributes() {
+    let root = html::parse(PAGE);
+    let document = reader::reader_document(&root).expect("no main content");
+    assert_eq!(tags(&document), ["html", "body", "article", "div", "h1", "p", "p"]);
+
+    let canvas = reader::render(&document, 700.0).unwrap();
+    assert_eq!(canvas.width, 700);
+    assert!(canvas.height > 64);
+}
@@ -0,0 +1,109 @@
+//! Reference tests: every `tests/reftests/NAME.html` must render exactly like `NAME-ref.html`.
+//!
+//! Either file may allow small differences, as in the Web Platform Tests, with an element like
+//! `<meta name="fuzzy" content="maxDifference=0-2;totalPixels=0-100"/>`: then up to 100 pixels
+//! may differ, by up to 2 in any channel.
+
+extern crate chameleon;
+
+use chameleon::css::ParseMode;
+use chameleon::document::{Document, Viewport};
+use chameleon::dom::{Node, NodeType};
+use chameleon::paint::Canvas;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const VIEWPORT: Viewport = Viewport { width: 200.0, height: 200.0 };
+
+/// How much a rendering may differ from its reference.
+#[derive(Clone, Copy, Default)]
+struct Fuzzy {
+    max_difference: u8,
+    total_pixels: usize,
+}
+
+impl Fuzzy {
+    /// The larger allowance of each kind.
+    fn max(self, other: Fuzzy) -> Fuzzy {
+        Fuzzy {
+            max_difference: self.max_difference.max(other.max_difference),
+            total_pixels: self.total_pixels.max(other.total_pixels),
+        }
+    }
+}
+
+/// Render a test or reference in strict mode, with the allowance it asks for.
+fn render(path: &Path) -> Result<(Canvas, Fuzzy), String> {
+    let html = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut document = Document::load_html_with_mode(&html, "", ParseMode::Strict)
+        .map_err(|error| error.to_string())?;
+    let fuzzy = fuzzy(document.root())?;
+    document.layout(VIEWPORT).map_err(|error| error.to_string())?;
+    let canvas = document.paint().map_err(|error| error.to_string())?;
+    Ok((canvas, fuzzy))
+}
+
+/// The allowance of the `<meta name="fuzzy">` element in a document, if any.
+fn fuzzy(node: &Node) -> Result<Fuzzy, String> {
+    let mut fuzzy = Fuzzy::default();
+    if let NodeType::Element(ref elem) = node.node_type {
+        if elem.tag == "meta" && elem.attributes.get("name").is_some_and(|name| name == "fuzzy") {
+            let content = elem.attributes.get("content").map_or("", |content| content);
+            for part in content.split(';').map(str::trim).filter(|part| !part.is_empty()) {
+                // Only the upper bound of a range like `0-2` matters.
+                let (key, range) = part.split_once('=').ok_or(format!("bad fuzzy {:?}", part))?;
+                let max = range.rsplit('-').next().unwrap_or(range).trim();
+                let bad = |_| format!("bad fuzzy {:?}", part);
+                match key.trim() {
+                    "maxDifference" => fuzzy.max_difference = max.parse().map_err(bad)?,
+                    "totalPixels" => fuzzy.total_pixels = max.parse().map_err(bad)?,
+                    _ => return Err(format!("bad fuzzy {:?}", part)),
+                }
+            }
+        }
+    }
+    for child in &node.children {
+        fuzzy = fuzzy.max(self::fuzzy(child)?);
+    }
+    Ok(fuzzy)
+}
+
+/// Render a test and its reference, and compare them.
+fn run(test: &Path, reference: &Path) -> Result<(), String> {
+    let (actual, test_fuzzy) = render(test)?;
+    let (expected, reference_fuzzy) = render(reference)?;
+    let fuzzy = test_fuzzy.max(reference_fuzzy);
+    let diff = actual.diff(&expected).ok_or("canvases differ in size")?;
+    if diff.differing_pixels == 0 ||
+       (diff.max_difference <= fuzzy.max_difference &&
+        diff.differing_pixels <= fuzzy.total_pixels) {
+        Ok(())
+    } else {
+        Err(format!("{} pixels differ, by up to {}", diff.differing_pixels, diff.max_difference))
+    }
+}
+
+#[test]
+fn reftests() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reftests");
+    let mut tests: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.ends_with(".html") && !name.ends_with("-ref.html")
+        })
+        .collect();
+    tests.sort();
+    assert!(!tests.is_empty(), "no reftests in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for test in &tests {
+        let stem = test.file_stem().unwrap().to_string_lossy();
+        let reference = test.with_file_name(format!("{}-ref.html", stem));
+        if let Err(error) = run(test, &reference) {
+            failures.push(format!("{}: {}", stem, error));
+        }
+    }
+    assert!(failures.is_empty(), "{} of {} reftests failed:\n{}", failures.len(), tests.len(),
+            failures.join("\n"));
+}
@@ -0,0 +1,57 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::Canvas;
+
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+
+fn engine() -> Engine {
+    let root = html::parse("<html><div></div></html>");
+    let stylesheet = css::parse("html, div { display: block; margin: 0; } \
+                                 div { width: 5px; height: 5px; margin-left: 2px; \
+                                       border-width: 1px; border-color: #ff0000; \
+                                       background-color: #0000ff; }");
+    let context = LayoutContext { viewport_width: 10.0, viewport_height: 8.0 };
+    Engine::new(&root, &stylesheet, context)
+}
+
+/// The colors of a row of pixels.
+fn row(canvas: &Canvas, y: usize) -> &[Color] {
+    &canvas.pixels[y * canvas.width..(y + 1) * canvas.width]
+}
+
+#[test]
+fn the_viewport_is_rendered_at_the_scale_factor() {
+    let mut engine = engine();
+    assert_eq!(engine.scale_factor(), 1.0);
+    let canvas = engine.render().unwrap();
+    assert_eq!((canvas.width, canvas.height), (10, 8));
+
+    engine.set_scale_factor(2.0);
+    let canvas = engine.render().unwrap();
+    assert_eq!((canvas.width, canvas.height), (20, 16));
+    // The 1px border is 2 device pixels wide, and layout is unchanged.
+    let middle = [WHITE, WHITE, WHITE, WHITE, RED, RED, BLUE, BLUE];
+    assert_eq!(&row(&canvas, 6)[..8], middle);
+    assert_eq!(engine.layout_tree().root().unwrap().geometry().content_box.width, 10.0);
+}
+
+#[test]
+fn borders_are_snapped_to_device_pixels_at_fractional_scales() {
+    let mut engine = engine();
+    engine.set_scale_factor(1.5);
+    let canvas = engine.render().unwrap();
+    assert_eq!((canvas.width, canvas.height), (15, 12));
+    // Every pixel is painted in one color, with no blended seams between the edges.
+    for y in 0..canvas.height {
+        assert!(row(&canvas, y).iter().all(|pixel| [WHITE, RED, BLUE].contains(pixel)), "{}", y);
+    }
+    let middle = row(&canvas, 5);
+    assert_eq!(middle.iter().filter(|&&pixel| pixel == RED).count(), 2);
+    assert!(middle.contains(&BLUE));
+}
@@ -0,0 +1,115 @@
+extern crate chameleon;
+
+use chameleon::document::{Document, Viewport};
+use chameleon::dom::{MutationError, NodeId};
+use chameleon::html;
+use chameleon::script::{self, Script, ScriptDom, ScriptHost};
+
+/// Runs scripts of one command per line, such as `append main p hello`, and records what ran. The
+/// `script` command adds a script, with `+` for each space in its text.
+#[derive(Default)]
+struct CommandHost {
+    ran: Vec<String>,
+}
+
+impl ScriptHost for CommandHost {
+    fn run(&mut self, script: &Script, dom: &mut ScriptDom) -> Result<(), String> {
+        self.ran.push(script.src.clone().unwrap_or_else(|| script.text.trim().to_string()));
+        if script.script_type.as_deref() == Some("text/plain") {
+            return Ok(());
+        }
+        for line in script.text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let words: Vec<&str> = line.split(' ').collect();
+            let find = |id: &str| dom.element_by_id(id).ok_or(format!("no element #{}", id));
+            match words[..] {
+                ["append", parent, tag, text] => {
+                    let parent = find(parent)?;
+                    let child = dom.create_element(tag);
+                    let text = dom.create_text_node(text);
+                    dom.append_child(child, text).map_err(|error| error.to_string())?;
+                    dom.append_child(parent, child).map_err(|error| error.to_string())?;
+                }
+                ["move", child, parent] => {
+                    let (child, parent) = (find(child)?, find(parent)?);
+                    dom.append_child(parent, child).map_err(|error| error.to_string())?;
+                }
+                ["set", id, name, value] => {
+                    let node = find(id)?;
+                    dom.set_attribute(node, name, value).map_err(|error| error.to_string())?;
+                }
+                ["script", parent, text] => {
+                    let parent = find(parent)?;
+                    let child = dom.create_element("SCRIPT");
+                    let text = text.replace('+', " ");
+                    dom.set_text_content(child, &text).map_err(|error| error.to_string())?;
+                    dom.append_child(parent, child).map_err(|error| error.to_string())?;
+                }
+                _ => return Err(format!("unknown command {:?}", line)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn scripts_run_in_document_order_and_change_the_document() {
+    let mut root = html::parse("<main id='main'>\
+                                <script>append main p one\nscript main set+main+class+x</script>\
+                                <script type='text/plain'>ignored</script>\
+                                <script src='b.js'></script>\
+                                <script>bogus</script>\
+                                <div id='box'><span id='inner'></span></div>\
+                                <script>move box inner</script></main>");
+    let mut host = CommandHost::default();
+    let errors = script::run_scripts(&mut root, &mut host);
+
+    assert_eq!(host.ran, ["append main p one\nscript main set+main+class+x", "ignored", "b.js",
+                          "bogus", "move box inner", "set main class x"]);
+    let messages: Vec<String> = errors.iter().map(|error| error.message.clone()).collect();
+    assert_eq!(messages, ["unknown command \"bogus\"", "node would contain itself"]);
+    assert_eq!(errors[0].node, root.children[3].id);
+
+    let html = root.to_html();
+    assert!(html.starts_with("<main class=\"x\" id=\"main\">"), "{}", html);
+    assert!(html.ends_with("<p>one</p><script>set main class x</script></main>"), "{}", html);
+}
+
+#[test]
+fn documents_update_after_their_scripts_run() {
+    let mut document = Document::load_html("<div id='list'></div>\
+                                            <script>check</script>",
+                                           "p { display: block; height: 10px; margin: 0; }");
+    document.layout(Viewport::default()).unwrap();
+
+    struct Checks;
+    impl ScriptHost for Checks {
+        fn run(&mut self, _: &Script, dom: &mut ScriptDom) -> Result<(), String> {
+            let list = dom.element_by_id("list").unwrap();
+            let root = dom.document_element();
+            assert_eq!(dom.parent(list), Ok(Some(root)));
+            assert_eq!(dom.append_child(list, root), Err(MutationError::Cycle));
+            assert_eq!(dom.children(NodeId::from_raw(u64::MAX).to_raw()),
+                       Err(MutationError::NotFound));
+
+            let (first, second) = (dom.create_element("p"), dom.create_element("P"));
+            dom.set_attribute(first, "ID", "first").unwrap();
+            assert_eq!(dom.attribute(first, "id"), Ok(Some("first".to_string())));
+            dom.append_child(list, second).unwrap();
+            dom.insert_before(list, first, Some(second)).unwrap();
+            assert_eq!(dom.children(list), Ok(vec![first, second]));
+            assert_eq!(dom.tag_name(second), Ok(Some("p".to_string())));
+            let third = dom.create_element("p");
+            dom.replace_child(list, third, second).unwrap();
+            dom.set_text_content(second, "detached").unwrap();
+            assert_eq!(dom.parent(second), Ok(None));
+            assert_eq!(dom.query_selector_all(root, "p"), Ok(vec![first, third]));
+            Ok(())
+        }
+    }
+    assert!(document.run_scripts(&mut Checks).is_empty());
+    assert_eq!(document.query_selector_all("p").len(), 2);
+    assert!(document.element_by_id("first").is_some());
+    let layout = document.engine().unwrap().layout_tree();
+    let list = document.element_by_id("list").unwrap();
+    assert_eq!(layout.box_for_node(list).unwrap().border_box.height, 20.0);
+}
@@ -0,0 +1,53 @@
+extern crate chameleon;
+
+use chameleon::dom::{self, Node, NodeType, SVG_NAMESPACE};
+use chameleon::html;
+use std::collections::HashMap;
+
+/// The tree under `node`, with the text nodes and attributes, but not the node ids.
+fn shape(node: &Node) -> String {
+    match node.node_type {
+        NodeType::Text(ref text) => format!("{:?}", text),
+        NodeType::Element(ref elem) => {
+            let mut attributes: Vec<_> = elem.attributes.iter().collect();
+            attributes.sort();
+            let children: Vec<String> = node.children.iter().map(shape).collect();
+            format!("{}{:?}[{}]", elem.tag, attributes, children.join(","))
+        }
+    }
+}
+
+#[test]
+fn parsed_documents_round_trip() {
+    let source = "<div class='a &amp; b' title=\"say &quot;hi&quot;\">1 &lt; 2 &amp;&amp; 3 &gt; 2\
+                  <br><img src='x.png' alt=\"it's\"><p>caf&#233;&nbsp;&#x2603; &bogus; &</p>\
+                  <style>p::before { content: \"&amp;>\"; }</style></div>";
+    let (root, errors) = html::parse_with_errors(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(root.children.len(), 5);
+    match root.children[0].node_type {
+        NodeType::Text(ref text) => assert_eq!(text, "1 < 2 && 3 > 2"),
+        NodeType::Element(_) => panic!("expected text"),
+    }
+    assert!(root.children[1].children.is_empty());
+
+    let html = root.to_html();
+    assert_eq!(html, "<div class=\"a &amp; b\" title=\"say &quot;hi&quot;\">\
+                      1 &lt; 2 &amp;&amp; 3 &gt; 2<br><img alt=\"it's\" src=\"x.png\">\
+                      <p>café&nbsp;☃ &amp;bogus; &amp;</p>\
+                      <style>p::before { content: \"&amp;>\"; }</style></div>");
+    let (reparsed, errors) = html::parse_with_errors(&html);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(shape(&reparsed), shape(&root));
+}
+
+#[test]
+fn foreign_elements_without_children_are_self_closing() {
+    let circle = dom::elem_ns(SVG_NAMESPACE, "circle", HashMap::new(), Vec::new());
+    let svg = dom::elem_ns(SVG_NAMESPACE, "svg", HashMap::new(), vec![circle]);
+    let mut attributes = HashMap::new();
+    attributes.insert("data-x".into(), "<\u{a0}>".into());
+    let root = dom::elem("span", attributes, vec![svg, dom::text("\u{a0}")]);
+    assert_eq!(root.to_html(),
+               "<span data-x=\"&lt;&nbsp;&gt;\"><svg><circle/></svg>&nbsp;</span>");
+}
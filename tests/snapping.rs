@@ -0,0 +1,142 @@
+extern crate chameleon;
+
+use chameleon::css::Color;
+use chameleon::geometry::Rect;
+use chameleon::paint::{self, Canvas, ColorSpace, DisplayCommand, DisplayList, PaintOptions};
+use chameleon::paint::SnapPolicy;
+
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const TRANSLUCENT: Color = Color { r: 0, g: 0, b: 255, a: 128 };
+
+const SCALES: [f32; 5] = [1.0, 1.25, 1.5, 2.0, 3.0];
+const POLICIES: [SnapPolicy; 3] = [SnapPolicy::Round, SnapPolicy::Floor, SnapPolicy::Ceil];
+
+/// A row and a column of translucent boxes at fractional positions, each sharing an edge with
+/// the next. Returns the list and the areas of the row and the column.
+fn adjacent_boxes() -> (DisplayList, [Rect; 2]) {
+    let (start, size, count) = (0.7, 10.3, 6);
+    let mut list = DisplayList::new();
+    for i in 0..count {
+        let offset = start + i as f32 * size;
+        list.push(DisplayCommand::SolidColor {
+            color: TRANSLUCENT, x: offset, y: start, width: size, height: size * 2.0,
+        });
+        list.push(DisplayCommand::SolidColor {
+            color: TRANSLUCENT, x: start, y: offset + size * 2.0, width: size * 2.0, height: size,
+        });
+    }
+    let extent = size * count as f32;
+    let row = Rect { x: start, y: start, width: extent, height: size * 2.0 };
+    let column = Rect { x: start, y: start + size * 2.0, width: size * 2.0, height: extent };
+    (list, [row, column])
+}
+
+fn paint(list: &DisplayList, scale: f32, snap: SnapPolicy) -> Canvas {
+    let region = Rect { x: 0.0, y: 0.0, width: 80.0, height: 90.0 };
+    let options = PaintOptions { tile_size: 16, snap, ..PaintOptions::default() };
+    paint::paint_region_with_options(list, region, scale, options)
+}
+
+#[test]
+fn adjacent_boxes_neither_overlap_nor_leave_seams() {
+    let (list, _) = adjacent_boxes();
+    let once = TRANSLUCENT.over(&WHITE);
+    for &snap in &POLICIES {
+        for &scale in &SCALES {
+            let canvas = paint(&list, scale, snap);
+            for pixel in &canvas.pixels {
+                assert!(*pixel == WHITE || *pixel == once,
+                        "{:?} at scale {}: pixel painted {:?}", snap, scale, pixel);
+            }
+        }
+    }
+}
+
+#[test]
+fn boxes_cover_their_area_to_within_a_pixel() {
+    let (list, areas) = adjacent_boxes();
+    for &snap in &POLICIES {
+        for &scale in &SCALES {
+            let canvas = paint(&list, scale, snap);
+            let covered = |x: f32, y: f32| {
+                areas.iter().any(|area| area.contains(x / scale, y / scale))
+            };
+            for y in 0..canvas.height {
+                for x in 0..canvas.width {
+                    // Snapping moves an edge by less than a pixel, so only the pixels that lie
+                    // more than a pixel away from any edge are certain.
+                    let (x0, y0, x1, y1) = (x as f32 - 1.0, y as f32 - 1.0, x as f32 + 2.0,
+                                            y as f32 + 2.0);
+                    let corners = [covered(x0, y0), covered(x1, y0), covered(x0, y1),
+                                   covered(x1, y1)];
+                    if corners.iter().any(|&corner| corner != corners[0]) {
+                        continue;
+                    }
+                    let painted = canvas.pixels[y * canvas.width + x] != WHITE;
+                    assert_eq!(painted, corners[0],
+                               "{:?} at scale {}: pixel ({}, {})", snap, scale, x, y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn policies_snap_edges_in_their_direction() {
+    let mut list = DisplayList::new();
+    list.push(DisplayCommand::SolidColor {
+        color: TRANSLUCENT, x: 2.4, y: 0.0, width: 3.2, height: 1.0,
+    });
+    let painted = |snap| {
+        let canvas = paint(&list, 1.0, snap);
+        (0..canvas.width).filter(|&x| canvas.pixels[x] != WHITE).collect::<Vec<_>>()
+    };
+    // The box spans 2.4 to 5.6.
+    assert_eq!(painted(SnapPolicy::Round), vec![2, 3, 4, 5]);
+    assert_eq!(painted(SnapPolicy::Floor), vec![2, 3, 4]);
+    assert_eq!(painted(SnapPolicy::Ceil), vec![3, 4, 5]);
+}
+
+#[test]
+fn hairlines_cover_a_pixel_at_any_scale() {
+    let mut list = DisplayList::new();
+    list.push(DisplayCommand::SolidColor {
+        color: TRANSLUCENT, x: 2.2, y: 0.0, width: 0.5, height: 1.0,
+    });
+    list.push(DisplayCommand::SolidColor {
+        color: TRANSLUCENT, x: 10.0, y: 0.0, width: 1.0, height: 1.0,
+    });
+    for &snap in &POLICIES {
+        for &scale in &[0.25, 0.5, 1.0] {
+            let canvas = paint(&list, scale, snap);
+            let painted = (0..canvas.width).filter(|&x| canvas.pixels[x] != WHITE).count();
+            assert_eq!(painted, 2, "{:?} at scale {}", snap, scale);
+        }
+    }
+    // Empty items still cover nothing.
+    let mut list = DisplayList::new();
+    list.push(DisplayCommand::SolidColor {
+        color: TRANSLUCENT, x: 2.5, y: 0.0, width: 0.0, height: 1.0,
+    });
+    assert!(paint(&list, 1.0, SnapPolicy::Ceil).pixels.iter().all(|&pixel| pixel == WHITE));
+}
+
+#[test]
+fn repainting_keeps_the_region_scale_and_options_of_the_canvas() {
+    let (before, _) = adjacent_boxes();
+    let mut after = before.clone();
+    let moved = Rect { x: 20.5, y: 30.3, width: 15.2, height: 7.7 };
+    after.push(DisplayCommand::SolidColor {
+        color: TRANSLUCENT, x: moved.x, y: moved.y, width: moved.width, height: moved.height,
+    });
+    let region = Rect { x: 5.3, y: 2.6, width: 70.0, height: 80.0 };
+    for &snap in POLICIES.iter().chain(&[SnapPolicy::Antialias]) {
+        let options = PaintOptions {
+            tile_size: 16, snap, blending: ColorSpace::LinearSrgb, ..PaintOptions::default()
+        };
+        let mut canvas = paint::paint_region_with_options(&before, region, 1.5, options);
+        canvas.repaint(&after, moved);
+        let painted = paint::paint_region_with_options(&after, region, 1.5, options);
+        assert!(canvas.pixels == painted.pixels, "{:?}: repainted pixels differ", snap);
+    }
+}
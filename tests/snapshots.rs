@@ -0,0 +1,44 @@
+extern crate chameleon;
+
+use chameleon::engine::Engine;
+use chameleon::layout::LayoutContext;
+use chameleon::snapshot::{self, SessionState};
+use chameleon::style::ElementStates;
+use chameleon::{css, html};
+
+#[test]
+fn sessions_are_restored_with_their_scroll_position_and_focus() {
+    let root = html::parse("<html><p>a</p><a id=\"link\" href=\"#\">b</a><p>c</p></html>");
+    let stylesheet = css::parse("html, p, a { display: block; } p { height: 300px; } \
+                                 a { height: 10px; } :focus { height: 20px; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let mut engine = Engine::new(&root, &stylesheet, context);
+    engine.focus_next(&root, &stylesheet).unwrap();
+    engine.scroll_to(0.0, 250.0);
+    let state = engine.session_state();
+    assert_eq!(state.scroll_position, (0.0, 250.0));
+
+    let saved = snapshot::save_session(&root, &stylesheet, &state);
+    let (restored_root, restored_stylesheet, restored_state) =
+        snapshot::restore_session(&saved).unwrap();
+    let link = restored_root.children[1].id;
+    assert_eq!(restored_state, SessionState {
+        scroll_position: (0.0, 250.0),
+        states: ElementStates { focused: Some(link) },
+    });
+
+    // The restored engine renders what the saved one did.
+    let mut restored = Engine::new(&restored_root, &restored_stylesheet, context);
+    restored.restore_session_state(&restored_root, &restored_stylesheet, restored_state).unwrap();
+    assert_eq!(restored.focused(), Some(link));
+    assert_eq!(restored.viewport(), engine.viewport());
+    let link_box = restored.layout_tree().box_for_node(&restored_root.children[1]).unwrap();
+    assert_eq!(link_box.border_box.height, 20.0);
+    assert_eq!(link_box.border_box,
+               engine.layout_tree().box_for_node(&root.children[1]).unwrap().border_box);
+    assert_eq!(restored.render().unwrap().pixels, engine.render().unwrap().pixels);
+
+    // Plain snapshots have no focus and are not scrolled.
+    let (_, _, state) = snapshot::restore_session(&snapshot::save(&root, &stylesheet)).unwrap();
+    assert_eq!(state, SessionState::default());
+}
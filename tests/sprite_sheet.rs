@@ -0,0 +1,39 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+
+#[test]
+fn sprite_sheet_has_a_thumbnail_of_every_page_in_a_grid() {
+    let root = html::parse("<html><div class=\"a\"></div><div class=\"b\"></div></html>");
+    let stylesheet = css::parse("html, div { display: block; } div { height: 150px; } \
+                                 .a { background-color: #ff0000; } \
+                                 .b { background-color: #0000ff; }");
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    let engine = Engine::new(&root, &stylesheet, context);
+
+    // The document is three pages long; two thumbnails fit in each row.
+    let sheet = engine.sprite_sheet(1, 2, 0.1).unwrap();
+    assert_eq!(sheet.frames.len(), 3);
+    assert_eq!(sheet.frames[2].y, 200.0);
+    assert_eq!((sheet.canvas.width, sheet.canvas.height), (20, 20));
+    assert_eq!(sheet.frame_pixels(2), Some((0, 10, 10, 10)));
+    assert_eq!(sheet.frame_pixels(3), None);
+
+    let red = Color { r: 255, g: 0, b: 0, a: 255 };
+    let blue = Color { r: 0, g: 0, b: 255, a: 255 };
+    let white = Color { r: 255, g: 255, b: 255, a: 255 };
+    let pixel = |x: usize, y: usize| sheet.canvas.pixels[y * sheet.canvas.width + x];
+    assert_eq!(pixel(5, 2), red);
+    assert_eq!((pixel(15, 2), pixel(15, 7)), (red, blue));
+    assert_eq!(pixel(5, 15), blue);
+    // The last cell of the grid is left blank.
+    assert_eq!(pixel(15, 15), white);
+
+    // With every other page, only the first and third are left.
+    let sheet = engine.sprite_sheet(2, 4, 0.1).unwrap();
+    assert_eq!(sheet.frames.len(), 2);
+    assert_eq!((sheet.canvas.width, sheet.canvas.height), (20, 10));
+}
@@ -0,0 +1,61 @@
+extern crate chameleon;
+
+use chameleon::document::{Document, Viewport};
+use chameleon::geometry::Rect;
+
+/// Lay out `html` with `css` in a 100px-wide viewport, and return the border boxes of the
+/// elements with the given ids.
+fn border_boxes(html: &str, css: &str, ids: &[&str]) -> Vec<Rect> {
+    let mut document = Document::load_html(html, css);
+    document.layout(Viewport { width: 100.0, height: 100.0 }).unwrap();
+    let layout = document.engine().unwrap().layout_tree();
+    ids.iter()
+        .map(|id| layout.box_for_node(document.element_by_id(id).unwrap()).unwrap().border_box)
+        .collect()
+}
+
+const TABLE: &str = "<table id=\"table\"><tr id=\"first\"><td id=\"a\"></td><td id=\"b\"></td></tr>\
+                     <tr id=\"second\"><td id=\"c\"></td><td id=\"d\"></td></tr></table>";
+
+#[test]
+fn columns_take_the_width_of_their_widest_cell() {
+    let css = "table { display: table; } tr { display: table-row; } \
+               td { display: table-cell; height: 10px; } \
+               #a { width: 10px; } #b { width: 5px; } #c { width: 20px; } #d { width: 15px; }";
+    assert_eq!(border_boxes(TABLE, css, &["table", "a", "b", "c", "d"]), [
+        Rect { x: 0.0, y: 0.0, width: 35.0, height: 20.0 },
+        Rect { x: 0.0, y: 0.0, width: 20.0, height: 10.0 },
+        Rect { x: 20.0, y: 0.0, width: 15.0, height: 10.0 },
+        Rect { x: 0.0, y: 10.0, width: 20.0, height: 10.0 },
+        Rect { x: 20.0, y: 10.0, width: 15.0, height: 10.0 },
+    ]);
+}
+
+#[test]
+fn a_wider_table_distributes_its_width_over_columns_and_spacing() {
+    // 50px less 3 spacings of 2px leaves 44px, split between the two equal columns.
+    let css = "table { display: table; width: 50px; border-spacing: 2px; } \
+               tr { display: table-row; } td { display: table-cell; width: 10px; height: 10px; }";
+    assert_eq!(border_boxes(TABLE, css, &["table", "a", "b", "c", "d"]), [
+        Rect { x: 0.0, y: 0.0, width: 50.0, height: 26.0 },
+        Rect { x: 2.0, y: 2.0, width: 22.0, height: 10.0 },
+        Rect { x: 26.0, y: 2.0, width: 22.0, height: 10.0 },
+        Rect { x: 2.0, y: 14.0, width: 22.0, height: 10.0 },
+        Rect { x: 26.0, y: 14.0, width: 22.0, height: 10.0 },
+    ]);
+}
+
+#[test]
+fn rows_take_the_height_of_their_tallest_cell() {
+    let css = "table { display: table; } tr { display: table-row; } \
+               td { display: table-cell; width: 10px; } \
+               #a { height: 5px; } #b { height: 30px; } #c { height: 8px; } #d { height: 2px; }";
+    assert_eq!(border_boxes(TABLE, css, &["first", "second", "a", "b", "c", "d"]), [
+        Rect { x: 0.0, y: 0.0, width: 20.0, height: 30.0 },
+        Rect { x: 0.0, y: 30.0, width: 20.0, height: 8.0 },
+        Rect { x: 0.0, y: 0.0, width: 10.0, height: 30.0 },
+        Rect { x: 10.0, y: 0.0, width: 10.0, height: 30.0 },
+        Rect { x: 0.0, y: 30.0, width: 10.0, height: 8.0 },
+        Rect { x: 10.0, y: 30.0, width: 10.0, height: 8.0 },
+    ]);
+}
@@ -0,0 +1,50 @@
+extern crate chameleon;
+
+use chameleon::css::Color;
+use chameleon::paint::Canvas;
+use chameleon::terminal::{self, AnsiColors};
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+fn canvas(width: usize, pixels: Vec<Color>) -> Canvas {
+    let height = pixels.len() / width;
+    Canvas { pixels, ..Canvas::new(width, height) }
+}
+
+#[test]
+fn each_character_shows_two_pixels_one_above_the_other() {
+    // Red over blue on the left, white on the right, and a last row of red.
+    let canvas = canvas(2, vec![RED, WHITE, BLUE, WHITE, RED, RED]);
+    let text = terminal::to_ansi(&canvas, 80, AnsiColors::TrueColor);
+    assert_eq!(text, "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\
+                      \x1b[38;2;255;255;255m\x1b[48;2;255;255;255m▀\x1b[0m\n\
+                      \x1b[38;2;255;0;0m\x1b[49m▀▀\x1b[0m\n");
+
+    // The palette has the primary colors and white in its color cube.
+    let text = terminal::to_ansi(&canvas, 80, AnsiColors::Palette256);
+    assert_eq!(text, "\x1b[38;5;196m\x1b[48;5;21m▀\x1b[38;5;231m\x1b[48;5;231m▀\x1b[0m\n\
+                      \x1b[38;5;196m\x1b[49m▀▀\x1b[0m\n");
+    assert!(terminal::to_ansi(&Canvas::new(0, 0), 80, AnsiColors::TrueColor).is_empty());
+}
+
+#[test]
+fn wide_canvases_are_averaged_down_to_fit() {
+    // Four squares of 2×2 pixels, in two columns: red and white above, blue and half red below.
+    let mut pixels = vec![WHITE; 16];
+    for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1), (2, 2), (2, 3)] {
+        pixels[y * 4 + x] = RED;
+    }
+    for &(x, y) in &[(0, 2), (1, 2), (0, 3), (1, 3)] {
+        pixels[y * 4 + x] = BLUE;
+    }
+    let text = terminal::to_ansi(&canvas(4, pixels), 2, AnsiColors::TrueColor);
+    assert_eq!(text, "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\
+                      \x1b[38;2;255;255;255m\x1b[48;2;255;128;128m▀\x1b[0m\n");
+
+    // Grays are matched to the gray ramp rather than the color cube.
+    let gray = Color { r: 100, g: 100, b: 100, a: 255 };
+    let text = terminal::to_ansi(&canvas(1, vec![gray, gray]), 1, AnsiColors::Palette256);
+    assert_eq!(text, "\x1b[38;5;241m\x1b[48;5;241m▀\x1b[0m\n");
+}
@@ -0,0 +1,86 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::geometry::{Point, Transform};
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::DisplayCommand;
+
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+
+fn engine(css: &str) -> Engine {
+    let root = html::parse("<html><div></div></html>");
+    let css = format!("html, div {{ display: block; }} div {{ width: 40px; height: 40px; \
+                       margin-left: 30px; margin-top: 30px; background-color: #ff0000; }} {}",
+                      css);
+    let stylesheet = css::parse(&css);
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    Engine::new(&root, &stylesheet, context)
+}
+
+#[test]
+fn transforms_compose_around_the_center_of_the_box() {
+    let engine = engine("div { transform: translate(10px, 0) rotate(90deg) scale(2, 1); }");
+    let items = engine.display_list().items();
+    let transform = items.iter().filter_map(|item| match *item {
+        DisplayCommand::PushTransform { transform } => Some(transform),
+        _ => None,
+    }).next().unwrap();
+    // The rightmost function applies first, and the center of the box (50, 50) stays put
+    // except for the translation.
+    assert_eq!(transform.apply(Point { x: 50.0, y: 50.0 }), Point { x: 60.0, y: 50.0 });
+    assert_eq!(transform.apply(Point { x: 55.0, y: 50.0 }), Point { x: 60.0, y: 60.0 });
+    assert_eq!(items.last(), Some(&DisplayCommand::PopTransform));
+
+    let engine = self::engine("div { transform: none; }");
+    assert!(engine.display_list().transforms().iter().all(|t| t == &Some(Transform::IDENTITY)));
+}
+
+#[test]
+fn transformed_boxes_are_painted_where_they_are_moved() {
+    let canvas = engine("div { transform: translateX(20px); }").render().unwrap();
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    assert_eq!(pixel(49, 50), WHITE);
+    assert_eq!(pixel(50, 50), RED);
+    assert_eq!(pixel(89, 50), RED);
+    assert_eq!(pixel(90, 50), WHITE);
+
+    let canvas = engine("div { transform: rotate(45deg); }").render().unwrap();
+    let pixel = |x: usize, y: usize| canvas.pixels[y * canvas.width + x];
+    // The diamond reaches past the sides of the box, but not into its corners.
+    assert_eq!(pixel(50, 50), RED);
+    assert_eq!(pixel(25, 50), RED);
+    assert_eq!(pixel(31, 31), WHITE);
+    assert_eq!(pixel(68, 68), WHITE);
+
+    let canvas = engine("div { transform: scale(0); }").render().unwrap();
+    assert!(canvas.pixels.iter().all(|&pixel| pixel == WHITE));
+}
+
+#[test]
+fn transformed_boxes_are_hit_where_they_are_painted() {
+    let root = html::parse("<html><div><p></p></div></html>");
+    let (div, p) = (root.children[0].id, root.children[0].children[0].id);
+    let hits = |css: &str, x: f32, y: f32| {
+        let css = format!("html, div, p {{ display: block; }} div {{ width: 40px; height: 40px; \
+                           margin-left: 30px; margin-top: 30px; }} p {{ height: 10px; }} {}",
+                          css);
+        let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+        Engine::new(&root, &css::parse(&css), context).layout_tree().hit_test(x, y)
+    };
+
+    // The div and its child are moved together.
+    let translated = "div { transform: translateX(20px); }";
+    assert_eq!(hits(translated, 35.0, 35.0), [root.id]);
+    assert_eq!(hits(translated, 55.0, 35.0), [p, div, root.id]);
+    assert_eq!(hits(translated, 85.0, 50.0), [div, root.id]);
+
+    let rotated = "div { transform: rotate(45deg); }";
+    assert_eq!(hits(rotated, 25.0, 50.0), [div, root.id]);
+    assert_eq!(hits(rotated, 31.0, 31.0), [root.id]);
+
+    // Boxes that are flattened to nothing cannot be hit.
+    assert_eq!(hits("div { transform: scale(0); }", 50.0, 50.0), [root.id]);
+}
@@ -0,0 +1,52 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::engine::Engine;
+use chameleon::html;
+use chameleon::layout::LayoutContext;
+use chameleon::paint::DisplayCommand;
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+const GREEN: Color = Color { r: 0, g: 128, b: 0, a: 255 };
+
+fn engine(css: &str) -> Engine {
+    let root = html::parse("<html><div><p></p></div><span></span></html>");
+    let css = format!("html, div, p, span {{ display: block; margin: 0; height: 10px; }} \
+                       div {{ background-color: #ff0000; height: 20px; }} \
+                       p {{ background-color: #0000ff; }} \
+                       span {{ background-color: #008000; }} {}", css);
+    let stylesheet = css::parse(&css);
+    let context = LayoutContext { viewport_width: 100.0, viewport_height: 100.0 };
+    Engine::new(&root, &stylesheet, context)
+}
+
+/// The colors of the solid rectangles painted, and the y coordinate of each.
+fn fills(engine: &Engine) -> Vec<(Color, f32)> {
+    engine.display_list().items().iter().filter_map(|item| match *item {
+        DisplayCommand::SolidColor { color, y, width, .. } if width > 0.0 && color.a > 0 => {
+            Some((color, y))
+        }
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn hidden_boxes_take_up_space_but_are_not_painted() {
+    assert_eq!(fills(&engine("")), [(RED, 0.0), (BLUE, 0.0), (GREEN, 20.0)]);
+
+    // The paragraph inherits the visibility of the div, but the span still comes after both.
+    let hidden = engine("div { visibility: hidden; }");
+    assert_eq!(fills(&hidden), [(GREEN, 20.0)]);
+    assert_eq!(hidden.layout_tree().hit_test(5.0, 5.0).len(), 1);
+
+    let collapsed = engine("div { visibility: collapse; } span { visibility: bogus; }");
+    assert_eq!(fills(&collapsed), [(GREEN, 20.0)]);
+}
+
+#[test]
+fn visible_descendants_of_hidden_boxes_are_painted() {
+    let engine = engine("div { visibility: hidden; } p { visibility: visible; }");
+    assert_eq!(fills(&engine), [(BLUE, 0.0), (GREEN, 20.0)]);
+    assert_eq!(engine.layout_tree().hit_test(5.0, 5.0).len(), 2);
+}
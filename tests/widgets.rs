@@ -0,0 +1,56 @@
+extern crate chameleon;
+
+use chameleon::css::{self, Color};
+use chameleon::document::{Document, Viewport};
+use chameleon::dom::NodeId;
+use chameleon::geometry::{Rect, Size};
+use chameleon::paint::{DisplayCommand, DisplayList};
+use chameleon::widget::{Widget, WidgetRegistry};
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+
+/// A chart that wants to be 120px wide and 40px tall, and paints a bar that overflows it.
+struct Chart;
+
+impl Widget for Chart {
+    fn intrinsic_size(&self, _node: NodeId, available_width: f32) -> Size {
+        Size { width: available_width.min(120.0), height: 40.0 }
+    }
+
+    fn paint(&self, _node: NodeId, size: Size, slot: &mut DisplayList) {
+        slot.push(DisplayCommand::SolidColor {
+            color: RED,
+            x: -10.0,
+            y: 10.0,
+            width: size.width + 20.0,
+            height: 10.0,
+        });
+    }
+}
+
+#[test]
+fn widget_sizes_and_paints_its_element() {
+    let html = "<div><x-chart class=\"a\"><p>fallback</p></x-chart><p></p></div>";
+    let css = "div, p, x-chart { display: block; } \
+               x-chart { padding-left: 5px; } \
+               p { height: 10px; }";
+    assert!(css::parse_with_errors(css).1.is_empty());
+    let mut widgets = WidgetRegistry::new();
+    widgets.register("X-Chart", Chart);
+    let mut document = Document::load_html(html, css);
+    document.set_widgets(widgets);
+    let engine = document.layout(Viewport { width: 100.0, height: 100.0 }).unwrap();
+
+    // The chart gets the width available to it and its own height, and its contents get no box.
+    let chart = engine.layout_tree().root().unwrap().children()[0].geometry();
+    assert_eq!(chart.content_box, Rect { x: 5.0, y: 0.0, width: 95.0, height: 40.0 });
+    let paragraph = engine.layout_tree().root().unwrap().children()[1].geometry();
+    assert_eq!(paragraph.margin_box.y, 40.0);
+
+    // The bar is moved onto the content box and clipped to it.
+    let bars: Vec<&DisplayCommand> = engine.display_list().items().iter()
+        .filter(|item| matches!(item, DisplayCommand::SolidColor { color: RED, .. }))
+        .collect();
+    let bar = DisplayCommand::SolidColor { color: RED, x: 5.0, y: 10.0, width: 95.0, height: 10.0 };
+    assert_eq!(bars, [&bar]);
+}